@@ -0,0 +1,171 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::comparer::diff::{diff_bytes, diff_text, ByteChunk, ChangeKind, Chunk, Granularity};
+use crate::comparer::json::{diff_json, JsonChange};
+use crate::flow::model::{Flow, FlowRequest, FlowResponse};
+
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    pub granularity: Granularity,
+    // Header names (case-insensitive) to leave out, e.g. Date
+    pub ignore_headers: Vec<String>,
+}
+
+// A header whose values differ; an empty side means the header is missing there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeaderChange {
+    pub name: String,
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "changes", rename_all = "lowercase")]
+pub enum BodyDiff {
+    Identical,
+    Json(Vec<JsonChange>),
+    Text(Vec<Chunk>),
+    Bytes(Vec<ByteChunk>),
+}
+
+// Differences between two requests or two responses
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MessageDiff {
+    // Request or status line, when it differs: (old, new)
+    pub first_line: Option<(String, String)>,
+    pub headers: Vec<HeaderChange>,
+    pub body: BodyDiff,
+}
+
+impl MessageDiff {
+    pub fn is_identical(&self) -> bool {
+        self.first_line.is_none() && self.headers.is_empty() && self.body == BodyDiff::Identical
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FlowComparison {
+    pub request: MessageDiff,
+    // None when either flow has no response
+    pub response: Option<MessageDiff>,
+}
+
+pub fn compare_flows(old: &Flow, new: &Flow, options: &CompareOptions) -> FlowComparison {
+    let response = match (&old.response, &new.response) {
+        (Some(old), Some(new)) => Some(diff_responses(old, new, options)),
+        _ => None,
+    };
+
+    FlowComparison {
+        request: diff_requests(&old.request, &new.request, options),
+        response,
+    }
+}
+
+pub fn diff_requests(old: &FlowRequest, new: &FlowRequest, options: &CompareOptions) -> MessageDiff {
+    MessageDiff {
+        first_line: diff_line(
+            format!("{} {}", old.method, old.uri),
+            format!("{} {}", new.method, new.uri),
+        ),
+        headers: diff_headers(&old.headers, &new.headers, &options.ignore_headers),
+        body: diff_body(
+            old.decoded_body().decoded_or_raw(),
+            new.decoded_body().decoded_or_raw(),
+            options.granularity,
+        ),
+    }
+}
+
+pub fn diff_responses(old: &FlowResponse, new: &FlowResponse, options: &CompareOptions) -> MessageDiff {
+    MessageDiff {
+        first_line: diff_line(
+            format!("{} {}", old.version, old.status),
+            format!("{} {}", new.version, new.status),
+        ),
+        headers: diff_headers(&old.headers, &new.headers, &options.ignore_headers),
+        body: diff_body(
+            old.decoded_body().decoded_or_raw(),
+            new.decoded_body().decoded_or_raw(),
+            options.granularity,
+        ),
+    }
+}
+
+fn diff_line(old: String, new: String) -> Option<(String, String)> {
+    (old != new).then_some((old, new))
+}
+
+// Compare headers by name, in the order they first appear
+pub fn diff_headers(old: &[(String, String)], new: &[(String, String)], ignore: &[String]) -> Vec<HeaderChange> {
+    let values = |headers: &[(String, String)], name: &str| -> Vec<String> {
+        headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+            .collect()
+    };
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut changes = Vec::new();
+    for (name, _) in old.iter().chain(new) {
+        let lower = name.to_ascii_lowercase();
+        if seen.contains(&lower) || ignore.iter().any(|ignored| ignored.eq_ignore_ascii_case(name)) {
+            continue;
+        }
+        seen.push(lower);
+
+        let (old_values, new_values) = (values(old, name), values(new, name));
+        if old_values != new_values {
+            changes.push(HeaderChange {
+                name: name.clone(),
+                old: old_values,
+                new: new_values,
+            });
+        }
+    }
+    changes
+}
+
+// Bodies are compared decompressed. JSON bodies are diffed structurally, other text by the chosen granularity,
+// and anything that is not UTF-8 byte by byte
+pub fn diff_body(old: &[u8], new: &[u8], granularity: Granularity) -> BodyDiff {
+    if old == new {
+        return BodyDiff::Identical;
+    }
+
+    let json = |body: &[u8]| serde_json::from_slice::<Value>(body).ok().filter(|_| !body.is_empty());
+    if let (Some(old_json), Some(new_json)) = (json(old), json(new)) {
+        let changes = diff_json(&old_json, &new_json);
+        // Bodies that differ only in formatting are the same document
+        return if changes.is_empty() { BodyDiff::Identical } else { BodyDiff::Json(changes) };
+    }
+
+    match (std::str::from_utf8(old), std::str::from_utf8(new), granularity) {
+        (Ok(old), Ok(new), Granularity::Line | Granularity::Word) => {
+            BodyDiff::Text(diff_text(old, new, granularity))
+        }
+        _ => BodyDiff::Bytes(diff_bytes(old, new)),
+    }
+}
+
+// Number of (removed, added) units in a body diff: JSON values, characters or bytes
+pub fn change_counts(body: &BodyDiff) -> (usize, usize) {
+    let sizes: Vec<(ChangeKind, usize)> = match body {
+        BodyDiff::Identical => Vec::new(),
+        BodyDiff::Json(changes) => changes
+            .iter()
+            .flat_map(|change| {
+                let removed = change.old.as_ref().map(|_| (ChangeKind::Delete, 1));
+                let added = change.new.as_ref().map(|_| (ChangeKind::Insert, 1));
+                removed.into_iter().chain(added)
+            })
+            .collect(),
+        BodyDiff::Text(chunks) => chunks.iter().map(|chunk| (chunk.kind, chunk.text.chars().count())).collect(),
+        BodyDiff::Bytes(chunks) => chunks.iter().map(|chunk| (chunk.kind, chunk.bytes.len())).collect(),
+    };
+
+    let total = |kind| sizes.iter().filter(|(k, _)| *k == kind).map(|(_, size)| size).sum();
+    (total(ChangeKind::Delete), total(ChangeKind::Insert))
+}
@@ -0,0 +1,108 @@
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use similar::{capture_diff_slices_deadline, Algorithm, ChangeTag, DiffTag, TextDiff};
+
+// Give up on finding a minimal diff after this long and fall back to a coarser one
+const DIFF_DEADLINE: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Granularity {
+    #[default]
+    Line,
+    Word,
+    Byte,
+}
+
+impl FromStr for Granularity {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "line" | "lines" => Ok(Self::Line),
+            "word" | "words" => Ok(Self::Word),
+            "byte" | "bytes" => Ok(Self::Byte),
+            _ => bail!("Unknown diff granularity {} (line, word, byte)", value),
+        }
+    }
+}
+
+// A run of text that is unchanged, removed or added
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chunk {
+    pub kind: ChangeKind,
+    pub text: String,
+}
+
+// A run of bytes, with where it starts in the old and new input
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ByteChunk {
+    pub kind: ChangeKind,
+    pub old_offset: usize,
+    pub new_offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+// Diff two texts by lines, words or (for Byte) characters; adjacent changes of the
+// same kind are merged
+pub fn diff_text(old: &str, new: &str, granularity: Granularity) -> Vec<Chunk> {
+    let mut config = TextDiff::configure();
+    config.timeout(DIFF_DEADLINE);
+    let diff = match granularity {
+        Granularity::Word => config.diff_words(old, new),
+        Granularity::Line => config.diff_lines(old, new),
+        Granularity::Byte => config.diff_chars(old, new),
+    };
+
+    let mut chunks: Vec<Chunk> = Vec::new();
+    for change in diff.iter_all_changes() {
+        let kind = match change.tag() {
+            ChangeTag::Equal => ChangeKind::Equal,
+            ChangeTag::Delete => ChangeKind::Delete,
+            ChangeTag::Insert => ChangeKind::Insert,
+        };
+        match chunks.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(change.value()),
+            _ => chunks.push(Chunk { kind, text: change.value().to_string() }),
+        }
+    }
+    chunks
+}
+
+// Diff two byte strings; replaced ranges become a delete followed by an insert
+pub fn diff_bytes(old: &[u8], new: &[u8]) -> Vec<ByteChunk> {
+    let deadline = Some(Instant::now() + DIFF_DEADLINE);
+    let mut chunks = Vec::new();
+
+    for op in capture_diff_slices_deadline(Algorithm::Myers, old, new, deadline) {
+        let (tag, old_range, new_range) = op.as_tag_tuple();
+        let mut push = |kind, bytes: &[u8]| {
+            chunks.push(ByteChunk {
+                kind,
+                old_offset: old_range.start,
+                new_offset: new_range.start,
+                bytes: bytes.to_vec(),
+            })
+        };
+
+        match tag {
+            DiffTag::Equal => push(ChangeKind::Equal, &old[old_range.clone()]),
+            DiffTag::Delete => push(ChangeKind::Delete, &old[old_range.clone()]),
+            DiffTag::Insert => push(ChangeKind::Insert, &new[new_range.clone()]),
+            DiffTag::Replace => {
+                push(ChangeKind::Delete, &old[old_range.clone()]);
+                push(ChangeKind::Insert, &new[new_range.clone()]);
+            }
+        }
+    }
+    chunks
+}
@@ -0,0 +1,84 @@
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+// A value that differs between two JSON documents, addressed by a path like `$.items[2].id`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonChange {
+    pub path: String,
+    pub kind: JsonChangeKind,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+// Structural diff of two JSON values. Object keys are matched by name and array
+// elements by index; a value whose type changed is reported as one change.
+pub fn diff_json(old: &Value, new: &Value) -> Vec<JsonChange> {
+    let mut changes = Vec::new();
+    walk("$", old, new, &mut changes);
+    changes
+}
+
+fn walk(path: &str, old: &Value, new: &Value, changes: &mut Vec<JsonChange>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let child = child_path(path, key);
+                match new_map.get(key) {
+                    Some(new_value) => walk(&child, old_value, new_value, changes),
+                    None => changes.push(removed(child, old_value)),
+                }
+            }
+            for (key, new_value) in new_map {
+                if !old_map.contains_key(key) {
+                    changes.push(added(child_path(path, key), new_value));
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for (index, old_value) in old_items.iter().enumerate() {
+                let child = format!("{}[{}]", path, index);
+                match new_items.get(index) {
+                    Some(new_value) => walk(&child, old_value, new_value, changes),
+                    None => changes.push(removed(child, old_value)),
+                }
+            }
+            for (index, new_value) in new_items.iter().enumerate().skip(old_items.len()) {
+                changes.push(added(format!("{}[{}]", path, index), new_value));
+            }
+        }
+        _ if old != new => changes.push(JsonChange {
+            path: path.to_string(),
+            kind: JsonChangeKind::Changed,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    let simple = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if simple {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+fn added(path: String, value: &Value) -> JsonChange {
+    JsonChange { path, kind: JsonChangeKind::Added, old: None, new: Some(value.clone()) }
+}
+
+fn removed(path: String, value: &Value) -> JsonChange {
+    JsonChange { path, kind: JsonChangeKind::Removed, old: Some(value.clone()), new: None }
+}
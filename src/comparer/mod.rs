@@ -0,0 +1,3 @@
+pub mod compare;
+pub mod diff;
+pub mod json;
@@ -0,0 +1,101 @@
+use crate::flow::model::FlowRequest;
+
+// Headers curl computes itself; copying them would only cause mismatches
+const SKIPPED_HEADERS: &[&str] = &["content-length", "connection", "proxy-connection"];
+
+#[derive(Debug, Clone, Default)]
+pub struct CurlOptions {
+    // Route the command through this proxy (e.g. "http://127.0.0.1:8080")
+    pub proxy: Option<String>,
+    // Force `-k`; it is added automatically for HTTPS requests sent through a proxy
+    pub insecure: bool,
+}
+
+// Render a request as a single ready-to-run `curl` command line
+pub fn to_curl(request: &FlowRequest, options: &CurlOptions) -> String {
+    let mut args = vec!["curl".to_string()];
+
+    let has_body = !request.body.is_empty();
+    let implied_method = if has_body { "POST" } else { "GET" };
+    if request.method == "HEAD" && !has_body {
+        args.push("-I".to_string());
+    } else if request.method != implied_method {
+        args.push("-X".to_string());
+        args.push(quote(&request.method));
+    }
+
+    args.push(quote(&request.uri));
+
+    let url_host = request
+        .uri
+        .parse::<hyper::Uri>()
+        .ok()
+        .and_then(|uri| uri.authority().map(|authority| authority.to_string()));
+
+    for (name, value) in &request.headers {
+        let lower = name.to_ascii_lowercase();
+        if SKIPPED_HEADERS.contains(&lower.as_str()) {
+            continue;
+        }
+        // curl derives Host from the URL; only keep it when it differs
+        if lower == "host" && url_host.as_deref() == Some(value.as_str()) {
+            continue;
+        }
+        args.push("-H".to_string());
+        args.push(quote(&format!("{}: {}", name, value)));
+    }
+
+    // --data-raw sends the body as given, even when it starts with `@`
+    if has_body {
+        args.push("--data-raw".to_string());
+        args.push(quote_bytes(&request.body));
+    }
+
+    if let Some(proxy) = &options.proxy {
+        args.push("--proxy".to_string());
+        args.push(quote(proxy));
+    }
+
+    let is_https = request.uri.starts_with("https://");
+    if options.insecure || (is_https && options.proxy.is_some()) {
+        args.push("-k".to_string());
+    }
+
+    args.join(" ")
+}
+
+// Quote a value for POSIX shells, leaving simple words alone
+fn quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c));
+
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+// Text bodies are single-quoted; binary bodies fall back to ANSI-C `$'...'` escapes
+fn quote_bytes(body: &[u8]) -> String {
+    match std::str::from_utf8(body) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && c != '\n' && c != '\t' && c != '\r') => {
+            quote(text)
+        }
+        _ => {
+            let mut quoted = String::from("$'");
+            for &byte in body {
+                match byte {
+                    b'\'' => quoted.push_str(r"\'"),
+                    b'\\' => quoted.push_str(r"\\"),
+                    0x20..=0x7e => quoted.push(byte as char),
+                    _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+                }
+            }
+            quoted.push('\'');
+            quoted
+        }
+    }
+}
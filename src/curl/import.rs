@@ -0,0 +1,324 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use log::debug;
+use std::collections::VecDeque;
+
+use crate::flow::model::FlowRequest;
+
+// A request parsed from a `curl` command line, plus the transport options it asked for
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurlCommand {
+    pub request: FlowRequest,
+    pub proxy: Option<String>,
+    pub insecure: bool,
+}
+
+// Options that take a value but do not affect the request itself
+const IGNORED_WITH_VALUE: &[&str] = &[
+    "-o", "--output", "-m", "--max-time", "--connect-timeout", "-w", "--write-out",
+    "--retry", "-c", "--cookie-jar", "--resolve", "--cacert", "--cert", "--key",
+];
+
+// Short options that take a value, which may be glued on as in `-XPOST`
+const SHORT_WITH_VALUE: &[char] = &['X', 'H', 'd', 'b', 'A', 'e', 'u', 'x', 'o', 'm', 'w', 'c', 'F'];
+
+// Parse a pasted `curl` command into a request
+pub fn parse_curl(command: &str) -> Result<CurlCommand> {
+    let mut words: VecDeque<Vec<u8>> = split_shell_bytes(command)?.into();
+
+    match words.pop_front().map(|word| String::from_utf8_lossy(&word).into_owned()) {
+        Some(word) if word == "curl" || word.ends_with("/curl") => {}
+        _ => bail!("Command does not start with curl"),
+    }
+
+    let mut url = None;
+    let mut method = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut data: Vec<Vec<u8>> = Vec::new();
+    let mut get = false;
+    let mut head = false;
+    let mut compressed = false;
+    let mut proxy = None;
+    let mut insecure = false;
+
+    while let Some(word) = words.pop_front() {
+        let text = String::from_utf8_lossy(&word).into_owned();
+        let (flag, inline) = split_flag(&text, &mut words);
+
+        let mut value = || -> Result<Vec<u8>> {
+            match &inline {
+                Some(value) => Ok(value.clone().into_bytes()),
+                None => words.pop_front().ok_or_else(|| anyhow!("Option {} needs a value", flag)),
+            }
+        };
+
+        match flag.as_str() {
+            "-X" | "--request" => method = Some(lossy(value()?)),
+            "-H" | "--header" => {
+                let header = lossy(value()?);
+                let (name, header_value) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Invalid header {}", header))?;
+                headers.push((name.trim().to_string(), header_value.trim().to_string()));
+            }
+            "-d" | "--data" | "--data-ascii" => {
+                // Plain --data strips newlines from file contents like curl does
+                let raw = read_data(value()?)?;
+                data.push(raw.into_iter().filter(|&b| b != b'\r' && b != b'\n').collect());
+            }
+            "--data-raw" => data.push(value()?),
+            "--data-binary" => data.push(read_data(value()?)?),
+            "--data-urlencode" => data.push(urlencode_data(&lossy(value()?)).into_bytes()),
+            "--json" => {
+                data.push(read_data(value()?)?);
+                headers.push(("Content-Type".to_string(), "application/json".to_string()));
+                headers.push(("Accept".to_string(), "application/json".to_string()));
+            }
+            "-b" | "--cookie" => headers.push(("Cookie".to_string(), lossy(value()?))),
+            "-A" | "--user-agent" => headers.push(("User-Agent".to_string(), lossy(value()?))),
+            "-e" | "--referer" => headers.push(("Referer".to_string(), lossy(value()?))),
+            "-u" | "--user" => {
+                let credentials = BASE64.encode(value()?);
+                headers.push(("Authorization".to_string(), format!("Basic {}", credentials)));
+            }
+            "-x" | "--proxy" => proxy = Some(lossy(value()?)),
+            "--url" => url = Some(lossy(value()?)),
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => head = true,
+            "-k" | "--insecure" => insecure = true,
+            "--compressed" => compressed = true,
+            "-F" | "--form" => bail!("Multipart form uploads (-F) are not supported"),
+            _ if IGNORED_WITH_VALUE.contains(&flag.as_str()) => {
+                value()?;
+            }
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                debug!("Ignoring curl option {}", flag);
+            }
+            _ => url = Some(text),
+        }
+    }
+
+    let mut url = url.ok_or_else(|| anyhow!("No URL found in curl command"))?;
+    if !url.contains("://") {
+        url = format!("http://{}", url);
+    }
+
+    let mut body = data.join(&b'&');
+    if get && !body.is_empty() {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        url = format!("{}{}{}", url, separator, String::from_utf8_lossy(&body));
+        body.clear();
+    }
+
+    let method = match method {
+        Some(method) => method.to_ascii_uppercase(),
+        None if head => "HEAD".to_string(),
+        None if !body.is_empty() => "POST".to_string(),
+        None => "GET".to_string(),
+    };
+
+    // curl sends form content by default when given --data without a Content-Type
+    let has_content_type = headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type"));
+    if !body.is_empty() && !has_content_type {
+        headers.push((
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        ));
+    }
+
+    let has_accept_encoding = headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("accept-encoding"));
+    if compressed && !has_accept_encoding {
+        headers.push(("Accept-Encoding".to_string(), "deflate, gzip, br, zstd".to_string()));
+    }
+
+    url.parse::<hyper::Uri>()
+        .with_context(|| format!("Invalid URL {}", url))?;
+
+    Ok(CurlCommand {
+        request: FlowRequest {
+            method,
+            uri: url,
+            version: "HTTP/1.1".to_string(),
+            headers,
+            body,
+        },
+        proxy,
+        insecure,
+    })
+}
+
+// Separate `--name=value` and `-Xvalue` forms; bundles like `-sSk` are expanded
+// into individual flags that are pushed back onto the word queue
+fn split_flag(word: &str, words: &mut VecDeque<Vec<u8>>) -> (String, Option<String>) {
+    if let Some(long) = word.strip_prefix("--") {
+        return match long.split_once('=') {
+            Some((name, value)) => (format!("--{}", name), Some(value.to_string())),
+            None => (word.to_string(), None),
+        };
+    }
+
+    let Some(short) = word.strip_prefix('-') else {
+        return (word.to_string(), None);
+    };
+
+    let mut chars = short.chars();
+    let Some(first) = chars.next() else {
+        return (word.to_string(), None);
+    };
+    let rest = chars.as_str();
+    if rest.is_empty() {
+        return (word.to_string(), None);
+    }
+
+    if SHORT_WITH_VALUE.contains(&first) {
+        return (format!("-{}", first), Some(rest.to_string()));
+    }
+
+    // Boolean bundle: handle the first flag now and queue the remainder
+    words.push_front(format!("-{}", rest).into_bytes());
+    (format!("-{}", first), None)
+}
+
+fn lossy(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn read_data(value: Vec<u8>) -> Result<Vec<u8>> {
+    match value.strip_prefix(b"@") {
+        Some(b"-") => bail!("Reading request data from stdin is not supported"),
+        Some(path) => {
+            let path = String::from_utf8_lossy(path).into_owned();
+            std::fs::read(&path).with_context(|| format!("Failed to read {}", path))
+        }
+        None => Ok(value),
+    }
+}
+
+// Implements curl's `name=content` / `content` forms of --data-urlencode
+fn urlencode_data(value: &str) -> String {
+    let encode = |text: &str| url::form_urlencoded::byte_serialize(text.as_bytes()).collect::<String>();
+
+    match value.split_once('=') {
+        Some(("", content)) => encode(content),
+        Some((name, content)) => format!("{}={}", name, encode(content)),
+        None => encode(value),
+    }
+}
+
+// Split a shell command line into words, handling the quoting styles browsers emit
+pub fn split_shell_words(input: &str) -> Result<Vec<String>> {
+    Ok(split_shell_bytes(input)?
+        .into_iter()
+        .map(|word| String::from_utf8_lossy(&word).into_owned())
+        .collect())
+}
+
+// Words are kept as bytes because `$'\xNN'` escapes can produce arbitrary binary data
+fn split_shell_bytes(input: &str) -> Result<Vec<Vec<u8>>> {
+    let mut words = Vec::new();
+    let mut current = Vec::new();
+    let mut in_word = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => push_char(&mut current, c),
+                        None => bail!("Unterminated single quote"),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                read_ansi_c_quoted(&mut chars, &mut current)?;
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(next @ ('"' | '\\' | '$' | '`')) => push_char(&mut current, next),
+                            Some('\n') => {}
+                            Some(next) => {
+                                current.push(b'\\');
+                                push_char(&mut current, next);
+                            }
+                            None => bail!("Unterminated double quote"),
+                        },
+                        Some(c) => push_char(&mut current, c),
+                        None => bail!("Unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                // Line continuation
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(next) => {
+                    in_word = true;
+                    push_char(&mut current, next);
+                }
+                None => {}
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                push_char(&mut current, c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(current);
+    }
+
+    Ok(words)
+}
+
+fn push_char(buf: &mut Vec<u8>, c: char) {
+    let mut utf8 = [0u8; 4];
+    buf.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+}
+
+fn read_ansi_c_quoted(
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    current: &mut Vec<u8>,
+) -> Result<()> {
+    loop {
+        match chars.next() {
+            Some('\'') => return Ok(()),
+            Some('\\') => match chars.next() {
+                Some('n') => current.push(b'\n'),
+                Some('r') => current.push(b'\r'),
+                Some('t') => current.push(b'\t'),
+                Some('0') => current.push(0),
+                Some('x') => {
+                    let hex: String = (0..2)
+                        .filter_map(|_| chars.next_if(|c| c.is_ascii_hexdigit()))
+                        .collect();
+                    current.push(u8::from_str_radix(&hex, 16).context("Invalid \\x escape")?);
+                }
+                Some(other) => push_char(current, other),
+                None => bail!("Unterminated $'...' string"),
+            },
+            Some(c) => push_char(current, c),
+            None => bail!("Unterminated $'...' string"),
+        }
+    }
+}
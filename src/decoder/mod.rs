@@ -0,0 +1,2 @@
+pub mod smart;
+pub mod transform;
@@ -0,0 +1,142 @@
+use crate::decoder::transform::Transform;
+
+// Stop peeling layers after this many, in case of pathological input
+const MAX_LAYERS: usize = 10;
+
+// Share of characters that must be printable for decoded output to count as text
+const PRINTABLE_RATIO: f64 = 0.9;
+
+// One layer removed by smart decoding
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeStep {
+    pub transform: Transform,
+    pub output: Vec<u8>,
+}
+
+// Guess how the input was encoded and peel off one layer at a time, e.g.
+// URL-encoded base64 of gzip. Returns the layers in the order they were removed;
+// an empty result means nothing recognisable was found.
+pub fn smart_decode(input: &[u8]) -> Vec<DecodeStep> {
+    let mut steps: Vec<DecodeStep> = Vec::new();
+    let mut current = input.to_vec();
+
+    while steps.len() < MAX_LAYERS {
+        let Some(step) = next_layer(&current) else {
+            break;
+        };
+        current = step.output.clone();
+        let done = step.transform == Transform::Jwt;
+        steps.push(step);
+        if done {
+            break;
+        }
+    }
+
+    steps
+}
+
+fn next_layer(data: &[u8]) -> Option<DecodeStep> {
+    let decode = |transform: Transform| {
+        transform
+            .decode(data)
+            .ok()
+            .filter(|output| output != data)
+            .map(|output| DecodeStep { transform, output })
+    };
+
+    if data.starts_with(&[0x1f, 0x8b]) {
+        return decode(Transform::Gzip);
+    }
+    if is_zlib(data) {
+        return decode(Transform::Deflate);
+    }
+    if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        return decode(Transform::Zstd);
+    }
+
+    let text = std::str::from_utf8(data).ok()?.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    // Hex and base64 are only accepted when they decode to something meaningful,
+    // since plenty of ordinary words are valid in both alphabets
+    let base64 = base64_alphabet(text);
+    let candidates = [
+        (looks_like_jwt(text), Transform::Jwt, false),
+        (has_percent_escape(text), Transform::Url, false),
+        (text.contains("\\u"), Transform::Unicode, false),
+        (text.contains('&') && text.contains(';'), Transform::Html, false),
+        (is_hex(text), Transform::Hex, true),
+        (base64.is_some(), base64.unwrap_or(Transform::Base64), true),
+    ];
+
+    candidates
+        .into_iter()
+        .filter(|(applies, _, _)| *applies)
+        .find_map(|(_, transform, strict)| {
+            decode(transform).filter(|step| !strict || is_meaningful(&step.output))
+        })
+}
+
+fn is_hex(text: &str) -> bool {
+    text.len() >= 4 && text.len().is_multiple_of(2) && text.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// Which base64 variant the text could be, if any
+fn base64_alphabet(text: &str) -> Option<Transform> {
+    if text.len() < 4 || text.len() % 4 == 1 {
+        return None;
+    }
+
+    let url_safe = text.contains(['-', '_']);
+    let standard = text.contains(['+', '/']);
+    let extra = if url_safe { "-_" } else { "+/" };
+    let alphabet_ok = text
+        .trim_end_matches('=')
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || extra.contains(c));
+
+    match (alphabet_ok, url_safe, standard) {
+        (true, true, false) => Some(Transform::Base64Url),
+        (true, false, _) => Some(Transform::Base64),
+        _ => None,
+    }
+}
+
+fn is_zlib(data: &[u8]) -> bool {
+    data.len() > 2 && data[0] == 0x78 && matches!(data[1], 0x01 | 0x5e | 0x9c | 0xda)
+}
+
+fn looks_like_jwt(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('.').collect();
+    parts.len() == 3
+        && parts[0].starts_with("eyJ")
+        && parts
+            .iter()
+            .all(|part| part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+fn has_percent_escape(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes
+        .windows(3)
+        .any(|window| window[0] == b'%' && window[1].is_ascii_hexdigit() && window[2].is_ascii_hexdigit())
+}
+
+// Mostly printable text, or compressed data that can be decoded further
+fn is_meaningful(data: &[u8]) -> bool {
+    if data.starts_with(&[0x1f, 0x8b]) || is_zlib(data) {
+        return true;
+    }
+
+    let Ok(text) = std::str::from_utf8(data) else {
+        return false;
+    };
+    let total = text.chars().count();
+    let printable = text
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .count();
+    total > 0 && printable as f64 / total as f64 >= PRINTABLE_RATIO
+}
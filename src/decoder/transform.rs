@@ -0,0 +1,301 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use percent_encoding::{percent_decode, percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+
+// Everything except RFC 3986 unreserved characters is percent-encoded
+const URL_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// Decoding accepts input with or without `=` padding
+const PADDING_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64: GeneralPurpose = GeneralPurpose::new(&alphabet::STANDARD, PADDING_INDIFFERENT);
+// base64url is written without padding, as in JWTs
+const BASE64_URL: GeneralPurpose =
+    GeneralPurpose::new(&alphabet::URL_SAFE, PADDING_INDIFFERENT.with_encode_padding(false));
+
+// Largest output decompression may produce, so a small bomb cannot use up memory
+pub const MAX_DECODED_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Url,
+    Html,
+    Base64,
+    Base64Url,
+    Hex,
+    Gzip,
+    // zlib-wrapped deflate, as used by `Content-Encoding: deflate`
+    Deflate,
+    Brotli,
+    Zstd,
+    // \uXXXX escapes
+    Unicode,
+    // Splits a JWT into its decoded header and payload as JSON
+    Jwt,
+}
+
+impl FromStr for Transform {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "url" => Ok(Self::Url),
+            "html" => Ok(Self::Html),
+            "base64" | "b64" => Ok(Self::Base64),
+            "base64url" | "b64url" => Ok(Self::Base64Url),
+            "hex" => Ok(Self::Hex),
+            "gzip" => Ok(Self::Gzip),
+            "deflate" | "zlib" => Ok(Self::Deflate),
+            "brotli" | "br" => Ok(Self::Brotli),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            "unicode" => Ok(Self::Unicode),
+            "jwt" => Ok(Self::Jwt),
+            _ => bail!(
+                "Unknown transform {} (url, html, base64, base64url, hex, gzip, deflate, brotli, zstd, unicode, jwt)",
+                name
+            ),
+        }
+    }
+}
+
+impl Transform {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Url => "url",
+            Self::Html => "html",
+            Self::Base64 => "base64",
+            Self::Base64Url => "base64url",
+            Self::Hex => "hex",
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "brotli",
+            Self::Zstd => "zstd",
+            Self::Unicode => "unicode",
+            Self::Jwt => "jwt",
+        }
+    }
+
+    pub fn encode(&self, input: &[u8]) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::Url => percent_encode(input, URL_ENCODE_SET).to_string().into_bytes(),
+            Self::Html => html_escape::encode_safe(&text(input)?).into_owned().into_bytes(),
+            Self::Base64 => BASE64.encode(input).into_bytes(),
+            Self::Base64Url => BASE64_URL.encode(input).into_bytes(),
+            Self::Hex => input.iter().map(|byte| format!("{:02x}", byte)).collect::<String>().into_bytes(),
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(input)?;
+                encoder.finish()?
+            }
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(input)?;
+                encoder.finish()?
+            }
+            Self::Brotli => {
+                let mut output = Vec::new();
+                let mut encoder = brotli::CompressorWriter::new(&mut output, 4096, 5, 22);
+                encoder.write_all(input)?;
+                drop(encoder);
+                output
+            }
+            Self::Zstd => zstd::encode_all(input, 0)?,
+            Self::Unicode => text(input)?
+                .encode_utf16()
+                .map(|unit| format!("\\u{:04x}", unit))
+                .collect::<String>()
+                .into_bytes(),
+            Self::Jwt => encode_jwt(input)?,
+        })
+    }
+
+    pub fn decode(&self, input: &[u8]) -> Result<Vec<u8>> {
+        self.decode_with_limit(input, MAX_DECODED_BYTES)
+    }
+
+    // Decode, failing if decompression would produce more than `limit` bytes
+    pub fn decode_with_limit(&self, input: &[u8], limit: usize) -> Result<Vec<u8>> {
+        Ok(match self {
+            Self::Url => percent_decode(&plus_as_space(input)).collect(),
+            Self::Html => html_escape::decode_html_entities(&text(input)?).into_owned().into_bytes(),
+            Self::Base64 => BASE64
+                .decode(strip_whitespace(input))
+                .context("Invalid base64")?,
+            Self::Base64Url => BASE64_URL
+                .decode(strip_whitespace(input))
+                .context("Invalid base64url")?,
+            Self::Hex => decode_hex(input)?,
+            Self::Gzip => checked(decompress(GzDecoder::new(input), limit), "gzip")?,
+            Self::Deflate => {
+                let zlib = match decompress(ZlibDecoder::new(input), limit) {
+                    Err(e) if e.kind() != ErrorKind::FileTooLarge => decompress(DeflateDecoder::new(input), limit),
+                    zlib => zlib,
+                };
+                checked(zlib, "deflate")?
+            }
+            Self::Brotli => checked(decompress(brotli::Decompressor::new(input, 4096), limit), "brotli")?,
+            Self::Zstd => checked(zstd::stream::read::Decoder::new(input).and_then(|zstd| decompress(zstd, limit)), "zstd")?,
+            Self::Unicode => decode_unicode(&text(input)?)?.into_bytes(),
+            Self::Jwt => decode_jwt(input)?,
+        })
+    }
+}
+
+// Parse a comma-separated list of transform names, e.g. "url,base64"
+pub fn parse_chain(spec: &str) -> Result<Vec<Transform>> {
+    spec.split(',')
+        .filter(|name| !name.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+// Apply encodings in the order given
+pub fn encode_chain(input: &[u8], chain: &[Transform]) -> Result<Vec<u8>> {
+    chain.iter().try_fold(input.to_vec(), |data, transform| {
+        transform
+            .encode(&data)
+            .with_context(|| format!("{} encoding failed", transform.name()))
+    })
+}
+
+// Apply decodings in the order given
+pub fn decode_chain(input: &[u8], chain: &[Transform]) -> Result<Vec<u8>> {
+    chain.iter().try_fold(input.to_vec(), |data, transform| {
+        transform
+            .decode(&data)
+            .with_context(|| format!("{} decoding failed", transform.name()))
+    })
+}
+
+fn text(input: &[u8]) -> Result<String> {
+    String::from_utf8(input.to_vec()).context("Input is not valid UTF-8")
+}
+
+// Read all output, failing with FileTooLarge past `limit` bytes
+fn decompress(reader: impl Read, limit: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut output)?;
+    if output.len() > limit {
+        let message = format!("Decompressed data is larger than {} bytes", limit);
+        return Err(io::Error::new(ErrorKind::FileTooLarge, message));
+    }
+    Ok(output)
+}
+
+fn checked(output: io::Result<Vec<u8>>, name: &str) -> Result<Vec<u8>> {
+    match output {
+        Err(e) if e.kind() == ErrorKind::FileTooLarge => Err(e.into()),
+        output => output.with_context(|| format!("Invalid {} data", name)),
+    }
+}
+
+fn strip_whitespace(input: &[u8]) -> Vec<u8> {
+    input.iter().copied().filter(|b| !b.is_ascii_whitespace()).collect()
+}
+
+// Form encoding uses `+` for spaces
+fn plus_as_space(input: &[u8]) -> Vec<u8> {
+    input.iter().map(|&b| if b == b'+' { b' ' } else { b }).collect()
+}
+
+// Accepts an optional 0x prefix and whitespace or colon separators
+fn decode_hex(input: &[u8]) -> Result<Vec<u8>> {
+    let input = std::str::from_utf8(input).context("Invalid hex")?.trim();
+    let input = input.strip_prefix("0x").unwrap_or(input);
+    let digits: Vec<char> = input.chars().filter(|c| !c.is_whitespace() && *c != ':').collect();
+    if !digits.len().is_multiple_of(2) {
+        bail!("Hex input has an odd number of digits");
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).with_context(|| format!("Invalid hex byte {}", pair))
+        })
+        .collect()
+}
+
+// Decode \uXXXX (including surrogate pairs) and \u{XXXXXX} escapes, leaving other text alone
+fn decode_unicode(input: &str) -> Result<String> {
+    let mut output = String::new();
+    let mut units: Vec<u16> = Vec::new();
+    let mut rest = input;
+
+    let flush = |units: &mut Vec<u16>, output: &mut String| {
+        output.push_str(&String::from_utf16_lossy(units));
+        units.clear();
+    };
+
+    while let Some(at) = rest.find("\\u") {
+        let (before, after) = rest.split_at(at);
+        if !before.is_empty() {
+            flush(&mut units, &mut output);
+            output.push_str(before);
+        }
+        let escape = &after[2..];
+
+        if let Some(braced) = escape.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| anyhow!("Unterminated \\u{{ escape"))?;
+            let code = u32::from_str_radix(&braced[..end], 16).context("Invalid \\u{} escape")?;
+            flush(&mut units, &mut output);
+            output.push(char::from_u32(code).ok_or_else(|| anyhow!("Invalid code point {:x}", code))?);
+            rest = &braced[end + 1..];
+        } else if escape.len() >= 4 && escape.is_char_boundary(4) && escape[..4].chars().all(|c| c.is_ascii_hexdigit()) {
+            units.push(u16::from_str_radix(&escape[..4], 16)?);
+            rest = &escape[4..];
+        } else {
+            flush(&mut units, &mut output);
+            output.push_str("\\u");
+            rest = escape;
+        }
+    }
+    flush(&mut units, &mut output);
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+// Render a JWT as {"header": ..., "payload": ..., "signature": "..."}
+fn decode_jwt(input: &[u8]) -> Result<Vec<u8>> {
+    let token = std::str::from_utf8(input).context("JWT is not valid UTF-8")?.trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        bail!("A JWT has three dot-separated parts, found {}", parts.len());
+    }
+
+    let part = |index: usize, name: &str| -> Result<Value> {
+        let bytes = BASE64_URL
+            .decode(parts[index])
+            .with_context(|| format!("JWT {} is not base64url", name))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("JWT {} is not JSON", name))
+    };
+
+    let decoded = json!({
+        "header": part(0, "header")?,
+        "payload": part(1, "payload")?,
+        "signature": parts[2],
+    });
+    Ok(serde_json::to_vec_pretty(&decoded)?)
+}
+
+// The inverse of decode_jwt; the signature is copied as given (empty if absent)
+fn encode_jwt(input: &[u8]) -> Result<Vec<u8>> {
+    let decoded: Value = serde_json::from_slice(input).context("JWT encoding needs a JSON object")?;
+    let part = |name: &str| -> Result<String> {
+        let value = decoded.get(name).ok_or_else(|| anyhow!("Missing JWT {}", name))?;
+        Ok(BASE64_URL.encode(serde_json::to_vec(value)?))
+    };
+    let signature = decoded.get("signature").and_then(Value::as_str).unwrap_or("");
+
+    Ok(format!("{}.{}.{}", part("header")?, part("payload")?, signature).into_bytes())
+}
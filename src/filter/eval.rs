@@ -0,0 +1,287 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::parse::{parse, Expr, Field, Op, ParseError, Side, Value};
+use crate::flow::body::Body;
+use crate::flow::model::{Flow, FlowRequest, FlowResponse};
+use crate::sitemap::links::request_url;
+
+// A compiled filter expression such as
+// `host ~ "api\." && method == POST && status >= 500 && res.body contains "error"`.
+//
+// Comparisons hold when any value of the field satisfies them (a flow has several tags,
+// and `body` reads both bodies). `!=` and `!~` hold when the field has values and none
+// of them are equal or match, so every comparison on a missing field is false.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Filter {
+    source: String,
+    expr: Expr,
+}
+
+// The parts of an exchange a filter can see. Requests still waiting for a
+// response, and messages seen outside a recorded flow, have less to go on.
+#[derive(Debug, Clone, Copy)]
+struct Subject<'a> {
+    request: &'a FlowRequest,
+    response: Option<&'a FlowResponse>,
+    flow: Option<&'a Flow>,
+}
+
+enum FieldValue<'a> {
+    Text(Cow<'a, [u8]>),
+    Number(f64),
+}
+
+impl FieldValue<'_> {
+    fn bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            Self::Text(bytes) => Cow::Borrowed(bytes.as_ref()),
+            Self::Number(number) => Cow::Owned(number.to_string().into_bytes()),
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        Ok(Self {
+            source: source.to_string(),
+            expr: parse(source)?,
+        })
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn matches(&self, flow: &Flow) -> bool {
+        self.eval(Subject {
+            request: &flow.request,
+            response: flow.response.as_ref(),
+            flow: Some(flow),
+        })
+    }
+
+    // For requests that have not been answered yet, e.g. in the interceptor
+    pub fn matches_request(&self, request: &FlowRequest) -> bool {
+        self.eval(Subject {
+            request,
+            response: None,
+            flow: None,
+        })
+    }
+
+    pub fn matches_response(&self, request: &FlowRequest, response: &FlowResponse) -> bool {
+        self.eval(Subject {
+            request,
+            response: Some(response),
+            flow: None,
+        })
+    }
+
+    fn eval(&self, subject: Subject<'_>) -> bool {
+        eval(&self.expr, subject)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(Self::parse(s)?)
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> anyhow::Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Filter> for String {
+    fn from(filter: Filter) -> Self {
+        filter.source
+    }
+}
+
+impl PartialEq for Filter {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn eval(expr: &Expr, subject: Subject<'_>) -> bool {
+    match expr {
+        Expr::And(left, right) => eval(left, subject) && eval(right, subject),
+        Expr::Or(left, right) => eval(left, subject) || eval(right, subject),
+        Expr::Not(inner) => !eval(inner, subject),
+        Expr::Present(field) => present(field, subject),
+        Expr::Compare { field, op, value } => compare(field, *op, value, subject),
+    }
+}
+
+fn present(field: &Field, subject: Subject<'_>) -> bool {
+    match field {
+        Field::Tls => subject.flow.map_or(subject.request.uri.starts_with("https://"), |flow| flow.tls.is_some()),
+        Field::Response => subject.response.is_some(),
+        _ => values(field, subject).iter().any(|value| match value {
+            FieldValue::Text(bytes) => !bytes.is_empty(),
+            FieldValue::Number(_) => true,
+        }),
+    }
+}
+
+fn compare(field: &Field, op: Op, value: &Value, subject: Subject<'_>) -> bool {
+    let values = values(field, subject);
+    if values.is_empty() {
+        return false;
+    }
+    let fold = field.case_insensitive();
+
+    let test = |candidate: &FieldValue<'_>| match (op, value, candidate) {
+        (Op::Matches | Op::NotMatches, Value::Regex(regex), candidate) => regex.is_match(&candidate.bytes()),
+        (Op::Contains, Value::Text(needle), candidate) => contains(&candidate.bytes(), needle.as_bytes(), fold),
+        (_, Value::Number(expected), FieldValue::Number(actual)) => match op {
+            Op::Lt => actual < expected,
+            Op::Le => actual <= expected,
+            Op::Gt => actual > expected,
+            Op::Ge => actual >= expected,
+            _ => actual == expected,
+        },
+        (_, Value::Text(expected), candidate) => {
+            let actual = candidate.bytes();
+            if fold {
+                actual.eq_ignore_ascii_case(expected.as_bytes())
+            } else {
+                *actual == *expected.as_bytes()
+            }
+        }
+        _ => false,
+    };
+
+    match op {
+        Op::Ne | Op::NotMatches => !values.iter().any(test),
+        _ => values.iter().any(test),
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8], fold: bool) -> bool {
+    if needle.is_empty() {
+        return true;
+    }
+    haystack.windows(needle.len()).any(|window| {
+        if fold {
+            window.eq_ignore_ascii_case(needle)
+        } else {
+            window == needle
+        }
+    })
+}
+
+fn text(value: &str) -> FieldValue<'_> {
+    FieldValue::Text(Cow::Borrowed(value.as_bytes()))
+}
+
+fn owned(value: String) -> FieldValue<'static> {
+    FieldValue::Text(Cow::Owned(value.into_bytes()))
+}
+
+// Every value of a field, from whichever messages it reads
+fn values<'a>(field: &Field, subject: Subject<'a>) -> Vec<FieldValue<'a>> {
+    let request = subject.request;
+    let url = || request_url(request);
+    let tls = subject.flow.and_then(|flow| flow.tls.as_ref());
+
+    match field {
+        Field::Method => vec![text(&request.method)],
+        Field::Url => vec![url().map_or_else(|| text(&request.uri), |url| owned(url.to_string()))],
+        Field::Scheme => url().map(|url| owned(url.scheme().to_string())).into_iter().collect(),
+        Field::Host => url()
+            .and_then(|url| url.host_str().map(|host| owned(host.to_ascii_lowercase())))
+            .into_iter()
+            .collect(),
+        Field::Port => url()
+            .and_then(|url| url.port_or_known_default())
+            .map(|port| FieldValue::Number(port.into()))
+            .into_iter()
+            .collect(),
+        Field::Path => url().map(|url| owned(url.path().to_string())).into_iter().collect(),
+        Field::Query => url()
+            .and_then(|url| url.query().map(|query| owned(query.to_string())))
+            .into_iter()
+            .collect(),
+        Field::Status => subject
+            .response
+            .map(|response| FieldValue::Number(response.status.into()))
+            .into_iter()
+            .collect(),
+        Field::Duration => subject
+            .flow
+            .and_then(|flow| flow.duration_ms)
+            .map(|ms| FieldValue::Number(ms as f64))
+            .into_iter()
+            .collect(),
+        Field::Tag => subject
+            .flow
+            .map(|flow| flow.tags.iter().map(|tag| text(tag)).collect())
+            .unwrap_or_default(),
+        Field::Notes => subject.flow.and_then(|flow| flow.notes.as_deref()).map(text).into_iter().collect(),
+        Field::Id => subject
+            .flow
+            .filter(|flow| flow.id != 0)
+            .map(|flow| FieldValue::Number(flow.id as f64))
+            .into_iter()
+            .collect(),
+        Field::TlsVersion => tls.and_then(|tls| tls.protocol_version.as_deref()).map(text).into_iter().collect(),
+        Field::TlsCipher => tls.and_then(|tls| tls.cipher_suite.as_deref()).map(text).into_iter().collect(),
+        Field::TlsAlpn => tls.and_then(|tls| tls.alpn_protocol.as_deref()).map(text).into_iter().collect(),
+        Field::TlsSni => tls.and_then(|tls| tls.server_name.as_deref()).map(text).into_iter().collect(),
+        Field::Tls | Field::Response => Vec::new(),
+        Field::Header(side, name) => sides(*side, subject)
+            .flat_map(|headers| headers.0.iter())
+            .filter_map(|(header, value)| match name {
+                Some(name) => header.eq_ignore_ascii_case(name).then(|| text(value)),
+                None => Some(owned(format!("{}: {}", header, value))),
+            })
+            .collect(),
+        // Bodies are matched with their Content-Encoding undone; size stays the bytes on the wire
+        Field::Body(side) => sides(*side, subject)
+            .map(|(headers, raw)| {
+                let body = Body::new(headers, raw);
+                match body.is_encoded() {
+                    true => FieldValue::Text(Cow::Owned(body.decoded_or_raw().to_vec())),
+                    false => FieldValue::Text(Cow::Borrowed(raw)),
+                }
+            })
+            .collect(),
+        Field::ContentType(side) => sides(*side, subject)
+            .flat_map(|headers| headers.0.iter())
+            .filter(|(header, _)| header.eq_ignore_ascii_case("content-type"))
+            .map(|(_, value)| text(value))
+            .collect(),
+        Field::Size(side) => sides(*side, subject)
+            .map(|(_, body)| FieldValue::Number(body.len() as f64))
+            .collect(),
+    }
+}
+
+// Headers and body of the messages a side refers to
+fn sides<'a>(side: Side, subject: Subject<'a>) -> impl Iterator<Item = (&'a [(String, String)], &'a [u8])> {
+    let request = matches!(side, Side::Request | Side::Either)
+        .then_some((subject.request.headers.as_slice(), subject.request.body.as_slice()));
+    let response = subject
+        .response
+        .filter(|_| matches!(side, Side::Response | Side::Either))
+        .map(|response| (response.headers.as_slice(), response.body.as_slice()));
+    request.into_iter().chain(response)
+}
@@ -0,0 +1,467 @@
+use std::fmt;
+use regex::bytes::Regex;
+
+// Which message a field reads from. Fields without a `req.`/`res.` prefix read both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Request,
+    Response,
+    Either,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    Method,
+    Url,
+    Scheme,
+    Host,
+    Port,
+    Path,
+    Query,
+    Status,
+    // Milliseconds
+    Duration,
+    Tag,
+    Notes,
+    Id,
+    // Whether the flow went over TLS
+    Tls,
+    TlsVersion,
+    TlsCipher,
+    TlsAlpn,
+    TlsSni,
+    // Whether there is a response at all
+    Response,
+    // A named header, or every header as `Name: value` lines when None
+    Header(Side, Option<String>),
+    Body(Side),
+    ContentType(Side),
+    // Body length in bytes
+    Size(Side),
+}
+
+// How a field's values compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Number,
+    Bytes,
+    Duration,
+    Flag,
+}
+
+impl Field {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Port | Self::Status | Self::Id => Kind::Number,
+            Self::Size(_) => Kind::Bytes,
+            Self::Duration => Kind::Duration,
+            Self::Tls | Self::Response => Kind::Flag,
+            _ => Kind::Text,
+        }
+    }
+
+    // Fields whose `==` and `contains` ignore ASCII case
+    pub fn case_insensitive(&self) -> bool {
+        matches!(
+            self,
+            Self::Method | Self::Scheme | Self::Host | Self::ContentType(_) | Self::TlsVersion | Self::TlsSni
+        )
+    }
+
+    fn lookup(name: &str) -> Option<Self> {
+        let lower = name.to_ascii_lowercase();
+        let (side, rest) = if let Some(rest) = lower.strip_prefix("req.").or_else(|| lower.strip_prefix("request.")) {
+            (Side::Request, rest)
+        } else if let Some(rest) = lower.strip_prefix("res.").or_else(|| lower.strip_prefix("response.")) {
+            (Side::Response, rest)
+        } else {
+            (Side::Either, lower.as_str())
+        };
+
+        let field = match rest {
+            "header" | "headers" => Self::Header(side, None),
+            "body" => Self::Body(side),
+            "content_type" | "content-type" | "type" => Self::ContentType(side),
+            "size" => Self::Size(side),
+            _ if rest.starts_with("header.") => {
+                // Keep the header name as written; matching ignores case anyway
+                let name = &name[name.len() - (rest.len() - "header.".len())..];
+                if name.is_empty() {
+                    return None;
+                }
+                Self::Header(side, Some(name.to_string()))
+            }
+            _ if side != Side::Either => return None,
+            "method" => Self::Method,
+            "url" => Self::Url,
+            "scheme" => Self::Scheme,
+            "host" => Self::Host,
+            "port" => Self::Port,
+            "path" => Self::Path,
+            "query" => Self::Query,
+            "status" => Self::Status,
+            "duration" => Self::Duration,
+            "tag" | "tags" => Self::Tag,
+            "notes" => Self::Notes,
+            "id" => Self::Id,
+            "tls" => Self::Tls,
+            "tls.version" => Self::TlsVersion,
+            "tls.cipher" => Self::TlsCipher,
+            "tls.alpn" => Self::TlsAlpn,
+            "tls.sni" => Self::TlsSni,
+            "res" | "response" => Self::Response,
+            _ => return None,
+        };
+        Some(field)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Matches,
+    NotMatches,
+    Contains,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn is_ordering(self) -> bool {
+        matches!(self, Self::Lt | Self::Le | Self::Gt | Self::Ge)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Matches => "~",
+            Self::NotMatches => "!~",
+            Self::Contains => "contains",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        };
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    // Sizes in bytes and durations in milliseconds once units are applied
+    Number(f64),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    // A bare field: true when the field has a value (or, for flags, is set)
+    Present(Field),
+    Compare { field: Field, op: Op, value: Value },
+}
+
+// A syntax or type error, pointing at the character where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    // Character offset into the expression
+    pub position: usize,
+    pub input: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} at position {}", self.message, self.position + 1)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}^", " ".repeat(self.position))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) => write!(f, "`{}`", word),
+            Self::Str(text) => write!(f, "{:?}", text),
+            Self::Op(op) => write!(f, "`{}`", op),
+            Self::And => f.write_str("`&&`"),
+            Self::Or => f.write_str("`||`"),
+            Self::Not => f.write_str("`!`"),
+            Self::Open => f.write_str("`(`"),
+            Self::Close => f.write_str("`)`"),
+            Self::End => f.write_str("the end of the filter"),
+        }
+    }
+}
+
+const SPECIAL: &[char] = &['(', ')', '"', '!', '=', '<', '>', '~', '&', '|'];
+
+// Tokens with the character offset each one starts at
+fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let error = |message: String, position: usize| ParseError {
+        message,
+        position,
+        input: input.to_string(),
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match (c, next) {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => Token::Open,
+            (')', _) => Token::Close,
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Op(Op::Eq),
+            ('!', Some('=')) => Token::Op(Op::Ne),
+            ('!', Some('~')) => Token::Op(Op::NotMatches),
+            ('<', Some('=')) => Token::Op(Op::Le),
+            ('>', Some('=')) => Token::Op(Op::Ge),
+            ('=', _) => Token::Op(Op::Eq),
+            ('~', _) => Token::Op(Op::Matches),
+            ('<', _) => Token::Op(Op::Lt),
+            ('>', _) => Token::Op(Op::Gt),
+            ('!', _) => Token::Not,
+            ('&', _) | ('|', _) => return Err(error(format!("Expected `{}{}`", c, c), start)),
+            ('"', _) => {
+                // Only `\"` and `\\` are escapes, so regexes such as "api\." keep their backslashes
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match (chars.get(i), chars.get(i + 1)) {
+                        (None, _) => return Err(error("Unterminated string".to_string(), start)),
+                        (Some('"'), _) => break,
+                        (Some('\\'), Some(escaped @ ('"' | '\\'))) => {
+                            text.push(*escaped);
+                            i += 2;
+                        }
+                        (Some(c), _) => {
+                            text.push(*c);
+                            i += 1;
+                        }
+                    }
+                }
+                i += 1;
+                tokens.push((Token::Str(text), start));
+                continue;
+            }
+            _ => {
+                while i < chars.len() && !chars[i].is_whitespace() && !SPECIAL.contains(&chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.to_ascii_lowercase().as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Op(Op::Contains),
+                    "matches" => Token::Op(Op::Matches),
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start));
+                continue;
+            }
+        };
+        i += match token {
+            Token::Open | Token::Close | Token::Not => 1,
+            Token::Op(Op::Eq) if next != Some('=') => 1,
+            Token::Op(Op::Matches | Op::Lt | Op::Gt) => 1,
+            _ => 2,
+        };
+        tokens.push((token, start));
+    }
+
+    tokens.push((Token::End, chars.len()));
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.index].1
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.index].clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: String, position: usize) -> ParseError {
+        ParseError {
+            message,
+            position,
+            input: self.input.to_string(),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while *self.peek() == Token::Or {
+            self.advance();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.unary()?;
+        while *self.peek() == Token::And {
+            self.advance();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let (token, position) = self.advance();
+        match token {
+            Token::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let expr = self.or()?;
+                let (token, position) = self.advance();
+                if token != Token::Close {
+                    return Err(self.error(format!("Expected `)` but found {}", token), position));
+                }
+                Ok(expr)
+            }
+            Token::Word(name) => self.comparison(&name, position),
+            other => Err(self.error(format!("Expected a field but found {}", other), position)),
+        }
+    }
+
+    fn comparison(&mut self, name: &str, position: usize) -> Result<Expr, ParseError> {
+        let field = Field::lookup(name).ok_or_else(|| self.error(format!("Unknown field `{}`", name), position))?;
+        let Token::Op(op) = *self.peek() else {
+            return Ok(Expr::Present(field));
+        };
+        let op_position = self.position();
+        self.advance();
+
+        let kind = field.kind();
+        if kind == Kind::Flag {
+            return Err(self.error(
+                format!("`{}` is true or false and cannot be compared; use `{}` or `!{}`", name, name, name),
+                op_position,
+            ));
+        }
+        if op.is_ordering() && kind == Kind::Text {
+            return Err(self.error(format!("`{}` compares numbers, but `{}` is text", op, name), op_position));
+        }
+
+        let (token, value_position) = self.advance();
+        let text = match token {
+            Token::Word(word) => word,
+            Token::Str(text) => text,
+            other => return Err(self.error(format!("Expected a value but found {}", other), value_position)),
+        };
+
+        let value = match op {
+            Op::Matches | Op::NotMatches => Value::Regex(
+                Regex::new(&text)
+                    .map_err(|e| self.error(format!("Invalid regex: {}", regex_reason(&e)), value_position))?,
+            ),
+            Op::Contains => Value::Text(text),
+            _ if kind == Kind::Text => Value::Text(text),
+            _ => Value::Number(
+                parse_number(&text, kind)
+                    .map_err(|message| self.error(format!("{} for `{}`", message, name), value_position))?,
+            ),
+        };
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+// Why a regex failed to compile. The error spans several lines, showing the
+// pattern with a caret under the problem, and ends with `error: <reason>`.
+fn regex_reason(error: &regex::Error) -> String {
+    let text = error.to_string();
+    let last = text.lines().rev().find(|line| !line.trim().is_empty()).unwrap_or(&text).trim();
+    last.strip_prefix("error: ").unwrap_or(last).to_string()
+}
+
+// A number with an optional unit: b/kb/mb/gb for sizes, ms/s/m for durations
+fn parse_number(text: &str, kind: Kind) -> Result<f64, String> {
+    let lower = text.to_ascii_lowercase();
+    let split = lower.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("Expected a number but found `{}`", text))?;
+
+    let scale = match (kind, unit) {
+        (_, "") => 1.0,
+        (Kind::Bytes, "b") => 1.0,
+        (Kind::Bytes, "kb" | "k") => 1024.0,
+        (Kind::Bytes, "mb") => 1024.0 * 1024.0,
+        (Kind::Bytes, "gb") => 1024.0 * 1024.0 * 1024.0,
+        (Kind::Duration, "ms") => 1.0,
+        (Kind::Duration, "s") => 1000.0,
+        (Kind::Duration, "m" | "min") => 60_000.0,
+        _ => return Err(format!("Unexpected unit `{}`", unit)),
+    };
+    Ok(number * scale)
+}
+
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        input,
+        tokens: tokenize(input)?,
+        index: 0,
+    };
+    if *parser.peek() == Token::End {
+        return Err(parser.error("Empty filter".to_string(), 0));
+    }
+    let expr = parser.or()?;
+    let (token, position) = parser.advance();
+    if token != Token::End {
+        let message = match token {
+            Token::Word(_) | Token::Str(_) => format!("Expected `&&` or `||` before {}", token),
+            other => format!("Unexpected {}", other),
+        };
+        return Err(parser.error(message, position));
+    }
+    Ok(expr)
+}
@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::decoder::transform::Transform;
+
+// What happens to Content-Encoding when a decoded body is changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EncodingPolicy {
+    // Compress the new content the same way the original was
+    #[default]
+    Reencode,
+    // Send the new content uncompressed and drop Content-Encoding
+    Strip,
+}
+
+// Content-Encoding as transforms in the order they were applied, or None for
+// an encoding that cannot be undone
+pub fn content_encodings(headers: &[(String, String)]) -> Option<Vec<Transform>> {
+    let mut transforms = Vec::new();
+    for (name, value) in headers {
+        if !name.eq_ignore_ascii_case("content-encoding") {
+            continue;
+        }
+        for encoding in value.split(',').map(|encoding| encoding.trim().to_ascii_lowercase()) {
+            match encoding.as_str() {
+                "" | "identity" => {}
+                "gzip" | "x-gzip" => transforms.push(Transform::Gzip),
+                "deflate" => transforms.push(Transform::Deflate),
+                "br" => transforms.push(Transform::Brotli),
+                "zstd" => transforms.push(Transform::Zstd),
+                _ => return None,
+            }
+        }
+    }
+    Some(transforms)
+}
+
+// The charset named by a Content-Type header, if encoding_rs knows it
+fn declared_charset(headers: &[(String, String)]) -> Option<&'static Encoding> {
+    let (_, content_type) = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))?;
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| Encoding::for_label(value.trim().trim_matches('"').as_bytes()))
+            .flatten()
+    })
+}
+
+// A message body as it went over the wire. The decoded content and its text are
+// only worked out when asked for, and the raw bytes stay available for showing
+// the body exactly as it was sent.
+pub struct Body<'a> {
+    headers: &'a [(String, String)],
+    raw: &'a [u8],
+    encodings: Option<Vec<Transform>>,
+    decoded: OnceCell<Result<Vec<u8>, String>>,
+}
+
+impl<'a> Body<'a> {
+    pub fn new(headers: &'a [(String, String)], raw: &'a [u8]) -> Self {
+        Self {
+            headers,
+            raw,
+            encodings: content_encodings(headers),
+            decoded: OnceCell::new(),
+        }
+    }
+
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    // Whether the body has a Content-Encoding other than identity
+    pub fn is_encoded(&self) -> bool {
+        self.encodings.as_ref().is_none_or(|encodings| !encodings.is_empty())
+    }
+
+    // The Content-Encoding header as sent, e.g. `gzip`
+    pub fn content_encoding(&self) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+            .map(|(_, value)| value.as_str())
+    }
+
+    // The content with every Content-Encoding undone
+    pub fn decoded(&self) -> Result<&[u8]> {
+        if !self.is_encoded() {
+            return Ok(self.raw);
+        }
+        let decoded = self.decoded.get_or_init(|| match &self.encodings {
+            Some(encodings) => encodings
+                .iter()
+                .rev()
+                .try_fold(self.raw.to_vec(), |data, transform| {
+                    transform
+                        .decode(&data)
+                        .with_context(|| format!("{} decoding failed", transform.name()))
+                })
+                .map_err(|e| format!("{:#}", e)),
+            None => Err(format!(
+                "Unsupported Content-Encoding {}",
+                self.content_encoding().unwrap_or_default()
+            )),
+        });
+        decoded.as_deref().map_err(|e| anyhow!("{}", e))
+    }
+
+    // The decoded content, or the raw bytes when they cannot be decoded
+    pub fn decoded_or_raw(&self) -> &[u8] {
+        self.decoded().unwrap_or(self.raw)
+    }
+
+    // The charset from Content-Type, UTF-8 if none is given or it is unknown
+    pub fn charset(&self) -> &'static Encoding {
+        declared_charset(self.headers).unwrap_or(UTF_8)
+    }
+
+    // The decoded content as text. A byte order mark wins over the declared
+    // charset, and invalid sequences become U+FFFD.
+    pub fn text(&self) -> Cow<'_, str> {
+        self.charset().decode(self.decoded_or_raw()).0
+    }
+}
+
+// Encode new content for the headers' Content-Encoding, or drop the header when
+// stripping. A Content-Length header is updated to match.
+pub fn encode_body(headers: &mut Vec<(String, String)>, content: &[u8], policy: EncodingPolicy) -> Result<Vec<u8>> {
+    let raw = match policy {
+        EncodingPolicy::Reencode => {
+            let encodings = content_encodings(headers).context("Cannot re-encode an unsupported Content-Encoding")?;
+            encodings.iter().try_fold(content.to_vec(), |data, transform| {
+                transform
+                    .encode(&data)
+                    .with_context(|| format!("{} encoding failed", transform.name()))
+            })?
+        }
+        EncodingPolicy::Strip => {
+            headers.retain(|(name, _)| !name.eq_ignore_ascii_case("content-encoding"));
+            content.to_vec()
+        }
+    };
+    for (name, value) in headers.iter_mut() {
+        if name.eq_ignore_ascii_case("content-length") {
+            *value = raw.len().to_string();
+        }
+    }
+    Ok(raw)
+}
+
+// Replace a body with new decoded content. Returns false, keeping the raw bytes
+// untouched, when the content is what the body already decodes to.
+pub fn set_decoded(
+    headers: &mut Vec<(String, String)>,
+    raw: &mut Vec<u8>,
+    content: &[u8],
+    policy: EncodingPolicy,
+) -> Result<bool> {
+    if Body::new(headers, raw).decoded().is_ok_and(|decoded| decoded == content) {
+        return Ok(false);
+    }
+    *raw = encode_body(headers, content, policy)?;
+    Ok(true)
+}
+
+// Replace a body with text, encoded in the body's charset first
+pub fn set_text(headers: &mut Vec<(String, String)>, raw: &mut Vec<u8>, text: &str, policy: EncodingPolicy) -> Result<bool> {
+    let charset = declared_charset(headers).unwrap_or(UTF_8);
+    let (content, _, _) = charset.encode(text);
+    set_decoded(headers, raw, &content, policy)
+}
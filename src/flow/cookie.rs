@@ -0,0 +1,65 @@
+// Parsing for `Cookie` and `Set-Cookie` header values
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SetCookie {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub expires: Option<String>,
+    pub max_age: Option<String>,
+    pub same_site: Option<String>,
+    pub http_only: bool,
+    pub secure: bool,
+}
+
+// Split a request `Cookie` header into name/value pairs
+pub fn parse_cookie_header(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|pair| {
+            let pair = pair.trim();
+            if pair.is_empty() {
+                return None;
+            }
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// Parse a single `Set-Cookie` header value, ignoring unknown attributes
+pub fn parse_set_cookie(value: &str) -> Option<SetCookie> {
+    let mut parts = value.split(';');
+    let (name, cookie_value) = parts.next()?.trim().split_once('=')?;
+    if name.trim().is_empty() {
+        return None;
+    }
+
+    let mut cookie = SetCookie {
+        name: name.trim().to_string(),
+        value: cookie_value.trim().to_string(),
+        ..Default::default()
+    };
+
+    for attribute in parts {
+        let attribute = attribute.trim();
+        let (key, attr_value) = match attribute.split_once('=') {
+            Some((key, attr_value)) => (key.trim(), Some(attr_value.trim().to_string())),
+            None => (attribute, None),
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            "path" => cookie.path = attr_value,
+            "domain" => cookie.domain = attr_value,
+            "expires" => cookie.expires = attr_value,
+            "max-age" => cookie.max_age = attr_value,
+            "samesite" => cookie.same_site = attr_value,
+            "httponly" => cookie.http_only = true,
+            "secure" => cookie.secure = true,
+            _ => {}
+        }
+    }
+
+    Some(cookie)
+}
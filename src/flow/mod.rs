@@ -0,0 +1,4 @@
+pub mod body;
+pub mod cookie;
+pub mod model;
+pub mod raw;
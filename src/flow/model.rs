@@ -0,0 +1,306 @@
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::flow::body::{self, Body, EncodingPolicy};
+
+pub type FlowId = i64;
+
+// A single request/response exchange seen by the proxy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Flow {
+    // Zero until the flow has been saved to a project
+    pub id: FlowId,
+    pub started_at: DateTime<Utc>,
+    pub request: FlowRequest,
+    pub response: Option<FlowResponse>,
+    pub duration_ms: Option<u64>,
+    pub timings: Option<Timings>,
+    pub tls: Option<TlsInfo>,
+    pub websocket_messages: Vec<WebSocketMessage>,
+    pub tags: Vec<String>,
+    pub notes: Option<String>,
+    // Set on flows produced by replaying an earlier one
+    pub replay_of: Option<FlowId>,
+    // Where the client sent the request when Map Remote redirected it; `request.uri` is where it went
+    pub original_uri: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowRequest {
+    pub method: String,
+    pub uri: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowResponse {
+    pub status: u16,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+// Phases of the upstream exchange, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    pub send_ms: f64,
+    pub wait_ms: f64,
+    pub receive_ms: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsInfo {
+    pub server_name: Option<String>,
+    pub protocol_version: Option<String>,
+    pub cipher_suite: Option<String>,
+    pub alpn_protocol: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebSocketOpcode {
+    Text,
+    Binary,
+    Ping,
+    Pong,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebSocketMessage {
+    pub timestamp: DateTime<Utc>,
+    pub direction: Direction,
+    pub opcode: WebSocketOpcode,
+    pub payload: Vec<u8>,
+}
+
+impl Flow {
+    pub fn new(request: FlowRequest) -> Self {
+        Self {
+            id: 0,
+            started_at: Utc::now(),
+            request,
+            response: None,
+            duration_ms: None,
+            timings: None,
+            tls: None,
+            websocket_messages: Vec::new(),
+            tags: Vec::new(),
+            notes: None,
+            replay_of: None,
+            original_uri: None,
+        }
+    }
+
+    pub fn host(&self) -> Option<String> {
+        self.request
+            .uri
+            .parse::<hyper::Uri>()
+            .ok()
+            .and_then(|uri| uri.host().map(|host| host.to_string()))
+            .or_else(|| self.request.header("host").map(|host| host.to_string()))
+    }
+}
+
+impl FlowRequest {
+    pub fn new(method: &str, uri: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            uri: uri.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    // Case-insensitive lookup of the first header with the given name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    // Replace every header with the given name by a single new value
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        set_header(&mut self.headers, name, value);
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    // The body with its Content-Encoding and charset undone on demand; `body` keeps the bytes as sent
+    pub fn decoded_body(&self) -> Body<'_> {
+        Body::new(&self.headers, &self.body)
+    }
+
+    // Replace the decoded content, re-encoding it or dropping Content-Encoding as the policy says
+    pub fn set_decoded_body(&mut self, content: &[u8], policy: EncodingPolicy) -> Result<bool> {
+        body::set_decoded(&mut self.headers, &mut self.body, content, policy)
+    }
+
+    pub fn set_body_text(&mut self, text: &str, policy: EncodingPolicy) -> Result<bool> {
+        body::set_text(&mut self.headers, &mut self.body, text, policy)
+    }
+
+    pub fn to_http(&self) -> Result<hyper::Request<Bytes>> {
+        let mut builder = hyper::Request::builder()
+            .method(self.method.as_str())
+            .uri(self.uri.as_str());
+
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        builder
+            .body(Bytes::from(self.body.clone()))
+            .with_context(|| format!("Invalid request {} {}", self.method, self.uri))
+    }
+
+    pub fn from_http(request: hyper::Request<Bytes>) -> Self {
+        let (parts, body) = request.into_parts();
+        Self {
+            method: parts.method.to_string(),
+            uri: parts.uri.to_string(),
+            version: version_string(parts.version),
+            headers: headers_from_map(&parts.headers),
+            body: body.to_vec(),
+        }
+    }
+}
+
+impl FlowResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            version: "HTTP/1.1".to_string(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        set_header(&mut self.headers, name, value);
+    }
+
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    pub fn decoded_body(&self) -> Body<'_> {
+        Body::new(&self.headers, &self.body)
+    }
+
+    pub fn set_decoded_body(&mut self, content: &[u8], policy: EncodingPolicy) -> Result<bool> {
+        body::set_decoded(&mut self.headers, &mut self.body, content, policy)
+    }
+
+    pub fn set_body_text(&mut self, text: &str, policy: EncodingPolicy) -> Result<bool> {
+        body::set_text(&mut self.headers, &mut self.body, text, policy)
+    }
+
+    pub fn to_http(&self) -> Result<hyper::Response<Bytes>> {
+        let mut builder = hyper::Response::builder().status(self.status);
+
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+
+        builder
+            .body(Bytes::from(self.body.clone()))
+            .with_context(|| format!("Invalid response with status {}", self.status))
+    }
+
+    pub fn from_http(response: hyper::Response<Bytes>) -> Self {
+        let (parts, body) = response.into_parts();
+        Self {
+            status: parts.status.as_u16(),
+            version: version_string(parts.version),
+            headers: headers_from_map(&parts.headers),
+            body: body.to_vec(),
+        }
+    }
+}
+
+impl WebSocketOpcode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Binary => "binary",
+            Self::Ping => "ping",
+            Self::Pong => "pong",
+            Self::Close => "close",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "binary" => Some(Self::Binary),
+            "ping" => Some(Self::Ping),
+            "pong" => Some(Self::Pong),
+            "close" => Some(Self::Close),
+            _ => None,
+        }
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn set_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    let mut replaced = false;
+    headers.retain_mut(|(key, existing)| {
+        if !key.eq_ignore_ascii_case(name) {
+            return true;
+        }
+        if replaced {
+            return false;
+        }
+        *existing = value.to_string();
+        replaced = true;
+        true
+    });
+
+    if !replaced {
+        headers.push((name.to_string(), value.to_string()));
+    }
+}
+
+// Convert hyper header maps into the ordered name/value pairs stored on a flow
+pub fn headers_from_map(map: &hyper::HeaderMap) -> Vec<(String, String)> {
+    map.iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+pub fn version_string(version: hyper::Version) -> String {
+    match version {
+        hyper::Version::HTTP_09 => "HTTP/0.9",
+        hyper::Version::HTTP_10 => "HTTP/1.0",
+        hyper::Version::HTTP_2 => "HTTP/2",
+        hyper::Version::HTTP_3 => "HTTP/3",
+        _ => "HTTP/1.1",
+    }
+    .to_string()
+}
@@ -0,0 +1,111 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::flow::model::{FlowRequest, FlowResponse};
+
+// Render a request in HTTP/1.1 wire format, using origin-form with a Host header
+pub fn format_request(request: &FlowRequest) -> Vec<u8> {
+    let uri = request.uri.parse::<hyper::Uri>().ok();
+    let target = uri
+        .as_ref()
+        .and_then(|uri| uri.path_and_query())
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri.clone());
+
+    let mut raw = format!("{} {} HTTP/1.1\r\n", request.method, target).into_bytes();
+
+    let authority = uri.as_ref().and_then(|uri| uri.authority()).map(|a| a.to_string());
+    if let (None, Some(authority)) = (request.header("host"), authority) {
+        raw.extend_from_slice(format!("Host: {}\r\n", authority).as_bytes());
+    }
+
+    for (name, value) in &request.headers {
+        raw.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    raw.extend_from_slice(b"\r\n");
+    raw.extend_from_slice(&request.body);
+    raw
+}
+
+pub fn format_response(response: &FlowResponse) -> Vec<u8> {
+    let reason = http::StatusCode::from_u16(response.status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("");
+
+    let mut raw = format!("{} {} {}\r\n", response.version, response.status, reason).into_bytes();
+    for (name, value) in &response.headers {
+        raw.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+    }
+    raw.extend_from_slice(b"\r\n");
+    raw.extend_from_slice(&response.body);
+    raw
+}
+
+// Parse a hand-written HTTP/1.1 request. Origin-form targets are resolved against
+// the Host header using https or http as requested; the body is everything after the
+// blank line, so Content-Length does not need to be kept in sync while editing.
+pub fn parse_request(raw: &[u8], https: bool) -> Result<FlowRequest> {
+    let start = raw.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(raw.len());
+    let (head, body) = split_head(&raw[start..]);
+    let head = std::str::from_utf8(head).context("Request head is not valid UTF-8")?;
+    let mut lines = head.lines();
+
+    let request_line = lines.next().ok_or_else(|| anyhow!("Request is empty"))?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        bail!("Invalid request line: {}", request_line);
+    };
+    let version = parts.next().unwrap_or("HTTP/1.1");
+
+    let mut headers = Vec::new();
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header line: {}", line))?;
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let uri = if target.contains("://") {
+        target.to_string()
+    } else {
+        let host = headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+            .map(|(_, value)| value.as_str())
+            .ok_or_else(|| anyhow!("Request has no Host header and no absolute URL"))?;
+        let scheme = if https { "https" } else { "http" };
+        format!("{}://{}{}", scheme, host, target)
+    };
+
+    uri.parse::<hyper::Uri>()
+        .with_context(|| format!("Invalid request target {}", uri))?;
+
+    // Editors tend to leave trailing newlines, which should not become a body
+    let body = if body.iter().all(|b| b.is_ascii_whitespace()) {
+        Vec::new()
+    } else {
+        body.to_vec()
+    };
+
+    Ok(FlowRequest {
+        method: method.to_string(),
+        uri,
+        version: version.to_string(),
+        headers,
+        body,
+    })
+}
+
+// Split at the first blank line, accepting both CRLF and bare LF line endings
+fn split_head(raw: &[u8]) -> (&[u8], &[u8]) {
+    for i in (0..raw.len()).filter(|&i| raw[i] == b'\n') {
+        let rest = &raw[i + 1..];
+        if rest.starts_with(b"\r\n") {
+            return (&raw[..i], &rest[2..]);
+        }
+        if rest.starts_with(b"\n") {
+            return (&raw[..i], &rest[1..]);
+        }
+    }
+    (raw, &[])
+}
@@ -0,0 +1,250 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::SecondsFormat;
+use std::io::Write;
+
+use crate::flow::body::Body;
+use crate::flow::cookie::{parse_cookie_header, parse_set_cookie};
+use crate::flow::model::{Flow, FlowRequest, FlowResponse};
+use crate::har::model::{
+    Content, Cookie, Creator, Entry, Har, Log, NameValue, PostData, PostParam, Request, Response,
+    Timings,
+};
+
+// Convert captured flows into a HAR 1.2 document
+pub fn flows_to_har(flows: &[Flow]) -> Har {
+    Har {
+        log: Log {
+            version: "1.2".to_string(),
+            creator: Creator {
+                name: "ferrum".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+            pages: Vec::new(),
+            entries: flows.iter().map(flow_to_entry).collect(),
+        },
+    }
+}
+
+// Serialize captured flows as pretty-printed HAR JSON
+pub fn write_har<W: Write>(flows: &[Flow], writer: W) -> Result<()> {
+    serde_json::to_writer_pretty(writer, &flows_to_har(flows)).context("Failed to write HAR")
+}
+
+pub fn flow_to_entry(flow: &Flow) -> Entry {
+    let timings = match flow.timings {
+        Some(timings) => Timings {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: timings.send_ms,
+            wait: timings.wait_ms,
+            receive: timings.receive_ms,
+            ssl: -1.0,
+        },
+        None => Timings {
+            blocked: -1.0,
+            dns: -1.0,
+            connect: -1.0,
+            send: 0.0,
+            wait: flow.duration_ms.unwrap_or(0) as f64,
+            receive: 0.0,
+            ssl: -1.0,
+        },
+    };
+
+    Entry {
+        pageref: None,
+        started_date_time: flow.started_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+        time: timings.send + timings.wait + timings.receive,
+        request: request_to_har(&flow.request, &absolute_url(flow)),
+        response: match &flow.response {
+            Some(response) => response_to_har(response),
+            None => empty_response(),
+        },
+        cache: serde_json::json!({}),
+        timings,
+        server_ip_address: None,
+        comment: flow.notes.clone(),
+    }
+}
+
+// HAR requires absolute URLs; rebuild one from the Host header if necessary
+fn absolute_url(flow: &Flow) -> String {
+    if flow.request.uri.contains("://") {
+        return flow.request.uri.clone();
+    }
+
+    let scheme = if flow.tls.is_some() { "https" } else { "http" };
+    let host = flow.request.header("host").unwrap_or("localhost");
+    format!("{}://{}{}", scheme, host, flow.request.uri)
+}
+
+fn request_to_har(request: &FlowRequest, url: &str) -> Request {
+    let cookies = request
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("cookie"))
+        .flat_map(|(_, value)| parse_cookie_header(value))
+        .map(|(name, value)| Cookie {
+            name,
+            value,
+            ..Default::default()
+        })
+        .collect();
+
+    let query_string = url::Url::parse(url)
+        .map(|url| {
+            url.query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.into_owned(),
+                    value: value.into_owned(),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let post_data = if request.body.is_empty() {
+        None
+    } else {
+        Some(post_data(request))
+    };
+
+    Request {
+        method: request.method.clone(),
+        url: url.to_string(),
+        http_version: request.version.clone(),
+        cookies,
+        headers: name_values(&request.headers),
+        query_string,
+        post_data,
+        headers_size: -1,
+        body_size: request.body.len() as i64,
+    }
+}
+
+fn post_data(request: &FlowRequest) -> PostData {
+    let mime_type = request.header("content-type").unwrap_or("").to_string();
+
+    // HAR has no encoding for post data, so binary bodies are exported lossily
+    let body = request.decoded_body();
+    let (text, binary) = match exact_text(&body) {
+        Some(text) => (text, false),
+        None => (body.text().into_owned(), true),
+    };
+
+    let params = if !binary && mime_type.starts_with("application/x-www-form-urlencoded") {
+        url::form_urlencoded::parse(text.as_bytes())
+            .map(|(name, value)| PostParam {
+                name: name.into_owned(),
+                value: Some(value.into_owned()),
+                file_name: None,
+                content_type: None,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    PostData {
+        mime_type,
+        text,
+        params,
+    }
+}
+
+// The decoded content as text, if it is valid in the body's charset
+fn exact_text(body: &Body<'_>) -> Option<String> {
+    let content = body.decoded_or_raw();
+    body.charset()
+        .decode_without_bom_handling_and_without_replacement(content)
+        .map(|text| text.into_owned())
+}
+
+fn response_to_har(response: &FlowResponse) -> Response {
+    let cookies = response
+        .headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, value)| parse_set_cookie(value))
+        .map(|cookie| Cookie {
+            name: cookie.name,
+            value: cookie.value,
+            path: cookie.path,
+            domain: cookie.domain,
+            expires: cookie.expires,
+            http_only: Some(cookie.http_only),
+            secure: Some(cookie.secure),
+        })
+        .collect();
+
+    let mime_type = response.header("content-type").unwrap_or("").to_string();
+
+    // Content is exported decompressed. Text is embedded as-is, anything else
+    // is base64 encoded.
+    let body = response.decoded_body();
+    let content = body.decoded_or_raw();
+    let (text, encoding) = if content.is_empty() {
+        (None, None)
+    } else {
+        match exact_text(&body) {
+            Some(text) => (Some(text), None),
+            None => (Some(BASE64.encode(content)), Some("base64".to_string())),
+        }
+    };
+
+    Response {
+        status: response.status as i64,
+        status_text: http::StatusCode::from_u16(response.status)
+            .ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or("")
+            .to_string(),
+        http_version: response.version.clone(),
+        cookies,
+        headers: name_values(&response.headers),
+        content: Content {
+            size: content.len() as i64,
+            compression: (content.len() != response.body.len())
+                .then(|| content.len() as i64 - response.body.len() as i64),
+            mime_type,
+            text,
+            encoding,
+        },
+        redirect_url: response.header("location").unwrap_or("").to_string(),
+        headers_size: -1,
+        body_size: response.body.len() as i64,
+    }
+}
+
+// Placeholder for flows that never received a response
+fn empty_response() -> Response {
+    Response {
+        status: 0,
+        status_text: String::new(),
+        http_version: String::new(),
+        cookies: Vec::new(),
+        headers: Vec::new(),
+        content: Content {
+            size: 0,
+            compression: None,
+            mime_type: String::new(),
+            text: None,
+            encoding: None,
+        },
+        redirect_url: String::new(),
+        headers_size: -1,
+        body_size: -1,
+    }
+}
+
+fn name_values(headers: &[(String, String)]) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.clone(),
+            value: value.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::flow::body::{encode_body, Body, EncodingPolicy};
+use crate::flow::model::{Flow, FlowId, FlowRequest, FlowResponse, Timings};
+use crate::har::model::{Entry, Har, NameValue};
+use crate::project::store::Project;
+
+pub fn read_har<R: Read>(reader: R) -> Result<Har> {
+    serde_json::from_reader(reader).context("Failed to parse HAR")
+}
+
+// Convert every entry of a HAR document into a flow
+pub fn har_to_flows(har: &Har) -> Result<Vec<Flow>> {
+    har.log.entries.iter().map(entry_to_flow).collect()
+}
+
+pub fn entry_to_flow(entry: &Entry) -> Result<Flow> {
+    let mut request_headers = headers(&entry.request.headers);
+    let request_body = match &entry.request.post_data {
+        Some(post_data) => decoded_body(&mut request_headers, &post_data.text, None)?,
+        None => Vec::new(),
+    };
+
+    let mut flow = Flow::new(FlowRequest {
+        method: entry.request.method.clone(),
+        uri: entry.request.url.clone(),
+        version: entry.request.http_version.clone(),
+        headers: request_headers,
+        body: request_body,
+    });
+
+    flow.started_at = DateTime::parse_from_rfc3339(&entry.started_date_time)
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+
+    // Browsers record aborted requests with a zero or -1 status
+    let status = u16::try_from(entry.response.status)
+        .ok()
+        .filter(|&status| http::StatusCode::from_u16(status).is_ok());
+    if let Some(status) = status {
+        let content = &entry.response.content;
+        let mut response_headers = headers(&entry.response.headers);
+        let body = match &content.text {
+            Some(text) => decoded_body(&mut response_headers, text, content.encoding.as_deref())?,
+            None => Vec::new(),
+        };
+
+        flow.response = Some(FlowResponse {
+            status,
+            version: entry.response.http_version.clone(),
+            headers: response_headers,
+            body,
+        });
+    }
+
+    flow.timings = Some(Timings {
+        send_ms: entry.timings.send.max(0.0),
+        wait_ms: entry.timings.wait.max(0.0),
+        receive_ms: entry.timings.receive.max(0.0),
+    });
+    flow.duration_ms = Some(entry.time.max(0.0) as u64);
+    flow.notes = entry.comment.clone();
+
+    Ok(flow)
+}
+
+// Load a HAR file into a project as ordinary flows, returning the new flow IDs
+pub fn import_har_file<P: AsRef<Path>>(path: P, project: &Project) -> Result<Vec<FlowId>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let har = read_har(BufReader::new(file))?;
+
+    let mut ids = Vec::with_capacity(har.log.entries.len());
+    for (index, entry) in har.log.entries.iter().enumerate() {
+        match entry_to_flow(entry) {
+            Ok(flow) => ids.push(project.save_flow(&flow)?),
+            Err(e) => warn!("Skipping HAR entry {}: {:#}", index, e),
+        }
+    }
+
+    info!("Imported {} flow(s) from {}", ids.len(), path.display());
+    Ok(ids)
+}
+
+// HAR content is stored decompressed, so the body is kept that way: Content-Encoding
+// is dropped and Content-Length follows. Text goes back into the declared charset.
+fn decoded_body(headers: &mut Vec<(String, String)>, text: &str, encoding: Option<&str>) -> Result<Vec<u8>> {
+    let content = match encoding {
+        Some(encoding) if encoding.eq_ignore_ascii_case("base64") => BASE64
+            .decode(text.trim())
+            .context("Invalid base64 content in HAR entry")?,
+        _ => Body::new(headers, &[]).charset().encode(text).0.into_owned(),
+    };
+    encode_body(headers, &content, EncodingPolicy::Strip)
+}
+
+// HTTP/2 pseudo-headers (":authority" and friends) are not real headers
+fn headers(values: &[NameValue]) -> Vec<(String, String)> {
+    values
+        .iter()
+        .filter(|header| !header.name.starts_with(':'))
+        .map(|header| (header.name.clone(), header.value.clone()))
+        .collect()
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+use crate::flow::body::EncodingPolicy;
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::intercept::script::RequestOutcome;
+use crate::intercept::wire::{WireMessage, WireRequest, WireResponse};
+
+// Tag on flows answered by an external process, or by ferrum when one failed closed
+pub const EXTERNAL_TAG: &str = "external";
+
+// How long a process may take to answer one message
+pub const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+// Restarts in a row without the process answering before it is given up on
+pub const DEFAULT_MAX_RESTARTS: u32 = 5;
+
+// Wait after a restart before the next one may happen, doubled each time up to the maximum
+const RESTART_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+// What happens to traffic when the process fails, times out or reports an error
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailMode {
+    // Let the message through unchanged
+    #[default]
+    Open,
+    // Answer with 502 Bad Gateway instead
+    Closed,
+}
+
+impl FailMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+impl FromStr for FailMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "open" => Ok(Self::Open),
+            "closed" => Ok(Self::Closed),
+            _ => bail!("Unknown fail mode {} (open, closed)", value),
+        }
+    }
+}
+
+// The process to run and how it is treated. Parsed from a command line split
+// the way a POSIX shell would, so arguments can be quoted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalConfig {
+    pub program: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+    pub fail: FailMode,
+    pub max_restarts: u32,
+}
+
+impl ExternalConfig {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            timeout: Duration::from_millis(DEFAULT_TIMEOUT_MS),
+            fail: FailMode::Open,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+        }
+    }
+
+    pub fn with_arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.to_string());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_fail_mode(mut self, fail: FailMode) -> Self {
+        self.fail = fail;
+        self
+    }
+
+    pub fn with_max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+}
+
+impl FromStr for ExternalConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let words = shlex::split(s).ok_or_else(|| anyhow!("Unbalanced quotes in {}", s))?;
+        let mut words = words.iter();
+        let program = words.next().ok_or_else(|| anyhow!("Expected a command to run"))?;
+        Ok(words.fold(Self::new(program), |config, arg| config.with_arg(arg)))
+    }
+}
+
+// The command line, quoted so that it parses back to the same config
+impl fmt::Display for ExternalConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quote = |word: &str| shlex::try_quote(word).map_or_else(|_| word.to_string(), |quoted| quoted.into_owned());
+        write!(f, "{}", quote(&self.program))?;
+        for arg in &self.args {
+            write!(f, " {}", quote(arg))?;
+        }
+        Ok(())
+    }
+}
+
+// One line sent to the process
+#[derive(Serialize)]
+struct ExternalCall<'a> {
+    id: u64,
+    hook: &'static str,
+    #[serde(flatten)]
+    message: &'a WireMessage,
+}
+
+// One line read back; `id` says which call it answers
+#[derive(Deserialize)]
+struct ExternalReply {
+    id: u64,
+    #[serde(flatten)]
+    message: WireMessage,
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<WireMessage>>>>;
+
+// Hands traffic to long-running child processes over JSON lines on stdin and
+// stdout. Each message is written as
+//
+//     {"id": 1, "hook": "request", "request": {"method": ..., "uri": ..., "headers": [[name, value]], "body": base64}}
+//     {"id": 2, "hook": "response", "request": {...}, "response": {"status": ..., "headers": [...], "body": base64}}
+//
+// and the process answers with a line carrying the same `id` and any of
+// `request`, `response`, `tags` and `error`, in the same shape as plugin hooks
+// return, with bodies decoded. Replies may come in any order, so a process can
+// work on several messages at once. Lines that are not JSON are logged and
+// skipped; stderr is passed through. A process that exits is started again for
+// the next message, with a growing wait between restarts while it keeps exiting
+// without answering, until `max_restarts` is reached.
+pub struct ExternalInterceptor {
+    processes: Vec<Process>,
+    encoding: EncodingPolicy,
+}
+
+struct Process {
+    config: ExternalConfig,
+    next_id: AtomicU64,
+    state: tokio::sync::Mutex<ProcessState>,
+}
+
+#[derive(Default)]
+struct ProcessState {
+    connection: Option<Connection>,
+    // Restarts since the process last answered, and when the next one may happen
+    restarts: u32,
+    retry_at: Option<Instant>,
+}
+
+struct Connection {
+    // Held so the child is killed when the connection is dropped
+    _child: Child,
+    stdin: ChildStdin,
+    pending: Pending,
+    closed: Arc<AtomicBool>,
+}
+
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// What ferrum answers with when a process failed closed
+fn failure_response(error: &anyhow::Error) -> FlowResponse {
+    let mut response = FlowResponse::new(502);
+    response.set_header("Content-Type", "text/plain; charset=utf-8");
+    response.body = format!("External interceptor failed: {:#}", error).into_bytes();
+    response
+}
+
+impl Process {
+    fn start(config: &ExternalConfig) -> Result<Connection> {
+        let mut child = Command::new(&config.program)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", config))?;
+        let stdin = child.stdin.take().context("No stdin for the external process")?;
+        let stdout = child.stdout.take().context("No stdout for the external process")?;
+
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let name = config.to_string();
+        let (reader_pending, reader_closed) = (Arc::clone(&pending), Arc::clone(&closed));
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            loop {
+                let line = match lines.next_line().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Failed to read from {}: {}", name, e);
+                        break;
+                    }
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<ExternalReply>(&line) {
+                    Ok(reply) => match lock(&reader_pending).remove(&reply.id) {
+                        Some(sender) => {
+                            let _ = sender.send(reply.message);
+                        }
+                        None => debug!("Dropping late reply {} from {}", reply.id, name),
+                    },
+                    Err(e) => warn!("Skipping invalid line from {}: {}", name, e),
+                }
+            }
+            reader_closed.store(true, Ordering::SeqCst);
+            // Dropping the senders fails every call still waiting
+            lock(&reader_pending).clear();
+            warn!("External interceptor {} exited", name);
+        });
+
+        Ok(Connection {
+            _child: child,
+            stdin,
+            pending,
+            closed,
+        })
+    }
+
+    // Start the process again, unless it is still waiting out the delay since
+    // its last restart or has used up its restarts without answering
+    fn restart(&self, state: &mut ProcessState) -> Result<Connection> {
+        if state.restarts >= self.config.max_restarts {
+            bail!("{} keeps exiting; gave up after {} restart(s)", self.config, state.restarts);
+        }
+        let now = Instant::now();
+        if let Some(retry_at) = state.retry_at
+            && now < retry_at
+        {
+            bail!("{} exited; restarting it in {} ms", self.config, (retry_at - now).as_millis());
+        }
+
+        state.restarts += 1;
+        let backoff = RESTART_BACKOFF.saturating_mul(1 << (state.restarts - 1).min(16));
+        state.retry_at = Some(now + backoff.min(MAX_RESTART_BACKOFF));
+        info!(
+            "Restarting external interceptor {} ({} of {})",
+            self.config, state.restarts, self.config.max_restarts
+        );
+        Self::start(&self.config)
+    }
+
+    async fn send(&self, id: u64, line: &[u8], sender: oneshot::Sender<WireMessage>) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let connection = match state.connection.take() {
+            Some(connection) if !connection.closed.load(Ordering::SeqCst) => state.connection.insert(connection),
+            _ => {
+                let connection = self.restart(&mut state)?;
+                state.connection.insert(connection)
+            }
+        };
+
+        lock(&connection.pending).insert(id, sender);
+        let written = async {
+            connection.stdin.write_all(line).await?;
+            connection.stdin.flush().await
+        };
+        if let Err(e) = written.await {
+            lock(&connection.pending).remove(&id);
+            connection.closed.store(true, Ordering::SeqCst);
+            return Err(e).with_context(|| format!("Failed to write to {}", self.config));
+        }
+        Ok(())
+    }
+
+    async fn call(&self, hook: &'static str, message: &WireMessage) -> Result<WireMessage> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut line = serde_json::to_vec(&ExternalCall { id, hook, message })?;
+        line.push(b'\n');
+
+        let (sender, receiver) = oneshot::channel();
+        let exchange = async {
+            self.send(id, &line, sender).await?;
+            receiver.await.map_err(|_| anyhow!("{} exited before answering", self.config))
+        };
+        let Ok(reply) = tokio::time::timeout(self.config.timeout, exchange).await else {
+            if let Some(connection) = self.state.lock().await.connection.as_ref() {
+                lock(&connection.pending).remove(&id);
+            }
+            bail!("{} did not answer within {} ms", self.config, self.config.timeout.as_millis());
+        };
+        let reply = reply?;
+
+        // A process that answers is working again, so it gets its restarts back
+        let mut state = self.state.lock().await;
+        state.restarts = 0;
+        state.retry_at = None;
+
+        match reply.error {
+            Some(error) => Err(anyhow!("{} reported: {}", self.config, error)),
+            None => Ok(reply),
+        }
+    }
+}
+
+impl Default for ExternalInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExternalInterceptor {
+    pub fn new() -> Self {
+        Self {
+            processes: Vec::new(),
+            encoding: EncodingPolicy::default(),
+        }
+    }
+
+    // How bodies changed by a process are encoded before they are sent on
+    pub fn with_encoding_policy(mut self, policy: EncodingPolicy) -> Self {
+        self.encoding = policy;
+        self
+    }
+
+    // Start a process and add it after the ones already running
+    pub fn spawn(&mut self, config: ExternalConfig) -> Result<()> {
+        let connection = Process::start(&config)?;
+        info!("Started external interceptor {} (fail {})", config, config.fail.as_str());
+        self.processes.push(Process {
+            config,
+            next_id: AtomicU64::new(1),
+            state: tokio::sync::Mutex::new(ProcessState {
+                connection: Some(connection),
+                ..Default::default()
+            }),
+        });
+        Ok(())
+    }
+
+    pub fn configs(&self) -> Vec<&ExternalConfig> {
+        self.processes.iter().map(|process| &process.config).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+
+    // Pass a request to every process, stopping at the first that answers it.
+    // A process failing closed answers with 502.
+    pub async fn on_request(&self, request: &mut FlowRequest) -> RequestOutcome {
+        let mut outcome = RequestOutcome::default();
+        for process in &self.processes {
+            let message = WireMessage {
+                request: Some(WireRequest::from(&*request)),
+                ..Default::default()
+            };
+            let reply = match process.call("request", &message).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    warn!("External interceptor failed on {} {}: {:#}", request.method, request.uri, e);
+                    if process.config.fail == FailMode::Closed {
+                        outcome.response = Some(failure_response(&e));
+                        break;
+                    }
+                    continue;
+                }
+            };
+            outcome.tags.extend(reply.tags);
+            if let Some(changed) = reply.request
+                && let Err(e) = changed.apply(request, self.encoding)
+            {
+                warn!("Ignoring the request from {}: {:#}", process.config, e);
+            }
+            if let Some(response) = reply.response {
+                match response.into_response(self.encoding) {
+                    Ok(response) => {
+                        outcome.response = Some(response);
+                        break;
+                    }
+                    Err(e) => warn!("Ignoring the response from {}: {:#}", process.config, e),
+                }
+            }
+        }
+        outcome
+    }
+
+    // Pass a response to every process, returning the tags they added.
+    // A process failing closed replaces the response with 502.
+    pub async fn on_response(&self, request: &FlowRequest, response: &mut FlowResponse) -> Vec<String> {
+        let mut tags = Vec::new();
+        for process in &self.processes {
+            let message = WireMessage {
+                request: Some(WireRequest::from(request)),
+                response: Some(WireResponse::from(&*response)),
+                ..Default::default()
+            };
+            let reply = match process.call("response", &message).await {
+                Ok(reply) => reply,
+                Err(e) => {
+                    warn!("External interceptor failed on the response to {}: {:#}", request.uri, e);
+                    if process.config.fail == FailMode::Closed {
+                        *response = failure_response(&e);
+                        tags.push(EXTERNAL_TAG.to_string());
+                        break;
+                    }
+                    continue;
+                }
+            };
+            tags.extend(reply.tags);
+            if let Some(changed) = reply.response
+                && let Err(e) = changed.apply(response, self.encoding)
+            {
+                warn!("Ignoring the response from {}: {:#}", process.config, e);
+            }
+        }
+        tags
+    }
+}
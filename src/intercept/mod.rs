@@ -1,2 +1,6 @@
+pub mod external;
+pub mod plugin;
 pub mod request;
 pub mod response;
+pub mod script;
+pub mod wire;
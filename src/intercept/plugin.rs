@@ -0,0 +1,596 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Handle;
+use wasmi::{AsContextMut, Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use crate::flow::body::EncodingPolicy;
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::intercept::script::RequestOutcome;
+use crate::intercept::wire::{WireMessage, WireRequest, WireResponse};
+use crate::project::scope::host_matches;
+use crate::proxy::upstream::UpstreamClient;
+use crate::sitemap::links::request_url;
+
+// Tag on flows answered by a plugin instead of the upstream server
+pub const PLUGIN_TAG: &str = "plugin";
+
+// Instructions one hook call may execute before it is stopped
+pub const DEFAULT_MAX_FUEL: u64 = 100_000_000;
+
+// Linear memory a plugin may grow to, in bytes
+pub const DEFAULT_MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+// How long an HTTP sub-request may take
+pub const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+// Module name of the host functions plugins import
+const HOST_MODULE: &str = "ferrum";
+
+fn default_max_fuel() -> u64 {
+    DEFAULT_MAX_FUEL
+}
+
+fn default_max_memory() -> usize {
+    DEFAULT_MAX_MEMORY
+}
+
+fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+// One plugin and the limits it runs under
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginConfig {
+    pub path: PathBuf,
+    // Shown in logs; defaults to the file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Handed to the plugin's `configure` export as JSON
+    #[serde(default)]
+    pub config: serde_json::Value,
+    #[serde(default = "default_max_fuel")]
+    pub max_fuel: u64,
+    #[serde(default = "default_max_memory")]
+    pub max_memory: usize,
+    // Host patterns the plugin may send sub-requests to; none by default
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl PluginConfig {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            name: None,
+            config: serde_json::Value::Null,
+            max_fuel: DEFAULT_MAX_FUEL,
+            max_memory: DEFAULT_MAX_MEMORY,
+            allow_hosts: Vec::new(),
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+        }
+    }
+
+    pub fn with_config(mut self, config: serde_json::Value) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_max_fuel(mut self, fuel: u64) -> Self {
+        self.max_fuel = fuel;
+        self
+    }
+
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = bytes;
+        self
+    }
+
+    pub fn with_allow_host(mut self, pattern: &str) -> Self {
+        self.allow_hosts.push(pattern.to_string());
+        self
+    }
+
+    pub fn with_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    // A JSON array of plugin configurations. Relative paths are taken from the
+    // file's directory.
+    pub fn load(path: &Path) -> Result<Vec<Self>> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut configs: Vec<Self> =
+            serde_json::from_str(&text).with_context(|| format!("Invalid plugin configuration in {}", path.display()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for config in &mut configs {
+            config.path = base.join(&config.path);
+        }
+        Ok(configs)
+    }
+
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .path
+                .file_name()
+                .map_or_else(|| self.path.display().to_string(), |name| name.to_string_lossy().into_owned()),
+        }
+    }
+}
+
+// A plugin path with the default configuration
+impl FromStr for PluginConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            bail!("Expected a path to a .wasm plugin");
+        }
+        Ok(Self::new(Path::new(s)))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Hook {
+    Request,
+    Response,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::Request => "on_request",
+            Hook::Response => "on_response",
+        }
+    }
+}
+
+// Runs WebAssembly plugins. A plugin is a module exporting
+//
+//     memory
+//     alloc(len: i32) -> i32                 // room for a message the host passes in
+//     dealloc(ptr: i32, len: i32)            // optional; called when the host is done with a buffer
+//     configure(ptr: i32, len: i32) -> i32   // optional; gets the plugin's config, non-zero rejects it
+//     on_request(ptr: i32, len: i32) -> i64  // optional
+//     on_response(ptr: i32, len: i32) -> i64 // optional
+//
+// Hooks get a JSON `WireMessage` and return 0 to leave it alone, or a pointer
+// and length packed as `ptr << 32 | len` to a `WireMessage` with their changes.
+// The only imports available are the `ferrum` host functions:
+//
+//     log(level: i32, ptr: i32, len: i32)    // 0 error, 1 warn, 2 info, 3 debug, 4 trace
+//     http_request(ptr: i32, len: i32) -> i64
+//
+// `http_request` takes a `WireRequest` and returns a `WireMessage` holding the
+// response or an error, allocated with the plugin's `alloc`. Only hosts listed in
+// the plugin's `allow_hosts` can be reached, and sub-requests bypass the proxy's
+// own interceptors. Each plugin handles one message at a time; a hook that traps,
+// runs out of fuel or returns invalid JSON leaves the message unchanged, and a
+// plugin that trapped gets a fresh instance for the next message.
+pub struct PluginInterceptor {
+    engine: Engine,
+    upstream: UpstreamClient,
+    plugins: Vec<Arc<Plugin>>,
+    encoding: EncodingPolicy,
+}
+
+struct Plugin {
+    name: String,
+    max_fuel: u64,
+    runtime: Mutex<Runtime>,
+    // What it takes to start the plugin again after a trap
+    engine: Engine,
+    module: Module,
+    config: PluginConfig,
+    upstream: UpstreamClient,
+}
+
+struct Runtime {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    dealloc: Option<TypedFunc<(i32, i32), ()>>,
+    on_request: Option<TypedFunc<(i32, i32), i64>>,
+    on_response: Option<TypedFunc<(i32, i32), i64>>,
+}
+
+// What host functions can reach while a plugin runs
+struct HostState {
+    name: String,
+    limits: StoreLimits,
+    upstream: UpstreamClient,
+    allow_hosts: Vec<String>,
+    timeout: Duration,
+    // Runtime of the hook call in progress, for driving sub-requests
+    handle: Option<Handle>,
+}
+
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn unpack(packed: i64) -> (usize, usize) {
+    let packed = packed as u64;
+    ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize)
+}
+
+fn pack(ptr: i32, len: usize) -> i64 {
+    (((ptr as u32) as u64) << 32 | len as u64) as i64
+}
+
+// Copy bytes into memory the plugin allocated for them
+fn write_guest(
+    mut ctx: impl AsContextMut<Data = HostState>,
+    memory: Memory,
+    alloc: &TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> Result<i32, wasmi::Error> {
+    let len = i32::try_from(bytes.len()).map_err(|_| wasmi::Error::new("message too large for the plugin"))?;
+    let ptr = alloc.call(&mut ctx, len)?;
+    memory.write(&mut ctx, ptr as u32 as usize, bytes)?;
+    Ok(ptr)
+}
+
+fn read_guest(data: &[u8], ptr: usize, len: usize) -> Result<&[u8], wasmi::Error> {
+    ptr.checked_add(len)
+        .and_then(|end| data.get(ptr..end))
+        .ok_or_else(|| wasmi::Error::new("pointer out of bounds"))
+}
+
+fn typed_hook<Params, Results>(
+    store: &Store<HostState>,
+    instance: &Instance,
+    name: &str,
+) -> Result<Option<TypedFunc<Params, Results>>>
+where
+    Params: wasmi::WasmParams,
+    Results: wasmi::WasmResults,
+{
+    match instance.get_func(store, name) {
+        Some(func) => Ok(Some(
+            func.typed(store).map_err(|e| anyhow!("Export {} has the wrong signature: {}", name, e))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+fn host_log(caller: Caller<'_, HostState>, level: i32, ptr: i32, len: i32) -> Result<(), wasmi::Error> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("plugin has no memory"))?;
+    let message = read_guest(memory.data(&caller), ptr as u32 as usize, len as u32 as usize)?;
+    let message = String::from_utf8_lossy(message);
+    let name = &caller.data().name;
+    match level {
+        0 => error!("[plugin {}] {}", name, message),
+        1 => warn!("[plugin {}] {}", name, message),
+        2 => info!("[plugin {}] {}", name, message),
+        3 => debug!("[plugin {}] {}", name, message),
+        _ => trace!("[plugin {}] {}", name, message),
+    }
+    Ok(())
+}
+
+fn host_http_request(mut caller: Caller<'_, HostState>, ptr: i32, len: i32) -> Result<i64, wasmi::Error> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("plugin has no memory"))?;
+    let alloc = caller
+        .get_export("alloc")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| wasmi::Error::new("plugin has no alloc export"))?
+        .typed::<i32, i32>(&caller)?;
+
+    let input = read_guest(memory.data(&caller), ptr as u32 as usize, len as u32 as usize)?;
+    let result = match sub_request(caller.data(), input) {
+        Ok(response) => WireMessage {
+            response: Some(response),
+            ..Default::default()
+        },
+        Err(e) => {
+            debug!("[plugin {}] Sub-request failed: {:#}", caller.data().name, e);
+            WireMessage {
+                error: Some(format!("{:#}", e)),
+                ..Default::default()
+            }
+        }
+    };
+    let output = serde_json::to_vec(&result).map_err(|e| wasmi::Error::new(e.to_string()))?;
+    let out_ptr = write_guest(&mut caller, memory, &alloc, &output)?;
+    Ok(pack(out_ptr, output.len()))
+}
+
+fn sub_request(state: &HostState, input: &[u8]) -> Result<WireResponse> {
+    let wire: WireRequest = serde_json::from_slice(input).context("Invalid sub-request")?;
+    // A sub-request body is compressed to match any Content-Encoding the plugin set
+    let request = wire.into_request(EncodingPolicy::Reencode)?;
+    let url = request_url(&request).with_context(|| format!("Sub-request {} has no host", request.uri))?;
+    let host = url.host_str().unwrap_or_default();
+    if !state.allow_hosts.iter().any(|pattern| host_matches(pattern, host)) {
+        bail!("{} is not in the plugin's allow_hosts", host);
+    }
+    let handle = state.handle.as_ref().context("Sub-requests are only possible during a hook")?;
+
+    debug!("[plugin {}] {} {}", state.name, request.method, request.uri);
+    let (response, _, _) = handle
+        .block_on(tokio::time::timeout(state.timeout, state.upstream.send(&request)))
+        .map_err(|_| anyhow!("Sub-request to {} timed out", url))??;
+    Ok(WireResponse::from(&response))
+}
+
+// A new instance of a plugin's module, configured and ready for hook calls
+fn instantiate(engine: &Engine, module: &Module, config: &PluginConfig, upstream: &UpstreamClient) -> Result<Runtime> {
+    let name = config.display_name();
+    let mut store = Store::new(
+        engine,
+        HostState {
+            name: name.clone(),
+            limits: StoreLimitsBuilder::new()
+                .memory_size(config.max_memory)
+                .instances(1)
+                .build(),
+            upstream: upstream.clone(),
+            allow_hosts: config.allow_hosts.clone(),
+            timeout: Duration::from_millis(config.timeout_ms),
+            handle: None,
+        },
+    );
+    store.limiter(|state| &mut state.limits);
+    store.set_fuel(config.max_fuel).map_err(|e| anyhow!("{}", e))?;
+
+    let mut linker = Linker::<HostState>::new(engine);
+    linker.func_wrap(HOST_MODULE, "log", host_log)?;
+    linker.func_wrap(HOST_MODULE, "http_request", host_http_request)?;
+    let instance = linker
+        .instantiate(&mut store, module)
+        .and_then(|instance| instance.start(&mut store))
+        .map_err(|e| anyhow!("Failed to start plugin {}: {}", name, e))?;
+
+    let memory = instance
+        .get_memory(&store, "memory")
+        .with_context(|| format!("Plugin {} does not export its memory", name))?;
+    let alloc = typed_hook(&store, &instance, "alloc")
+        .and_then(|alloc| alloc.context("missing export alloc"))
+        .with_context(|| format!("Invalid plugin {}", name))?;
+    let mut runtime = Runtime {
+        dealloc: typed_hook(&store, &instance, "dealloc").with_context(|| format!("Invalid plugin {}", name))?,
+        on_request: typed_hook(&store, &instance, "on_request").with_context(|| format!("Invalid plugin {}", name))?,
+        on_response: typed_hook(&store, &instance, "on_response").with_context(|| format!("Invalid plugin {}", name))?,
+        store,
+        memory,
+        alloc,
+    };
+    if runtime.on_request.is_none() && runtime.on_response.is_none() {
+        bail!("Plugin {} exports neither on_request nor on_response", name);
+    }
+
+    if let Some(configure) = typed_hook::<(i32, i32), i32>(&runtime.store, &instance, "configure")? {
+        let input = serde_json::to_vec(&config.config)?;
+        runtime.store.set_fuel(config.max_fuel).map_err(|e| anyhow!("{}", e))?;
+        let ptr = write_guest(&mut runtime.store, runtime.memory, &runtime.alloc, &input)?;
+        let status = configure.call(&mut runtime.store, (ptr, input.len() as i32));
+        let freed = runtime.free(ptr, input.len(), config.max_fuel);
+        let status = status.map_err(|e| anyhow!("Failed to configure plugin {}: {}", name, e))?;
+        freed?;
+        if status != 0 {
+            bail!("Plugin {} rejected its configuration (status {})", name, status);
+        }
+    }
+
+    Ok(runtime)
+}
+
+impl Runtime {
+    fn hook(&self, hook: Hook) -> Option<TypedFunc<(i32, i32), i64>> {
+        match hook {
+            Hook::Request => self.on_request,
+            Hook::Response => self.on_response,
+        }
+    }
+
+    // Hand a buffer back to the plugin, with fuel of its own so that a hook
+    // that used up its fuel does not keep it from being freed
+    fn free(&mut self, ptr: i32, len: usize, fuel: u64) -> Result<()> {
+        if let Some(dealloc) = self.dealloc {
+            self.store.set_fuel(fuel).map_err(|e| anyhow!("{}", e))?;
+            dealloc.call(&mut self.store, (ptr, len as i32))?;
+        }
+        Ok(())
+    }
+
+    // Hand `input` to a hook and return what it wrote back, if anything. The
+    // buffers are freed whether or not the hook succeeds.
+    fn call(&mut self, func: TypedFunc<(i32, i32), i64>, input: &[u8], fuel: u64) -> Result<Option<Vec<u8>>> {
+        self.store.set_fuel(fuel).map_err(|e| anyhow!("{}", e))?;
+        let ptr = write_guest(&mut self.store, self.memory, &self.alloc, input)?;
+        let packed = func.call(&mut self.store, (ptr, input.len() as i32));
+        let freed = self.free(ptr, input.len(), fuel);
+        let packed = packed?;
+        freed?;
+        if packed == 0 {
+            return Ok(None);
+        }
+
+        let (out_ptr, out_len) = unpack(packed);
+        let output = read_guest(self.memory.data(&self.store), out_ptr, out_len).map(<[u8]>::to_vec);
+        let freed = self.free(out_ptr as i32, out_len, fuel);
+        let output = output?;
+        freed?;
+        Ok(Some(output))
+    }
+}
+
+impl Plugin {
+    fn call(&self, hook: Hook, input: &WireMessage, handle: Handle) -> Result<Option<WireMessage>> {
+        let mut runtime = lock(&self.runtime);
+        let Some(func) = runtime.hook(hook) else {
+            return Ok(None);
+        };
+        runtime.store.data_mut().handle = Some(handle);
+        let input = serde_json::to_vec(input)?;
+        let output = runtime.call(func, &input, self.max_fuel);
+        runtime.store.data_mut().handle = None;
+
+        // A trap can leave the instance's memory and globals half updated, so
+        // the next message gets a fresh one
+        if output.is_err() {
+            match instantiate(&self.engine, &self.module, &self.config, &self.upstream) {
+                Ok(fresh) => {
+                    *runtime = fresh;
+                    debug!("Restarted plugin {} after a failed {}", self.name, hook.name());
+                }
+                Err(e) => warn!("Failed to restart plugin {}: {:#}", self.name, e),
+            }
+        }
+
+        match output? {
+            Some(output) => Ok(Some(
+                serde_json::from_slice(&output).with_context(|| format!("Invalid output from {}", hook.name()))?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Default for PluginInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PluginInterceptor {
+    pub fn new() -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Self {
+            engine: Engine::new(&config),
+            upstream: UpstreamClient::new(),
+            plugins: Vec::new(),
+            encoding: EncodingPolicy::default(),
+        }
+    }
+
+    // Send sub-requests through a differently configured upstream client
+    pub fn with_upstream(mut self, upstream: UpstreamClient) -> Self {
+        self.upstream = upstream;
+        self
+    }
+
+    // How bodies changed by a plugin are encoded before they are sent on
+    pub fn with_encoding_policy(mut self, policy: EncodingPolicy) -> Self {
+        self.encoding = policy;
+        self
+    }
+
+    // Instantiate and configure a plugin, adding it after the ones already loaded
+    pub fn load(&mut self, config: &PluginConfig) -> Result<()> {
+        let name = config.display_name();
+        let wasm = std::fs::read(&config.path).with_context(|| format!("Failed to read plugin {}", config.path.display()))?;
+        let module = Module::new(&self.engine, &wasm[..]).map_err(|e| anyhow!("Invalid plugin {}: {}", name, e))?;
+        let runtime = instantiate(&self.engine, &module, config, &self.upstream)?;
+
+        info!("Loaded plugin {} from {}", name, config.path.display());
+        self.plugins.push(Arc::new(Plugin {
+            name,
+            max_fuel: config.max_fuel,
+            runtime: Mutex::new(runtime),
+            engine: self.engine.clone(),
+            module,
+            config: config.clone(),
+            upstream: self.upstream.clone(),
+        }));
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|plugin| plugin.name.as_str()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    // Plugins run on the blocking pool so they can wait for sub-requests
+    async fn run(plugin: &Arc<Plugin>, hook: Hook, input: WireMessage) -> Option<WireMessage> {
+        let handle = Handle::current();
+        let task = Arc::clone(plugin);
+        match tokio::task::spawn_blocking(move || task.call(hook, &input, handle)).await {
+            Ok(Ok(output)) => {
+                if let Some(error) = output.as_ref().and_then(|output| output.error.as_ref()) {
+                    warn!("Plugin {} reported an error in {}: {}", plugin.name, hook.name(), error);
+                }
+                output
+            }
+            Ok(Err(e)) => {
+                warn!("Plugin {} failed in {}: {:#}", plugin.name, hook.name(), e);
+                None
+            }
+            Err(e) => {
+                warn!("Plugin {} failed in {}: {}", plugin.name, hook.name(), e);
+                None
+            }
+        }
+    }
+
+    // Run every plugin's on_request hook, stopping at the first that answers the request
+    pub async fn on_request(&self, request: &mut FlowRequest) -> RequestOutcome {
+        let mut outcome = RequestOutcome::default();
+        for plugin in &self.plugins {
+            let input = WireMessage {
+                request: Some(WireRequest::from(&*request)),
+                ..Default::default()
+            };
+            let Some(output) = Self::run(plugin, Hook::Request, input).await else {
+                continue;
+            };
+            outcome.tags.extend(output.tags);
+            if let Some(changed) = output.request
+                && let Err(e) = changed.apply(request, self.encoding)
+            {
+                warn!("Ignoring the request from plugin {}: {:#}", plugin.name, e);
+            }
+            if let Some(response) = output.response {
+                match response.into_response(self.encoding) {
+                    Ok(response) => {
+                        outcome.response = Some(response);
+                        break;
+                    }
+                    Err(e) => warn!("Ignoring the response from plugin {}: {:#}", plugin.name, e),
+                }
+            }
+        }
+        outcome
+    }
+
+    // Run every plugin's on_response hook, returning the tags they added
+    pub async fn on_response(&self, request: &FlowRequest, response: &mut FlowResponse) -> Vec<String> {
+        let mut tags = Vec::new();
+        for plugin in &self.plugins {
+            let input = WireMessage {
+                request: Some(WireRequest::from(request)),
+                response: Some(WireResponse::from(&*response)),
+                ..Default::default()
+            };
+            let Some(output) = Self::run(plugin, Hook::Response, input).await else {
+                continue;
+            };
+            tags.extend(output.tags);
+            if let Some(changed) = output.response
+                && let Err(e) = changed.apply(response, self.encoding)
+            {
+                warn!("Ignoring the response from plugin {}: {:#}", plugin.name, e);
+            }
+        }
+        tags
+    }
+}
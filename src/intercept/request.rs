@@ -1,16 +1,27 @@
 use anyhow::Result;
-use hyper::{Request, body::Incoming};
-use http_body_util::Empty;
-use bytes::Bytes;
+use hyper::Request;
 use log::info;
 
+use crate::filter::eval::Filter;
+use crate::flow::model::FlowRequest;
+
 pub struct RequestInterceptor {
     enabled: bool,
+    filter: Option<Filter>,
+}
+
+impl Default for RequestInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RequestInterceptor {
     pub fn new() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            filter: None,
+        }
     }
 
     pub fn enable(&mut self) {
@@ -25,6 +36,21 @@ impl RequestInterceptor {
         self.enabled
     }
 
+    // Only intercept requests matching this filter expression
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    // Whether a request should be intercepted; everything is without a filter
+    pub fn matches(&self, request: &FlowRequest) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches_request(request))
+    }
+
     pub async fn intercept<B>(&self, request: Request<B>) -> Result<Request<B>> {
         if !self.enabled {
             return Ok(request);
@@ -1,18 +1,27 @@
 use anyhow::Result;
 use hyper::Response;
 use log::info;
-use bytes::Bytes;
-use http_body_util::combinators::BoxBody;
+
+use crate::filter::eval::Filter;
+use crate::flow::model::{FlowRequest, FlowResponse};
 
 pub struct ResponseInterceptor {
     enabled: bool,
+    filter: Option<Filter>,
 }
 
-type ProxyBody = BoxBody<Bytes, hyper::Error>;
+impl Default for ResponseInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl ResponseInterceptor {
     pub fn new() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            filter: None,
+        }
     }
 
     pub fn enable(&mut self) {
@@ -27,6 +36,21 @@ impl ResponseInterceptor {
         self.enabled
     }
 
+    // Only intercept responses matching this filter expression
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    // Whether a response should be intercepted; everything is without a filter
+    pub fn matches(&self, request: &FlowRequest, response: &FlowResponse) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.matches_response(request, response))
+    }
+
     pub async fn intercept<B>(&self, response: Response<B>) -> Result<Response<B>> {
         if !self.enabled {
             return Ok(response);
@@ -0,0 +1,431 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Array, Blob, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+
+use crate::flow::body::EncodingPolicy;
+use crate::flow::model::{Direction, FlowRequest, FlowResponse, WebSocketMessage};
+use crate::sitemap::links::request_url;
+
+// Tag on flows answered by a script instead of the upstream server
+pub const SCRIPT_TAG: &str = "script";
+
+// Operations one hook call may run before it is stopped
+pub const DEFAULT_MAX_OPERATIONS: u64 = 1_000_000;
+
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_STRING_SIZE: usize = 64 * 1024 * 1024;
+const MAX_ARRAY_SIZE: usize = 100_000;
+const MAX_MAP_SIZE: usize = 10_000;
+
+// What the request hooks made of a request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOutcome {
+    pub tags: Vec<String>,
+    // A response returned by a script, sent to the client instead of forwarding the request
+    pub response: Option<FlowResponse>,
+}
+
+// Runs Rhai scripts defining any of
+//
+//     fn on_request(req) { ... }            // may return response(status, body)
+//     fn on_response(req, res) { ... }
+//     fn on_websocket_message(req, msg) { ... }
+//
+// Scripts run in the order they were loaded and are recompiled when their file
+// changes. A script that fails, or runs out of operations, leaves the message as
+// it was before that script ran; traffic is never held up by a broken script.
+pub struct ScriptInterceptor {
+    engine: Engine,
+    scripts: Vec<Script>,
+    encoding: EncodingPolicy,
+}
+
+struct Script {
+    path: PathBuf,
+    state: Mutex<Compiled>,
+}
+
+struct Compiled {
+    // Modification time and length of the file the AST was compiled from
+    stamp: Option<(SystemTime, u64)>,
+    ast: Arc<AST>,
+}
+
+// Handles passed to scripts. Clones share their message, so changes a script
+// makes through its argument are seen here. Bodies are read and written decoded;
+// `encoding` says how a changed body goes back on the wire.
+#[derive(Clone)]
+struct ScriptRequest {
+    request: Arc<Mutex<FlowRequest>>,
+    tags: Arc<Mutex<Vec<String>>>,
+    encoding: EncodingPolicy,
+}
+
+#[derive(Clone)]
+struct ScriptResponse {
+    response: Arc<Mutex<FlowResponse>>,
+    tags: Arc<Mutex<Vec<String>>>,
+    encoding: EncodingPolicy,
+}
+
+#[derive(Clone)]
+struct ScriptMessage {
+    message: Arc<Mutex<WebSocketMessage>>,
+    discarded: Arc<AtomicBool>,
+}
+
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn shared<T>(value: T) -> Arc<Mutex<T>> {
+    Arc::new(Mutex::new(value))
+}
+
+fn optional(value: Option<&str>) -> Dynamic {
+    value.map_or(Dynamic::UNIT, |value| value.to_string().into())
+}
+
+fn header_pairs(headers: &[(String, String)]) -> Array {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let pair: Array = vec![name.clone().into(), value.clone().into()];
+            pair.into()
+        })
+        .collect()
+}
+
+fn status(code: i64) -> Result<u16, Box<EvalAltResult>> {
+    u16::try_from(code)
+        .ok()
+        .filter(|code| (100..=999).contains(code))
+        .ok_or_else(|| format!("Invalid status {}", code).into())
+}
+
+impl Default for ScriptInterceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptInterceptor {
+    pub fn new() -> Self {
+        Self {
+            engine: engine(DEFAULT_MAX_OPERATIONS),
+            scripts: Vec::new(),
+            encoding: EncodingPolicy::default(),
+        }
+    }
+
+    // How bodies changed by a script are encoded before they are sent on
+    pub fn with_encoding_policy(mut self, policy: EncodingPolicy) -> Self {
+        self.encoding = policy;
+        self
+    }
+
+    // Cap the work one hook call may do; 0 removes the limit
+    pub fn with_max_operations(mut self, operations: u64) -> Self {
+        self.engine.set_max_operations(operations);
+        self
+    }
+
+    // Compile a script and add it after the ones already loaded
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let ast = self
+            .engine
+            .compile_file(path.to_path_buf())
+            .map_err(|e| anyhow!("Failed to load script {}: {}", path.display(), e))?;
+        info!("Loaded script {}", path.display());
+        self.scripts.push(Script {
+            path: path.to_path_buf(),
+            state: Mutex::new(Compiled {
+                stamp: stamp(path),
+                ast: Arc::new(ast),
+            }),
+        });
+        Ok(())
+    }
+
+    pub fn paths(&self) -> Vec<&Path> {
+        self.scripts.iter().map(|script| script.path.as_path()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    // Run every `on_request` hook until one returns a response
+    pub fn on_request(&self, request: &mut FlowRequest) -> RequestOutcome {
+        let mut outcome = RequestOutcome::default();
+        for script in &self.scripts {
+            let ast = self.current(script);
+            if !has_hook(&ast, "on_request", 1) {
+                continue;
+            }
+
+            let handle = ScriptRequest {
+                request: shared(request.clone()),
+                tags: shared(Vec::new()),
+                encoding: self.encoding,
+            };
+            match self.call(&ast, "on_request", (handle.clone(),)) {
+                Ok(result) => {
+                    *request = lock(&handle.request).clone();
+                    outcome.tags.append(&mut lock(&handle.tags));
+                    if let Some(response) = result.try_cast::<ScriptResponse>() {
+                        debug!("Script {} answered {} {}", script.path.display(), request.method, request.uri);
+                        outcome.response = Some(lock(&response.response).clone());
+                        break;
+                    }
+                }
+                Err(e) => warn!("Script {} failed in on_request: {}", script.path.display(), e),
+            }
+        }
+        outcome
+    }
+
+    // Run every `on_response` hook; returns the tags the scripts added
+    pub fn on_response(&self, request: &FlowRequest, response: &mut FlowResponse) -> Vec<String> {
+        let mut tags = Vec::new();
+        for script in &self.scripts {
+            let ast = self.current(script);
+            if !has_hook(&ast, "on_response", 2) {
+                continue;
+            }
+
+            let shared_tags = shared(Vec::new());
+            let req = ScriptRequest {
+                request: shared(request.clone()),
+                tags: Arc::clone(&shared_tags),
+                encoding: self.encoding,
+            };
+            let res = ScriptResponse {
+                response: shared(response.clone()),
+                tags: Arc::clone(&shared_tags),
+                encoding: self.encoding,
+            };
+            match self.call(&ast, "on_response", (req, res.clone())) {
+                Ok(_) => {
+                    *response = lock(&res.response).clone();
+                    tags.append(&mut lock(&shared_tags));
+                }
+                Err(e) => warn!("Script {} failed in on_response: {}", script.path.display(), e),
+            }
+        }
+        tags
+    }
+
+    // Run every `on_websocket_message` hook; false if a script discarded the message
+    pub fn on_websocket_message(&self, request: &FlowRequest, message: &mut WebSocketMessage) -> bool {
+        for script in &self.scripts {
+            let ast = self.current(script);
+            if !has_hook(&ast, "on_websocket_message", 2) {
+                continue;
+            }
+
+            let req = ScriptRequest {
+                request: shared(request.clone()),
+                tags: shared(Vec::new()),
+                encoding: self.encoding,
+            };
+            let msg = ScriptMessage {
+                message: shared(message.clone()),
+                discarded: Arc::new(AtomicBool::new(false)),
+            };
+            match self.call(&ast, "on_websocket_message", (req, msg.clone())) {
+                Ok(_) => {
+                    *message = lock(&msg.message).clone();
+                    if msg.discarded.load(Ordering::Relaxed) {
+                        return false;
+                    }
+                }
+                Err(e) => warn!("Script {} failed in on_websocket_message: {}", script.path.display(), e),
+            }
+        }
+        true
+    }
+
+    fn call(&self, ast: &AST, hook: &str, args: impl rhai::FuncArgs) -> Result<Dynamic, Box<EvalAltResult>> {
+        // Top-level statements are not run again for every hook call
+        let options = CallFnOptions::new().eval_ast(false);
+        self.engine.call_fn_with_options(options, &mut Scope::new(), ast, hook, args)
+    }
+
+    // The script's AST, recompiled first if the file changed. A script that no
+    // longer compiles keeps running its last good version.
+    fn current(&self, script: &Script) -> Arc<AST> {
+        let mut state = lock(&script.state);
+        let now = stamp(&script.path);
+        if now.is_some() && now != state.stamp {
+            state.stamp = now;
+            match self.engine.compile_file(script.path.clone()) {
+                Ok(ast) => {
+                    info!("Reloaded script {}", script.path.display());
+                    state.ast = Arc::new(ast);
+                }
+                Err(e) => warn!("Keeping the previous version of {}: {}", script.path.display(), e),
+            }
+        }
+        Arc::clone(&state.ast)
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn has_hook(ast: &AST, name: &str, params: usize) -> bool {
+    ast.iter_functions().any(|function| function.name == name && function.params.len() == params)
+}
+
+// An engine with the scripting API and no way out of the sandbox: no imports,
+// no `eval`, and limits on operations, nesting and data sizes
+fn engine(max_operations: u64) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.set_max_operations(max_operations);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_MAP_SIZE);
+    engine.on_print(|text| info!("[script] {}", text));
+    engine.on_debug(|text, _, position| debug!("[script] {} at {}", text, position));
+
+    engine
+        .register_type_with_name::<ScriptRequest>("Request")
+        .register_get_set(
+            "method",
+            |req: &mut ScriptRequest| lock(&req.request).method.clone(),
+            |req: &mut ScriptRequest, method: String| lock(&req.request).method = method.to_uppercase(),
+        )
+        .register_get_set(
+            "uri",
+            |req: &mut ScriptRequest| lock(&req.request).uri.clone(),
+            |req: &mut ScriptRequest, uri: String| lock(&req.request).uri = uri,
+        )
+        .register_get("host", |req: &mut ScriptRequest| {
+            let url = request_url(&lock(&req.request));
+            optional(url.as_ref().and_then(|url| url.host_str()))
+        })
+        .register_get("path", |req: &mut ScriptRequest| {
+            let url = request_url(&lock(&req.request));
+            optional(url.as_ref().map(|url| url.path()))
+        })
+        .register_get_set(
+            "body",
+            |req: &mut ScriptRequest| lock(&req.request).decoded_body().text().into_owned(),
+            |req: &mut ScriptRequest, body: String| -> Result<(), Box<EvalAltResult>> {
+                lock(&req.request).set_body_text(&body, req.encoding).map_err(|e| format!("{:#}", e))?;
+                Ok(())
+            },
+        )
+        .register_get_set(
+            "body_bytes",
+            |req: &mut ScriptRequest| -> Blob { lock(&req.request).decoded_body().decoded_or_raw().to_vec() },
+            |req: &mut ScriptRequest, body: Blob| -> Result<(), Box<EvalAltResult>> {
+                lock(&req.request).set_decoded_body(&body, req.encoding).map_err(|e| format!("{:#}", e))?;
+                Ok(())
+            },
+        )
+        .register_get("raw_body", |req: &mut ScriptRequest| -> Blob { lock(&req.request).body.clone() })
+        .register_get("headers", |req: &mut ScriptRequest| header_pairs(&lock(&req.request).headers))
+        .register_fn("header", |req: &mut ScriptRequest, name: &str| optional(lock(&req.request).header(name)))
+        .register_fn("set_header", |req: &mut ScriptRequest, name: &str, value: &str| {
+            lock(&req.request).set_header(name, value)
+        })
+        .register_fn("add_header", |req: &mut ScriptRequest, name: &str, value: &str| {
+            lock(&req.request).headers.push((name.to_string(), value.to_string()))
+        })
+        .register_fn("remove_header", |req: &mut ScriptRequest, name: &str| {
+            lock(&req.request).remove_header(name)
+        })
+        .register_fn("tag", |req: &mut ScriptRequest, tag: &str| lock(&req.tags).push(tag.to_string()));
+
+    engine
+        .register_type_with_name::<ScriptResponse>("Response")
+        .register_fn("response", |code: i64| -> Result<ScriptResponse, Box<EvalAltResult>> {
+            Ok(ScriptResponse {
+                response: shared(FlowResponse::new(status(code)?)),
+                tags: shared(Vec::new()),
+                encoding: EncodingPolicy::default(),
+            })
+        })
+        .register_fn("response", |code: i64, body: &str| -> Result<ScriptResponse, Box<EvalAltResult>> {
+            let mut response = FlowResponse::new(status(code)?);
+            response.set_header("Content-Type", "text/plain; charset=utf-8");
+            response.body = body.as_bytes().to_vec();
+            Ok(ScriptResponse {
+                response: shared(response),
+                tags: shared(Vec::new()),
+                encoding: EncodingPolicy::default(),
+            })
+        })
+        .register_get_set(
+            "status",
+            |res: &mut ScriptResponse| lock(&res.response).status as i64,
+            |res: &mut ScriptResponse, code: i64| -> Result<(), Box<EvalAltResult>> {
+                lock(&res.response).status = status(code)?;
+                Ok(())
+            },
+        )
+        .register_get_set(
+            "body",
+            |res: &mut ScriptResponse| lock(&res.response).decoded_body().text().into_owned(),
+            |res: &mut ScriptResponse, body: String| -> Result<(), Box<EvalAltResult>> {
+                lock(&res.response).set_body_text(&body, res.encoding).map_err(|e| format!("{:#}", e))?;
+                Ok(())
+            },
+        )
+        .register_get_set(
+            "body_bytes",
+            |res: &mut ScriptResponse| -> Blob { lock(&res.response).decoded_body().decoded_or_raw().to_vec() },
+            |res: &mut ScriptResponse, body: Blob| -> Result<(), Box<EvalAltResult>> {
+                lock(&res.response).set_decoded_body(&body, res.encoding).map_err(|e| format!("{:#}", e))?;
+                Ok(())
+            },
+        )
+        .register_get("raw_body", |res: &mut ScriptResponse| -> Blob { lock(&res.response).body.clone() })
+        .register_get("headers", |res: &mut ScriptResponse| header_pairs(&lock(&res.response).headers))
+        .register_fn("header", |res: &mut ScriptResponse, name: &str| optional(lock(&res.response).header(name)))
+        .register_fn("set_header", |res: &mut ScriptResponse, name: &str, value: &str| {
+            lock(&res.response).set_header(name, value)
+        })
+        .register_fn("add_header", |res: &mut ScriptResponse, name: &str, value: &str| {
+            lock(&res.response).headers.push((name.to_string(), value.to_string()))
+        })
+        .register_fn("remove_header", |res: &mut ScriptResponse, name: &str| {
+            lock(&res.response).remove_header(name)
+        })
+        .register_fn("tag", |res: &mut ScriptResponse, tag: &str| lock(&res.tags).push(tag.to_string()));
+
+    engine
+        .register_type_with_name::<ScriptMessage>("WebSocketMessage")
+        .register_get("direction", |msg: &mut ScriptMessage| match lock(&msg.message).direction {
+            Direction::ClientToServer => "client".to_string(),
+            Direction::ServerToClient => "server".to_string(),
+        })
+        .register_get("opcode", |msg: &mut ScriptMessage| lock(&msg.message).opcode.as_str().to_string())
+        .register_get_set(
+            "text",
+            |msg: &mut ScriptMessage| String::from_utf8_lossy(&lock(&msg.message).payload).into_owned(),
+            |msg: &mut ScriptMessage, text: String| lock(&msg.message).payload = text.into_bytes(),
+        )
+        .register_get_set(
+            "payload",
+            |msg: &mut ScriptMessage| -> Blob { lock(&msg.message).payload.clone() },
+            |msg: &mut ScriptMessage, payload: Blob| lock(&msg.message).payload = payload,
+        )
+        .register_fn("discard", |msg: &mut ScriptMessage| msg.discarded.store(true, Ordering::Relaxed));
+
+    engine
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::flow::body::{self, content_encodings, Body, EncodingPolicy};
+use crate::flow::model::{FlowRequest, FlowResponse};
+
+// JSON form of requests and responses exchanged with plugins and external
+// processes. Bodies are base64 encoded so binary content survives the trip, and
+// are sent with their Content-Encoding undone; a body that comes back changed is
+// encoded again, or sent without Content-Encoding, as the policy says.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireRequest {
+    pub method: String,
+    pub uri: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, with = "base64_body")]
+    pub body: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WireResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default, with = "base64_body")]
+    pub body: Vec<u8>,
+}
+
+// What is sent to a hook and what it answers with. On the way in it carries the
+// message being handled; on the way out a request or response replaces the
+// original, and a response returned for a request answers it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WireMessage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<WireRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<WireResponse>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&FlowRequest> for WireRequest {
+    fn from(request: &FlowRequest) -> Self {
+        Self {
+            method: request.method.clone(),
+            uri: request.uri.clone(),
+            headers: request.headers.clone(),
+            body: request.decoded_body().decoded_or_raw().to_vec(),
+        }
+    }
+}
+
+impl WireRequest {
+    pub fn into_request(self, policy: EncodingPolicy) -> Result<FlowRequest> {
+        let mut request = FlowRequest::new(&self.method, &self.uri);
+        self.apply(&mut request, policy)?;
+        Ok(request)
+    }
+
+    // Overwrite a request with this one, keeping its HTTP version
+    pub fn apply(self, request: &mut FlowRequest, policy: EncodingPolicy) -> Result<()> {
+        let mut headers = self.headers;
+        let body = wire_body(&request.headers, &request.body, &mut headers, &self.body, policy)?;
+        request.method = self.method;
+        request.uri = self.uri;
+        request.headers = headers;
+        request.body = body;
+        Ok(())
+    }
+}
+
+impl From<&FlowResponse> for WireResponse {
+    fn from(response: &FlowResponse) -> Self {
+        Self {
+            status: response.status,
+            headers: response.headers.clone(),
+            body: response.decoded_body().decoded_or_raw().to_vec(),
+        }
+    }
+}
+
+impl WireResponse {
+    pub fn into_response(self, policy: EncodingPolicy) -> Result<FlowResponse> {
+        let mut response = FlowResponse::new(self.status);
+        self.apply(&mut response, policy)?;
+        Ok(response)
+    }
+
+    // Overwrite a response with this one, keeping its HTTP version
+    pub fn apply(self, response: &mut FlowResponse, policy: EncodingPolicy) -> Result<()> {
+        let mut headers = self.headers;
+        let body = wire_body(&response.headers, &response.body, &mut headers, &self.body, policy)?;
+        response.status = self.status;
+        response.headers = headers;
+        response.body = body;
+        Ok(())
+    }
+}
+
+// The raw body for decoded content that came back over the wire, adjusting the
+// new headers to match. The original bytes are kept when neither the content
+// nor its encoding changed.
+fn wire_body(
+    old_headers: &[(String, String)],
+    old_body: &[u8],
+    headers: &mut Vec<(String, String)>,
+    content: &[u8],
+    policy: EncodingPolicy,
+) -> Result<Vec<u8>> {
+    let unchanged = content_encodings(old_headers) == content_encodings(headers)
+        && Body::new(old_headers, old_body).decoded_or_raw() == content;
+    if unchanged {
+        return Ok(old_body.to_vec());
+    }
+    body::encode_body(headers, content, policy)
+}
+
+mod base64_body {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        BASE64.decode(text).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,318 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{bail, Context, Result};
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use regex::bytes::Regex;
+
+use crate::decoder::transform::{encode_chain, Transform};
+use crate::flow::model::{FlowId, FlowRequest};
+use crate::flow::raw::format_response;
+use crate::intruder::payload::PayloadSet;
+use crate::intruder::template::Template;
+use crate::project::scope::Scope;
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+
+// Tag added to every flow an attack records into a project
+pub const INTRUDER_TAG: &str = "intruder";
+
+// How payloads are assigned to insertion points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackMode {
+    // One set; each position in turn receives every payload while the others keep their defaults
+    Sniper,
+    // One set; every position receives the same payload at once
+    BatteringRam,
+    // One set per position, advanced in lockstep
+    Pitchfork,
+    // One set per position, trying every combination
+    ClusterBomb,
+}
+
+impl AttackMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sniper => "sniper",
+            Self::BatteringRam => "battering-ram",
+            Self::Pitchfork => "pitchfork",
+            Self::ClusterBomb => "cluster-bomb",
+        }
+    }
+}
+
+impl FromStr for AttackMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().replace('_', "-").as_str() {
+            "sniper" => Ok(Self::Sniper),
+            "battering-ram" | "ram" => Ok(Self::BatteringRam),
+            "pitchfork" => Ok(Self::Pitchfork),
+            "cluster-bomb" | "cluster" => Ok(Self::ClusterBomb),
+            _ => bail!("Unknown attack mode {} (sniper, battering-ram, pitchfork, cluster-bomb)", value),
+        }
+    }
+}
+
+// The outcome of one request of an attack
+#[derive(Debug, Clone)]
+pub struct AttackResult {
+    pub index: usize,
+    // The payload placed at each position, before encoding; None where the default was kept
+    pub payloads: Vec<Option<Vec<u8>>>,
+    pub request: FlowRequest,
+    pub status: Option<u16>,
+    // Response body size in bytes
+    pub length: Option<usize>,
+    pub duration_ms: Option<u64>,
+    // Whether each grep pattern matched the response, in the order they were given
+    pub grep_matches: Vec<bool>,
+    pub flow_id: Option<FlowId>,
+    pub error: Option<String>,
+}
+
+// Sends a template once for every payload assignment of the chosen mode
+pub struct Attack {
+    template: Template,
+    mode: AttackMode,
+    sets: Vec<PayloadSet>,
+    forwarder: Forwarder,
+    project: Option<Arc<Project>>,
+    scope: Scope,
+    concurrency: usize,
+    delay: Option<Duration>,
+    grep: Vec<Regex>,
+    encoding: Vec<Transform>,
+}
+
+impl Attack {
+    pub fn new(template: Template, mode: AttackMode, sets: Vec<PayloadSet>, forwarder: Forwarder) -> Result<Self> {
+        let positions = template.positions();
+        if positions == 0 {
+            bail!("Request template has no insertion points");
+        }
+
+        match mode {
+            AttackMode::Sniper | AttackMode::BatteringRam if sets.len() != 1 => {
+                bail!("{} attacks take exactly one payload set, got {}", mode.as_str(), sets.len());
+            }
+            AttackMode::Pitchfork | AttackMode::ClusterBomb if sets.len() != positions => {
+                bail!(
+                    "{} attacks take one payload set per position: {} position(s), {} set(s)",
+                    mode.as_str(),
+                    positions,
+                    sets.len()
+                );
+            }
+            _ => {}
+        }
+
+        Ok(Self {
+            template,
+            mode,
+            sets,
+            forwarder,
+            project: None,
+            scope: Scope::new(),
+            concurrency: 4,
+            delay: None,
+            grep: Vec::new(),
+            encoding: Vec::new(),
+        })
+    }
+
+    // Maximum number of requests in flight at once
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Minimum time between starting consecutive requests
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    // Flag responses (status line, headers and body) matching each pattern
+    pub fn with_grep(mut self, patterns: &[String]) -> Result<Self> {
+        self.grep = patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid grep pattern {}", pattern)))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
+    // Encode every payload with these transforms, in order, before it is inserted
+    pub fn with_payload_encoding(mut self, encoding: Vec<Transform>) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    // Record every completed request into the given project
+    pub fn with_project(mut self, project: Arc<Project>) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    // Requests outside the scope are not sent and come back with an error instead
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn request_count(&self) -> usize {
+        let lengths = self.sets.iter().map(PayloadSet::len);
+        match self.mode {
+            AttackMode::Sniper => self.template.positions() * self.sets[0].len(),
+            AttackMode::BatteringRam => self.sets[0].len(),
+            AttackMode::Pitchfork => lengths.min().unwrap_or(0),
+            AttackMode::ClusterBomb => lengths.fold(1usize, |total, len| total.saturating_mul(len)),
+        }
+    }
+
+    // The payload for each position in request number `index`
+    pub fn payloads_for(&self, index: usize) -> Vec<Option<Vec<u8>>> {
+        let positions = self.template.positions();
+
+        match self.mode {
+            AttackMode::Sniper => {
+                let len = self.sets[0].len();
+                let mut payloads = vec![None; positions];
+                payloads[index / len] = self.sets[0].get(index % len);
+                payloads
+            }
+            AttackMode::BatteringRam => vec![self.sets[0].get(index); positions],
+            AttackMode::Pitchfork => self.sets.iter().map(|set| set.get(index)).collect(),
+            AttackMode::ClusterBomb => {
+                // The first position changes fastest
+                let mut rest = index;
+                self.sets
+                    .iter()
+                    .map(|set| {
+                        let payload = set.get(rest % set.len());
+                        rest /= set.len();
+                        payload
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub async fn run(&self) -> Result<Vec<AttackResult>> {
+        self.run_with(|_| {}).await
+    }
+
+    // Run the attack, handing each result to `on_result` as it completes.
+    // The returned results are in request order.
+    pub async fn run_with(&self, mut on_result: impl FnMut(&AttackResult)) -> Result<Vec<AttackResult>> {
+        let count = self.request_count();
+        info!(
+            "Starting {} attack: {} request(s), {} at a time",
+            self.mode.as_str(),
+            count,
+            self.concurrency
+        );
+
+        let delay = self.delay;
+        let attempts = stream::iter(0..count)
+            .then(move |index| async move {
+                if let (Some(delay), true) = (delay, index > 0) {
+                    tokio::time::sleep(delay).await;
+                }
+                index
+            })
+            .map(|index| self.attempt(index))
+            .buffer_unordered(self.concurrency);
+        let mut attempts = std::pin::pin!(attempts);
+
+        let mut results = Vec::with_capacity(count);
+        while let Some(result) = attempts.next().await {
+            let result = result?;
+            on_result(&result);
+            results.push(result);
+        }
+
+        results.sort_by_key(|result| result.index);
+        Ok(results)
+    }
+
+    async fn attempt(&self, index: usize) -> Result<AttackResult> {
+        let payloads = self.payloads_for(index);
+        let encoded = payloads
+            .iter()
+            .map(|payload| {
+                payload
+                    .as_ref()
+                    .map(|payload| encode_chain(payload, &self.encoding))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>();
+
+        let mut result = AttackResult {
+            index,
+            payloads: payloads.clone(),
+            request: FlowRequest::new("", ""),
+            status: None,
+            length: None,
+            duration_ms: None,
+            grep_matches: vec![false; self.grep.len()],
+            flow_id: None,
+            error: None,
+        };
+
+        // A payload can make the request unparseable; that is a result, not a failure of the attack
+        let rendered = encoded.and_then(|encoded| {
+            let slices: Vec<Option<&[u8]>> = encoded.iter().map(|payload| payload.as_deref()).collect();
+            self.template.render(&slices)
+        });
+        let request = match rendered {
+            Ok(request) => request,
+            Err(e) => {
+                result.error = Some(format!("{:#}", e));
+                return Ok(result);
+            }
+        };
+        result.request = request.clone();
+        if !self.scope.contains(&request) {
+            result.error = Some(format!("{} is out of scope", request.uri));
+            return Ok(result);
+        }
+
+        match self.forwarder.forward(request).await {
+            Ok(mut flow) => {
+                if let Some(response) = &flow.response {
+                    let raw = format_response(response);
+                    result.status = Some(response.status);
+                    result.length = Some(response.body.len());
+                    result.grep_matches = self.grep.iter().map(|regex| regex.is_match(&raw)).collect();
+                }
+                result.duration_ms = flow.duration_ms;
+
+                if let Some(project) = &self.project {
+                    flow.tags.push(INTRUDER_TAG.to_string());
+                    result.flow_id = Some(project.save_flow(&flow)?);
+                }
+            }
+            Err(e) => {
+                warn!("Attack request {} failed: {:#}", index, e);
+                result.error = Some(format!("{:#}", e));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+// Payloads of a result for display; positions that kept their default show as "-"
+pub fn describe_payloads(payloads: &[Option<Vec<u8>>]) -> String {
+    payloads
+        .iter()
+        .map(|payload| match payload {
+            Some(payload) => String::from_utf8_lossy(payload).into_owned(),
+            None => "-".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
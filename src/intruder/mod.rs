@@ -0,0 +1,3 @@
+pub mod attack;
+pub mod payload;
+pub mod template;
@@ -0,0 +1,168 @@
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::bytes::Regex;
+
+use crate::flow::model::Flow;
+use crate::project::store::Project;
+
+// A sequence of payloads; generated sets are computed on demand so large ranges
+// don't need to be held in memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadSet {
+    // Fixed payloads, e.g. a wordlist
+    List(Vec<Vec<u8>>),
+    // Numbers from `start` to `end` inclusive, zero-padded to `width` digits
+    Numbers { start: i64, end: i64, step: i64, width: usize },
+    // `base` repeated `min` to `max` times, in increments of `step`
+    CharBlocks { base: String, min: usize, max: usize, step: usize },
+}
+
+impl PayloadSet {
+    pub fn list<I, T>(payloads: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Vec<u8>>,
+    {
+        Self::List(payloads.into_iter().map(Into::into).collect())
+    }
+
+    // One payload per line; blank lines are skipped
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self::List(
+            data.split(|&b| b == b'\n')
+                .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                .filter(|line| !line.is_empty())
+                .map(<[u8]>::to_vec)
+                .collect(),
+        ))
+    }
+
+    pub fn numbers(start: i64, end: i64, step: i64) -> Result<Self> {
+        if step == 0 || (end - start).signum() * step.signum() < 0 {
+            bail!("Step {} never reaches {} from {}", step, end, start);
+        }
+        Ok(Self::Numbers { start, end, step, width: 0 })
+    }
+
+    pub fn char_blocks(base: &str, min: usize, max: usize, step: usize) -> Result<Self> {
+        if base.is_empty() || step == 0 || min > max {
+            bail!("Invalid character block {} x {}..{} step {}", base, min, max, step);
+        }
+        Ok(Self::CharBlocks { base: base.to_string(), min, max, step })
+    }
+
+    // Every distinct match of `pattern` in the responses of previous flows. The first
+    // capture group is used when the pattern has one, otherwise the whole match.
+    pub fn from_responses(flows: &[Flow], pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid pattern {}", pattern))?;
+        let mut payloads: Vec<Vec<u8>> = Vec::new();
+
+        for response in flows.iter().filter_map(|flow| flow.response.as_ref()) {
+            for captures in regex.captures_iter(&response.body) {
+                let found = captures.get(1).or_else(|| captures.get(0)).map(|m| m.as_bytes());
+                if let Some(found) = found
+                    && !payloads.iter().any(|existing| existing == found)
+                {
+                    payloads.push(found.to_vec());
+                }
+            }
+        }
+
+        Ok(Self::List(payloads))
+    }
+
+    // Parse a command-line payload description:
+    //   list:a,b,c          comma-separated payloads
+    //   file:words.txt      one payload per line (a bare path works too)
+    //   range:1..100[:step] numbers; a zero-padded start such as 001 sets the width
+    //   chars:A:1..500[:step] repeated characters
+    //   extract:REGEX       values found in the responses stored in the project
+    pub fn parse_spec(spec: &str, project: Option<&Project>) -> Result<Self> {
+        let (kind, value) = spec.split_once(':').unwrap_or(("file", spec));
+
+        match kind {
+            "list" => Ok(Self::list(value.split(','))),
+            "file" => Self::from_file(Path::new(value)),
+            "range" => {
+                let (range, step) = match value.split_once(':') {
+                    Some((range, step)) => (range, Some(parse_number(step)?)),
+                    None => (value, None),
+                };
+                let (start, end) = parse_range(range)?;
+                let width = if start.len() > 1 && start.starts_with('0') { start.len() } else { 0 };
+                let set = Self::numbers(parse_number(start)?, parse_number(end)?, step.unwrap_or(1))?;
+                match set {
+                    Self::Numbers { start, end, step, .. } => Ok(Self::Numbers { start, end, step, width }),
+                    other => Ok(other),
+                }
+            }
+            "chars" => {
+                let pieces: Vec<&str> = value.split(':').collect();
+                let at = pieces
+                    .iter()
+                    .rposition(|piece| piece.contains(".."))
+                    .filter(|&at| at > 0 && pieces.len() - at <= 2)
+                    .ok_or_else(|| anyhow!("Expected chars:BASE:MIN..MAX[:STEP], got {}", spec))?;
+                let (min, max) = parse_range(pieces[at])?;
+                let step = pieces.get(at + 1).map(|step| parse_count(step)).transpose()?;
+                Self::char_blocks(&pieces[..at].join(":"), parse_count(min)?, parse_count(max)?, step.unwrap_or(1))
+            }
+            "extract" => {
+                let project = project.ok_or_else(|| anyhow!("extract: payloads need a --project"))?;
+                let flows = project
+                    .list_flows()?
+                    .into_iter()
+                    .filter_map(|summary| project.load_flow(summary.id).transpose())
+                    .collect::<Result<Vec<_>>>()?;
+                Self::from_responses(&flows, value)
+            }
+            _ => Self::from_file(Path::new(spec)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::List(payloads) => payloads.len(),
+            Self::Numbers { start, end, step, .. } => ((end - start) / step) as usize + 1,
+            Self::CharBlocks { min, max, step, .. } => (max - min) / step + 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Vec<u8>> {
+        if index >= self.len() {
+            return None;
+        }
+
+        match self {
+            Self::List(payloads) => payloads.get(index).cloned(),
+            Self::Numbers { start, step, width, .. } => {
+                let number = start + step * index as i64;
+                Some(format!("{:0width$}", number, width = *width).into_bytes())
+            }
+            Self::CharBlocks { base, min, step, .. } => Some(base.repeat(min + step * index).into_bytes()),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+}
+
+fn parse_range(range: &str) -> Result<(&str, &str)> {
+    range
+        .split_once("..")
+        .ok_or_else(|| anyhow!("Expected a range like 1..100, got {}", range))
+}
+
+fn parse_number(value: &str) -> Result<i64> {
+    value.trim().parse().with_context(|| format!("Invalid number {}", value))
+}
+
+fn parse_count(value: &str) -> Result<usize> {
+    value.trim().parse().with_context(|| format!("Invalid count {}", value))
+}
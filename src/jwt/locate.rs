@@ -0,0 +1,105 @@
+use std::sync::LazyLock;
+use log::warn;
+use regex::bytes::Regex;
+
+use crate::flow::body::EncodingPolicy;
+use crate::flow::model::{Flow, FlowRequest};
+use crate::jwt::token::Jwt;
+use crate::scanner::finding::{Evidence, Location};
+
+// Header and claims both start with `{"` once encoded; the signature may be empty
+static JWT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\beyJ[A-Za-z0-9_-]{2,}={0,2}\.eyJ[A-Za-z0-9_-]{2,}={0,2}\.[A-Za-z0-9_-]*={0,2}").unwrap());
+
+// A JWT found in a flow and where it was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundToken {
+    pub token: String,
+    pub jwt: Jwt,
+    pub evidence: Evidence,
+    // e.g. `request header Authorization` or `response body`
+    pub source: String,
+}
+
+// Every decodable JWT in a flow: URL, request headers (including cookies),
+// request body, response headers and response body. Bodies are searched as
+// decoded text, which is what body evidence offsets refer to.
+pub fn find_tokens(flow: &Flow) -> Vec<FoundToken> {
+    let mut found = Vec::new();
+    let mut search = |bytes: &[u8], location: Location, source: String| {
+        for token in JWT.find_iter(bytes) {
+            let text = String::from_utf8_lossy(token.as_bytes()).into_owned();
+            if let Ok(jwt) = Jwt::parse(&text) {
+                found.push(FoundToken {
+                    token: text,
+                    jwt,
+                    evidence: Evidence::new(location.clone(), token.start(), token.end()),
+                    source: source.clone(),
+                });
+            }
+        }
+    };
+
+    search(flow.request.uri.as_bytes(), Location::Url, "URL".to_string());
+    for (index, (name, value)) in flow.request.headers.iter().enumerate() {
+        search(value.as_bytes(), Location::RequestHeader(index), format!("request header {}", name));
+    }
+    search(flow.request.decoded_body().text().as_bytes(), Location::RequestBody, "request body".to_string());
+    if let Some(response) = &flow.response {
+        for (index, (name, value)) in response.headers.iter().enumerate() {
+            search(value.as_bytes(), Location::ResponseHeader(index), format!("response header {}", name));
+        }
+        search(response.decoded_body().text().as_bytes(), Location::ResponseBody, "response body".to_string());
+    }
+
+    found
+}
+
+// The request with every occurrence of one token swapped for another. The body
+// is rewritten decoded and re-encoded, keeping its other bytes and Content-Length
+// in step; a body that cannot be decoded is rewritten as it is.
+pub fn replace_token(request: &FlowRequest, old: &str, new: &str) -> FlowRequest {
+    let mut request = request.clone();
+    if old.is_empty() {
+        return request;
+    }
+
+    request.uri = request.uri.replace(old, new);
+    for (_, value) in request.headers.iter_mut() {
+        *value = value.replace(old, new);
+    }
+
+    let body = request.decoded_body();
+    let decodable = body.decoded().is_ok();
+    let Some(content) = replace_bytes(body.decoded_or_raw(), old.as_bytes(), new.as_bytes()) else {
+        return request;
+    };
+    if decodable {
+        if let Err(e) = request.set_decoded_body(&content, EncodingPolicy::Reencode) {
+            warn!("Leaving the request body unchanged: {:#}", e);
+        }
+    } else {
+        request.body = content;
+        if request.header("content-length").is_some() {
+            request.set_header("Content-Length", &request.body.len().to_string());
+        }
+    }
+
+    request
+}
+
+// `haystack` with every occurrence of `old` replaced, or None if there is none
+fn replace_bytes(haystack: &[u8], old: &[u8], new: &[u8]) -> Option<Vec<u8>> {
+    let mut replaced = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    while let Some(position) = rest.windows(old.len()).position(|window| window == old) {
+        replaced.extend_from_slice(&rest[..position]);
+        replaced.extend_from_slice(new);
+        rest = &rest[position + old.len()..];
+    }
+    if rest.len() == haystack.len() {
+        return None;
+    }
+    replaced.extend_from_slice(rest);
+    Some(replaced)
+}
@@ -0,0 +1,3 @@
+pub mod attack;
+pub mod locate;
+pub mod token;
@@ -0,0 +1,139 @@
+use std::fmt;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::{Sha256, Sha384, Sha512};
+
+// A decoded JSON Web Token. The original encoded segments are kept so the
+// signature can be checked against exactly what was signed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jwt {
+    pub header: Map<String, Value>,
+    pub claims: Map<String, Value>,
+    pub signature: Vec<u8>,
+    encoded_header: String,
+    encoded_claims: String,
+}
+
+impl Jwt {
+    pub fn parse(token: &str) -> Result<Self> {
+        let token = token.trim();
+        let mut parts = token.split('.');
+        let (Some(header), Some(claims), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!("A JWT has three dot-separated parts");
+        };
+
+        Ok(Self {
+            header: decode_object(header).context("Invalid JWT header")?,
+            claims: decode_object(claims).context("Invalid JWT claims")?,
+            signature: decode_segment(signature).context("Invalid JWT signature")?,
+            encoded_header: header.to_string(),
+            encoded_claims: claims.to_string(),
+        })
+    }
+
+    pub fn alg(&self) -> Option<&str> {
+        self.header.get("alg").and_then(Value::as_str)
+    }
+
+    // The header and claims as originally encoded, joined by a dot
+    pub fn signing_input(&self) -> String {
+        format!("{}.{}", self.encoded_header, self.encoded_claims)
+    }
+
+    // Whether the signature is a valid HMAC of the original token under `secret`;
+    // always false for tokens that are not HS256/384/512
+    pub fn verify_hmac(&self, secret: &[u8]) -> bool {
+        self.alg()
+            .and_then(|alg| hmac_sign(alg, secret, self.signing_input().as_bytes()).ok())
+            .is_some_and(|signature| signature == self.signature)
+    }
+
+    // Re-encode the current header and claims with an empty signature
+    pub fn unsigned(&self) -> String {
+        format!("{}.", self.encoded_input())
+    }
+
+    // Re-encode the current header and claims with the original signature
+    pub fn with_original_signature(&self) -> String {
+        format!("{}.{}", self.encoded_input(), URL_SAFE_NO_PAD.encode(&self.signature))
+    }
+
+    // Re-encode and sign with the HMAC algorithm named by the header
+    pub fn sign_hmac(&self, secret: &[u8]) -> Result<String> {
+        let alg = self.alg().ok_or_else(|| anyhow!("JWT header has no alg"))?;
+        let input = self.encoded_input();
+        let signature = hmac_sign(alg, secret, input.as_bytes())?;
+        Ok(format!("{}.{}", input, URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    // Header and claims as pretty-printed JSON, for display
+    pub fn describe(&self) -> String {
+        let header = serde_json::to_string_pretty(&self.header).unwrap_or_default();
+        let claims = serde_json::to_string_pretty(&self.claims).unwrap_or_default();
+        format!("{}\n{}", header, claims)
+    }
+
+    // Segments are only re-encoded once the header or claims have been edited,
+    // so an untouched token keeps its exact encoding
+    fn encoded_input(&self) -> String {
+        let header = match decode_object(&self.encoded_header) {
+            Ok(original) if original == self.header => self.encoded_header.clone(),
+            _ => encode_object(&self.header),
+        };
+        let claims = match decode_object(&self.encoded_claims) {
+            Ok(original) if original == self.claims => self.encoded_claims.clone(),
+            _ => encode_object(&self.claims),
+        };
+        format!("{}.{}", header, claims)
+    }
+}
+
+impl fmt::Display for Jwt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.with_original_signature())
+    }
+}
+
+pub fn hmac_sign(alg: &str, secret: &[u8], input: &[u8]) -> Result<Vec<u8>> {
+    fn sign<M: Mac + hmac::digest::KeyInit>(secret: &[u8], input: &[u8]) -> Vec<u8> {
+        let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(input);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    match alg.to_ascii_uppercase().as_str() {
+        "HS256" => Ok(sign::<Hmac<Sha256>>(secret, input)),
+        "HS384" => Ok(sign::<Hmac<Sha384>>(secret, input)),
+        "HS512" => Ok(sign::<Hmac<Sha512>>(secret, input)),
+        other => bail!("{} is not an HMAC algorithm", other),
+    }
+}
+
+// base64url, tolerating padding and the standard alphabet
+fn decode_segment(segment: &str) -> Result<Vec<u8>> {
+    let normalized: String = segment
+        .trim_end_matches('=')
+        .chars()
+        .map(|c| match c {
+            '+' => '-',
+            '/' => '_',
+            c => c,
+        })
+        .collect();
+    Ok(URL_SAFE_NO_PAD.decode(normalized)?)
+}
+
+fn decode_object(segment: &str) -> Result<Map<String, Value>> {
+    match serde_json::from_slice(&decode_segment(segment)?)? {
+        Value::Object(object) => Ok(object),
+        _ => bail!("Not a JSON object"),
+    }
+}
+
+fn encode_object(object: &Map<String, Value>) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(object).unwrap_or_default())
+}
@@ -1,12 +1,29 @@
 // Expose modules for testing
 pub mod certificates;
+pub mod comparer;
+pub mod curl;
+pub mod decoder;
+pub mod filter;
+pub mod flow;
+pub mod har;
 pub mod intercept;
+pub mod intruder;
+pub mod jwt;
+pub mod project;
 pub mod proxy;
+pub mod repeater;
+pub mod rules;
+pub mod scanner;
+pub mod sequencer;
+pub mod sitemap;
+pub mod spider;
 pub mod ui;
 pub mod utils;
 
 // Re-export the core types for convenient importing
 pub use crate::certificates::ca::CertificateAuthority;
+pub use crate::flow::model::Flow;
+pub use crate::project::store::Project;
 pub use crate::proxy::server::ProxyServer;
 pub use crate::intercept::request::RequestInterceptor;
 pub use crate::intercept::response::ResponseInterceptor;
@@ -18,6 +35,7 @@ mod tests {
     #[test]
     fn it_works() {
         // A simple test to verify the library is loadable
-        assert!(true);
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(ProxyServer::new(addr).address(), addr);
     }
 }
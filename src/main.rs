@@ -1,16 +1,20 @@
-mod certificates;
-mod intercept;
-mod proxy;
-mod ui;
-mod utils;
+use std::sync::{Arc, Mutex};
 
 use anyhow::{Result, Context};
 use log::info;
 
-use crate::certificates::ca::CertificateAuthority;
-use crate::proxy::server::ProxyServer;
-use crate::ui::cli::{parse_cli, Commands};
-use crate::utils::logger;
+use ferrum::certificates::ca::CertificateAuthority;
+use ferrum::comparer::compare::CompareOptions;
+use ferrum::jwt::attack::Signing;
+use ferrum::project::store::Project;
+use ferrum::proxy::server::ProxyServer;
+use ferrum::rules::block::BlockRules;
+use ferrum::rules::map_local::MapLocal;
+use ferrum::scanner::passive::PassiveScanner;
+use ferrum::sitemap::tree::SiteMap;
+use ferrum::ui::cli::{parse_cli, Commands, ExportFormat, ImportFormat, JwtCommand};
+use ferrum::ui::commands;
+use ferrum::utils::logger;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -23,7 +27,7 @@ async fn main() -> Result<()> {
     let cli = parse_cli();
 
     match cli.command {
-        Commands::Proxy { addr } => {
+        Commands::Proxy(args) => {
             // Initialize Certificate Authority
             let home_dir = dirs::home_dir().context("Failed to get home directory")?;
             let ca_dir = home_dir.join(".ferrum").join("certs");
@@ -36,9 +40,149 @@ async fn main() -> Result<()> {
             ca.init()?;
 
             // Start proxy server
-            let server = ProxyServer::new(addr);
-            server.start().await?;
+            let project = args.project.as_ref().map(Project::open).transpose()?;
+            let mut scope = commands::scope(&args.scope, project.as_ref())?;
+            if args.skip_out_of_scope {
+                scope = scope.with_record_out_of_scope(false).with_intercept_out_of_scope(false);
+            }
+            if !scope.is_unrestricted() {
+                info!(
+                    "Scope: {} include and {} exclude rule(s)",
+                    scope.include.len(),
+                    scope.exclude.len()
+                );
+            }
+            let forwarder = commands::forwarder(&args.upstream, &args.intercept)?.with_scope(scope.clone());
+            let mut map_local = match &args.map_local_rules {
+                Some(path) => MapLocal::load(path)?,
+                None => MapLocal::new(),
+            };
+            for rule in args.map_local {
+                map_local.add(rule);
+            }
+            if !map_local.is_empty() {
+                info!("Answering {} Map Local rule(s) from disk", map_local.rules().len());
+            }
+            let mut block_rules = match &args.block_rules {
+                Some(path) => BlockRules::load(path)?,
+                None => BlockRules::new(),
+            };
+            for rule in args.block {
+                block_rules.add(rule);
+            }
+            if !block_rules.is_empty() {
+                info!("Blocking requests with {} rule(s)", block_rules.rules().len());
+            }
+            let mut server = ProxyServer::new(args.addr)
+                .with_forwarder(forwarder)
+                .with_map_local(map_local)
+                .with_block_rules(block_rules);
+            if let (Some(path), Some(project)) = (&args.project, project) {
+                info!("Recording flows to {} ({} existing)", path.display(), project.flow_count()?);
+                server = server.with_project(Arc::new(project));
+            }
+            if args.passive_scan {
+                server = server.with_scanner(Arc::new(Mutex::new(PassiveScanner::new().with_scope(scope))));
+            }
+            // Only map the traffic when the site map will be written out
+            let site_map = args.site_map.as_ref().map(|_| Arc::new(Mutex::new(SiteMap::new())));
+            if let Some(site_map) = &site_map {
+                server = server.with_site_map(Arc::clone(site_map));
+            }
+            tokio::select! {
+                result = server.start() => result?,
+                _ = tokio::signal::ctrl_c() => info!("Stopping the proxy"),
+            }
+            if let (Some(path), Some(site_map)) = (&args.site_map, site_map) {
+                let json = site_map.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).to_json()?;
+                std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+                info!("Wrote the site map to {}", path.display());
+            }
         }
+        Commands::Open { path, flow, filter, raw } => {
+            commands::open_project(&path, flow, filter.as_ref(), raw)?;
+        }
+        Commands::Export { format } => match format {
+            ExportFormat::Har { project, output, flows, filter } => {
+                commands::export_har(&project, output.as_ref(), &flows, filter.as_ref())?;
+            }
+            ExportFormat::Curl { project, flow, proxy, insecure } => {
+                commands::export_curl(&project, flow, proxy, insecure)?;
+            }
+        },
+        Commands::Import { format } => match format {
+            ImportFormat::Har { file, project } => {
+                commands::import_har(&file, &project)?;
+            }
+            ImportFormat::Curl(args) => {
+                commands::import_curl(&args).await?;
+            }
+        },
+        Commands::Replay { project, flows, target, intercept, upstream } => {
+            commands::replay(&project, &flows, target.as_deref(), &upstream, &intercept).await?;
+        }
+        Commands::Repeat(args) => {
+            commands::repeat(&args).await?;
+        }
+        Commands::Intruder(args) => {
+            commands::intruder(&args).await?;
+        }
+        Commands::Sequencer(args) => {
+            commands::sequencer(&args).await?;
+        }
+        Commands::Decode { input, with } => {
+            commands::decode(input.as_deref(), &with)?;
+        }
+        Commands::Encode { input, with } => {
+            commands::encode(input.as_deref(), &with)?;
+        }
+        Commands::Compare { project, old, new, granularity, ignore_header, json } => {
+            let options = CompareOptions {
+                granularity,
+                ignore_headers: ignore_header,
+            };
+            commands::compare(&project, old, new, &options, json)?;
+        }
+        Commands::Audit(args) => {
+            commands::audit(&args).await?;
+        }
+        Commands::Scan { project, scope, min_severity, json } => {
+            commands::scan(&project, &scope, min_severity, json)?;
+        }
+        Commands::Sitemap { project, host, unrequested, json } => {
+            commands::sitemap(&project, host.as_deref(), unrequested, json)?;
+        }
+        Commands::Spider(args) => {
+            commands::spider(&args).await?;
+        }
+        Commands::Jwt { action } => match action {
+            JwtCommand::Decode { token } => {
+                commands::jwt_decode(&token)?;
+            }
+            JwtCommand::Find { project, flow } => {
+                commands::jwt_find(&project, &flow)?;
+            }
+            JwtCommand::Crack { token, wordlist } => {
+                commands::jwt_crack(&token, wordlist.as_deref())?;
+            }
+            JwtCommand::Forge { token, claims, secret, public_key, none, strip } => {
+                let signing = if let Some(secret) = secret {
+                    Signing::Secret(secret.into_bytes())
+                } else if let Some(path) = public_key {
+                    Signing::PublicKey(std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?)
+                } else if none {
+                    Signing::None("none".to_string())
+                } else if strip {
+                    Signing::Stripped
+                } else {
+                    Signing::Original
+                };
+                commands::jwt_forge(&token, &claims, &signing)?;
+            }
+            JwtCommand::Attack(args) => {
+                commands::jwt_attack(&args).await?;
+            }
+        },
     }
 
     Ok(())
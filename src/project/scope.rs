@@ -0,0 +1,347 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Context, Result};
+use ipnet::IpNet;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use url::{Host, Url};
+
+use crate::filter::eval::Filter;
+use crate::flow::model::FlowRequest;
+use crate::rules::matcher::glob_match;
+use crate::sitemap::links::request_url;
+
+// A regex matched against the whole URL, kept with its source for saving
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UrlPattern(Regex);
+
+impl UrlPattern {
+    pub fn is_match(&self, url: &str) -> bool {
+        self.0.is_match(url)
+    }
+}
+
+impl PartialEq for UrlPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl FromStr for UrlPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self(Regex::new(s).with_context(|| format!("Invalid scope regex {}", s))?))
+    }
+}
+
+impl TryFrom<String> for UrlPattern {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<UrlPattern> for String {
+    fn from(pattern: UrlPattern) -> Self {
+        pattern.0.as_str().to_string()
+    }
+}
+
+// One include or exclude entry. Every field that is set has to match; a rule
+// with no fields matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScopeRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheme: Option<String>,
+    // `example.com` matches that host only, `*.example.com` it and its subdomains;
+    // other `*` wildcards match any characters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    // Address range, e.g. 10.0.0.0/8; only matches URLs whose host is an IP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpNet>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    // Path prefix, matched on whole segments unless it ends with `/`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<UrlPattern>,
+}
+
+impl ScopeRule {
+    pub fn host(pattern: &str) -> Self {
+        Self {
+            host: Some(pattern.to_ascii_lowercase()),
+            ..Default::default()
+        }
+    }
+
+    pub fn ip(range: IpNet) -> Self {
+        Self {
+            ip: Some(range),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_scheme(mut self, scheme: &str) -> Self {
+        self.scheme = Some(scheme.to_ascii_lowercase());
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        if self.scheme.as_deref().is_some_and(|scheme| scheme != url.scheme()) {
+            return false;
+        }
+        if let Some(pattern) = &self.host {
+            let host = url.host_str().unwrap_or_default().trim_start_matches('[').trim_end_matches(']');
+            if !host_matches(pattern, host) {
+                return false;
+            }
+        }
+        if let Some(range) = &self.ip {
+            let address = match url.host() {
+                Some(Host::Ipv4(address)) => IpAddr::V4(address),
+                Some(Host::Ipv6(address)) => IpAddr::V6(address),
+                _ => return false,
+            };
+            if !range.contains(&address) {
+                return false;
+            }
+        }
+        if self.port.is_some_and(|port| Some(port) != url.port_or_known_default()) {
+            return false;
+        }
+        if let Some(prefix) = &self.path {
+            let Some(rest) = url.path().strip_prefix(prefix.as_str()) else {
+                return false;
+            };
+            if !(prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/')) {
+                return false;
+            }
+        }
+        self.regex.as_ref().is_none_or(|regex| regex.is_match(url.as_str()))
+    }
+}
+
+// `regex:PATTERN`, an IP address or CIDR range, or `[scheme://]host[:port][/path]`
+// where the host may be a glob and `*` alone matches any host
+impl FromStr for ScopeRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(pattern) = s.strip_prefix("regex:") {
+            return Ok(Self {
+                regex: Some(pattern.parse()?),
+                ..Default::default()
+            });
+        }
+        if let Ok(range) = s.parse::<IpNet>() {
+            return Ok(Self::ip(range));
+        }
+        if let Ok(address) = s.parse::<IpAddr>() {
+            return Ok(Self::ip(address.into()));
+        }
+
+        let (scheme, rest) = match s.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, s),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                let port = port.parse().map_err(|_| anyhow!("Invalid port {:?} in scope rule {}", port, s))?;
+                (host, Some(port))
+            }
+            _ => (authority, None),
+        };
+        if host.is_empty() {
+            bail!("Invalid scope rule {}, expected a host, IP range or regex:PATTERN", s);
+        }
+
+        let bare = host.trim_start_matches('[').trim_end_matches(']');
+        let mut rule = match bare.parse::<IpAddr>() {
+            Ok(address) => Self::ip(address.into()),
+            Err(_) if host == "*" => Self::default(),
+            Err(_) => Self::host(host),
+        };
+        rule.scheme = scheme;
+        rule.port = port;
+        if !path.is_empty() && path != "/" {
+            rule.path = Some(path.to_string());
+        }
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for ScopeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(regex) = &self.regex {
+            return write!(f, "regex:{}", regex.0.as_str());
+        }
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}://", scheme)?;
+        }
+        match (&self.host, &self.ip) {
+            (Some(host), _) => write!(f, "{}", host)?,
+            (None, Some(IpNet::V6(range))) if range.prefix_len() == 128 => write!(f, "[{}]", range.addr())?,
+            (None, Some(range)) if range.prefix_len() == range.max_prefix_len() => write!(f, "{}", range.addr())?,
+            (None, Some(range)) => write!(f, "{}", range)?,
+            (None, None) => write!(f, "*")?,
+        }
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path.as_deref().unwrap_or_default())
+    }
+}
+
+// Whether a host pattern from a scope matches a host, ignoring case
+pub fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.to_ascii_lowercase();
+    let host = host.to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) if !domain.contains('*') => host == domain || host.ends_with(&format!(".{}", domain)),
+        _ => glob_match(&pattern, &host),
+    }
+}
+
+// What the tools are allowed to touch. A URL is in scope when it matches an include
+// rule (or there are none), no exclude rule, and the filter expression if there is one.
+// Out-of-scope traffic is still proxied; the flags decide whether it is recorded and
+// whether it goes through the interceptors.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scope {
+    #[serde(default)]
+    pub include: Vec<ScopeRule>,
+    #[serde(default)]
+    pub exclude: Vec<ScopeRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Filter>,
+    #[serde(default = "crate::utils::default_true")]
+    pub record_out_of_scope: bool,
+    #[serde(default = "crate::utils::default_true")]
+    pub intercept_out_of_scope: bool,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scope {
+    // Everything is in scope
+    pub fn new() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            filter: None,
+            record_out_of_scope: true,
+            intercept_out_of_scope: true,
+        }
+    }
+
+    // A scope including these host patterns
+    pub fn from_hosts(hosts: &[String]) -> Self {
+        Self {
+            include: hosts.iter().map(|host| ScopeRule::host(host)).collect(),
+            ..Self::new()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&text).with_context(|| format!("Invalid scope in {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn with_include(mut self, rule: ScopeRule) -> Self {
+        self.include.push(rule);
+        self
+    }
+
+    pub fn with_exclude(mut self, rule: ScopeRule) -> Self {
+        self.exclude.push(rule);
+        self
+    }
+
+    // Also require requests to match a filter expression, e.g. `method != OPTIONS`
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    // Leave out-of-scope traffic out of the project, site map and scanners
+    pub fn with_record_out_of_scope(mut self, record: bool) -> Self {
+        self.record_out_of_scope = record;
+        self
+    }
+
+    // Send out-of-scope traffic straight through, bypassing the interceptors
+    pub fn with_intercept_out_of_scope(mut self, intercept: bool) -> Self {
+        self.intercept_out_of_scope = intercept;
+        self
+    }
+
+    // Whether the scope lets everything through
+    pub fn is_unrestricted(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.filter.is_none()
+    }
+
+    // A URL on its own is filtered as a GET request for it
+    pub fn contains_url(&self, url: &Url) -> bool {
+        self.rules_match(url)
+            && self
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches_request(&FlowRequest::new("GET", url.as_str())))
+    }
+
+    // Requests without a usable URL are only in scope when nothing is included explicitly
+    pub fn contains(&self, request: &FlowRequest) -> bool {
+        let rules_match = match request_url(request) {
+            Some(url) => self.rules_match(&url),
+            None => self.include.is_empty(),
+        };
+        rules_match && self.filter.as_ref().is_none_or(|filter| filter.matches_request(request))
+    }
+
+    fn rules_match(&self, url: &Url) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|rule| rule.matches(url)))
+            && !self.exclude.iter().any(|rule| rule.matches(url))
+    }
+
+    // Whether the proxy should record this request's flow
+    pub fn records(&self, request: &FlowRequest) -> bool {
+        self.record_out_of_scope || self.contains(request)
+    }
+
+    // Whether this request goes through the interceptors
+    pub fn intercepts(&self, request: &FlowRequest) -> bool {
+        self.intercept_out_of_scope || self.contains(request)
+    }
+}
@@ -0,0 +1,635 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use log::info;
+use rusqlite::{params, Connection, DatabaseName, OpenFlags, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::flow::model::{
+    Direction, Flow, FlowId, FlowRequest, FlowResponse, Timings, TlsInfo, WebSocketMessage,
+    WebSocketOpcode,
+};
+use crate::project::scope::Scope;
+
+// Schema migrations, applied in order and tracked through `PRAGMA user_version`
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE bodies (
+        id INTEGER PRIMARY KEY,
+        hash BLOB NOT NULL UNIQUE,
+        size INTEGER NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE TABLE flows (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        started_at TEXT NOT NULL,
+        method TEXT NOT NULL,
+        uri TEXT NOT NULL,
+        request_version TEXT NOT NULL,
+        request_headers TEXT NOT NULL,
+        request_body_id INTEGER REFERENCES bodies(id),
+        status INTEGER,
+        response_version TEXT,
+        response_headers TEXT,
+        response_body_id INTEGER REFERENCES bodies(id),
+        duration_ms INTEGER,
+        tls TEXT,
+        notes TEXT
+    );
+    CREATE TABLE flow_tags (
+        flow_id INTEGER NOT NULL REFERENCES flows(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (flow_id, tag)
+    );
+    CREATE TABLE websocket_messages (
+        id INTEGER PRIMARY KEY,
+        flow_id INTEGER NOT NULL REFERENCES flows(id) ON DELETE CASCADE,
+        timestamp TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        opcode TEXT NOT NULL,
+        payload BLOB NOT NULL
+    );
+    CREATE INDEX websocket_messages_flow ON websocket_messages(flow_id);",
+    "ALTER TABLE flows ADD COLUMN timings TEXT;",
+    "ALTER TABLE flows ADD COLUMN replay_of INTEGER REFERENCES flows(id);",
+    "ALTER TABLE flows ADD COLUMN original_uri TEXT;",
+    "CREATE TABLE scope (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        data TEXT NOT NULL
+    );",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyPart {
+    Request,
+    Response,
+}
+
+// Lightweight view of a stored flow that does not touch the body table
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowSummary {
+    pub id: FlowId,
+    pub started_at: DateTime<Utc>,
+    pub method: String,
+    pub uri: String,
+    pub status: Option<u16>,
+    pub request_body_size: u64,
+    pub response_body_size: u64,
+    pub duration_ms: Option<u64>,
+    pub tags: Vec<String>,
+    pub replay_of: Option<FlowId>,
+}
+
+// A ferrum project: every captured flow persisted into a single SQLite file
+pub struct Project {
+    conn: Mutex<Connection>,
+    path: Option<PathBuf>,
+    read_only: bool,
+}
+
+impl Project {
+    // Create a new project file or reopen an existing one to continue a session
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        info!("Opening project {}", path.display());
+
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).context("Failed to create project directory")?;
+        }
+
+        let conn = Connection::open(path).context("Failed to open project file")?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .context("Failed to enable write-ahead logging")?;
+
+        Self::from_connection(conn, Some(path.to_path_buf()), false)
+    }
+
+    // Open an existing project for browsing; all writes are rejected
+    pub fn open_read_only<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        info!("Opening project {} (read-only)", path.display());
+
+        if !path.exists() {
+            bail!("Project file {} does not exist", path.display());
+        }
+
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .context("Failed to open project file")?;
+
+        Self::from_connection(conn, Some(path.to_path_buf()), true)
+    }
+
+    // A throwaway project kept entirely in memory
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory().context("Failed to create in-memory project")?;
+        Self::from_connection(conn, None, false)
+    }
+
+    fn from_connection(mut conn: Connection, path: Option<PathBuf>, read_only: bool) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)
+            .context("Failed to enable foreign keys")?;
+
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            bail!(
+                "Project was created by a newer version of ferrum (schema {}, supported {})",
+                version,
+                MIGRATIONS.len()
+            );
+        }
+
+        if version < MIGRATIONS.len() {
+            if read_only {
+                bail!("Project schema is out of date; open it read-write once to upgrade it");
+            }
+
+            // Each step commits with its version bump, so a crash never leaves one half applied
+            for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+                let tx = conn.transaction()?;
+                tx.execute_batch(migration)
+                    .with_context(|| format!("Failed to apply project migration {}", index + 1))?;
+                tx.pragma_update(None, "user_version", index + 1)?;
+                tx.commit()
+                    .with_context(|| format!("Failed to apply project migration {}", index + 1))?;
+            }
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path,
+            read_only,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // A panic while the lock was held leaves the connection usable, so carry on with it
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn writable_conn(&self) -> Result<MutexGuard<'_, Connection>> {
+        if self.read_only {
+            bail!("Project is open read-only");
+        }
+        Ok(self.conn())
+    }
+
+    // Persist a complete flow and return the ID it was stored under
+    pub fn save_flow(&self, flow: &Flow) -> Result<FlowId> {
+        let mut conn = self.writable_conn()?;
+        let tx = conn.transaction()?;
+
+        let request_body_id = store_body(&tx, &flow.request.body)?;
+        let response_body_id = match &flow.response {
+            Some(response) => store_body(&tx, &response.body)?,
+            None => None,
+        };
+
+        let tls = flow.tls.as_ref().map(serde_json::to_string).transpose()?;
+        let timings = flow.timings.as_ref().map(serde_json::to_string).transpose()?;
+        let response_headers = flow
+            .response
+            .as_ref()
+            .map(|response| serde_json::to_string(&response.headers))
+            .transpose()?;
+
+        tx.execute(
+            "INSERT INTO flows (
+                started_at, method, uri, request_version, request_headers, request_body_id,
+                status, response_version, response_headers, response_body_id,
+                duration_ms, tls, notes, timings, replay_of, original_uri
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                flow.started_at.to_rfc3339(),
+                flow.request.method,
+                flow.request.uri,
+                flow.request.version,
+                serde_json::to_string(&flow.request.headers)?,
+                request_body_id,
+                flow.response.as_ref().map(|response| response.status),
+                flow.response.as_ref().map(|response| response.version.clone()),
+                response_headers,
+                response_body_id,
+                flow.duration_ms,
+                tls,
+                flow.notes,
+                timings,
+                flow.replay_of,
+                flow.original_uri,
+            ],
+        )
+        .context("Failed to save flow")?;
+
+        let id = tx.last_insert_rowid();
+
+        for tag in &flow.tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO flow_tags (flow_id, tag) VALUES (?1, ?2)",
+                params![id, tag],
+            )?;
+        }
+
+        for message in &flow.websocket_messages {
+            insert_websocket_message(&tx, id, message)?;
+        }
+
+        tx.commit()?;
+        Ok(id)
+    }
+
+    // Load a flow with all of its bodies and messages
+    pub fn load_flow(&self, id: FlowId) -> Result<Option<Flow>> {
+        self.load(id, true)
+    }
+
+    // Load a flow with its headers and metadata but empty bodies; read_body and
+    // body_size get at the bodies when they are needed
+    pub fn load_flow_metadata(&self, id: FlowId) -> Result<Option<Flow>> {
+        self.load(id, false)
+    }
+
+    fn load(&self, id: FlowId, with_bodies: bool) -> Result<Option<Flow>> {
+        let conn = self.conn();
+        let bodies = if with_bodies {
+            "(SELECT data FROM bodies WHERE id = f.request_body_id),
+             (SELECT data FROM bodies WHERE id = f.response_body_id)"
+        } else {
+            "NULL, NULL"
+        };
+
+        let row = conn
+            .query_row(
+                &format!(
+                    "SELECT f.started_at, f.method, f.uri, f.request_version, f.request_headers,
+                            f.status, f.response_version, f.response_headers,
+                            f.duration_ms, f.tls, f.notes, f.timings, f.replay_of, f.original_uri, {}
+                     FROM flows f
+                     WHERE f.id = ?1",
+                    bodies
+                ),
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, Option<u16>>(5)?,
+                        row.get::<_, Option<String>>(6)?,
+                        row.get::<_, Option<String>>(7)?,
+                        row.get::<_, Option<u64>>(8)?,
+                        row.get::<_, Option<String>>(9)?,
+                        row.get::<_, Option<String>>(10)?,
+                        row.get::<_, Option<String>>(11)?,
+                        row.get::<_, Option<FlowId>>(12)?,
+                        row.get::<_, Option<String>>(13)?,
+                        row.get::<_, Option<Vec<u8>>>(14)?,
+                        row.get::<_, Option<Vec<u8>>>(15)?,
+                    ))
+                },
+            )
+            .optional()
+            .context("Failed to load flow")?;
+
+        let Some((
+            started_at,
+            method,
+            uri,
+            request_version,
+            request_headers,
+            status,
+            response_version,
+            response_headers,
+            duration_ms,
+            tls,
+            notes,
+            timings,
+            replay_of,
+            original_uri,
+            request_body,
+            response_body,
+        )) = row
+        else {
+            return Ok(None);
+        };
+
+        let response = match status {
+            Some(status) => Some(FlowResponse {
+                status,
+                version: response_version.unwrap_or_else(|| "HTTP/1.1".to_string()),
+                headers: match response_headers {
+                    Some(headers) => serde_json::from_str(&headers)?,
+                    None => Vec::new(),
+                },
+                body: response_body.unwrap_or_default(),
+            }),
+            None => None,
+        };
+
+        let tls: Option<TlsInfo> = tls.map(|tls| serde_json::from_str(&tls)).transpose()?;
+        let timings: Option<Timings> =
+            timings.map(|timings| serde_json::from_str(&timings)).transpose()?;
+
+        Ok(Some(Flow {
+            id,
+            started_at: parse_timestamp(&started_at)?,
+            request: FlowRequest {
+                method,
+                uri,
+                version: request_version,
+                headers: serde_json::from_str(&request_headers)?,
+                body: request_body.unwrap_or_default(),
+            },
+            response,
+            duration_ms,
+            timings,
+            tls,
+            websocket_messages: load_websocket_messages(&conn, id)?,
+            tags: load_tags(&conn, id)?,
+            notes,
+            replay_of,
+            original_uri,
+        }))
+    }
+
+    // List every stored flow, oldest first, without loading any bodies
+    pub fn list_flows(&self) -> Result<Vec<FlowSummary>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT f.id, f.started_at, f.method, f.uri, f.status,
+                    COALESCE(rb.size, 0), COALESCE(sb.size, 0), f.duration_ms, f.replay_of
+             FROM flows f
+             LEFT JOIN bodies rb ON rb.id = f.request_body_id
+             LEFT JOIN bodies sb ON sb.id = f.response_body_id
+             ORDER BY f.id",
+        )?;
+
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, FlowId>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, Option<u16>>(4)?,
+                    row.get::<_, u64>(5)?,
+                    row.get::<_, u64>(6)?,
+                    row.get::<_, Option<u64>>(7)?,
+                    row.get::<_, Option<FlowId>>(8)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        // Every flow's tags in one query rather than one per flow
+        let mut tags: HashMap<FlowId, Vec<String>> = HashMap::new();
+        let mut stmt = conn.prepare("SELECT flow_id, tag FROM flow_tags ORDER BY flow_id, tag")?;
+        for row in stmt.query_map([], |row| Ok((row.get::<_, FlowId>(0)?, row.get::<_, String>(1)?)))? {
+            let (id, tag) = row?;
+            tags.entry(id).or_default().push(tag);
+        }
+
+        rows.into_iter()
+            .map(
+                |(
+                    id,
+                    started_at,
+                    method,
+                    uri,
+                    status,
+                    request_size,
+                    response_size,
+                    duration_ms,
+                    replay_of,
+                )| {
+                    Ok(FlowSummary {
+                        id,
+                        started_at: parse_timestamp(&started_at)?,
+                        method,
+                        uri,
+                        status,
+                        request_body_size: request_size,
+                        response_body_size: response_size,
+                        duration_ms,
+                        tags: tags.remove(&id).unwrap_or_default(),
+                        replay_of,
+                    })
+                },
+            )
+            .collect()
+    }
+
+    pub fn flow_count(&self) -> Result<usize> {
+        let count: usize = self
+            .conn()
+            .query_row("SELECT COUNT(*) FROM flows", [], |row| row.get(0))?;
+        Ok(count)
+    }
+
+    // Read part of a stored body without pulling the whole blob into memory
+    pub fn read_body(&self, id: FlowId, part: BodyPart, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let conn = self.conn();
+        let Some(body_id) = body_id(&conn, id, part)? else {
+            return Ok(Vec::new());
+        };
+
+        let blob = conn
+            .blob_open(DatabaseName::Main, "bodies", "data", body_id, true)
+            .context("Failed to open body")?;
+
+        let size = blob.len() as u64;
+        if offset >= size {
+            return Ok(Vec::new());
+        }
+
+        let len = len.min((size - offset) as usize);
+        let mut buf = vec![0u8; len];
+        blob.read_at_exact(&mut buf, offset as usize)?;
+        Ok(buf)
+    }
+
+    pub fn body_size(&self, id: FlowId, part: BodyPart) -> Result<u64> {
+        let conn = self.conn();
+        let column = match part {
+            BodyPart::Request => "request_body_id",
+            BodyPart::Response => "response_body_id",
+        };
+        let size: Option<u64> = conn
+            .query_row(
+                &format!(
+                    "SELECT b.size FROM flows f JOIN bodies b ON b.id = f.{} WHERE f.id = ?1",
+                    column
+                ),
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(size.unwrap_or(0))
+    }
+
+    pub fn add_tag(&self, id: FlowId, tag: &str) -> Result<()> {
+        self.writable_conn()?.execute(
+            "INSERT OR IGNORE INTO flow_tags (flow_id, tag) VALUES (?1, ?2)",
+            params![id, tag],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_tag(&self, id: FlowId, tag: &str) -> Result<()> {
+        self.writable_conn()?.execute(
+            "DELETE FROM flow_tags WHERE flow_id = ?1 AND tag = ?2",
+            params![id, tag],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_notes(&self, id: FlowId, notes: Option<&str>) -> Result<()> {
+        let updated = self.writable_conn()?.execute(
+            "UPDATE flows SET notes = ?1 WHERE id = ?2",
+            params![notes, id],
+        )?;
+        if updated == 0 {
+            bail!("Flow {} does not exist", id);
+        }
+        Ok(())
+    }
+
+    pub fn add_websocket_message(&self, id: FlowId, message: &WebSocketMessage) -> Result<()> {
+        let conn = self.writable_conn()?;
+        insert_websocket_message(&conn, id, message)
+    }
+
+    // The engagement's scope, replacing any saved before
+    pub fn save_scope(&self, scope: &Scope) -> Result<()> {
+        let json = serde_json::to_string(scope)?;
+        self.writable_conn()?
+            .execute("INSERT OR REPLACE INTO scope (id, data) VALUES (1, ?1)", params![json])
+            .context("Failed to save scope")?;
+        Ok(())
+    }
+
+    // The scope saved with save_scope, if there is one
+    pub fn load_scope(&self) -> Result<Option<Scope>> {
+        let json: Option<String> = self
+            .conn()
+            .query_row("SELECT data FROM scope WHERE id = 1", [], |row| row.get(0))
+            .optional()?;
+        json.map(|json| serde_json::from_str(&json).context("Invalid scope in project"))
+            .transpose()
+    }
+}
+
+// Store a body once per distinct content and return its row ID
+fn store_body(conn: &Connection, data: &[u8]) -> Result<Option<i64>> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let hash = Sha256::digest(data).to_vec();
+    if let Some(id) = conn
+        .query_row("SELECT id FROM bodies WHERE hash = ?1", params![hash], |row| row.get(0))
+        .optional()?
+    {
+        return Ok(Some(id));
+    }
+
+    conn.execute(
+        "INSERT INTO bodies (hash, size, data) VALUES (?1, ?2, ?3)",
+        params![hash, data.len() as u64, data],
+    )
+    .context("Failed to store body")?;
+
+    Ok(Some(conn.last_insert_rowid()))
+}
+
+fn body_id(conn: &Connection, id: FlowId, part: BodyPart) -> Result<Option<i64>> {
+    let sql = match part {
+        BodyPart::Request => "SELECT request_body_id FROM flows WHERE id = ?1",
+        BodyPart::Response => "SELECT response_body_id FROM flows WHERE id = ?1",
+    };
+
+    conn.query_row(sql, params![id], |row| row.get::<_, Option<i64>>(0))
+        .optional()?
+        .ok_or_else(|| anyhow!("Flow {} does not exist", id))
+}
+
+fn insert_websocket_message(conn: &Connection, id: FlowId, message: &WebSocketMessage) -> Result<()> {
+    let direction = match message.direction {
+        Direction::ClientToServer => "client",
+        Direction::ServerToClient => "server",
+    };
+
+    conn.execute(
+        "INSERT INTO websocket_messages (flow_id, timestamp, direction, opcode, payload)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            id,
+            message.timestamp.to_rfc3339(),
+            direction,
+            message.opcode.as_str(),
+            message.payload,
+        ],
+    )
+    .context("Failed to store WebSocket message")?;
+
+    Ok(())
+}
+
+fn load_websocket_messages(conn: &Connection, id: FlowId) -> Result<Vec<WebSocketMessage>> {
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, direction, opcode, payload FROM websocket_messages
+         WHERE flow_id = ?1 ORDER BY id",
+    )?;
+
+    let rows = stmt
+        .query_map(params![id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    rows.into_iter()
+        .map(|(timestamp, direction, opcode, payload)| {
+            Ok(WebSocketMessage {
+                timestamp: parse_timestamp(&timestamp)?,
+                direction: if direction == "client" {
+                    Direction::ClientToServer
+                } else {
+                    Direction::ServerToClient
+                },
+                opcode: WebSocketOpcode::parse(&opcode)
+                    .ok_or_else(|| anyhow!("Unknown WebSocket opcode {}", opcode))?,
+                payload,
+            })
+        })
+        .collect()
+}
+
+fn load_tags(conn: &Connection, id: FlowId) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT tag FROM flow_tags WHERE flow_id = ?1 ORDER BY tag")?;
+    let tags = stmt
+        .query_map(params![id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(tags)
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(value)
+        .with_context(|| format!("Invalid timestamp {}", value))?
+        .with_timezone(&Utc))
+}
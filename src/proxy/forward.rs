@@ -0,0 +1,287 @@
+use std::sync::Arc;
+use anyhow::Result;
+use hyper::upgrade::Upgraded;
+use log::{debug, info, warn};
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+use crate::flow::model::{Flow, FlowRequest, FlowResponse, WebSocketMessage};
+use crate::intercept::external::{ExternalInterceptor, EXTERNAL_TAG};
+use crate::intercept::plugin::{PluginInterceptor, PLUGIN_TAG};
+use crate::intercept::request::RequestInterceptor;
+use crate::intercept::response::ResponseInterceptor;
+use crate::intercept::script::{ScriptInterceptor, SCRIPT_TAG};
+use crate::project::scope::Scope;
+use crate::proxy::upstream::UpstreamClient;
+use crate::rules::map_remote::MapRemote;
+use crate::rules::replace::ReplaceRules;
+
+// The interceptor chain plus upstream client that every outgoing request goes through.
+// Live traffic, replays and other tools share one instance so they behave identically.
+#[derive(Clone)]
+pub struct Forwarder {
+    req_interceptor: Arc<RwLock<RequestInterceptor>>,
+    res_interceptor: Arc<RwLock<ResponseInterceptor>>,
+    scripts: Arc<RwLock<ScriptInterceptor>>,
+    plugins: Arc<RwLock<PluginInterceptor>>,
+    external: Arc<RwLock<ExternalInterceptor>>,
+    replace_rules: Arc<RwLock<ReplaceRules>>,
+    map_remote: Arc<RwLock<MapRemote>>,
+    scope: Arc<RwLock<Scope>>,
+    upstream: UpstreamClient,
+}
+
+impl Default for Forwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Forwarder {
+    pub fn new() -> Self {
+        Self {
+            req_interceptor: Arc::new(RwLock::new(RequestInterceptor::new())),
+            res_interceptor: Arc::new(RwLock::new(ResponseInterceptor::new())),
+            scripts: Arc::new(RwLock::new(ScriptInterceptor::new())),
+            plugins: Arc::new(RwLock::new(PluginInterceptor::new())),
+            external: Arc::new(RwLock::new(ExternalInterceptor::new())),
+            replace_rules: Arc::new(RwLock::new(ReplaceRules::new())),
+            map_remote: Arc::new(RwLock::new(MapRemote::new())),
+            scope: Arc::new(RwLock::new(Scope::new())),
+            upstream: UpstreamClient::new(),
+        }
+    }
+
+    // Send through a differently configured upstream client (proxy chaining, TLS options)
+    pub fn with_upstream(mut self, upstream: UpstreamClient) -> Self {
+        self.upstream = upstream;
+        self
+    }
+
+    pub fn with_request_interceptor(self, interceptor: RequestInterceptor) -> Self {
+        Self {
+            req_interceptor: Arc::new(RwLock::new(interceptor)),
+            ..self
+        }
+    }
+
+    pub fn request_interceptor(&self) -> &Arc<RwLock<RequestInterceptor>> {
+        &self.req_interceptor
+    }
+
+    pub fn with_response_interceptor(self, interceptor: ResponseInterceptor) -> Self {
+        Self {
+            res_interceptor: Arc::new(RwLock::new(interceptor)),
+            ..self
+        }
+    }
+
+    pub fn response_interceptor(&self) -> &Arc<RwLock<ResponseInterceptor>> {
+        &self.res_interceptor
+    }
+
+    pub fn with_scripts(self, scripts: ScriptInterceptor) -> Self {
+        Self {
+            scripts: Arc::new(RwLock::new(scripts)),
+            ..self
+        }
+    }
+
+    // Rhai scripts run after the interceptors, before match-and-replace rules
+    pub fn scripts(&self) -> &Arc<RwLock<ScriptInterceptor>> {
+        &self.scripts
+    }
+
+    pub fn with_plugins(self, plugins: PluginInterceptor) -> Self {
+        Self {
+            plugins: Arc::new(RwLock::new(plugins)),
+            ..self
+        }
+    }
+
+    // WebAssembly plugins run after the scripts
+    pub fn plugins(&self) -> &Arc<RwLock<PluginInterceptor>> {
+        &self.plugins
+    }
+
+    pub fn with_external(self, external: ExternalInterceptor) -> Self {
+        Self {
+            external: Arc::new(RwLock::new(external)),
+            ..self
+        }
+    }
+
+    // External processes run after the plugins
+    pub fn external(&self) -> &Arc<RwLock<ExternalInterceptor>> {
+        &self.external
+    }
+
+    pub fn with_replace_rules(self, rules: ReplaceRules) -> Self {
+        Self {
+            replace_rules: Arc::new(RwLock::new(rules)),
+            ..self
+        }
+    }
+
+    // Match-and-replace rules; shared by every clone, so edits apply to live traffic
+    pub fn replace_rules(&self) -> &Arc<RwLock<ReplaceRules>> {
+        &self.replace_rules
+    }
+
+    // The replace rules, reloaded first if their file changed
+    async fn current_replace_rules(&self) -> RwLockReadGuard<'_, ReplaceRules> {
+        if self.replace_rules.read().await.is_stale() {
+            let mut rules = self.replace_rules.write().await;
+            if rules.is_stale() {
+                match rules.reload() {
+                    Ok(()) => info!("Reloaded {} match-and-replace rule(s)", rules.rules().len()),
+                    Err(e) => warn!("Keeping the previous match-and-replace rules: {:#}", e),
+                }
+            }
+        }
+        self.replace_rules.read().await
+    }
+
+    pub fn with_map_remote(self, map_remote: MapRemote) -> Self {
+        Self {
+            map_remote: Arc::new(RwLock::new(map_remote)),
+            ..self
+        }
+    }
+
+    // Map Remote rules, applied last so they decide where the upstream client connects
+    pub fn map_remote(&self) -> &Arc<RwLock<MapRemote>> {
+        &self.map_remote
+    }
+
+    pub fn with_scope(self, scope: Scope) -> Self {
+        Self {
+            scope: Arc::new(RwLock::new(scope)),
+            ..self
+        }
+    }
+
+    // The target scope; also read by the proxy server to decide what gets recorded
+    pub fn scope(&self) -> &Arc<RwLock<Scope>> {
+        &self.scope
+    }
+
+    pub fn upstream(&self) -> &UpstreamClient {
+        &self.upstream
+    }
+
+    // Run a request through the interceptors, send it upstream and return the completed flow.
+    // Out-of-scope requests skip the interceptors if the scope says so, and the request and
+    // response interceptors only see messages matching their filters.
+    pub async fn forward(&self, request: FlowRequest) -> Result<Flow> {
+        let intercept = self.scope.read().await.intercepts(&request);
+        let mut request = if !intercept {
+            debug!("Not intercepting out-of-scope request {} {}", request.method, request.uri);
+            request
+        } else if self.req_interceptor.read().await.matches(&request) {
+            let http_request = request.to_http()?;
+            let http_request = self.req_interceptor.read().await.intercept(http_request).await?;
+            FlowRequest {
+                version: request.version,
+                ..FlowRequest::from_http(http_request)
+            }
+        } else {
+            request
+        };
+        let mut extended = if intercept {
+            self.scripts.read().await.on_request(&mut request)
+        } else {
+            Default::default()
+        };
+        let mut answered_by = SCRIPT_TAG;
+        if intercept && extended.response.is_none() {
+            let plugged = self.plugins.read().await.on_request(&mut request).await;
+            extended.tags.extend(plugged.tags);
+            extended.response = plugged.response;
+            answered_by = PLUGIN_TAG;
+        }
+        if intercept && extended.response.is_none() {
+            let external = self.external.read().await.on_request(&mut request).await;
+            extended.tags.extend(external.tags);
+            extended.response = external.response;
+            answered_by = EXTERNAL_TAG;
+        }
+
+        // A script, plugin or external process answered, so nothing goes upstream
+        if let Some(response) = extended.response {
+            let mut flow = Flow::new(request);
+            flow.response = Some(response);
+            flow.duration_ms = Some(0);
+            flow.tags = extended.tags;
+            flow.tags.push(answered_by.to_string());
+            return Ok(flow);
+        }
+
+        self.current_replace_rules().await.apply_request(&mut request);
+        let original_uri = self.map_remote.read().await.apply(&mut request);
+
+        let mut flow = Flow::new(request);
+        flow.original_uri = original_uri;
+        flow.tags = extended.tags;
+
+        let (response, timings, tls) = self.upstream.send(&flow.request).await?;
+        flow.tls = tls;
+
+        let mut response = if intercept && self.res_interceptor.read().await.matches(&flow.request, &response) {
+            let http_response = response.to_http()?;
+            let http_response = self.res_interceptor.read().await.intercept(http_response).await?;
+            FlowResponse {
+                version: response.version,
+                ..FlowResponse::from_http(http_response)
+            }
+        } else {
+            response
+        };
+        if intercept {
+            let tags = self.scripts.read().await.on_response(&flow.request, &mut response);
+            flow.tags.extend(tags);
+            let tags = self.plugins.read().await.on_response(&flow.request, &mut response).await;
+            flow.tags.extend(tags);
+            let tags = self.external.read().await.on_response(&flow.request, &mut response).await;
+            flow.tags.extend(tags);
+        }
+        self.current_replace_rules().await.apply_response(&flow.request, &mut response);
+
+        debug!(
+            "Completed {} {} -> {}",
+            flow.request.method, flow.request.uri, response.status
+        );
+
+        flow.duration_ms = Some((timings.send_ms + timings.wait_ms + timings.receive_ms) as u64);
+        flow.timings = Some(timings);
+        flow.response = Some(response);
+
+        Ok(flow)
+    }
+
+    // Send a WebSocket handshake upstream, through Map Remote like any other request.
+    // When the server switches protocols the upgraded connection comes back with the flow.
+    pub async fn open_websocket(&self, mut request: FlowRequest) -> Result<(Flow, Option<Upgraded>)> {
+        let original_uri = self.map_remote.read().await.apply(&mut request);
+        let mut flow = Flow::new(request);
+        flow.original_uri = original_uri;
+
+        let (response, tls, upgraded) = self.upstream.upgrade(&flow.request).await?;
+        debug!(
+            "Completed {} {} -> {}",
+            flow.request.method, flow.request.uri, response.status
+        );
+        flow.duration_ms = Some((chrono::Utc::now() - flow.started_at).num_milliseconds().max(0) as u64);
+        flow.tls = tls;
+        flow.response = Some(response);
+        Ok((flow, upgraded))
+    }
+
+    // Run the script hooks over a relayed WebSocket message; false if a script discarded it.
+    // Messages on out-of-scope connections skip the hooks if the scope says so.
+    pub async fn on_websocket_message(&self, request: &FlowRequest, message: &mut WebSocketMessage) -> bool {
+        if !self.scope.read().await.intercepts(request) {
+            return true;
+        }
+        self.scripts.read().await.on_websocket_message(request, message)
+    }
+}
@@ -1 +1,5 @@
+pub mod forward;
+pub mod replay;
 pub mod server;
+pub mod upstream;
+pub mod websocket;
@@ -0,0 +1,98 @@
+use anyhow::{bail, Context, Result};
+use hyper::Uri;
+use log::{info, warn};
+
+use crate::flow::model::{Flow, FlowId, FlowRequest};
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+
+// Point a request at a different scheme/host/port, keeping its path and query
+pub fn retarget_request(request: &mut FlowRequest, target: &Uri) -> Result<()> {
+    let (Some(scheme), Some(authority)) = (target.scheme_str(), target.authority()) else {
+        bail!("Replay target {} must include a scheme and host", target);
+    };
+
+    let original = request
+        .uri
+        .parse::<Uri>()
+        .with_context(|| format!("Invalid request URL {}", request.uri))?;
+    let path_and_query = original
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+
+    request.uri = format!("{}://{}{}", scheme, authority, path_and_query);
+    request.set_header("Host", authority.as_str());
+
+    Ok(())
+}
+
+// Resend a flow through the interceptor chain, optionally to another host
+pub async fn replay_flow(forwarder: &Forwarder, original: &Flow, target: Option<&Uri>) -> Result<Flow> {
+    let mut request = original.request.clone();
+    if let Some(target) = target {
+        retarget_request(&mut request, target)?;
+    }
+
+    info!("Replaying flow {}: {} {}", original.id, request.method, request.uri);
+
+    let mut flow = forwarder.forward(request).await?;
+    flow.replay_of = Some(original.id);
+    // Keep the original's tags alongside any the interceptors just added
+    for tag in &original.tags {
+        if !flow.tags.contains(tag) {
+            flow.tags.push(tag.clone());
+        }
+    }
+
+    Ok(flow)
+}
+
+// Outcome of replaying one stored flow: the ID of the new flow, or the error that
+// stopped it
+#[derive(Debug, Clone)]
+pub struct Replayed {
+    pub original: FlowId,
+    pub replay: Option<FlowId>,
+    pub error: Option<String>,
+}
+
+// Replay stored flows and save each new flow next to the original it came from.
+// A flow that fails is reported in its result and the rest are still replayed.
+pub async fn replay_project_flows(
+    project: &Project,
+    forwarder: &Forwarder,
+    ids: &[FlowId],
+    target: Option<&Uri>,
+) -> Vec<Replayed> {
+    let mut replayed = Vec::with_capacity(ids.len());
+
+    for &id in ids {
+        let result = async {
+            let original = project
+                .load_flow(id)?
+                .with_context(|| format!("Flow {} does not exist", id))?;
+            let flow = replay_flow(forwarder, &original, target).await?;
+            project.save_flow(&flow)
+        }
+        .await;
+
+        match result {
+            Ok(new_id) => replayed.push(Replayed {
+                original: id,
+                replay: Some(new_id),
+                error: None,
+            }),
+            Err(e) => {
+                warn!("Failed to replay flow {}: {:#}", id, e);
+                replayed.push(Replayed {
+                    original: id,
+                    replay: None,
+                    error: Some(format!("{:#}", e)),
+                });
+            }
+        }
+    }
+
+    replayed
+}
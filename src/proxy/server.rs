@@ -1,39 +1,147 @@
+use std::fmt;
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 use anyhow::{Result, Context};
+use hyper::header::HeaderValue;
+use hyper::upgrade::OnUpgrade;
 use hyper::{Request, Response};
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
-use hyper_util::rt::TokioExecutor;
 use log::{info, error, debug};
 use tokio::net::TcpListener;
-use http_body_util::{Full, BodyExt, Empty};
+use tokio::sync::mpsc;
+use http_body_util::{Full, BodyExt};
 use bytes::Bytes;
 
-use crate::intercept::request::RequestInterceptor;
-use crate::intercept::response::ResponseInterceptor;
+use crate::flow::model::{headers_from_map, version_string, Flow, FlowRequest};
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+use crate::proxy::upstream::HOP_BY_HOP_HEADERS;
+use crate::proxy::websocket;
+use crate::rules::block::{BlockAction, BlockRules, BLOCKED_TAG};
+use crate::rules::map_local::{MapLocal, MAP_LOCAL_TAG};
+use crate::scanner::passive::PassiveScanner;
+use crate::sitemap::tree::SiteMap;
 
 #[derive(Clone)]
 pub struct ProxyServer {
     addr: SocketAddr,
     bound_addr: Arc<Mutex<Option<SocketAddr>>>,
-    _req_interceptor: Arc<Mutex<RequestInterceptor>>,
-    _res_interceptor: Arc<Mutex<ResponseInterceptor>>,
+    forwarder: Forwarder,
+    project: Option<Arc<Project>>,
+    site_map: Option<Arc<Mutex<SiteMap>>>,
+    scanner: Option<Arc<Mutex<PassiveScanner>>>,
+    map_local: Arc<tokio::sync::RwLock<MapLocal>>,
+    block_rules: Arc<tokio::sync::RwLock<BlockRules>>,
 }
 
+// What each connection needs to handle its requests
+#[derive(Clone)]
+struct Handler {
+    forwarder: Forwarder,
+    project: Option<Arc<Project>>,
+    map_local: Arc<tokio::sync::RwLock<MapLocal>>,
+    block_rules: Arc<tokio::sync::RwLock<BlockRules>>,
+    // Completed in-scope flows, saved and analysed in order by the recorder task
+    recorder: mpsc::UnboundedSender<Flow>,
+    // A second handle on the client socket, used to reset it
+    connection: Option<Arc<socket2::Socket>>,
+}
+
+// Returned by the service so hyper drops a connection a block rule resets
+#[derive(Debug)]
+struct ConnectionReset;
+
+impl fmt::Display for ConnectionReset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection reset by block rule")
+    }
+}
+
+impl std::error::Error for ConnectionReset {}
+
 type BoxBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 impl ProxyServer {
     pub fn new(addr: SocketAddr) -> Self {
         Self {
             addr,
             bound_addr: Arc::new(Mutex::new(None)),
-            _req_interceptor: Arc::new(Mutex::new(RequestInterceptor::new())),
-            _res_interceptor: Arc::new(Mutex::new(ResponseInterceptor::new())),
+            forwarder: Forwarder::new(),
+            project: None,
+            site_map: None,
+            scanner: None,
+            map_local: Arc::new(tokio::sync::RwLock::new(MapLocal::new())),
+            block_rules: Arc::new(tokio::sync::RwLock::new(BlockRules::new())),
         }
     }
 
+    // Share an interceptor chain and upstream client with other tools
+    pub fn with_forwarder(mut self, forwarder: Forwarder) -> Self {
+        self.forwarder = forwarder;
+        self
+    }
+
+    // Record every forwarded flow into the given project
+    pub fn with_project(mut self, project: Arc<Project>) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    pub fn project(&self) -> Option<&Arc<Project>> {
+        self.project.as_ref()
+    }
+
+    // Add every forwarded flow to a site map shared with the caller
+    pub fn with_site_map(mut self, site_map: Arc<Mutex<SiteMap>>) -> Self {
+        self.site_map = Some(site_map);
+        self
+    }
+
+    pub fn site_map(&self) -> Option<&Arc<Mutex<SiteMap>>> {
+        self.site_map.as_ref()
+    }
+
+    // Run passive checks over every forwarded flow
+    pub fn with_scanner(mut self, scanner: Arc<Mutex<PassiveScanner>>) -> Self {
+        self.scanner = Some(scanner);
+        self
+    }
+
+    pub fn scanner(&self) -> Option<&Arc<Mutex<PassiveScanner>>> {
+        self.scanner.as_ref()
+    }
+
+    // Answer matching requests from disk instead of forwarding them
+    pub fn with_map_local(mut self, map_local: MapLocal) -> Self {
+        self.map_local = Arc::new(tokio::sync::RwLock::new(map_local));
+        self
+    }
+
+    // Map Local rules, shared with running connections so edits apply to the next request
+    pub fn map_local(&self) -> &Arc<tokio::sync::RwLock<MapLocal>> {
+        &self.map_local
+    }
+
+    // Drop or answer matching requests instead of forwarding them
+    pub fn with_block_rules(mut self, block_rules: BlockRules) -> Self {
+        self.block_rules = Arc::new(tokio::sync::RwLock::new(block_rules));
+        self
+    }
+
+    // Block rules, shared with running connections so edits apply to the next request
+    pub fn block_rules(&self) -> &Arc<tokio::sync::RwLock<BlockRules>> {
+        &self.block_rules
+    }
+
+    // The interceptor chain and upstream client used for live traffic
+    pub fn forwarder(&self) -> &Forwarder {
+        &self.forwarder
+    }
+
     pub fn address(&self) -> SocketAddr {
         if let Some(addr) = *self.bound_addr.lock().unwrap() {
             addr
@@ -59,6 +167,9 @@ impl ProxyServer {
             info!("Proxy server bound to {}", local_addr);
         }
 
+        let (recorder, flows) = mpsc::unbounded_channel();
+        tokio::spawn(record_flows(flows, self.project.clone(), self.site_map.clone(), self.scanner.clone()));
+
         loop {
             let (stream, remote_addr) = match listener.accept().await {
                 Ok(conn) => conn,
@@ -70,91 +181,302 @@ impl ProxyServer {
 
             info!("Accepted connection from {}", remote_addr);
 
-            let req_interceptor = Arc::clone(&self._req_interceptor);
-            let res_interceptor = Arc::clone(&self._res_interceptor);
+            let connection = match socket2::SockRef::from(&stream).try_clone() {
+                Ok(socket) => Some(Arc::new(socket)),
+                Err(e) => {
+                    debug!("Block rules will close instead of reset {}: {}", remote_addr, e);
+                    None
+                }
+            };
+            let handler = Handler {
+                forwarder: self.forwarder.clone(),
+                project: self.project.clone(),
+                map_local: self.map_local.clone(),
+                block_rules: self.block_rules.clone(),
+                recorder: recorder.clone(),
+                connection,
+            };
 
             // Spawn a new task for each connection
             tokio::spawn(async move {
                 let io = TokioIo::new(stream);
 
-                let service = service_fn(move |req| {
-                    let _req_interceptor = Arc::clone(&req_interceptor);
-                    let _res_interceptor = Arc::clone(&res_interceptor);
-                    handle_request(req)
-                });
+                let service = service_fn(move |req| handle_request(req, handler.clone()));
 
                 if let Err(e) = http1::Builder::new()
                     .serve_connection(io, service)
+                    .with_upgrades()
                     .await
                 {
-                    error!("Error serving connection: {}", e);
+                    if std::error::Error::source(&e).is_some_and(|source| source.is::<ConnectionReset>()) {
+                        debug!("Reset connection from {}", remote_addr);
+                    } else {
+                        error!("Error serving connection: {}", e);
+                    }
                 }
             });
         }
     }
 }
 
+// A panic while the lock was held leaves the data usable, so carry on with it
+fn lock<T>(value: &Mutex<T>) -> MutexGuard<'_, T> {
+    value.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
 fn full<T: Into<Bytes>>(chunk: T) -> BoxBody {
     Full::new(chunk.into())
         .map_err(|never| match never {})
         .boxed()
 }
 
-async fn handle_request(
-    req: Request<hyper::body::Incoming>,
-) -> Result<Response<BoxBody>, hyper::Error> {
+fn forward_target(req: &Request<hyper::body::Incoming>) -> Option<String> {
+    // An explicit target header takes precedence (used by integration tests)
+    if let Some(target) = req.headers().get("X-Target-Url").and_then(|v| v.to_str().ok()) {
+        return Some(target.to_string());
+    }
+
+    // Otherwise forward absolute-form requests, as sent by clients using us as a proxy
+    if req.uri().scheme().is_some() && req.uri().authority().is_some() {
+        return Some(req.uri().to_string());
+    }
+
+    None
+}
+
+async fn handle_request(mut req: Request<hyper::body::Incoming>, handler: Handler) -> Result<Response<BoxBody>, BoxError> {
     debug!("Received request: {} {}", req.method(), req.uri());
 
-    // Check if this is a request to be forwarded (for integration tests)
-    if let Some(target_url) = req.headers().get("X-Target-Url") {
-        if let Ok(target) = target_url.to_str() {
-            debug!("Forwarding request to target: {}", target);
-
-            // Create an HTTP client
-            let client = hyper_util::client::legacy::Client::builder(TokioExecutor::new())
-                .build::<_, Empty<Bytes>>(
-                    hyper_util::client::legacy::connect::HttpConnector::new());
-
-            // Parse the target URL
-            let target_uri = target.parse::<hyper::Uri>().unwrap();
-
-            // Forward the request
-            let target_req = Request::builder()
-                .method(req.method().clone())
-                .uri(target_uri)
-                .body(http_body_util::Empty::<Bytes>::new())
-                .unwrap();
-
-            // Make the request
-            if let Ok(resp) = client.request(target_req).await {
-                // Convert the response body
-                let (parts, body) = resp.into_parts();
-
-                // Collect the body
-                let body_bytes = match body.collect().await {
-                    Ok(collected) => collected.to_bytes(),
-                    Err(_) => Bytes::new(),
-                };
-
-                // Return the response with the same status code
-                return Ok(Response::from_parts(
-                    parts,
-                    full(body_bytes)
-                ));
-            } else {
+    let Some(target) = forward_target(&req) else {
+        // Default response
+        let response = Response::builder()
+            .status(200)
+            .body(full("Ferrum Proxy - Request received and logged"))
+            .unwrap();
+
+        return Ok(response);
+    };
+
+    debug!("Forwarding request to target: {}", target);
+
+    let on_upgrade = hyper::upgrade::on(&mut req);
+    let (parts, body) = req.into_parts();
+    let body_bytes = body.collect().await?.to_bytes();
+
+    let mut request = FlowRequest {
+        method: parts.method.to_string(),
+        uri: target.clone(),
+        version: version_string(parts.version),
+        headers: headers_from_map(&parts.headers),
+        body: body_bytes.to_vec(),
+    };
+    request.remove_header("X-Target-Url");
+
+    // Scope decides what is kept, judged on the request as the client sent it
+    let record = handler.forwarder.scope().read().await.records(&request);
+
+    // Block rules and Map Local answer before anything reaches the upstream client
+    let blocked = handler.block_rules.read().await.resolve(&request);
+    if blocked.is_none() && websocket::is_handshake(&request) {
+        return open_websocket(on_upgrade, request, record, handler).await;
+    }
+    let local = match blocked {
+        Some(_) => None,
+        None => handler.map_local.read().await.resolve(&request),
+    };
+    let flow = if let Some(action) = &blocked {
+        let mut flow = Flow::new(request);
+        flow.response = action.response();
+        flow.duration_ms = Some(0);
+        flow.tags.push(BLOCKED_TAG.to_string());
+        flow
+    } else if let Some(local) = local {
+        let mut flow = Flow::new(request);
+        flow.response = Some(local.read().await);
+        flow.duration_ms = Some((chrono::Utc::now() - flow.started_at).num_milliseconds().max(0) as u64);
+        flow.tags.push(MAP_LOCAL_TAG.to_string());
+        flow
+    } else {
+        match handler.forwarder.forward(request).await {
+            Ok(flow) => flow,
+            Err(e) => {
+                error!("Error forwarding request to {}: {:#}", target, e);
                 return Ok(Response::builder()
                     .status(500)
                     .body(full("Error forwarding request"))
                     .unwrap());
             }
         }
+    };
+
+    let response = match blocked {
+        Some(BlockAction::Reset) => None,
+        _ => Some(client_response(&flow)),
+    };
+
+    if record {
+        handler.recorder.send(flow).ok();
+    } else {
+        debug!("Not recording out-of-scope request to {}", target);
+    }
+
+    match response {
+        Some(response) => Ok(response),
+        None => {
+            // With no lingering, closing the socket sends a RST instead of a FIN
+            if let Some(connection) = &handler.connection
+                && let Err(e) = connection.set_linger(Some(Duration::ZERO))
+            {
+                debug!("Failed to set up connection reset: {}", e);
+            }
+            Err(Box::new(ConnectionReset))
+        }
     }
+}
 
-    // Default response
-    let response = Response::builder()
-        .status(200)
-        .body(full("Ferrum Proxy - Request received and logged"))
-        .unwrap();
+// Open a WebSocket upstream and pass the server's handshake answer back. Once both
+// sides have switched protocols, messages are relayed between them in the background.
+async fn open_websocket(
+    on_upgrade: OnUpgrade,
+    mut request: FlowRequest,
+    record: bool,
+    handler: Handler,
+) -> Result<Response<BoxBody>, BoxError> {
+    // Compression and other extensions would hide message payloads from the relay
+    request.remove_header("Sec-WebSocket-Extensions");
+    let target = request.uri.clone();
+    let (flow, upstream) = match handler.forwarder.open_websocket(request).await {
+        Ok(opened) => opened,
+        Err(e) => {
+            error!("Error opening WebSocket to {}: {:#}", target, e);
+            return Ok(Response::builder()
+                .status(500)
+                .body(full("Error forwarding request"))
+                .unwrap());
+        }
+    };
 
+    let mut response = client_response(&flow);
+    let Some(upstream) = upstream else {
+        // The server answered without switching protocols, like any other request
+        if record {
+            handler.recorder.send(flow).ok();
+        }
+        return Ok(response);
+    };
+    response.headers_mut().insert(hyper::header::CONNECTION, HeaderValue::from_static("Upgrade"));
+    response.headers_mut().insert(hyper::header::UPGRADE, HeaderValue::from_static("websocket"));
+
+    tokio::spawn(async move {
+        let recording = match handler.project.filter(|_| record) {
+            Some(project) => record_flow(Arc::clone(&project), flow.clone())
+                .await
+                .filter(|saved| saved.id != 0)
+                .map(|saved| (project, saved.id)),
+            None => None,
+        };
+        match on_upgrade.await {
+            Ok(client) => websocket::relay(client, upstream, handler.forwarder, flow.request, recording).await,
+            Err(e) => error!("Client did not switch protocols for {}: {}", target, e),
+        }
+    });
     Ok(response)
 }
+
+// Build the response sent back to the client from the (possibly modified) flow response
+fn client_response(flow: &Flow) -> Response<BoxBody> {
+    let Some(response) = &flow.response else {
+        return Response::builder()
+            .status(502)
+            .body(full("No response from upstream"))
+            .unwrap();
+    };
+
+    // Bodyless responses keep the upstream Content-Length, everything else is recomputed
+    let bodyless = flow.request.method == "HEAD" || matches!(response.status, 101 | 204 | 304);
+
+    let mut builder = Response::builder().status(response.status);
+    for (name, value) in &response.headers {
+        let lower = name.to_ascii_lowercase();
+        if (lower == "content-length" && !bodyless) || HOP_BY_HOP_HEADERS.contains(&lower.as_str()) {
+            continue;
+        }
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    if !bodyless {
+        builder = builder.header("content-length", response.body.len());
+    }
+
+    builder
+        .body(full(response.body.clone()))
+        .unwrap_or_else(|_| {
+            Response::builder()
+                .status(502)
+                .body(full("Invalid upstream response"))
+                .unwrap()
+        })
+}
+
+// Save each flow, then add it to the site map and run passive checks over it. This
+// runs apart from the connections so clients are never kept waiting on the disk, and
+// takes flows one at a time so they are stored in the order they completed.
+async fn record_flows(
+    mut flows: mpsc::UnboundedReceiver<Flow>,
+    project: Option<Arc<Project>>,
+    site_map: Option<Arc<Mutex<SiteMap>>>,
+    scanner: Option<Arc<Mutex<PassiveScanner>>>,
+) {
+    while let Some(flow) = flows.recv().await {
+        let flow = match &project {
+            Some(project) => record_flow(Arc::clone(project), flow).await,
+            None => Some(flow),
+        };
+        let Some(flow) = flow else {
+            continue;
+        };
+        if site_map.is_none() && scanner.is_none() {
+            continue;
+        }
+
+        let (site_map, scanner) = (site_map.clone(), scanner.clone());
+        let analysed = tokio::task::spawn_blocking(move || {
+            if let Some(site_map) = site_map {
+                lock(&site_map).add_flow(&flow);
+            }
+            if let Some(scanner) = scanner {
+                lock(&scanner).record(&flow);
+            }
+        })
+        .await;
+        if let Err(e) = analysed {
+            error!("Failed to analyse flow: {}", e);
+        }
+    }
+}
+
+// Save a flow and hand it back with its new ID (unchanged if saving failed)
+async fn record_flow(project: Arc<Project>, mut flow: Flow) -> Option<Flow> {
+    let result = tokio::task::spawn_blocking(move || {
+        let saved = project.save_flow(&flow);
+        if let Ok(id) = saved {
+            flow.id = id;
+        }
+        (flow, saved)
+    })
+    .await;
+
+    match result {
+        Ok((flow, Ok(id))) => {
+            debug!("Recorded flow {}", id);
+            Some(flow)
+        }
+        Ok((flow, Err(e))) => {
+            error!("Failed to record flow: {:#}", e);
+            Some(flow)
+        }
+        Err(e) => {
+            error!("Failed to record flow: {}", e);
+            None
+        }
+    }
+}
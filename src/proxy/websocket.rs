@@ -0,0 +1,241 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use hyper::upgrade::Upgraded;
+use hyper_util::rt::TokioIo;
+use log::{debug, error};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::flow::model::{Direction, FlowId, FlowRequest, WebSocketMessage, WebSocketOpcode};
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+
+// Messages larger than this end the connection instead of being buffered
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+const CONTINUATION: u8 = 0x0;
+
+// Whether a request asks to open a WebSocket
+pub fn is_handshake(request: &FlowRequest) -> bool {
+    request.method.eq_ignore_ascii_case("GET")
+        && request
+            .header("upgrade")
+            .is_some_and(|protocol| protocol.eq_ignore_ascii_case("websocket"))
+}
+
+// A relayed connection: the handshake request the hooks see, and where messages go to be recorded
+struct Session {
+    forwarder: Forwarder,
+    request: FlowRequest,
+    recorder: Option<mpsc::UnboundedSender<WebSocketMessage>>,
+}
+
+// Save messages against the handshake flow one at a time, so they keep their order
+fn recorder(project: Arc<Project>, id: FlowId) -> mpsc::UnboundedSender<WebSocketMessage> {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<WebSocketMessage>();
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let project = Arc::clone(&project);
+            let saved = tokio::task::spawn_blocking(move || project.add_websocket_message(id, &message)).await;
+            match saved {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("Failed to record WebSocket message: {:#}", e),
+                Err(e) => error!("Failed to record WebSocket message: {}", e),
+            }
+        }
+    });
+    sender
+}
+
+// Relay messages between the client and server until both sides close. Each message
+// goes through the script hooks as a whole, with fragments joined, and is recorded
+// against the handshake flow as it was passed on. Extensions are not negotiated (the
+// proxy drops `Sec-WebSocket-Extensions`), so frames carry plain payloads.
+pub async fn relay(
+    client: Upgraded,
+    server: Upgraded,
+    forwarder: Forwarder,
+    request: FlowRequest,
+    recording: Option<(Arc<Project>, FlowId)>,
+) {
+    let session = Session {
+        forwarder,
+        request,
+        recorder: recording.map(|(project, id)| recorder(project, id)),
+    };
+    let (client_read, client_write) = tokio::io::split(TokioIo::new(client));
+    let (server_read, server_write) = tokio::io::split(TokioIo::new(server));
+
+    let result = tokio::try_join!(
+        pump(client_read, server_write, Direction::ClientToServer, &session),
+        pump(server_read, client_write, Direction::ServerToClient, &session),
+    );
+    match result {
+        Ok(_) => debug!("WebSocket to {} closed", session.request.uri),
+        Err(e) => error!("WebSocket to {} failed: {:#}", session.request.uri, e),
+    }
+}
+
+// Pass messages one way until the sender closes its connection
+async fn pump<R, W>(mut reader: R, mut writer: W, direction: Direction, session: &Session) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    // Masking is required from clients and forbidden from servers
+    let mask = direction == Direction::ClientToServer;
+    let mut fragmented: Option<(WebSocketOpcode, Vec<u8>)> = None;
+
+    while let Some(frame) = read_frame(&mut reader).await? {
+        let (opcode, payload) = if frame.opcode == CONTINUATION {
+            let Some((opcode, mut payload)) = fragmented.take() else {
+                bail!("Continuation frame without a message to continue");
+            };
+            if payload.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                bail!("Message larger than {} bytes", MAX_MESSAGE_SIZE);
+            }
+            payload.extend_from_slice(&frame.payload);
+            (opcode, payload)
+        } else {
+            let opcode = opcode(frame.opcode)?;
+            if fragmented.is_some() && !is_control(opcode) {
+                bail!("New message started before the last one finished");
+            }
+            (opcode, frame.payload)
+        };
+
+        // Control frames may arrive between the fragments of a message
+        if !frame.fin {
+            if is_control(opcode) {
+                bail!("Fragmented control frame");
+            }
+            fragmented = Some((opcode, payload));
+            continue;
+        }
+
+        let mut message = WebSocketMessage {
+            timestamp: Utc::now(),
+            direction,
+            opcode,
+            payload,
+        };
+        if !session.forwarder.on_websocket_message(&session.request, &mut message).await {
+            debug!("Script discarded a {} message to {}", message.opcode.as_str(), session.request.uri);
+            continue;
+        }
+        write_frame(&mut writer, message.opcode, &message.payload, mask).await?;
+        if let Some(recorder) = &session.recorder {
+            recorder.send(message).ok();
+        }
+    }
+
+    writer.shutdown().await.ok();
+    Ok(())
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+// The next frame with its payload unmasked, or None at the end of the stream
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Frame>> {
+    let mut head = [0u8; 2];
+    match reader.read_exact(&mut head).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e).context("Failed to read WebSocket frame"),
+    }
+    if head[0] & 0x70 != 0 {
+        bail!("WebSocket frame uses an extension that was not negotiated");
+    }
+
+    let length = match head[1] & 0x7f {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        length => length as u64,
+    };
+    if length > MAX_MESSAGE_SIZE as u64 {
+        bail!("Message larger than {} bytes", MAX_MESSAGE_SIZE);
+    }
+    let key = match head[1] & 0x80 != 0 {
+        true => Some(reader.read_u32().await?.to_be_bytes()),
+        false => None,
+    };
+
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload).await.context("Failed to read WebSocket frame")?;
+    if let Some(key) = key {
+        apply_mask(&mut payload, key);
+    }
+    Ok(Some(Frame {
+        fin: head[0] & 0x80 != 0,
+        opcode: head[0] & 0x0f,
+        payload,
+    }))
+}
+
+// Send a message as a single frame
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, opcode: WebSocketOpcode, payload: &[u8], mask: bool) -> Result<()> {
+    let mut frame = vec![0x80 | opcode_byte(opcode)];
+    let mask_bit = if mask { 0x80 } else { 0 };
+    match payload.len() {
+        length @ 0..=125 => frame.push(mask_bit | length as u8),
+        length @ 126..=0xffff => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+
+    let start = frame.len();
+    if mask {
+        let key = (RandomState::new().hash_one(Utc::now().timestamp_nanos_opt()) as u32).to_be_bytes();
+        frame.extend_from_slice(&key);
+        frame.extend_from_slice(payload);
+        apply_mask(&mut frame[start + 4..], key);
+    } else {
+        frame.extend_from_slice(payload);
+    }
+
+    writer.write_all(&frame).await.context("Failed to send WebSocket frame")?;
+    writer.flush().await.context("Failed to send WebSocket frame")
+}
+
+fn apply_mask(payload: &mut [u8], key: [u8; 4]) {
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= key[index % 4];
+    }
+}
+
+fn is_control(opcode: WebSocketOpcode) -> bool {
+    matches!(opcode, WebSocketOpcode::Close | WebSocketOpcode::Ping | WebSocketOpcode::Pong)
+}
+
+fn opcode(byte: u8) -> Result<WebSocketOpcode> {
+    Ok(match byte {
+        0x1 => WebSocketOpcode::Text,
+        0x2 => WebSocketOpcode::Binary,
+        0x8 => WebSocketOpcode::Close,
+        0x9 => WebSocketOpcode::Ping,
+        0xa => WebSocketOpcode::Pong,
+        other => bail!("Unknown WebSocket opcode {:#x}", other),
+    })
+}
+
+fn opcode_byte(opcode: WebSocketOpcode) -> u8 {
+    match opcode {
+        WebSocketOpcode::Text => 0x1,
+        WebSocketOpcode::Binary => 0x2,
+        WebSocketOpcode::Close => 0x8,
+        WebSocketOpcode::Ping => 0x9,
+        WebSocketOpcode::Pong => 0xa,
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+
+use crate::curl::import::parse_curl;
+use crate::flow::model::{Flow, FlowRequest};
+use crate::flow::raw::parse_request;
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+
+pub type TabId = usize;
+
+// Tag added to every flow the repeater records into a project
+pub const REPEATER_TAG: &str = "repeater";
+
+// One send of a tab's request: the request exactly as it was sent, and either the
+// completed flow or the error that stopped it
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub request: FlowRequest,
+    pub flow: Option<Flow>,
+    pub error: Option<String>,
+    pub sent_at: DateTime<Utc>,
+}
+
+// An editable request plus the history of every time it was sent
+#[derive(Debug, Clone)]
+pub struct RepeaterTab {
+    pub id: TabId,
+    pub name: String,
+    pub request: FlowRequest,
+    pub history: Vec<Attempt>,
+}
+
+impl RepeaterTab {
+    pub fn last_attempt(&self) -> Option<&Attempt> {
+        self.history.last()
+    }
+}
+
+// Holds repeater tabs and sends them through the same forwarder as live traffic
+pub struct Repeater {
+    forwarder: Forwarder,
+    project: Option<Arc<Project>>,
+    tabs: Vec<RepeaterTab>,
+    next_id: TabId,
+}
+
+impl Repeater {
+    pub fn new(forwarder: Forwarder) -> Self {
+        Self {
+            forwarder,
+            project: None,
+            tabs: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    // Record every completed attempt into the given project
+    pub fn with_project(mut self, project: Arc<Project>) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    pub fn open_tab(&mut self, name: impl Into<String>, request: FlowRequest) -> TabId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.tabs.push(RepeaterTab {
+            id,
+            name: name.into(),
+            request,
+            history: Vec::new(),
+        });
+        id
+    }
+
+    // Start a tab from a request in history
+    pub fn open_from_flow(&mut self, flow: &Flow) -> TabId {
+        let name = format!("#{} {} {}", flow.id, flow.request.method, flow.request.uri);
+        self.open_tab(name, flow.request.clone())
+    }
+
+    // Start a tab from a raw HTTP/1.1 request
+    pub fn open_from_raw(&mut self, raw: &[u8], https: bool) -> Result<TabId> {
+        let request = parse_request(raw, https)?;
+        let name = format!("{} {}", request.method, request.uri);
+        Ok(self.open_tab(name, request))
+    }
+
+    // Start a tab from a pasted curl command. Its proxy and -k options are ignored;
+    // the repeater always uses the forwarder's upstream configuration.
+    pub fn open_from_curl(&mut self, command: &str) -> Result<TabId> {
+        let parsed = parse_curl(command)?;
+        if parsed.proxy.is_some() || parsed.insecure {
+            warn!("Ignoring curl transport options; the repeater uses ferrum's upstream settings");
+        }
+        let name = format!("{} {}", parsed.request.method, parsed.request.uri);
+        Ok(self.open_tab(name, parsed.request))
+    }
+
+    pub fn tabs(&self) -> &[RepeaterTab] {
+        &self.tabs
+    }
+
+    pub fn tab(&self, id: TabId) -> Option<&RepeaterTab> {
+        self.tabs.iter().find(|tab| tab.id == id)
+    }
+
+    pub fn tab_mut(&mut self, id: TabId) -> Option<&mut RepeaterTab> {
+        self.tabs.iter_mut().find(|tab| tab.id == id)
+    }
+
+    pub fn close_tab(&mut self, id: TabId) -> Option<RepeaterTab> {
+        let index = self.tabs.iter().position(|tab| tab.id == id)?;
+        Some(self.tabs.remove(index))
+    }
+
+    // Send the tab's current request. Network failures are recorded in the history
+    // rather than returned, so only a missing tab or a project write fails here.
+    pub async fn send(&mut self, id: TabId) -> Result<&Attempt> {
+        let forwarder = self.forwarder.clone();
+        let project = self.project.clone();
+        let tab = self
+            .tab_mut(id)
+            .with_context(|| format!("Repeater tab {} does not exist", id))?;
+
+        let request = tab.request.clone();
+        let sent_at = Utc::now();
+        info!("Repeater tab {}: {} {}", id, request.method, request.uri);
+
+        let attempt = match forwarder.forward(request.clone()).await {
+            Ok(mut flow) => {
+                flow.tags.push(REPEATER_TAG.to_string());
+                if let Some(project) = &project {
+                    flow.id = project.save_flow(&flow)?;
+                }
+                Attempt { request, flow: Some(flow), error: None, sent_at }
+            }
+            Err(e) => {
+                warn!("Repeater tab {} failed: {:#}", id, e);
+                Attempt { request, flow: None, error: Some(format!("{:#}", e)), sent_at }
+            }
+        };
+
+        tab.history.push(attempt);
+        Ok(tab.history.last().expect("attempt was just pushed"))
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::{bail, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::rules::matcher::RuleMatch;
+
+// Tag on flows answered or dropped by a block rule
+pub const BLOCKED_TAG: &str = "blocked";
+
+// What ferrum does with a blocked request instead of forwarding it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockAction {
+    // Abort the client connection with a TCP reset
+    Reset,
+    // An empty 204
+    NoContent,
+    Respond {
+        status: u16,
+        #[serde(default)]
+        body: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+    },
+}
+
+impl BlockAction {
+    // The response sent back, or None when the connection is reset
+    pub fn response(&self) -> Option<FlowResponse> {
+        match self {
+            Self::Reset => None,
+            Self::NoContent => Some(FlowResponse::new(204)),
+            Self::Respond { status, body, headers } => {
+                let mut response = FlowResponse::new(*status);
+                if !body.is_empty() {
+                    response.set_header("Content-Type", "text/plain; charset=utf-8");
+                }
+                for (name, value) in headers {
+                    response.set_header(name, value);
+                }
+                response.body = body.as_bytes().to_vec();
+                response.set_header("Content-Length", &response.body.len().to_string());
+                Some(response)
+            }
+        }
+    }
+}
+
+// `reset`, `204`, or `STATUS[:BODY]` such as `503:Service Unavailable`
+impl FromStr for BlockAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("reset") {
+            return Ok(Self::Reset);
+        }
+        let (status, body) = s.split_once(':').unwrap_or((s, ""));
+        let status: u16 = status
+            .parse()
+            .ok()
+            .filter(|status| (100..=999).contains(status))
+            .with_context(|| format!("Invalid block action {}, expected reset or STATUS[:BODY]", s))?;
+        if status == 204 && body.is_empty() {
+            return Ok(Self::NoContent);
+        }
+        Ok(Self::Respond {
+            status,
+            body: body.to_string(),
+            headers: BTreeMap::new(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "crate::utils::default_true")]
+    pub enabled: bool,
+    #[serde(default, rename = "match")]
+    pub matcher: RuleMatch,
+    pub action: BlockAction,
+}
+
+impl BlockRule {
+    pub fn new(matcher: RuleMatch, action: BlockAction) -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            matcher,
+            action,
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+}
+
+// `URL_GLOB[=ACTION]`, e.g. `*.google-analytics.com=404` or `POST api.example.com/pay/*=503:Down`.
+// Without an action the connection is reset. The action starts at the first `=` followed by
+// one, so both the pattern (`/items;v=2/*`) and the response body may contain `=`.
+impl FromStr for BlockRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let split = s.match_indices('=').find_map(|(index, _)| {
+            let action = s[index + 1..].parse::<BlockAction>().ok()?;
+            Some((&s[..index], action))
+        });
+        let (pattern, action) = split.unwrap_or((s, BlockAction::Reset));
+        Ok(Self::new(pattern.parse()?, action))
+    }
+}
+
+// Ordered block rules; the first enabled rule that matches decides
+#[derive(Debug, Clone, Default)]
+pub struct BlockRules {
+    rules: Vec<BlockRule>,
+}
+
+impl BlockRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rules(rules: Vec<BlockRule>) -> Self {
+        Self { rules }
+    }
+
+    // Rules from a JSON file holding an array of rules
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let rules = serde_json::from_str(&text).with_context(|| format!("Invalid block rules in {}", path.display()))?;
+        Ok(Self::from_rules(rules))
+    }
+
+    pub fn add(&mut self, rule: BlockRule) -> usize {
+        self.rules.push(rule);
+        self.rules.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<BlockRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        match self.rules.get_mut(index) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                Ok(())
+            }
+            None => bail!("Block rule {} does not exist", index),
+        }
+    }
+
+    pub fn rules(&self) -> &[BlockRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // What to do with a request, if a rule blocks it
+    pub fn resolve(&self, request: &FlowRequest) -> Option<BlockAction> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.enabled && rule.matcher.matches_request(request))?;
+        debug!("Block rule {:?} matched {} {}", rule.name, request.method, request.uri);
+        Some(rule.action.clone())
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::rules::matcher::RuleMatch;
+use crate::sitemap::links::request_url;
+
+// Tag on flows answered from disk instead of upstream
+pub const MAP_LOCAL_TAG: &str = "map-local";
+
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("js", "application/javascript; charset=utf-8"),
+    ("mjs", "application/javascript; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("svg", "image/svg+xml"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mp3", "audio/mpeg"),
+];
+
+// Answers matching requests with a file, or with files from a directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapLocalRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "crate::utils::default_true")]
+    pub enabled: bool,
+    #[serde(default, rename = "match")]
+    pub matcher: RuleMatch,
+    // A file served for every match, or a directory the request path is looked up in.
+    // For directories, the part of the path glob before its first `*` is stripped.
+    pub local: PathBuf,
+    // 200 unless set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    // Extra response headers; Content-Type here overrides the guessed one
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl MapLocalRule {
+    pub fn new(matcher: RuleMatch, local: impl Into<PathBuf>) -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            matcher,
+            local: local.into(),
+            status: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // The file on disk for a request this rule matches
+    fn file_for(&self, request: &FlowRequest) -> Result<PathBuf> {
+        if !self.local.is_dir() {
+            return Ok(self.local.clone());
+        }
+
+        let url = request_url(request).ok_or_else(|| anyhow!("Request has no URL"))?;
+        let prefix = self
+            .matcher
+            .path
+            .as_deref()
+            .map_or("/", |glob| glob.split('*').next().unwrap_or_default());
+        let path = percent_decode_str(url.path()).decode_utf8_lossy().into_owned();
+        let relative = path.strip_prefix(prefix).unwrap_or(&path).trim_start_matches('/');
+
+        // Never leave the mapped directory
+        let relative = Path::new(relative);
+        if relative.components().any(|component| !matches!(component, Component::Normal(_))) {
+            bail!("Refusing to map {} outside {}", path, self.local.display());
+        }
+
+        let file = self.local.join(relative);
+        if file.is_dir() || relative.as_os_str().is_empty() {
+            return Ok(file.join("index.html"));
+        }
+        Ok(file)
+    }
+}
+
+// `URL_GLOB=PATH`, e.g. `https://example.com/static/*=./build`; see `RuleMatch` for the glob
+impl FromStr for MapLocalRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (pattern, local) = s
+            .rsplit_once('=')
+            .ok_or_else(|| anyhow!("Invalid mapping {}, expected URL_GLOB=PATH", s))?;
+        Ok(Self::new(pattern.parse()?, local))
+    }
+}
+
+// A response to build from disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalResponse {
+    pub file: Result<PathBuf, String>,
+    pub status: Option<u16>,
+    pub headers: BTreeMap<String, String>,
+}
+
+impl LocalResponse {
+    // Read the file; a missing file becomes a 404 from ferrum rather than an error
+    pub async fn read(&self) -> FlowResponse {
+        let file = match &self.file {
+            Ok(file) => file,
+            Err(reason) => return not_found(reason),
+        };
+        let body = match tokio::fs::read(file).await {
+            Ok(body) => body,
+            Err(e) => return not_found(&format!("Map Local could not read {}: {}", file.display(), e)),
+        };
+
+        let mut response = FlowResponse::new(self.status.unwrap_or(200));
+        response.set_header("Content-Type", content_type(file));
+        for (name, value) in &self.headers {
+            response.set_header(name, value);
+        }
+        response.set_header("Content-Length", &body.len().to_string());
+        response.body = body;
+        response
+    }
+}
+
+fn not_found(reason: &str) -> FlowResponse {
+    let mut response = FlowResponse::new(404);
+    response.set_header("Content-Type", "text/plain; charset=utf-8");
+    response.body = reason.as_bytes().to_vec();
+    response.set_header("Content-Length", &response.body.len().to_string());
+    response
+}
+
+// Content-Type guessed from the file extension
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    CONTENT_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(extension))
+        .map_or("application/octet-stream", |(_, content_type)| content_type)
+}
+
+// Ordered Map Local rules; the first enabled rule that matches answers the request
+#[derive(Debug, Clone, Default)]
+pub struct MapLocal {
+    rules: Vec<MapLocalRule>,
+}
+
+impl MapLocal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rules(rules: Vec<MapLocalRule>) -> Self {
+        Self { rules }
+    }
+
+    // Rules from a JSON file holding an array of rules
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let rules = serde_json::from_str(&text).with_context(|| format!("Invalid Map Local rules in {}", path.display()))?;
+        Ok(Self::from_rules(rules))
+    }
+
+    pub fn add(&mut self, rule: MapLocalRule) -> usize {
+        self.rules.push(rule);
+        self.rules.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<MapLocalRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        match self.rules.get_mut(index) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                Ok(())
+            }
+            None => bail!("Map Local rule {} does not exist", index),
+        }
+    }
+
+    pub fn rules(&self) -> &[MapLocalRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // What to answer a request with, if a rule maps it
+    pub fn resolve(&self, request: &FlowRequest) -> Option<LocalResponse> {
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.enabled && rule.matcher.matches_request(request))?;
+        let file = rule.file_for(request).map_err(|e| format!("{:#}", e));
+        debug!("Map Local: {} {} -> {:?}", request.method, request.uri, file);
+        Some(LocalResponse {
+            file,
+            status: rule.status,
+            headers: rule.headers.clone(),
+        })
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::flow::model::FlowRequest;
+use crate::rules::matcher::glob_match;
+use crate::sitemap::links::request_url;
+
+// `[scheme://]host[:port][/path]`. Left-out parts match anything when matching and
+// keep the request's own value when rewriting, except that a scheme without a port
+// means the scheme's default port. The host may be a glob when matching.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Location {
+    pub scheme: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+    // Path prefix, matched on whole segments; empty for none
+    pub path: String,
+}
+
+impl Location {
+    fn matches(&self, url: &url::Url) -> bool {
+        if self.scheme.as_deref().is_some_and(|scheme| scheme != url.scheme()) {
+            return false;
+        }
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if !glob_match(&self.host, &host) {
+            return false;
+        }
+        if self.port().is_some_and(|port| Some(port) != url.port_or_known_default()) {
+            return false;
+        }
+        self.strip_path(url.path()).is_some()
+    }
+
+    fn port(&self) -> Option<u16> {
+        self.port.or(match self.scheme.as_deref() {
+            Some("http") => Some(80),
+            Some("https") => Some(443),
+            _ => None,
+        })
+    }
+
+    // The rest of `path` after this location's prefix
+    fn strip_path<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let prefix = self.path.trim_end_matches('/');
+        let rest = path.strip_prefix(prefix)?;
+        (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+    }
+}
+
+impl FromStr for Location {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (scheme, rest) = match s.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, s),
+        };
+        if let Some(scheme) = &scheme
+            && scheme != "http"
+            && scheme != "https"
+        {
+            bail!("Unsupported scheme in {}, expected http or https", s);
+        }
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, ""),
+        };
+        // IPv6 hosts keep their brackets, so only a colon after `]` starts the port
+        let port_start = match authority.rfind(']') {
+            Some(end) => authority[end..].find(':').map(|index| end + index),
+            None => authority.rfind(':'),
+        };
+        let (host, port) = match port_start {
+            Some(index) => {
+                let port = authority[index + 1..]
+                    .parse()
+                    .with_context(|| format!("Invalid port in {}", s))?;
+                (&authority[..index], Some(port))
+            }
+            None => (authority, None),
+        };
+        if host.is_empty() {
+            bail!("Missing host in {}", s);
+        }
+
+        Ok(Self {
+            scheme,
+            host: host.to_ascii_lowercase(),
+            port,
+            path: path.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for Location {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<Location> for String {
+    fn from(location: Location) -> Self {
+        location.to_string()
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}://", scheme)?;
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+// What the Host header says after a request is redirected
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostHeader {
+    // The new host, so virtual hosts on the destination route it
+    #[default]
+    Rewrite,
+    // What the client sent, for destinations that serve the original site
+    Preserve,
+    Set(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapRemoteRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "crate::utils::default_true")]
+    pub enabled: bool,
+    pub from: Location,
+    pub to: Location,
+    #[serde(default)]
+    pub host_header: HostHeader,
+}
+
+impl MapRemoteRule {
+    pub fn new(from: Location, to: Location) -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            from,
+            to,
+            host_header: HostHeader::default(),
+        }
+    }
+
+    pub fn with_host_header(mut self, host_header: HostHeader) -> Self {
+        self.host_header = host_header;
+        self
+    }
+
+    // The redirected URL, if this rule applies to it
+    fn rewrite(&self, url: &url::Url) -> Option<url::Url> {
+        if !self.from.matches(url) {
+            return None;
+        }
+        let rest = self.from.strip_path(url.path())?;
+
+        let mut target = url.clone();
+        if let Some(scheme) = &self.to.scheme {
+            target.set_scheme(scheme).ok()?;
+        }
+        target.set_host(Some(&self.to.host)).ok()?;
+        if self.to.port.is_some() || self.to.scheme.is_some() {
+            target.set_port(self.to.port()).ok()?;
+        }
+
+        let prefix = self.to.path.trim_end_matches('/');
+        let path = format!("{}{}", prefix, rest);
+        target.set_path(if path.is_empty() { "/" } else { &path });
+        Some(target)
+    }
+}
+
+// `FROM=TO`, e.g. `https://api.example.com/v1=http://localhost:3000/api`
+impl FromStr for MapRemoteRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid mapping {}, expected FROM=TO", s))?;
+        Ok(Self::new(from.parse()?, to.parse()?))
+    }
+}
+
+// Ordered Map Remote rules; the first enabled rule that matches redirects the request
+#[derive(Debug, Clone, Default)]
+pub struct MapRemote {
+    rules: Vec<MapRemoteRule>,
+}
+
+impl MapRemote {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rules(rules: Vec<MapRemoteRule>) -> Self {
+        Self { rules }
+    }
+
+    // Rules from a JSON file holding an array of rules
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let rules = serde_json::from_str(&text).with_context(|| format!("Invalid Map Remote rules in {}", path.display()))?;
+        Ok(Self::from_rules(rules))
+    }
+
+    pub fn add(&mut self, rule: MapRemoteRule) -> usize {
+        self.rules.push(rule);
+        self.rules.len() - 1
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<MapRemoteRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        match self.rules.get_mut(index) {
+            Some(rule) => {
+                rule.enabled = enabled;
+                Ok(())
+            }
+            None => bail!("Map Remote rule {} does not exist", index),
+        }
+    }
+
+    pub fn rules(&self) -> &[MapRemoteRule] {
+        &self.rules
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Point the request at its new destination; returns the URL it originally had
+    pub fn apply(&self, request: &mut FlowRequest) -> Option<String> {
+        let url = request_url(request)?;
+        let (rule, target) = self
+            .rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .find_map(|rule| rule.rewrite(&url).map(|target| (rule, target)))?;
+
+        let original = url.to_string();
+        let original_host = request.header("host").map(str::to_string).unwrap_or_else(|| authority(&url));
+        match &rule.host_header {
+            HostHeader::Rewrite => request.set_header("Host", &authority(&target)),
+            HostHeader::Preserve => request.set_header("Host", &original_host),
+            HostHeader::Set(host) => request.set_header("Host", host),
+        }
+        request.uri = target.to_string();
+
+        debug!("Map Remote: {} -> {}", original, request.uri);
+        Some(original)
+    }
+}
+
+// `host[:port]`, leaving out the scheme's default port
+fn authority(url: &url::Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
@@ -0,0 +1,148 @@
+use std::str::FromStr;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::sitemap::links::request_url;
+
+// Which requests a rule applies to. Every field left out matches anything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleMatch {
+    // Host glob such as `*.example.com`, or `host:port` to pin the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    // Path glob such as `/api/*`, matched against the path without the query
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    // Methods, case-insensitive
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+    // Substring of the Content-Type of the message a rule acts on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+impl RuleMatch {
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = Some(host.to_string());
+        self
+    }
+
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn with_method(mut self, method: &str) -> Self {
+        self.methods.push(method.to_string());
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    // Host, path and method, ignoring the content type
+    pub fn matches_target(&self, request: &FlowRequest) -> bool {
+        if !self.methods.is_empty() && !self.methods.iter().any(|method| method.eq_ignore_ascii_case(&request.method)) {
+            return false;
+        }
+        if self.host.is_none() && self.path.is_none() {
+            return true;
+        }
+        let Some(url) = request_url(request) else {
+            return false;
+        };
+
+        if let Some(pattern) = &self.host {
+            let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+            let host = if pattern.contains(':') {
+                format!("{}:{}", host, url.port_or_known_default().unwrap_or_default())
+            } else {
+                host
+            };
+            if !glob_match(&pattern.to_ascii_lowercase(), &host) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.path
+            && !glob_match(pattern, url.path())
+        {
+            return false;
+        }
+        true
+    }
+
+    pub fn matches_request(&self, request: &FlowRequest) -> bool {
+        self.matches_target(request) && content_type_matches(self.content_type.as_deref(), request.header("content-type"))
+    }
+
+    pub fn matches_response(&self, request: &FlowRequest, response: &FlowResponse) -> bool {
+        self.matches_target(request) && content_type_matches(self.content_type.as_deref(), response.header("content-type"))
+    }
+}
+
+// `[METHOD ][scheme://]host[/path]` with globs in the host and path, e.g.
+// `POST https://*.example.com/api/*`. The scheme is accepted but not matched.
+impl FromStr for RuleMatch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (method, pattern) = match s.trim().split_once(char::is_whitespace) {
+            Some((method, pattern)) => (Some(method), pattern.trim()),
+            None => (None, s.trim()),
+        };
+        let target = pattern.split_once("://").map_or(pattern, |(_, rest)| rest);
+        let (host, path) = match target.find('/') {
+            Some(index) => (&target[..index], &target[index..]),
+            None => (target, "/*"),
+        };
+        if host.is_empty() {
+            bail!("Invalid pattern {}, the URL needs a host", s);
+        }
+
+        let mut matcher = Self::default().with_host(host);
+        if path != "/*" {
+            matcher = matcher.with_path(path);
+        }
+        if let Some(method) = method {
+            if !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                bail!("Invalid method {:?} in pattern {}", method, s);
+            }
+            matcher = matcher.with_method(&method.to_ascii_uppercase());
+        }
+        Ok(matcher)
+    }
+}
+
+fn content_type_matches(pattern: Option<&str>, content_type: Option<&str>) -> bool {
+    match pattern {
+        None => true,
+        Some(pattern) => content_type.is_some_and(|content_type| {
+            content_type.to_ascii_lowercase().contains(&pattern.to_ascii_lowercase())
+        }),
+    }
+}
+
+// `*` matches any run of characters, including none and including `/`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
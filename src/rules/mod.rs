@@ -0,0 +1,5 @@
+pub mod block;
+pub mod map_local;
+pub mod map_remote;
+pub mod matcher;
+pub mod replace;
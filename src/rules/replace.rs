@@ -0,0 +1,344 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::{bail, Context, Result};
+use log::{debug, warn};
+use regex::bytes::NoExpand;
+use serde::{Deserialize, Serialize};
+
+use crate::flow::body::{self, content_encodings, Body, EncodingPolicy};
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::rules::matcher::RuleMatch;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Request,
+    Response,
+}
+
+// The part of a message a rule rewrites
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Part {
+    // `METHOD URI VERSION` for requests, `VERSION STATUS` for responses
+    FirstLine,
+    // Values of the named header. With an empty pattern the header is set to
+    // the replacement (added if missing); a header whose value ends up empty is removed.
+    Header(String),
+    // The body, decoded first if it has a Content-Encoding ferrum understands
+    Body,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplaceRule {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "crate::utils::default_true")]
+    pub enabled: bool,
+    pub stage: Stage,
+    #[serde(default, rename = "match")]
+    pub matcher: RuleMatch,
+    pub part: Part,
+    // Literal text, or a regex when `regex` is set
+    #[serde(default)]
+    pub pattern: String,
+    #[serde(default)]
+    pub regex: bool,
+    // Inserted as-is for literal rules; `$1` and `${name}` refer to capture groups for regex rules
+    #[serde(default)]
+    pub replacement: String,
+}
+
+impl ReplaceRule {
+    pub fn new(stage: Stage, part: Part, pattern: &str, replacement: &str) -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            stage,
+            matcher: RuleMatch::default(),
+            part,
+            pattern: pattern.to_string(),
+            regex: false,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_regex(mut self) -> Self {
+        self.regex = true;
+        self
+    }
+
+    pub fn with_match(mut self, matcher: RuleMatch) -> Self {
+        self.matcher = matcher;
+        self
+    }
+}
+
+// A rule with its pattern compiled; literal patterns are escaped so one code path serves both
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: ReplaceRule,
+    pattern: regex::bytes::Regex,
+    // Read from the rules file, so replaced when it is reloaded
+    from_file: bool,
+}
+
+impl CompiledRule {
+    fn compile(rule: ReplaceRule) -> Result<Self> {
+        let source = if rule.regex {
+            rule.pattern.clone()
+        } else {
+            regex::escape(&rule.pattern)
+        };
+        let pattern = regex::bytes::Regex::new(&source)
+            .with_context(|| format!("Invalid pattern in replace rule {:?}", rule.name))?;
+        Ok(Self {
+            rule,
+            pattern,
+            from_file: false,
+        })
+    }
+
+    fn replace(&self, input: &[u8]) -> Vec<u8> {
+        if self.rule.regex {
+            self.pattern.replace_all(input, self.rule.replacement.as_bytes()).into_owned()
+        } else if self.rule.pattern.is_empty() {
+            input.to_vec()
+        } else {
+            self.pattern.replace_all(input, NoExpand(self.rule.replacement.as_bytes())).into_owned()
+        }
+    }
+
+    fn replace_text(&self, input: &str) -> String {
+        String::from_utf8_lossy(&self.replace(input.as_bytes())).into_owned()
+    }
+
+    fn replace_headers(&self, headers: &mut Vec<(String, String)>, name: &str) -> bool {
+        let before = headers.clone();
+        if self.rule.pattern.is_empty() {
+            headers.retain(|(header, _)| !header.eq_ignore_ascii_case(name));
+            if !self.rule.replacement.is_empty() {
+                headers.push((name.to_string(), self.rule.replacement.clone()));
+            }
+        } else {
+            for (header, value) in headers.iter_mut() {
+                if header.eq_ignore_ascii_case(name) {
+                    *value = self.replace_text(value);
+                }
+            }
+            headers.retain(|(header, value)| !(header.eq_ignore_ascii_case(name) && value.is_empty()));
+        }
+        *headers != before
+    }
+
+    fn replace_body(&self, headers: &mut Vec<(String, String)>, body: &mut Vec<u8>, policy: EncodingPolicy) -> bool {
+        if content_encodings(headers).is_none() {
+            debug!("Skipping body rule {:?}: unsupported Content-Encoding", self.rule.name);
+            return false;
+        }
+        let replaced = match Body::new(headers, body).decoded() {
+            Ok(decoded) => self.replace(decoded),
+            Err(e) => {
+                warn!("Skipping body rule {:?}: {:#}", self.rule.name, e);
+                return false;
+            }
+        };
+        body::set_decoded(headers, body, &replaced, policy).unwrap_or_else(|e| {
+            warn!("Skipping body rule {:?}: {:#}", self.rule.name, e);
+            false
+        })
+    }
+}
+
+// Ordered match-and-replace rules, applied by the forwarder to every request
+// and response after the interceptors have seen them
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceRules {
+    rules: Vec<CompiledRule>,
+    encoding: EncodingPolicy,
+    // The file the rules were loaded from, with its modification time and size then
+    source: Option<PathBuf>,
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl ReplaceRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // How bodies changed by a rule are encoded before they are sent on
+    pub fn with_encoding_policy(mut self, policy: EncodingPolicy) -> Self {
+        self.encoding = policy;
+        self
+    }
+
+    pub fn from_rules(rules: Vec<ReplaceRule>) -> Result<Self> {
+        let mut set = Self::new();
+        for rule in rules {
+            set.add(rule)?;
+        }
+        Ok(set)
+    }
+
+    // Rules from a JSON file holding an array of rules. The file is remembered
+    // so that `reload` can pick up later edits.
+    pub fn load(path: &Path) -> Result<Self> {
+        let stamp = stamp(path);
+        let mut set = Self::from_rules(read_rules(path)?)?;
+        for compiled in &mut set.rules {
+            compiled.from_file = true;
+        }
+        set.source = Some(path.to_path_buf());
+        set.stamp = stamp;
+        Ok(set)
+    }
+
+    // Whether the file the rules came from has changed since it was read
+    pub fn is_stale(&self) -> bool {
+        match &self.source {
+            Some(path) => {
+                let now = stamp(path);
+                now.is_some() && now != self.stamp
+            }
+            None => false,
+        }
+    }
+
+    // Read the rules file again. The file's rules replace the ones it held before and
+    // rules added with `add` are kept after them. Rules that fail to load leave the
+    // current ones in place; the edit is not retried until the file changes again.
+    pub fn reload(&mut self) -> Result<()> {
+        let Some(path) = self.source.clone() else {
+            bail!("Replace rules were not loaded from a file");
+        };
+        self.stamp = stamp(&path);
+        let mut rules = Vec::new();
+        for rule in read_rules(&path)? {
+            let mut compiled = CompiledRule::compile(rule)?;
+            compiled.from_file = true;
+            rules.push(compiled);
+        }
+        rules.extend(self.rules.drain(..).filter(|compiled| !compiled.from_file));
+        self.rules = rules;
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.rules())?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    // Append a rule; returns its index
+    pub fn add(&mut self, rule: ReplaceRule) -> Result<usize> {
+        self.rules.push(CompiledRule::compile(rule)?);
+        Ok(self.rules.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<ReplaceRule> {
+        (index < self.rules.len()).then(|| self.rules.remove(index).rule)
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> Result<()> {
+        match self.rules.get_mut(index) {
+            Some(compiled) => {
+                compiled.rule.enabled = enabled;
+                Ok(())
+            }
+            None => bail!("Replace rule {} does not exist", index),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn rules(&self) -> Vec<ReplaceRule> {
+        self.rules.iter().map(|compiled| compiled.rule.clone()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Apply every matching request rule; returns how many changed something
+    pub fn apply_request(&self, request: &mut FlowRequest) -> usize {
+        let mut applied = 0;
+        for compiled in self.active(Stage::Request) {
+            if !compiled.rule.matcher.matches_request(request) {
+                continue;
+            }
+            let changed = match &compiled.rule.part {
+                Part::FirstLine => {
+                    let line = format!("{} {} {}", request.method, request.uri, request.version);
+                    let replaced = compiled.replace_text(&line);
+                    let mut fields = replaced.split_whitespace();
+                    if let (Some(method), Some(uri)) = (fields.next(), fields.next()) {
+                        request.method = method.to_string();
+                        request.uri = uri.to_string();
+                        if let Some(version) = fields.next() {
+                            request.version = version.to_string();
+                        }
+                    }
+                    replaced != line
+                }
+                Part::Header(name) => compiled.replace_headers(&mut request.headers, name),
+                Part::Body => compiled.replace_body(&mut request.headers, &mut request.body, self.encoding),
+            };
+            if changed {
+                debug!("Replace rule {:?} rewrote {} {}", compiled.rule.name, request.method, request.uri);
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    // Apply every matching response rule; the request is only used for matching
+    pub fn apply_response(&self, request: &FlowRequest, response: &mut FlowResponse) -> usize {
+        let mut applied = 0;
+        for compiled in self.active(Stage::Response) {
+            if !compiled.rule.matcher.matches_response(request, response) {
+                continue;
+            }
+            let changed = match &compiled.rule.part {
+                Part::FirstLine => {
+                    let line = format!("{} {}", response.version, response.status);
+                    let replaced = compiled.replace_text(&line);
+                    let mut fields = replaced.split_whitespace();
+                    if let (Some(version), Some(status)) = (fields.next(), fields.next().and_then(|s| s.parse().ok())) {
+                        response.version = version.to_string();
+                        response.status = status;
+                    }
+                    replaced != line
+                }
+                Part::Header(name) => compiled.replace_headers(&mut response.headers, name),
+                Part::Body => compiled.replace_body(&mut response.headers, &mut response.body, self.encoding),
+            };
+            if changed {
+                debug!("Replace rule {:?} rewrote the response to {}", compiled.rule.name, request.uri);
+                applied += 1;
+            }
+        }
+        applied
+    }
+
+    fn active(&self, stage: Stage) -> impl Iterator<Item = &CompiledRule> {
+        self.rules
+            .iter()
+            .filter(move |compiled| compiled.rule.enabled && compiled.rule.stage == stage)
+    }
+}
+
+fn read_rules(path: &Path) -> Result<Vec<ReplaceRule>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid replace rules in {}", path.display()))
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
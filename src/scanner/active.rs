@@ -0,0 +1,300 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::flow::model::{Flow, FlowRequest};
+use crate::project::scope::Scope;
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+use crate::scanner::callback::CallbackListener;
+use crate::scanner::checks::{HeaderInjection, OpenRedirect, PathTraversal, ReflectedXss, SqlInjection, Ssrf};
+use crate::scanner::finding::Finding;
+use crate::scanner::insertion::{insertion_points, InsertionPoint};
+use crate::sitemap::links::request_url;
+
+// Tag on flows sent by the active scanner
+pub const SCANNER_TAG: &str = "scanner";
+
+// Default delay injected by time-based probes
+const DEFAULT_TIME_DELAY: Duration = Duration::from_secs(5);
+
+// How long out-of-band checks wait for the target to call back
+const DEFAULT_CALLBACK_WAIT: Duration = Duration::from_secs(3);
+
+static TOKEN_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// A probe that sends modified requests. `scan` is called once for every
+// insertion point of every scanned request.
+pub trait ActiveCheck: Send + Sync {
+    // Short identifier, e.g. `sqli`
+    fn name(&self) -> &'static str;
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>>;
+}
+
+// Unique alphanumeric marker for matching a probe to what it caused
+pub fn unique_token() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.subsec_nanos());
+    let count = TOKEN_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("fr{:08x}{:04x}", nanos, count & 0xffff)
+}
+
+// Spaces requests out so that no more than `per_second` leave per second
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / per_second),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        let now = Instant::now();
+        if *next > now {
+            tokio::time::sleep_until(*next).await;
+        }
+        *next = (*next).max(now) + self.interval;
+    }
+}
+
+// Everything a check needs to probe one request
+pub struct ScanContext {
+    original: Flow,
+    baseline: Flow,
+    forwarder: Forwarder,
+    scope: Arc<Scope>,
+    limiter: Option<Arc<RateLimiter>>,
+    project: Option<Arc<Project>>,
+    callback: Option<Arc<CallbackListener>>,
+    time_delay: Duration,
+    callback_wait: Duration,
+}
+
+impl ScanContext {
+    // The request being scanned
+    pub fn request(&self) -> &FlowRequest {
+        &self.original.request
+    }
+
+    // Fresh response to the unmodified request, for comparison with probes
+    pub fn baseline(&self) -> &Flow {
+        &self.baseline
+    }
+
+    pub fn callback(&self) -> Option<&CallbackListener> {
+        self.callback.as_deref()
+    }
+
+    pub fn time_delay(&self) -> Duration {
+        self.time_delay
+    }
+
+    pub fn callback_wait(&self) -> Duration {
+        self.callback_wait
+    }
+
+    // Send a request within the scope and rate limit, recording it to the project
+    pub async fn send(&self, request: FlowRequest) -> Result<Flow> {
+        let url = request_url(&request).with_context(|| format!("Request {} has no host", request.uri))?;
+        if !self.scope.contains_url(&url) {
+            bail!("{} is out of scope", url);
+        }
+
+        if let Some(limiter) = &self.limiter {
+            limiter.wait().await;
+        }
+        let mut flow = self.forwarder.forward(request).await?;
+        flow.tags.push(SCANNER_TAG.to_string());
+
+        if let Some(project) = &self.project {
+            let project = Arc::clone(project);
+            let stored = flow.clone();
+            flow.id = tokio::task::spawn_blocking(move || project.save_flow(&stored)).await??;
+        }
+        Ok(flow)
+    }
+
+    // Send the scanned request with one insertion point set to `value`
+    pub async fn probe(&self, point: &InsertionPoint, value: &str) -> Result<Flow> {
+        self.send(point.inject(self.request(), value)).await
+    }
+}
+
+// Runs active checks against selected flows
+pub struct ActiveScanner {
+    forwarder: Forwarder,
+    checks: Vec<Arc<dyn ActiveCheck>>,
+    scope: Arc<Scope>,
+    concurrency: usize,
+    limiter: Option<Arc<RateLimiter>>,
+    project: Option<Arc<Project>>,
+    callback: Option<Arc<CallbackListener>>,
+    time_delay: Duration,
+    callback_wait: Duration,
+}
+
+impl ActiveScanner {
+    // A scanner with every built-in check. SSRF probes only run once a
+    // callback listener is attached.
+    pub fn new(forwarder: Forwarder) -> Self {
+        Self::empty(forwarder)
+            .with_check(Arc::new(ReflectedXss))
+            .with_check(Arc::new(SqlInjection))
+            .with_check(Arc::new(PathTraversal))
+            .with_check(Arc::new(OpenRedirect))
+            .with_check(Arc::new(Ssrf))
+            .with_check(Arc::new(HeaderInjection))
+    }
+
+    pub fn empty(forwarder: Forwarder) -> Self {
+        Self {
+            forwarder,
+            checks: Vec::new(),
+            scope: Arc::new(Scope::new()),
+            concurrency: 4,
+            limiter: None,
+            project: None,
+            callback: None,
+            time_delay: DEFAULT_TIME_DELAY,
+            callback_wait: DEFAULT_CALLBACK_WAIT,
+        }
+    }
+
+    pub fn with_check(mut self, check: Arc<dyn ActiveCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    // Keep only the named checks
+    pub fn with_only_checks(mut self, names: &[String]) -> Result<Self> {
+        for name in names {
+            if !self.checks.iter().any(|check| check.name() == name) {
+                bail!("Unknown check {} (available: {})", name, self.check_names().join(", "));
+            }
+        }
+        self.checks.retain(|check| names.iter().any(|name| name == check.name()));
+        Ok(self)
+    }
+
+    pub fn check_names(&self) -> Vec<&'static str> {
+        self.checks.iter().map(|check| check.name()).collect()
+    }
+
+    // Where probes may be sent; flows outside it are not scanned
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = Arc::new(scope);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Cap the request rate across all checks
+    pub fn with_rate_limit(mut self, per_second: f64) -> Self {
+        self.limiter = (per_second > 0.0).then(|| Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    // Save every probe to the project, tagged `scanner`
+    pub fn with_project(mut self, project: Arc<Project>) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    pub fn with_callback(mut self, callback: CallbackListener) -> Self {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_time_delay(mut self, delay: Duration) -> Self {
+        self.time_delay = delay;
+        self
+    }
+
+    pub fn with_callback_wait(mut self, wait: Duration) -> Self {
+        self.callback_wait = wait;
+        self
+    }
+
+    // Scan each flow's insertion points with every check. Flows outside the
+    // scope are skipped; failing checks are logged and do not stop the scan.
+    pub async fn scan(&self, flows: &[Flow]) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+
+        for flow in flows {
+            let Some(url) = request_url(&flow.request).filter(|url| url.host().is_some()) else {
+                warn!("Skipping flow {}: no target host", flow.id);
+                continue;
+            };
+            if !self.scope.contains_url(&url) {
+                warn!("Skipping flow {}: {} is out of scope", flow.id, url);
+                continue;
+            }
+
+            let points = insertion_points(&flow.request);
+            if points.is_empty() {
+                info!("Skipping flow {}: nothing to inject into", flow.id);
+                continue;
+            }
+
+            let mut context = ScanContext {
+                original: flow.clone(),
+                baseline: flow.clone(),
+                forwarder: self.forwarder.clone(),
+                scope: Arc::clone(&self.scope),
+                limiter: self.limiter.clone(),
+                project: self.project.clone(),
+                callback: self.callback.clone(),
+                time_delay: self.time_delay,
+                callback_wait: self.callback_wait,
+            };
+            context.baseline = match context.send(flow.request.clone()).await {
+                Ok(baseline) => baseline,
+                Err(e) => {
+                    warn!("Skipping flow {}: baseline request failed: {:#}", flow.id, e);
+                    continue;
+                }
+            };
+
+            info!("Scanning {} {} ({} insertion points)", flow.request.method, flow.request.uri, points.len());
+            let context = &context;
+            let jobs = points
+                .iter()
+                .flat_map(|point| self.checks.iter().map(move |check| (point, check)));
+            let results: Vec<_> = stream::iter(jobs)
+                .map(|(point, check)| async move { (check.name(), point, check.scan(context, point).await) })
+                .buffer_unordered(self.concurrency)
+                .collect()
+                .await;
+
+            for (name, point, result) in results {
+                match result {
+                    Ok(found) => findings.extend(found),
+                    Err(e) => warn!("Check {} failed on {}: {:#}", name, point.describe(), e),
+                }
+            }
+        }
+
+        findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.url.cmp(&b.url))
+                .then_with(|| a.check.cmp(&b.check))
+        });
+        Ok(findings)
+    }
+}
@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::Full;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use log::{debug, info};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+// How often `wait_for` checks for a new hit
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// A request that reached the callback listener
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallbackHit {
+    // First path segment, which carries the probe token
+    pub token: String,
+    pub method: String,
+    pub path: String,
+    pub remote: SocketAddr,
+    pub received_at: DateTime<Utc>,
+}
+
+// Small HTTP server that out-of-band probes point targets at. A hit for a
+// token proves the target made a request on the scanner's behalf (SSRF).
+pub struct CallbackListener {
+    addr: SocketAddr,
+    hits: Arc<Mutex<Vec<CallbackHit>>>,
+    task: JoinHandle<()>,
+}
+
+impl CallbackListener {
+    pub async fn start(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind callback listener to {}", addr))?;
+        let addr = listener.local_addr()?;
+        let hits = Arc::new(Mutex::new(Vec::new()));
+        info!("Callback listener on {}", addr);
+
+        let recorded = Arc::clone(&hits);
+        let task = tokio::spawn(async move {
+            loop {
+                let Ok((stream, remote)) = listener.accept().await else {
+                    continue;
+                };
+                let recorded = Arc::clone(&recorded);
+
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: Request<hyper::body::Incoming>| {
+                        let recorded = Arc::clone(&recorded);
+                        async move {
+                            let path = req.uri().path().to_string();
+                            let token = path.trim_start_matches('/').split('/').next().unwrap_or_default().to_string();
+                            debug!("Callback hit {} {} from {}", req.method(), path, remote);
+                            recorded.lock().unwrap().push(CallbackHit {
+                                token,
+                                method: req.method().to_string(),
+                                path,
+                                remote,
+                                received_at: Utc::now(),
+                            });
+                            Ok::<_, hyper::Error>(Response::new(Full::new(Bytes::from_static(b"ok"))))
+                        }
+                    });
+                    let _ = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await;
+                });
+            }
+        });
+
+        Ok(Self { addr, hits, task })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // URL a target has to fetch for the given token to register a hit
+    pub fn url(&self, token: &str) -> String {
+        format!("http://{}/{}", self.addr, token)
+    }
+
+    pub fn hits(&self) -> Vec<CallbackHit> {
+        self.hits.lock().unwrap().clone()
+    }
+
+    // Wait up to `timeout` for a hit carrying the token
+    pub async fn wait_for(&self, token: &str, timeout: Duration) -> Option<CallbackHit> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let hit = self.hits.lock().unwrap().iter().find(|hit| hit.token == token).cloned();
+            if hit.is_some() || tokio::time::Instant::now() >= deadline {
+                return hit;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Drop for CallbackListener {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,342 @@
+use std::sync::LazyLock;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use regex::bytes::Regex;
+
+use crate::flow::model::{Flow, FlowResponse};
+use crate::scanner::active::{unique_token, ActiveCheck, ScanContext};
+use crate::scanner::finding::{Confidence, Finding, Location, Severity};
+use crate::scanner::insertion::InsertionPoint;
+use crate::scanner::passive::sql_error;
+use crate::sitemap::links::{is_html, request_url, resolve};
+
+// Host that open redirect probes try to send the browser to
+const REDIRECT_HOST: &str = "ferrum-redirect.invalid";
+
+// Header that header injection probes try to smuggle into the response
+const INJECTED_HEADER: &str = "x-ferrum-injected";
+
+const TRAVERSAL_PAYLOADS: &[&str] = &[
+    "../../../../../../../../etc/passwd",
+    "....//....//....//....//....//....//etc/passwd",
+    "..%2f..%2f..%2f..%2f..%2f..%2fetc%2fpasswd",
+    "/etc/passwd",
+    "..\\..\\..\\..\\..\\..\\windows\\win.ini",
+    "C:\\windows\\win.ini",
+];
+
+static TRAVERSAL_SIGNATURES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"root:[^:\r\n]*:0:0:|(?i)\[(?:fonts|extensions|mci extensions)\]").unwrap());
+
+// Appended to the original value: (condition that holds, condition that fails)
+const BOOLEAN_PAYLOADS: &[(&str, &str)] = &[
+    (" AND 1=1", " AND 1=2"),
+    ("' AND '1'='1", "' AND '1'='2"),
+    ("\" AND \"1\"=\"1", "\" AND \"1\"=\"2"),
+];
+
+const ERROR_PAYLOADS: &[&str] = &["'", "\"", "')", "\\"];
+
+// Time-based payloads; `{}` is replaced by the delay in seconds
+const TIME_PAYLOADS: &[&str] = &[
+    "' AND SLEEP({})-- -",
+    " AND SLEEP({})",
+    "'; WAITFOR DELAY '0:0:{}'--",
+    "' || pg_sleep({})-- -",
+];
+
+fn point_finding(check: &str, title: &str, severity: Severity, confidence: Confidence, probe: &Flow, point: &InsertionPoint) -> Finding {
+    let mut finding = Finding::new(check, title, severity, confidence, probe);
+    if let Some(evidence) = point.locate(&probe.request) {
+        finding.evidence.push(evidence);
+    }
+    finding
+}
+
+// Whether two responses look like the same page: same status and decoded body
+// lengths within 2% of each other
+fn same_page(a: &Flow, b: &Flow) -> bool {
+    match (&a.response, &b.response) {
+        (Some(x), Some(y)) => {
+            let (x_len, y_len) = (body(a).len(), body(b).len());
+            x.status == y.status && x_len.abs_diff(y_len) * 50 <= x_len.max(y_len)
+        }
+        _ => false,
+    }
+}
+
+// The response body as decoded text, which evidence offsets count into
+fn body(flow: &Flow) -> String {
+    flow.response
+        .as_ref()
+        .map_or_else(String::new, |response| response.decoded_body().text().into_owned())
+}
+
+fn header_index(response: &FlowResponse, name: &str) -> Option<usize> {
+    response.headers.iter().position(|(header, _)| header.eq_ignore_ascii_case(name))
+}
+
+// Input echoed into HTML without encoding angle brackets or quotes
+pub struct ReflectedXss;
+
+impl ActiveCheck for ReflectedXss {
+    fn name(&self) -> &'static str {
+        "xss"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            let token = unique_token();
+            let probe = context.probe(point, &format!("{}\"'<{}>", token, token)).await?;
+            let Some(response) = &probe.response else {
+                return Ok(Vec::new());
+            };
+
+            let tag = format!("<{}>", token);
+            let Some(start) = body(&probe).find(&tag) else {
+                return Ok(Vec::new());
+            };
+            let confidence = if is_html(response) { Confidence::Firm } else { Confidence::Tentative };
+
+            Ok(vec![
+                Finding::new("reflected-xss", "Reflected cross-site scripting", Severity::High, confidence, &probe)
+                    .with_detail(format!("{} is reflected into the response without HTML encoding", point.describe()))
+                    .with_evidence(Location::ResponseBody, start, start + tag.len()),
+            ])
+        })
+    }
+}
+
+// Error-, boolean- and time-based SQL injection, reporting the first technique that works
+pub struct SqlInjection;
+
+impl SqlInjection {
+    async fn error_based(context: &ScanContext, point: &InsertionPoint) -> Result<Option<Finding>> {
+        if sql_error(body(context.baseline()).as_bytes()).is_some() {
+            return Ok(None);
+        }
+        for suffix in ERROR_PAYLOADS {
+            let probe = context.probe(point, &format!("{}{}", point.value, suffix)).await?;
+            if let Some(range) = sql_error(body(&probe).as_bytes()) {
+                return Ok(Some(
+                    Finding::new("sql-injection", "SQL injection", Severity::High, Confidence::Firm, &probe)
+                        .with_detail(format!("{} triggers a database error (error-based)", point.describe()))
+                        .with_evidence(Location::ResponseBody, range.start, range.end),
+                ));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn boolean_based(context: &ScanContext, point: &InsertionPoint) -> Result<Option<Finding>> {
+        for (holds, fails) in BOOLEAN_PAYLOADS {
+            let true_probe = context.probe(point, &format!("{}{}", point.value, holds)).await?;
+            if !same_page(&true_probe, context.baseline()) {
+                continue;
+            }
+            let false_probe = context.probe(point, &format!("{}{}", point.value, fails)).await?;
+            if same_page(&false_probe, context.baseline()) {
+                continue;
+            }
+
+            // Repeat both to rule out a page that simply changes between requests
+            let true_again = context.probe(point, &format!("{}{}", point.value, holds)).await?;
+            let false_again = context.probe(point, &format!("{}{}", point.value, fails)).await?;
+            if same_page(&true_again, &true_probe) && same_page(&false_again, &false_probe) {
+                return Ok(Some(
+                    point_finding("sql-injection", "SQL injection", Severity::High, Confidence::Firm, &false_probe, point)
+                        .with_detail(format!(
+                            "{} changes the response with the injected condition (boolean-based: {:?} vs {:?})",
+                            point.describe(),
+                            holds,
+                            fails
+                        )),
+                ));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn time_based(context: &ScanContext, point: &InsertionPoint) -> Result<Option<Finding>> {
+        let seconds = context.time_delay().as_secs().max(1);
+        let expected_ms = seconds * 1000;
+        if context.baseline().duration_ms.unwrap_or(0) * 2 >= expected_ms {
+            // The page is too slow already to tell an injected delay apart
+            return Ok(None);
+        }
+
+        for payload in TIME_PAYLOADS {
+            let value = format!("{}{}", point.value, payload.replace("{}", &seconds.to_string()));
+            let probe = context.probe(point, &value).await?;
+            if probe.duration_ms.unwrap_or(0) * 10 < expected_ms * 9 {
+                continue;
+            }
+            let again = context.probe(point, &value).await?;
+            if again.duration_ms.unwrap_or(0) * 10 >= expected_ms * 9 {
+                return Ok(Some(
+                    point_finding("sql-injection", "SQL injection", Severity::High, Confidence::Firm, &again, point)
+                        .with_detail(format!(
+                            "{} delays the response by {}s when asked to sleep (time-based)",
+                            point.describe(),
+                            seconds
+                        )),
+                ));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl ActiveCheck for SqlInjection {
+    fn name(&self) -> &'static str {
+        "sqli"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            if let Some(finding) = Self::error_based(context, point).await? {
+                return Ok(vec![finding]);
+            }
+            if let Some(finding) = Self::boolean_based(context, point).await? {
+                return Ok(vec![finding]);
+            }
+            Ok(Self::time_based(context, point).await?.into_iter().collect())
+        })
+    }
+}
+
+// Parameters used as file paths that escape the intended directory
+pub struct PathTraversal;
+
+impl ActiveCheck for PathTraversal {
+    fn name(&self) -> &'static str {
+        "traversal"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            if TRAVERSAL_SIGNATURES.is_match(body(context.baseline()).as_bytes()) {
+                return Ok(Vec::new());
+            }
+            for payload in TRAVERSAL_PAYLOADS {
+                let probe = context.probe(point, payload).await?;
+                if let Some(found) = TRAVERSAL_SIGNATURES.find(body(&probe).as_bytes()) {
+                    return Ok(vec![
+                        Finding::new("path-traversal", "Path traversal", Severity::High, Confidence::Firm, &probe)
+                            .with_detail(format!("{} set to {} returns a system file", point.describe(), payload))
+                            .with_evidence(Location::ResponseBody, found.start(), found.end()),
+                    ]);
+                }
+            }
+            Ok(Vec::new())
+        })
+    }
+}
+
+// Redirects to an arbitrary external host chosen by the attacker
+pub struct OpenRedirect;
+
+impl ActiveCheck for OpenRedirect {
+    fn name(&self) -> &'static str {
+        "redirect"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            let Some(base) = request_url(context.request()) else {
+                return Ok(Vec::new());
+            };
+
+            for payload in [
+                format!("https://{}/", REDIRECT_HOST),
+                format!("//{}/", REDIRECT_HOST),
+                format!("/\\{}/", REDIRECT_HOST),
+            ] {
+                let probe = context.probe(point, &payload).await?;
+                let Some(response) = &probe.response else {
+                    continue;
+                };
+                for name in ["location", "refresh"] {
+                    let Some(index) = header_index(response, name) else {
+                        continue;
+                    };
+                    let value = &response.headers[index].1;
+                    let target = value.split_once("url=").map_or(value.as_str(), |(_, url)| url);
+                    if resolve(&base, target).is_some_and(|url| url.host_str() == Some(REDIRECT_HOST)) {
+                        return Ok(vec![
+                            Finding::new("open-redirect", "Open redirect", Severity::Medium, Confidence::Certain, &probe)
+                                .with_detail(format!("{} controls the redirect target", point.describe()))
+                                .with_evidence(Location::ResponseHeader(index), 0, value.len()),
+                        ]);
+                    }
+                }
+            }
+            Ok(Vec::new())
+        })
+    }
+}
+
+// Server-side requests to a URL taken from the input, confirmed out of band
+pub struct Ssrf;
+
+impl ActiveCheck for Ssrf {
+    fn name(&self) -> &'static str {
+        "ssrf"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            let Some(callback) = context.callback() else {
+                return Ok(Vec::new());
+            };
+            let token = unique_token();
+            let probe = context.probe(point, &callback.url(&token)).await?;
+
+            let Some(hit) = callback.wait_for(&token, context.callback_wait()).await else {
+                return Ok(Vec::new());
+            };
+            Ok(vec![
+                point_finding("ssrf", "Server-side request forgery", Severity::High, Confidence::Certain, &probe, point)
+                    .with_detail(format!(
+                        "{} made the server send {} {} to the callback listener (from {})",
+                        point.describe(),
+                        hit.method,
+                        hit.path,
+                        hit.remote
+                    )),
+            ])
+        })
+    }
+}
+
+// CR/LF in the input that ends up splitting response headers
+pub struct HeaderInjection;
+
+impl ActiveCheck for HeaderInjection {
+    fn name(&self) -> &'static str {
+        "crlf"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            for newline in ["\r\n", "\n"] {
+                let token = unique_token();
+                let payload = format!("{}{}{}: {}", point.value, newline, INJECTED_HEADER, token);
+                let probe = context.probe(point, &payload).await?;
+                let Some(response) = &probe.response else {
+                    continue;
+                };
+                if let Some(index) = header_index(response, INJECTED_HEADER)
+                    && response.headers[index].1.contains(&token)
+                {
+                    return Ok(vec![
+                        Finding::new("header-injection", "HTTP header injection", Severity::High, Confidence::Certain, &probe)
+                            .with_detail(format!("{} can add arbitrary response headers", point.describe()))
+                            .with_evidence(Location::ResponseHeader(index), 0, response.headers[index].1.len()),
+                    ]);
+                }
+            }
+            Ok(Vec::new())
+        })
+    }
+}
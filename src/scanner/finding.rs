@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::flow::body::Body;
+use crate::flow::model::{Flow, FlowId};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "info" => Ok(Self::Info),
+            "low" => Ok(Self::Low),
+            "medium" => Ok(Self::Medium),
+            "high" => Ok(Self::High),
+            _ => bail!("Unknown severity {} (expected info, low, medium or high)", value),
+        }
+    }
+}
+
+// How sure a check is that the finding is real
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Tentative,
+    Firm,
+    Certain,
+}
+
+impl Confidence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Tentative => "tentative",
+            Self::Firm => "firm",
+            Self::Certain => "certain",
+        }
+    }
+}
+
+// Part of a flow that evidence points into. Headers are addressed by their
+// index in the header list, since names like Set-Cookie repeat.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(tag = "part", content = "index", rename_all = "snake_case")]
+pub enum Location {
+    // The request URI as recorded
+    Url,
+    RequestHeader(usize),
+    RequestBody,
+    ResponseHeader(usize),
+    ResponseBody,
+}
+
+// Byte range inside one part of a flow that shows why a finding was raised.
+// Body ranges count bytes of the decoded text, so they hold for compressed bodies too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Evidence {
+    pub location: Location,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Evidence {
+    pub fn new(location: Location, start: usize, end: usize) -> Self {
+        Self { location, start, end }
+    }
+
+    // The bytes this evidence covers, or None if it does not fit the flow
+    pub fn bytes<'a>(&self, flow: &'a Flow) -> Option<Cow<'a, [u8]>> {
+        let response = flow.response.as_ref();
+        let part: &[u8] = match &self.location {
+            Location::Url => flow.request.uri.as_bytes(),
+            Location::RequestHeader(index) => flow.request.headers.get(*index)?.1.as_bytes(),
+            Location::RequestBody => return self.text_bytes(flow.request.decoded_body()),
+            Location::ResponseHeader(index) => response?.headers.get(*index)?.1.as_bytes(),
+            Location::ResponseBody => return self.text_bytes(response?.decoded_body()),
+        };
+        part.get(self.start..self.end).map(Cow::Borrowed)
+    }
+
+    fn text_bytes<'a>(&self, body: Body<'_>) -> Option<Cow<'a, [u8]>> {
+        let text = body.text();
+        let bytes = text.as_bytes().get(self.start..self.end)?;
+        Some(Cow::Owned(bytes.to_vec()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    // Identifier of the check that raised it, e.g. `missing-hsts`
+    pub check: String,
+    pub title: String,
+    pub severity: Severity,
+    pub confidence: Confidence,
+    pub flow_id: FlowId,
+    pub url: String,
+    pub detail: String,
+    // Empty for findings about something that is absent, like a missing header
+    pub evidence: Vec<Evidence>,
+}
+
+impl Finding {
+    pub fn new(check: &str, title: &str, severity: Severity, confidence: Confidence, flow: &Flow) -> Self {
+        Self {
+            check: check.to_string(),
+            title: title.to_string(),
+            severity,
+            confidence,
+            flow_id: flow.id,
+            url: flow.request.uri.clone(),
+            detail: String::new(),
+            evidence: Vec::new(),
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = detail.into();
+        self
+    }
+
+    pub fn with_evidence(mut self, location: Location, start: usize, end: usize) -> Self {
+        self.evidence.push(Evidence::new(location, start, end));
+        self
+    }
+}
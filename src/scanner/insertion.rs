@@ -0,0 +1,228 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::flow::model::FlowRequest;
+use crate::scanner::finding::{Evidence, Location};
+
+// Characters that would end or split a cookie value
+const COOKIE_VALUE: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b',').add(b';').add(b'\\').add(b'%');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointKind {
+    Query,
+    // application/x-www-form-urlencoded body field
+    Form,
+    // Scalar member of a JSON body, named by its JSON pointer
+    Json,
+    Cookie,
+}
+
+impl PointKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Form => "form",
+            Self::Json => "json",
+            Self::Cookie => "cookie",
+        }
+    }
+}
+
+// A place in a request where the scanner can substitute its own value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InsertionPoint {
+    pub kind: PointKind,
+    pub name: String,
+    // Decoded original value
+    pub value: String,
+    // Pair index for query, form and cookie points
+    #[serde(skip)]
+    index: usize,
+}
+
+impl InsertionPoint {
+    // Human-readable label such as `query parameter id`
+    pub fn describe(&self) -> String {
+        match self.kind {
+            PointKind::Query => format!("query parameter {}", self.name),
+            PointKind::Form => format!("form field {}", self.name),
+            PointKind::Json => format!("JSON member {}", self.name),
+            PointKind::Cookie => format!("cookie {}", self.name),
+        }
+    }
+
+    // Copy of the request with this point's value replaced, encoded as its
+    // context requires. The caller supplies the whole new value, so appending
+    // a payload means passing `point.value` plus the payload.
+    pub fn inject(&self, request: &FlowRequest, value: &str) -> FlowRequest {
+        let mut request = request.clone();
+        match self.kind {
+            PointKind::Query => {
+                if let Some((path, query)) = request.uri.split_once('?') {
+                    let (query, fragment) = match query.split_once('#') {
+                        Some((query, fragment)) => (query, format!("#{}", fragment)),
+                        None => (query, String::new()),
+                    };
+                    let query = replace_pair(query, "&", self.index, &form_encode(value));
+                    request.uri = format!("{}?{}{}", path, query, fragment);
+                }
+            }
+            PointKind::Form => {
+                let body = String::from_utf8_lossy(&request.body).into_owned();
+                request.body = replace_pair(&body, "&", self.index, &form_encode(value)).into_bytes();
+            }
+            PointKind::Json => {
+                if let Ok(mut json) = serde_json::from_slice::<Value>(&request.body)
+                    && let Some(member) = json.pointer_mut(&self.name)
+                {
+                    *member = Value::String(value.to_string());
+                    request.body = json.to_string().into_bytes();
+                }
+            }
+            PointKind::Cookie => {
+                if let Some(cookie) = request.header("cookie") {
+                    let encoded = utf8_percent_encode(value, COOKIE_VALUE).to_string();
+                    let cookie = replace_pair(cookie, ";", self.index, &encoded);
+                    request.set_header("Cookie", &cookie);
+                }
+            }
+        }
+
+        if request.header("content-length").is_some() {
+            let length = request.body.len().to_string();
+            request.set_header("Content-Length", &length);
+        }
+        request
+    }
+
+    // Byte range of this point's value in a request built from it, for evidence
+    pub fn locate(&self, request: &FlowRequest) -> Option<Evidence> {
+        match self.kind {
+            PointKind::Query => {
+                let start = request.uri.find('?')? + 1;
+                let end = request.uri[start..].find('#').map_or(request.uri.len(), |at| start + at);
+                let range = pair_value_range(&request.uri[start..end], "&", self.index)?;
+                Some(Evidence::new(Location::Url, start + range.0, start + range.1))
+            }
+            PointKind::Form => {
+                let body = std::str::from_utf8(&request.body).ok()?;
+                let range = pair_value_range(body, "&", self.index)?;
+                Some(Evidence::new(Location::RequestBody, range.0, range.1))
+            }
+            PointKind::Json => {
+                let json: Value = serde_json::from_slice(&request.body).ok()?;
+                let needle = json.pointer(&self.name)?.to_string();
+                let start = find(&request.body, needle.as_bytes())?;
+                Some(Evidence::new(Location::RequestBody, start, start + needle.len()))
+            }
+            PointKind::Cookie => {
+                let index = request.headers.iter().position(|(name, _)| name.eq_ignore_ascii_case("cookie"))?;
+                let range = pair_value_range(&request.headers[index].1, ";", self.index)?;
+                Some(Evidence::new(Location::RequestHeader(index), range.0, range.1))
+            }
+        }
+    }
+}
+
+// Every query parameter, form field, JSON scalar and cookie of a request
+pub fn insertion_points(request: &FlowRequest) -> Vec<InsertionPoint> {
+    let mut points = Vec::new();
+
+    if let Some((_, query)) = request.uri.split_once('?') {
+        let query = query.split('#').next().unwrap_or_default();
+        points.extend(pairs(query, "&", PointKind::Query, true));
+    }
+
+    let content_type = request.header("content-type").unwrap_or("").to_ascii_lowercase();
+    if content_type.contains("x-www-form-urlencoded") {
+        points.extend(pairs(&String::from_utf8_lossy(&request.body), "&", PointKind::Form, true));
+    } else if !request.body.is_empty()
+        && let Ok(json) = serde_json::from_slice::<Value>(&request.body)
+    {
+        json_scalars(&json, String::new(), &mut points);
+    }
+
+    if let Some(cookie) = request.header("cookie") {
+        points.extend(pairs(cookie, ";", PointKind::Cookie, false));
+    }
+
+    points
+}
+
+fn pairs(text: &str, separator: &str, kind: PointKind, form_encoded: bool) -> Vec<InsertionPoint> {
+    text.split(separator)
+        .enumerate()
+        .filter_map(|(index, pair)| {
+            let (name, value) = pair.trim_start().split_once('=')?;
+            let decode = |text: &str| {
+                let text = if form_encoded { text.replace('+', " ") } else { text.to_string() };
+                percent_decode_str(&text).decode_utf8_lossy().into_owned()
+            };
+            Some(InsertionPoint {
+                kind,
+                name: decode(name),
+                value: decode(value),
+                index,
+            })
+        })
+        .collect()
+}
+
+fn json_scalars(value: &Value, pointer: String, points: &mut Vec<InsertionPoint>) {
+    match value {
+        Value::Object(members) => {
+            for (key, member) in members {
+                let key = key.replace('~', "~0").replace('/', "~1");
+                json_scalars(member, format!("{}/{}", pointer, key), points);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                json_scalars(item, format!("{}/{}", pointer, index), points);
+            }
+        }
+        scalar if !pointer.is_empty() => points.push(InsertionPoint {
+            kind: PointKind::Json,
+            name: pointer,
+            value: match scalar {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            },
+            index: 0,
+        }),
+        _ => {}
+    }
+}
+
+fn form_encode(value: &str) -> String {
+    url::form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+fn replace_pair(text: &str, separator: &str, index: usize, value: &str) -> String {
+    text.split(separator)
+        .enumerate()
+        .map(|(at, pair)| match pair.split_once('=') {
+            Some((name, _)) if at == index => format!("{}={}", name, value),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn pair_value_range(text: &str, separator: &str, index: usize) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    for (at, pair) in text.split(separator).enumerate() {
+        if at == index {
+            let eq = pair.find('=')?;
+            return Some((offset + eq + 1, offset + pair.len()));
+        }
+        offset += pair.len() + separator.len();
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
@@ -0,0 +1,7 @@
+pub mod active;
+pub mod callback;
+pub mod checks;
+pub mod finding;
+pub mod insertion;
+pub mod passive;
+pub mod secrets;
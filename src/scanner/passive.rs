@@ -0,0 +1,619 @@
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use anyhow::{Context, Result};
+use log::info;
+use percent_encoding::percent_decode_str;
+use regex::bytes::Regex;
+use url::Url;
+
+use crate::flow::cookie::parse_set_cookie;
+use crate::flow::model::{Flow, FlowResponse};
+use crate::jwt::attack::crack;
+use crate::jwt::locate::find_tokens;
+use crate::project::scope::Scope;
+use crate::project::store::Project;
+use crate::scanner::finding::{Confidence, Finding, Location, Severity};
+use crate::scanner::secrets::find_secrets;
+use crate::sitemap::links::{is_html, request_url, url_attributes};
+use crate::sitemap::scripts::{extract_endpoints, is_script, EndpointKind};
+
+// Shortest HSTS max-age not reported as weak (180 days)
+const MIN_HSTS_MAX_AGE: u64 = 15_552_000;
+
+static ERROR_PATTERNS: LazyLock<Vec<(&'static str, Regex)>> = LazyLock::new(|| {
+    [
+        ("Java stack trace", r"at [\w$.]+\([\w$]+\.java:\d+\)"),
+        ("Python traceback", r"Traceback \(most recent call last\):"),
+        ("PHP error", r"(?:Fatal error|Warning|Parse error|Notice)</b>?: .{0,300}? in <b>?/[^<\s]+</b>? on line"),
+        (".NET exception", r"(?:Server Error in '[^']*' Application|System\.[\w.]+Exception:)"),
+        ("Node.js stack trace", r"at [\w$.<>]+ \((?:/|[A-Z]:\\)[^)\s]+\.js:\d+:\d+\)"),
+        ("Ruby backtrace", r"\.rb:\d+:in `"),
+        ("Go panic", r"goroutine \d+ \[running\]:"),
+        ("SQL error", r"(?i)(?:You have an error in your SQL syntax|ORA-\d{5}|PG::\w+Error|SQLSTATE\[\w+\]|Unclosed quotation mark after the character string|SQLite3?::\w*Exception)"),
+    ]
+    .into_iter()
+    .map(|(name, pattern)| (name, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+static LISTING_PATTERNS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"(?i)<title>\s*Index of /",
+        r"(?i)<title>\s*Directory listing for /",
+        r"\[To Parent Directory\]",
+        r"(?i)<h1>\s*Directory: /",
+    ]
+    .into_iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+// Range of the first database error message in a body
+pub(crate) fn sql_error(body: &[u8]) -> Option<std::ops::Range<usize>> {
+    ERROR_PATTERNS
+        .iter()
+        .find(|(kind, _)| *kind == "SQL error")
+        .and_then(|(_, pattern)| pattern.find(body))
+        .map(|found| found.range())
+}
+
+// Query parameter names that should never carry their value in a URL
+const SECRET_PARAMETERS: &[(&str, Severity)] = &[
+    ("password", Severity::Medium),
+    ("passwd", Severity::Medium),
+    ("pwd", Severity::Medium),
+    ("pass", Severity::Medium),
+    ("secret", Severity::Medium),
+    ("client_secret", Severity::Medium),
+    ("token", Severity::Low),
+    ("access_token", Severity::Low),
+    ("refresh_token", Severity::Low),
+    ("id_token", Severity::Low),
+    ("api_key", Severity::Low),
+    ("apikey", Severity::Low),
+    ("auth", Severity::Low),
+    ("session", Severity::Low),
+    ("sessionid", Severity::Low),
+    ("session_id", Severity::Low),
+    ("jsessionid", Severity::Low),
+    ("phpsessid", Severity::Low),
+];
+
+// A check that inspects a flow that has already happened, without sending anything
+pub trait PassiveCheck: Send + Sync {
+    // Short identifier, e.g. `cookies`
+    fn name(&self) -> &'static str;
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding>;
+}
+
+// Runs passive checks over flows and keeps their findings, reporting each
+// issue once per URL path rather than once per request
+pub struct PassiveScanner {
+    checks: Vec<Box<dyn PassiveCheck>>,
+    findings: Vec<Finding>,
+    seen: HashSet<(String, String, String)>,
+    scope: Scope,
+}
+
+impl Default for PassiveScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PassiveScanner {
+    // A scanner with every built-in check
+    pub fn new() -> Self {
+        Self::empty()
+            .with_check(Box::new(SecurityHeaders))
+            .with_check(Box::new(Cookies))
+            .with_check(Box::new(MixedContent))
+            .with_check(Box::new(VerboseErrors))
+            .with_check(Box::new(DirectoryListing))
+            .with_check(Box::new(SensitiveUrl))
+            .with_check(Box::new(ScriptContents))
+            .with_check(Box::new(JwtWeaknesses))
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            checks: Vec::new(),
+            findings: Vec::new(),
+            seen: HashSet::new(),
+            scope: Scope::new(),
+        }
+    }
+
+    pub fn with_check(mut self, check: Box<dyn PassiveCheck>) -> Self {
+        self.checks.push(check);
+        self
+    }
+
+    pub fn check_names(&self) -> Vec<&'static str> {
+        self.checks.iter().map(|check| check.name()).collect()
+    }
+
+    // Only record findings for flows within the scope
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    // Every finding for one flow, without deduplication or recording
+    pub fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        self.checks.iter().flat_map(|check| check.scan(flow)).collect()
+    }
+
+    // Scan a flow and keep the findings not already reported; returns the new ones.
+    // Out-of-scope flows are not scanned.
+    pub fn record(&mut self, flow: &Flow) -> Vec<Finding> {
+        let mut new = Vec::new();
+        if !self.scope.contains(&flow.request) {
+            return new;
+        }
+        for finding in self.scan(flow) {
+            let path = finding.url.split(['?', '#']).next().unwrap_or_default().to_string();
+            if self.seen.insert((finding.check.clone(), path, finding.detail.clone())) {
+                info!("[{}] {}: {}", finding.severity, finding.title, finding.url);
+                new.push(finding);
+            }
+        }
+        self.findings.extend(new.iter().cloned());
+        new
+    }
+
+    // Scan every flow stored in a project
+    pub fn record_project(&mut self, project: &Project) -> Result<usize> {
+        let before = self.findings.len();
+        for summary in project.list_flows()? {
+            let flow = project
+                .load_flow(summary.id)?
+                .with_context(|| format!("Flow {} disappeared while reading the project", summary.id))?;
+            self.record(&flow);
+        }
+        Ok(self.findings.len() - before)
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+}
+
+fn header_index(headers: &[(String, String)], name: &str) -> Option<usize> {
+    headers.iter().position(|(header, _)| header.eq_ignore_ascii_case(name))
+}
+
+fn is_https(flow: &Flow) -> bool {
+    flow.request.uri.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+// Text responses worth searching for error messages; images and the like are skipped
+fn is_textual(response: &FlowResponse) -> bool {
+    match response.header("content-type") {
+        Some(content_type) => {
+            let content_type = content_type.to_ascii_lowercase();
+            content_type.starts_with("text/")
+                || content_type.contains("json")
+                || content_type.contains("xml")
+                || content_type.contains("javascript")
+        }
+        None => true,
+    }
+}
+
+// CSP, X-Frame-Options and HSTS on HTML pages
+pub struct SecurityHeaders;
+
+impl PassiveCheck for SecurityHeaders {
+    fn name(&self) -> &'static str {
+        "security-headers"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let Some(response) = &flow.response else {
+            return Vec::new();
+        };
+        let mut findings = Vec::new();
+        let headers = &response.headers;
+
+        if is_https(flow) {
+            match header_index(headers, "strict-transport-security") {
+                None => findings.push(
+                    Finding::new("missing-hsts", "Strict-Transport-Security not set", Severity::Low, Confidence::Certain, flow)
+                        .with_detail("HTTPS response without HSTS; clients can be downgraded to plain HTTP"),
+                ),
+                Some(index) => {
+                    let value = &headers[index].1;
+                    let max_age = value
+                        .split(';')
+                        .filter_map(|directive| directive.trim().split_once('='))
+                        .find(|(name, _)| name.trim().eq_ignore_ascii_case("max-age"))
+                        .and_then(|(_, age)| age.trim().trim_matches('"').parse::<u64>().ok());
+                    if max_age.is_none_or(|age| age < MIN_HSTS_MAX_AGE) {
+                        findings.push(
+                            Finding::new("weak-hsts", "Weak Strict-Transport-Security", Severity::Low, Confidence::Firm, flow)
+                                .with_detail(format!("max-age below {} seconds", MIN_HSTS_MAX_AGE))
+                                .with_evidence(Location::ResponseHeader(index), 0, value.len()),
+                        );
+                    }
+                }
+            }
+        }
+
+        if !(200..300).contains(&response.status) || !is_html(response) {
+            return findings;
+        }
+
+        let csp = header_index(headers, "content-security-policy");
+        match csp {
+            None => findings.push(
+                Finding::new("missing-csp", "Content-Security-Policy not set", Severity::Low, Confidence::Certain, flow)
+                    .with_detail("HTML response without a Content-Security-Policy"),
+            ),
+            Some(index) => {
+                let value = &headers[index].1;
+                for (weakness, detail) in [
+                    ("'unsafe-inline'", "allows inline scripts"),
+                    ("'unsafe-eval'", "allows eval"),
+                ] {
+                    if let Some(start) = script_directive_position(value, weakness) {
+                        findings.push(
+                            Finding::new("weak-csp", "Weak Content-Security-Policy", Severity::Low, Confidence::Firm, flow)
+                                .with_detail(format!("Policy {}", detail))
+                                .with_evidence(Location::ResponseHeader(index), start, start + weakness.len()),
+                        );
+                    }
+                }
+                if let Some(start) = wildcard_source(value) {
+                    findings.push(
+                        Finding::new("weak-csp", "Weak Content-Security-Policy", Severity::Low, Confidence::Firm, flow)
+                            .with_detail("Policy allows scripts from any origin")
+                            .with_evidence(Location::ResponseHeader(index), start, start + 1),
+                    );
+                }
+            }
+        }
+
+        let frame_ancestors = csp.is_some_and(|index| headers[index].1.to_ascii_lowercase().contains("frame-ancestors"));
+        match header_index(headers, "x-frame-options") {
+            None if !frame_ancestors => findings.push(
+                Finding::new("missing-x-frame-options", "Page can be framed", Severity::Low, Confidence::Firm, flow)
+                    .with_detail("Neither X-Frame-Options nor CSP frame-ancestors is set; the page may be vulnerable to clickjacking"),
+            ),
+            Some(index) => {
+                let value = headers[index].1.trim();
+                if !value.eq_ignore_ascii_case("deny") && !value.eq_ignore_ascii_case("sameorigin") {
+                    findings.push(
+                        Finding::new("invalid-x-frame-options", "Invalid X-Frame-Options", Severity::Info, Confidence::Firm, flow)
+                            .with_detail("Browsers only honour DENY and SAMEORIGIN")
+                            .with_evidence(Location::ResponseHeader(index), 0, headers[index].1.len()),
+                    );
+                }
+            }
+            None => {}
+        }
+
+        findings
+    }
+}
+
+// Position of a source keyword in the directive that governs scripts
+fn script_directive_position(policy: &str, keyword: &str) -> Option<usize> {
+    let directive = script_directive(policy)?;
+    let lower = policy[directive.clone()].to_ascii_lowercase();
+    lower.find(keyword).map(|offset| directive.start + offset)
+}
+
+fn wildcard_source(policy: &str) -> Option<usize> {
+    let directive = script_directive(policy)?;
+    let mut offset = directive.start;
+    for token in policy[directive].split(' ') {
+        if matches!(token, "*" | "http:" | "https:") {
+            return Some(offset);
+        }
+        offset += token.len() + 1;
+    }
+    None
+}
+
+// Byte range of `script-src`, or `default-src` when there is no script-src
+fn script_directive(policy: &str) -> Option<std::ops::Range<usize>> {
+    let mut start = 0;
+    let mut default = None;
+    for directive in policy.split(';') {
+        let name = directive.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+        let range = start..start + directive.len();
+        if name == "script-src" {
+            return Some(range);
+        }
+        if name == "default-src" {
+            default = Some(range);
+        }
+        start += directive.len() + 1;
+    }
+    default
+}
+
+// Set-Cookie attributes that protect session cookies
+pub struct Cookies;
+
+impl PassiveCheck for Cookies {
+    fn name(&self) -> &'static str {
+        "cookies"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let Some(response) = &flow.response else {
+            return Vec::new();
+        };
+        let https = is_https(flow);
+        let mut findings = Vec::new();
+
+        for (index, (name, value)) in response.headers.iter().enumerate() {
+            if !name.eq_ignore_ascii_case("set-cookie") {
+                continue;
+            }
+            let Some(cookie) = parse_set_cookie(value) else {
+                continue;
+            };
+            let end = value.find(';').unwrap_or(value.len());
+            let issue = |check: &str, title: &str, severity: Severity, detail: &str| {
+                Finding::new(check, title, severity, Confidence::Certain, flow)
+                    .with_detail(format!("Cookie {} {}", cookie.name, detail))
+                    .with_evidence(Location::ResponseHeader(index), 0, end)
+            };
+
+            if https && !cookie.secure {
+                findings.push(issue("cookie-without-secure", "Cookie without Secure flag", Severity::Low, "can be sent over plain HTTP"));
+            }
+            if !cookie.http_only {
+                findings.push(issue("cookie-without-httponly", "Cookie without HttpOnly flag", Severity::Low, "is readable from JavaScript"));
+            }
+            match cookie.same_site.as_deref() {
+                None => findings.push(issue("cookie-without-samesite", "Cookie without SameSite attribute", Severity::Info, "relies on the browser's default SameSite policy")),
+                Some(same_site) if same_site.eq_ignore_ascii_case("none") && !cookie.secure => findings.push(issue(
+                    "cookie-samesite-none-without-secure",
+                    "SameSite=None cookie without Secure flag",
+                    Severity::Low,
+                    "sets SameSite=None without Secure and will be rejected by modern browsers",
+                )),
+                Some(_) => {}
+            }
+        }
+
+        findings
+    }
+}
+
+// HTTPS pages that pull in or submit to plain HTTP URLs
+pub struct MixedContent;
+
+impl PassiveCheck for MixedContent {
+    fn name(&self) -> &'static str {
+        "mixed-content"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let Some(response) = &flow.response else {
+            return Vec::new();
+        };
+        if !is_https(flow) || !is_html(response) {
+            return Vec::new();
+        }
+
+        url_attributes(response.decoded_body().text().as_bytes())
+            .into_iter()
+            .filter(|attribute| {
+                attribute.value.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"))
+                    && !matches!((attribute.tag.as_str(), attribute.name.as_str()), ("a" | "area" | "base", "href"))
+            })
+            .map(|attribute| {
+                // Scripts, frames, stylesheets and forms can change or leak the whole page
+                let active = matches!(
+                    (attribute.tag.as_str(), attribute.name.as_str()),
+                    ("script" | "iframe" | "frame" | "link" | "object" | "embed", _) | (_, "action" | "formaction")
+                );
+                let severity = if active { Severity::Medium } else { Severity::Low };
+                Finding::new("mixed-content", "Mixed content", severity, Confidence::Certain, flow)
+                    .with_detail(format!("<{} {}> loads {} over plain HTTP", attribute.tag, attribute.name, attribute.value))
+                    .with_evidence(Location::ResponseBody, attribute.range.start, attribute.range.end)
+            })
+            .collect()
+    }
+}
+
+// Stack traces and database errors leaking implementation details
+pub struct VerboseErrors;
+
+impl PassiveCheck for VerboseErrors {
+    fn name(&self) -> &'static str {
+        "verbose-errors"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let Some(response) = &flow.response else {
+            return Vec::new();
+        };
+        if !is_textual(response) {
+            return Vec::new();
+        }
+
+        let text = response.decoded_body().text().into_owned();
+        ERROR_PATTERNS
+            .iter()
+            .filter_map(|(kind, pattern)| {
+                let found = pattern.find(text.as_bytes())?;
+                Some(
+                    Finding::new("verbose-error", "Verbose error message", Severity::Low, Confidence::Firm, flow)
+                        .with_detail(format!("{} in response", kind))
+                        .with_evidence(Location::ResponseBody, found.start(), found.end()),
+                )
+            })
+            .collect()
+    }
+}
+
+// Auto-generated web server directory indexes
+pub struct DirectoryListing;
+
+impl PassiveCheck for DirectoryListing {
+    fn name(&self) -> &'static str {
+        "directory-listing"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let Some(response) = &flow.response else {
+            return Vec::new();
+        };
+        if response.status != 200 || !is_html(response) {
+            return Vec::new();
+        }
+
+        let text = response.decoded_body().text().into_owned();
+        LISTING_PATTERNS
+            .iter()
+            .find_map(|pattern| pattern.find(text.as_bytes()))
+            .map(|found| {
+                Finding::new("directory-listing", "Directory listing", Severity::Low, Confidence::Firm, flow)
+                    .with_detail("Server returned an index of the directory contents")
+                    .with_evidence(Location::ResponseBody, found.start(), found.end())
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+// Credentials, tokens and session IDs in request URLs, which end up in logs,
+// browser history and Referer headers
+pub struct SensitiveUrl;
+
+impl PassiveCheck for SensitiveUrl {
+    fn name(&self) -> &'static str {
+        "sensitive-url"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let uri = &flow.request.uri;
+        let mut findings = Vec::new();
+
+        if let Ok(url) = Url::parse(uri)
+            && url.password().is_some()
+            && let Some(start) = uri.find("://").map(|index| index + 3)
+            && let Some(end) = uri[start..].find('@').map(|index| start + index)
+        {
+            findings.push(
+                Finding::new("credentials-in-url", "Credentials in URL", Severity::Medium, Confidence::Certain, flow)
+                    .with_detail("URL contains a user name and password")
+                    .with_evidence(Location::Url, start, end),
+            );
+        }
+
+        // Walk the raw query so evidence offsets point into the recorded URI
+        let Some(query_start) = uri.find('?').map(|index| index + 1) else {
+            return findings;
+        };
+        let query_end = uri[query_start..].find('#').map_or(uri.len(), |index| query_start + index);
+        let mut offset = query_start;
+
+        for pair in uri[query_start..query_end].split('&') {
+            let start = offset;
+            offset += pair.len() + 1;
+
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if value.is_empty() {
+                continue;
+            }
+            let name = percent_decode_str(&name.replace('+', " ")).decode_utf8_lossy().to_ascii_lowercase();
+            let value = percent_decode_str(value).decode_utf8_lossy().into_owned();
+
+            let secret = SECRET_PARAMETERS
+                .iter()
+                .find(|(secret, _)| name == *secret)
+                .map(|(_, severity)| *severity);
+            let jwt = value.starts_with("eyJ") && value.split('.').count() == 3;
+
+            if let Some(severity) = secret.or(jwt.then_some(Severity::Low)) {
+                findings.push(
+                    Finding::new("sensitive-data-in-url", "Sensitive data in URL", severity, Confidence::Firm, flow)
+                        .with_detail(format!("Query parameter {} carries a {}", name, if jwt { "JWT" } else { "secret" }))
+                        .with_evidence(Location::Url, start, start + pair.len()),
+                );
+            }
+        }
+
+        findings
+    }
+}
+
+// Endpoints, GraphQL operations and secrets in JavaScript and JSON responses
+pub struct ScriptContents;
+
+impl PassiveCheck for ScriptContents {
+    fn name(&self) -> &'static str {
+        "scripts"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let (Some(response), Some(url)) = (&flow.response, request_url(&flow.request)) else {
+            return Vec::new();
+        };
+        if !is_script(&url, response) {
+            return Vec::new();
+        }
+        let text = response.decoded_body().text().into_owned();
+        let mut findings = Vec::new();
+
+        for secret in find_secrets(text.as_bytes()) {
+            findings.push(
+                Finding::new("exposed-secret", "Secret in response", secret.severity, secret.confidence, flow)
+                    .with_detail(format!("{} {}", secret.kind, secret.masked(text.as_bytes())))
+                    .with_evidence(Location::ResponseBody, secret.range.start, secret.range.end),
+            );
+        }
+
+        for endpoint in extract_endpoints(text.as_bytes()) {
+            let (check, title) = match endpoint.kind {
+                EndpointKind::Graphql => ("graphql-operation", "GraphQL operation in script"),
+                _ => ("script-endpoint", "Endpoint in script"),
+            };
+            findings.push(
+                Finding::new(check, title, Severity::Info, Confidence::Firm, flow)
+                    .with_detail(endpoint.value)
+                    .with_evidence(Location::ResponseBody, endpoint.range.start, endpoint.range.end),
+            );
+        }
+
+        findings
+    }
+}
+
+// JWTs that are unsigned or signed with a guessable HMAC secret
+pub struct JwtWeaknesses;
+
+impl PassiveCheck for JwtWeaknesses {
+    fn name(&self) -> &'static str {
+        "jwt"
+    }
+
+    fn scan(&self, flow: &Flow) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for found in find_tokens(flow) {
+            let evidence = &found.evidence;
+            if found.jwt.alg().is_some_and(|alg| alg.eq_ignore_ascii_case("none")) {
+                findings.push(
+                    Finding::new("jwt-alg-none", "Unsigned JWT", Severity::High, Confidence::Firm, flow)
+                        .with_detail(format!("JWT in {} uses alg none", found.source))
+                        .with_evidence(evidence.location.clone(), evidence.start, evidence.end),
+                );
+            } else if let Some(secret) = crack(&found.jwt, []) {
+                findings.push(
+                    Finding::new("jwt-weak-secret", "JWT signed with a weak secret", Severity::High, Confidence::Certain, flow)
+                        .with_detail(format!("JWT in {} is signed with the secret {:?}", found.source, secret))
+                        .with_evidence(evidence.location.clone(), evidence.start, evidence.end),
+                );
+            }
+        }
+        findings
+    }
+}
@@ -0,0 +1,85 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::bytes::Regex;
+
+use crate::scanner::finding::{Confidence, Severity};
+
+// Name, severity, confidence and pattern of each kind of secret; patterns
+// with a capture group report only that group
+static SECRET_PATTERNS: LazyLock<Vec<(&'static str, Severity, Confidence, Regex)>> = LazyLock::new(|| {
+    [
+        (
+            "private key",
+            Severity::High,
+            Confidence::Certain,
+            r"-----BEGIN (?:RSA |EC |DSA |OPENSSH |PGP |ENCRYPTED )?PRIVATE KEY(?: BLOCK)?-----",
+        ),
+        ("AWS access key ID", Severity::High, Confidence::Firm, r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b"),
+        (
+            "AWS secret access key",
+            Severity::High,
+            Confidence::Firm,
+            r#"(?i)aws_?secret_?(?:access_?)?key["']?\s*[:=]\s*["']?([A-Za-z0-9/+=]{40})\b"#,
+        ),
+        ("GitHub token", Severity::High, Confidence::Firm, r"\bgh[pousr]_[0-9A-Za-z]{36}\b"),
+        ("Slack token", Severity::High, Confidence::Firm, r"\bxox[abposr]-[0-9A-Za-z-]{10,}"),
+        ("Stripe secret key", Severity::High, Confidence::Firm, r"\b[sr]k_live_[0-9A-Za-z]{24,}\b"),
+        ("Google API key", Severity::Medium, Confidence::Firm, r"\bAIza[0-9A-Za-z_-]{35}\b"),
+        ("JWT", Severity::Medium, Confidence::Firm, r"\beyJ[A-Za-z0-9_-]{8,}\.eyJ[A-Za-z0-9_-]{8,}\.[A-Za-z0-9_-]*"),
+        (
+            "hard-coded credential",
+            Severity::Low,
+            Confidence::Tentative,
+            r#"(?i)["']?(?:api_?key|api_?secret|client_?secret|access_?token|auth_?token|secret_?key|password)["']?\s*[:=]\s*["']([^"'\s]{12,200})["']"#,
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, severity, confidence, pattern)| (kind, severity, confidence, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+// A likely secret located by byte range in a body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Secret {
+    pub kind: &'static str,
+    pub severity: Severity,
+    pub confidence: Confidence,
+    pub range: Range<usize>,
+}
+
+impl Secret {
+    // The secret with all but its first and last four characters hidden
+    pub fn masked(&self, body: &[u8]) -> String {
+        let value = String::from_utf8_lossy(&body[self.range.clone()]).into_owned();
+        let chars: Vec<char> = value.chars().collect();
+        if chars.len() <= 12 {
+            return "*".repeat(chars.len());
+        }
+        let head: String = chars[..4].iter().collect();
+        let tail: String = chars[chars.len() - 4..].iter().collect();
+        format!("{}…{}", head, tail)
+    }
+}
+
+// Every likely secret in a body, in order of appearance; a secret matched
+// by a specific pattern is not reported again by a generic one
+pub fn find_secrets(body: &[u8]) -> Vec<Secret> {
+    let mut secrets: Vec<Secret> = Vec::new();
+    for (kind, severity, confidence, pattern) in SECRET_PATTERNS.iter() {
+        for captures in pattern.captures_iter(body) {
+            let found = captures.get(1).unwrap_or_else(|| captures.get(0).unwrap());
+            let range = found.range();
+            if secrets.iter().any(|secret| secret.range.start < range.end && range.start < secret.range.end) {
+                continue;
+            }
+            secrets.push(Secret {
+                kind,
+                severity: *severity,
+                confidence: *confidence,
+                range,
+            });
+        }
+    }
+    secrets.sort_by_key(|secret| secret.range.start);
+    secrets
+}
@@ -0,0 +1,292 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+use anyhow::{bail, Result};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+// FIPS 140-2 statistical tests work on blocks of this many bits
+pub const FIPS_BLOCK_BITS: usize = 20_000;
+
+// Below this many tokens the entropy estimates are not meaningful
+const MIN_RELIABLE_SAMPLE: usize = 100;
+
+// Acceptance intervals of the FIPS 140-2 runs test for run lengths 1..=5 and 6+
+const RUNS_INTERVALS: [(usize, usize); 6] = [
+    (2315, 2685),
+    (1114, 1386),
+    (527, 723),
+    (240, 384),
+    (103, 209),
+    (103, 209),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rating {
+    VeryPoor,
+    Poor,
+    Reasonable,
+    Good,
+    Excellent,
+}
+
+impl Rating {
+    fn from_bits(bits: f64) -> Self {
+        match bits {
+            b if b < 32.0 => Self::VeryPoor,
+            b if b < 64.0 => Self::Poor,
+            b if b < 80.0 => Self::Reasonable,
+            b if b < 112.0 => Self::Good,
+            _ => Self::Excellent,
+        }
+    }
+
+    fn downgrade(self) -> Self {
+        match self {
+            Self::Excellent => Self::Good,
+            Self::Good => Self::Reasonable,
+            Self::Reasonable => Self::Poor,
+            _ => Self::VeryPoor,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::VeryPoor => "very poor",
+            Self::Poor => "poor",
+            Self::Reasonable => "reasonable",
+            Self::Good => "good",
+            Self::Excellent => "excellent",
+        }
+    }
+}
+
+// Number of 20,000-bit blocks that passed each FIPS 140-2 test
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FipsResults {
+    pub blocks: usize,
+    pub monobit_passed: usize,
+    pub poker_passed: usize,
+    pub runs_passed: usize,
+    pub long_run_passed: usize,
+}
+
+impl FipsResults {
+    pub fn all_passed(&self) -> bool {
+        [self.monobit_passed, self.poker_passed, self.runs_passed, self.long_run_passed]
+            .iter()
+            .all(|&passed| passed == self.blocks)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub sample_size: usize,
+    pub distinct: usize,
+    pub min_length: usize,
+    pub max_length: usize,
+    // Every character seen in the sample, sorted
+    pub charset: String,
+    // Bits used to encode one character for the bit-level tests
+    pub bits_per_char: u32,
+    // Shannon entropy of the character distribution, in bits per character
+    pub char_entropy: f64,
+    // Sum of the entropy of each character position
+    pub char_level_bits: f64,
+    // Sum of the entropy of each bit position
+    pub bit_level_bits: f64,
+    // Positions (by character) that had the same value in every token
+    pub constant_positions: usize,
+    // None when the sample holds fewer than one 20,000-bit block
+    pub fips: Option<FipsResults>,
+    // Deflate-compressed size over the size of the tokens encoded at bits_per_char
+    pub compression_ratio: f64,
+    pub effective_bits: f64,
+    pub rating: Rating,
+    pub findings: Vec<String>,
+}
+
+// Estimate how predictable a sample of tokens is
+pub fn analyze(tokens: &[String]) -> Result<Report> {
+    if tokens.is_empty() {
+        bail!("No tokens to analyse");
+    }
+
+    let tokens: Vec<Vec<char>> = tokens.iter().map(|token| token.chars().collect()).collect();
+    let lengths = tokens.iter().map(Vec::len);
+    let min_length = lengths.clone().min().unwrap_or(0);
+    let max_length = lengths.max().unwrap_or(0);
+    let distinct = tokens.iter().collect::<HashSet<_>>().len();
+
+    let charset: Vec<char> = tokens.iter().flatten().copied().collect::<BTreeSet<_>>().into_iter().collect();
+    let bits_per_char = (usize::BITS - (charset.len().max(2) - 1).leading_zeros()).max(1);
+    let index: HashMap<char, usize> = charset.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+
+    let char_entropy = entropy(tokens.iter().flatten().copied());
+
+    let position_entropy: Vec<f64> = (0..max_length)
+        .map(|position| entropy(tokens.iter().filter_map(|token| token.get(position).copied())))
+        .collect();
+    let char_level_bits: f64 = position_entropy.iter().sum();
+    let constant_positions = position_entropy.iter().filter(|&&bits| bits == 0.0).count();
+
+    let encoded: Vec<Vec<bool>> = tokens
+        .iter()
+        .map(|token| {
+            token
+                .iter()
+                .flat_map(|c| {
+                    let value = index[c];
+                    (0..bits_per_char).rev().map(move |bit| value >> bit & 1 == 1)
+                })
+                .collect()
+        })
+        .collect();
+
+    let bit_level_bits: f64 = (0..max_length * bits_per_char as usize)
+        .map(|position| entropy(encoded.iter().filter_map(|bits| bits.get(position).copied())))
+        .sum();
+
+    let stream: Vec<bool> = encoded.iter().flatten().copied().collect();
+    let fips = (stream.len() >= FIPS_BLOCK_BITS).then(|| fips_tests(&stream));
+    let compression_ratio = compression_ratio(&tokens, stream.len());
+
+    let effective_bits = char_level_bits.min(bit_level_bits);
+    let mut rating = Rating::from_bits(effective_bits);
+    let mut findings = Vec::new();
+
+    if tokens.len() < MIN_RELIABLE_SAMPLE {
+        findings.push(format!(
+            "Only {} token(s) collected; at least {} are needed for reliable estimates",
+            tokens.len(),
+            MIN_RELIABLE_SAMPLE
+        ));
+    }
+    if distinct < tokens.len() {
+        findings.push(format!("{} duplicate token(s) seen", tokens.len() - distinct));
+        rating = Rating::VeryPoor;
+    }
+    if constant_positions > 0 {
+        findings.push(format!(
+            "{} of {} character position(s) never change",
+            constant_positions, max_length
+        ));
+    }
+    if let Some(fips) = fips.as_ref().filter(|fips| !fips.all_passed()) {
+        findings.push(format!(
+            "FIPS 140-2 failures over {} block(s): monobit {}, poker {}, runs {}, long run {}",
+            fips.blocks,
+            fips.blocks - fips.monobit_passed,
+            fips.blocks - fips.poker_passed,
+            fips.blocks - fips.runs_passed,
+            fips.blocks - fips.long_run_passed
+        ));
+        rating = rating.downgrade();
+    }
+    // Random data barely compresses below its encoded size
+    if compression_ratio < 0.8 {
+        findings.push(format!(
+            "Tokens compress to {:.0}% of their encoded size, so they contain patterns",
+            compression_ratio * 100.0
+        ));
+        rating = rating.downgrade();
+    }
+
+    Ok(Report {
+        sample_size: tokens.len(),
+        distinct,
+        min_length,
+        max_length,
+        charset: charset.into_iter().collect(),
+        bits_per_char,
+        char_entropy,
+        char_level_bits,
+        bit_level_bits,
+        constant_positions,
+        fips,
+        compression_ratio,
+        effective_bits,
+        rating,
+        findings,
+    })
+}
+
+// Shannon entropy, in bits, of the values produced by an iterator
+fn entropy<T: std::hash::Hash + Eq>(values: impl Iterator<Item = T>) -> f64 {
+    let mut counts: HashMap<T, usize> = HashMap::new();
+    let mut total = 0;
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+        total += 1;
+    }
+
+    counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum::<f64>()
+        .max(0.0)
+}
+
+fn compression_ratio(tokens: &[Vec<char>], encoded_bits: usize) -> f64 {
+    let raw: String = tokens.iter().flatten().collect();
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    let compressed = encoder
+        .write_all(raw.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|compressed| compressed.len())
+        .unwrap_or(raw.len());
+
+    let encoded_bytes = encoded_bits.div_ceil(8).max(1);
+    compressed as f64 / encoded_bytes as f64
+}
+
+// Run the FIPS 140-2 monobit, poker, runs and long run tests on each full block
+pub fn fips_tests(bits: &[bool]) -> FipsResults {
+    let mut results = FipsResults::default();
+
+    for block in bits.chunks_exact(FIPS_BLOCK_BITS) {
+        results.blocks += 1;
+
+        let ones = block.iter().filter(|&&bit| bit).count();
+        if ones > 9725 && ones < 10275 {
+            results.monobit_passed += 1;
+        }
+
+        let mut nibbles = [0usize; 16];
+        for nibble in block.chunks_exact(4) {
+            let value = nibble.iter().fold(0, |acc, &bit| acc << 1 | bit as usize);
+            nibbles[value] += 1;
+        }
+        let sum: usize = nibbles.iter().map(|count| count * count).sum();
+        let poker = 16.0 / 5000.0 * sum as f64 - 5000.0;
+        if poker > 2.16 && poker < 46.17 {
+            results.poker_passed += 1;
+        }
+
+        // runs[bit][length - 1], with length 6 counting every longer run too
+        let mut runs = [[0usize; 6]; 2];
+        let mut longest = 0;
+        for run in block.chunk_by(|a, b| a == b) {
+            runs[run[0] as usize][run.len().min(6) - 1] += 1;
+            longest = longest.max(run.len());
+        }
+        let runs_ok = runs.iter().all(|counts| {
+            counts
+                .iter()
+                .zip(RUNS_INTERVALS)
+                .all(|(&count, (low, high))| count >= low && count <= high)
+        });
+        if runs_ok {
+            results.runs_passed += 1;
+        }
+        if longest < 26 {
+            results.long_run_passed += 1;
+        }
+    }
+
+    results
+}
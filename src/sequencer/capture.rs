@@ -0,0 +1,108 @@
+use std::str::FromStr;
+use anyhow::{anyhow, bail, Context, Result};
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use regex::Regex;
+
+use crate::flow::cookie::parse_set_cookie;
+use crate::flow::model::{FlowRequest, FlowResponse};
+use crate::proxy::forward::Forwarder;
+
+// Where in a response the token under test is found
+#[derive(Debug, Clone)]
+pub enum TokenSource {
+    // Value of a cookie set by the response
+    Cookie(String),
+    // Value of a response header
+    Header(String),
+    // First capture group (or whole match) of a pattern in the response body
+    Body(Regex),
+}
+
+impl FromStr for TokenSource {
+    type Err = anyhow::Error;
+
+    // cookie:NAME, header:NAME or regex:PATTERN
+    fn from_str(spec: &str) -> Result<Self> {
+        let (kind, value) = spec
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Expected cookie:NAME, header:NAME or regex:PATTERN, got {}", spec))?;
+
+        match kind {
+            "cookie" => Ok(Self::Cookie(value.to_string())),
+            "header" => Ok(Self::Header(value.to_string())),
+            "regex" => Ok(Self::Body(
+                Regex::new(value).with_context(|| format!("Invalid pattern {}", value))?,
+            )),
+            _ => bail!("Unknown token source {}", kind),
+        }
+    }
+}
+
+impl TokenSource {
+    pub fn extract(&self, response: &FlowResponse) -> Option<String> {
+        match self {
+            Self::Cookie(name) => response
+                .headers
+                .iter()
+                .filter(|(header, _)| header.eq_ignore_ascii_case("set-cookie"))
+                .filter_map(|(_, value)| parse_set_cookie(value))
+                .find(|cookie| &cookie.name == name && !cookie.value.is_empty())
+                .map(|cookie| cookie.value),
+            Self::Header(name) => response.header(name).map(str::to_string),
+            Self::Body(regex) => {
+                let body = String::from_utf8_lossy(&response.body);
+                let captures = regex.captures(&body)?;
+                captures
+                    .get(1)
+                    .or_else(|| captures.get(0))
+                    .map(|found| found.as_str().to_string())
+            }
+        }
+    }
+}
+
+// Tokens gathered by a live capture
+#[derive(Debug, Clone, Default)]
+pub struct TokenSample {
+    pub tokens: Vec<String>,
+    // Requests that failed or whose response did not contain the token
+    pub misses: usize,
+}
+
+// Send `request` `count` times and extract a token from every response
+pub async fn collect_tokens(
+    forwarder: &Forwarder,
+    request: &FlowRequest,
+    source: &TokenSource,
+    count: usize,
+    concurrency: usize,
+) -> Result<TokenSample> {
+    info!("Collecting {} token(s) from {} {}", count, request.method, request.uri);
+
+    let mut responses = stream::iter(0..count)
+        .map(|_| forwarder.forward(request.clone()))
+        .buffer_unordered(concurrency.max(1));
+
+    let mut sample = TokenSample::default();
+    while let Some(result) = responses.next().await {
+        let token = match result {
+            Ok(flow) => flow.response.as_ref().and_then(|response| source.extract(response)),
+            Err(e) => {
+                warn!("Token request failed: {:#}", e);
+                None
+            }
+        };
+
+        match token {
+            Some(token) => sample.tokens.push(token),
+            None => sample.misses += 1,
+        }
+    }
+
+    if sample.tokens.is_empty() {
+        bail!("No tokens found in {} response(s)", count);
+    }
+
+    Ok(sample)
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::Regex;
+use serde::Serialize;
+use url::Url;
+
+use crate::flow::model::{FlowRequest, FlowResponse};
+
+// Opening tags with their attribute text; comments and closing tags never match
+static TAG: LazyLock<regex::bytes::Regex> =
+    LazyLock::new(|| regex::bytes::Regex::new(r"(?is)<([a-z][a-z0-9-]*)(\s[^>]*)?>").unwrap());
+
+static URL_ATTRIBUTE: LazyLock<regex::bytes::Regex> = LazyLock::new(|| {
+    regex::bytes::Regex::new(
+        r#"(?is)(?:^|\s)(href|src|action|formaction|poster|data)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#,
+    )
+    .unwrap()
+});
+
+static META_CONTENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)content\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+static REFRESH_URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)url\s*=\s*['"]?([^'"\s;]+)"#).unwrap());
+
+// How a response referred to another URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    // `<a href>` and `<area href>`
+    Anchor,
+    // Scripts, stylesheets, images, frames and other embedded content
+    Resource,
+    // `<form action>` and `<button formaction>`
+    Form,
+    // `Location`, `Content-Location` or `Refresh`
+    Redirect,
+}
+
+// A URL-bearing attribute of an HTML tag, located by byte range in the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlAttribute {
+    // Lowercased tag and attribute names
+    pub tag: String,
+    pub name: String,
+    // Entity-decoded value
+    pub value: String,
+    // Range of the raw value, without quotes
+    pub range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    pub url: Url,
+    pub kind: LinkKind,
+}
+
+// Absolute URL of a request, using the Host header for origin-form targets
+pub fn request_url(request: &FlowRequest) -> Option<Url> {
+    if let Ok(url) = Url::parse(&request.uri) {
+        return Some(url);
+    }
+    let host = request.header("host")?;
+    Url::parse(&format!("http://{}{}", host, request.uri)).ok()
+}
+
+// Resolve a reference found in a response. Fragments are dropped and only
+// http(s) targets are kept, so `javascript:`, `mailto:` and `data:` are ignored.
+pub fn resolve(base: &Url, reference: &str) -> Option<Url> {
+    let reference = reference.trim();
+    if reference.is_empty() || reference.starts_with('#') {
+        return None;
+    }
+
+    let mut url = base.join(reference).ok()?;
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return None;
+    }
+    url.set_fragment(None);
+    Some(url)
+}
+
+// Every URL a response points at: redirect headers plus, for HTML, the URL
+// attributes of its tags. A `<base href>` changes how later links resolve.
+pub fn extract_links(base: &Url, response: &FlowResponse) -> Vec<Link> {
+    let mut links = Vec::new();
+
+    for name in ["location", "content-location"] {
+        if let Some(url) = response.header(name).and_then(|value| resolve(base, value)) {
+            links.push(Link { url, kind: LinkKind::Redirect });
+        }
+    }
+    if let Some(url) = response.header("refresh").and_then(|value| refresh_target(base, value)) {
+        links.push(Link { url, kind: LinkKind::Redirect });
+    }
+
+    if is_html(response) {
+        links.extend(html_links(base, response.decoded_body().text().as_bytes()));
+    }
+
+    links
+}
+
+// Links in an HTML document, in document order
+pub fn html_links(base: &Url, html: &[u8]) -> Vec<Link> {
+    let mut base = base.clone();
+    let mut links = Vec::new();
+
+    for tag in TAG.captures_iter(html) {
+        let Some(attributes) = tag.get(2) else {
+            continue;
+        };
+        if tag[1].eq_ignore_ascii_case(b"meta") {
+            if let Some(url) = meta_refresh(&base, &String::from_utf8_lossy(attributes.as_bytes())) {
+                links.push(Link { url, kind: LinkKind::Redirect });
+            }
+            continue;
+        }
+
+        for attribute in tag_attributes(&tag[1], attributes.as_bytes(), attributes.start()) {
+            let Some(url) = resolve(&base, &attribute.value) else {
+                continue;
+            };
+            if attribute.tag == "base" {
+                base = url;
+                break;
+            }
+            let kind = match (attribute.tag.as_str(), attribute.name.as_str()) {
+                ("a" | "area", "href") => LinkKind::Anchor,
+                (_, "action" | "formaction") => LinkKind::Form,
+                _ => LinkKind::Resource,
+            };
+            links.push(Link { url, kind });
+        }
+    }
+
+    links
+}
+
+// Every URL-bearing attribute in an HTML document, in document order
+pub fn url_attributes(html: &[u8]) -> Vec<UrlAttribute> {
+    TAG.captures_iter(html)
+        .filter_map(|tag| {
+            let attributes = tag.get(2)?;
+            Some(tag_attributes(&tag[1], attributes.as_bytes(), attributes.start()))
+        })
+        .flatten()
+        .collect()
+}
+
+fn tag_attributes(tag: &[u8], attributes: &[u8], offset: usize) -> Vec<UrlAttribute> {
+    let tag = String::from_utf8_lossy(tag).to_ascii_lowercase();
+    URL_ATTRIBUTE
+        .captures_iter(attributes)
+        .filter_map(|captures| {
+            let value = captures.get(2).or_else(|| captures.get(3)).or_else(|| captures.get(4))?;
+            Some(UrlAttribute {
+                tag: tag.clone(),
+                name: String::from_utf8_lossy(&captures[1]).to_ascii_lowercase(),
+                value: html_escape::decode_html_entities(&String::from_utf8_lossy(value.as_bytes())).into_owned(),
+                range: offset + value.start()..offset + value.end(),
+            })
+        })
+        .collect()
+}
+
+pub fn is_html(response: &FlowResponse) -> bool {
+    match response.header("content-type") {
+        Some(content_type) => {
+            let content_type = content_type.to_ascii_lowercase();
+            content_type.contains("text/html") || content_type.contains("xhtml")
+        }
+        None => response.body.trim_ascii_start().starts_with(b"<"),
+    }
+}
+
+fn refresh_target(base: &Url, value: &str) -> Option<Url> {
+    REFRESH_URL.captures(value).and_then(|captures| resolve(base, &captures[1]))
+}
+
+fn meta_refresh(base: &Url, attributes: &str) -> Option<Url> {
+    let lower = attributes.to_ascii_lowercase();
+    if !lower.contains("http-equiv") || !lower.contains("refresh") {
+        return None;
+    }
+    let content = META_CONTENT.captures(attributes)?;
+    let value = content.get(1).or_else(|| content.get(2))?.as_str();
+    refresh_target(base, &html_escape::decode_html_entities(value))
+}
@@ -0,0 +1,3 @@
+pub mod links;
+pub mod scripts;
+pub mod tree;
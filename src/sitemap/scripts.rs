@@ -0,0 +1,145 @@
+use std::ops::Range;
+use std::sync::LazyLock;
+use regex::bytes::Regex;
+use serde::Serialize;
+use url::Url;
+
+use crate::flow::model::FlowResponse;
+use crate::sitemap::links::resolve;
+
+// Contents of string literals; escapes and line breaks end the match, so
+// only plain strings are considered
+static STRING_LITERAL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#""([^"\\\s<>]{2,500})"|'([^'\\\s<>]{2,500})'|`([^`\\\s<>]{2,500})`"#).unwrap()
+});
+
+static GRAPHQL_OPERATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b(query|mutation|subscription)\s+([A-Za-z_][A-Za-z0-9_]*)\s*[({]").unwrap());
+
+// Relative paths are only taken when they look like a route rather than prose or a MIME type
+static RELATIVE_ROUTE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"(?i)^(?:(?:api|rest|graphql|v\d+)/[\w\-./]*|[\w\-]+(?:/[\w\-]+)*\.(?:php|aspx?|jsp|json|action|do|cgi|pl|html?|xml))(?:\?.*)?$",
+    )
+    .unwrap()
+});
+
+const MIME_PREFIXES: &[&str] = &["application/", "text/", "image/", "audio/", "video/", "font/", "multipart/", "model/"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointKind {
+    // Absolute or protocol-relative URL
+    Url,
+    // Root-relative or relative route
+    Path,
+    // Named GraphQL query, mutation or subscription
+    Graphql,
+}
+
+// Something a script or JSON document refers to, located by byte range in the body
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Endpoint {
+    pub kind: EndpointKind,
+    // The URL or path as written, cut at the first template placeholder;
+    // `query GetUser` style for GraphQL operations
+    pub value: String,
+    #[serde(skip)]
+    pub range: Range<usize>,
+}
+
+impl Endpoint {
+    // The URL this endpoint most likely stands for. Relative routes in a
+    // bundle are relative to the page, not the script, so they are resolved
+    // against the origin root.
+    pub fn url(&self, base: &Url) -> Option<Url> {
+        match self.kind {
+            EndpointKind::Graphql => None,
+            EndpointKind::Url => resolve(base, &self.value),
+            EndpointKind::Path if self.value.starts_with('/') => resolve(base, &self.value),
+            EndpointKind::Path => resolve(&base.join("/").ok()?, &self.value),
+        }
+    }
+}
+
+// JavaScript and JSON responses, by content type or, without one, by extension
+pub fn is_script(url: &Url, response: &FlowResponse) -> bool {
+    match response.header("content-type") {
+        Some(content_type) => {
+            let content_type = content_type.to_ascii_lowercase();
+            content_type.contains("javascript") || content_type.contains("ecmascript") || content_type.contains("json")
+        }
+        None => [".js", ".mjs", ".json", ".map"].iter().any(|extension| url.path().ends_with(extension)),
+    }
+}
+
+// URLs, API routes and GraphQL operations in a script or JSON document,
+// in order of appearance and without duplicates
+pub fn extract_endpoints(body: &[u8]) -> Vec<Endpoint> {
+    let mut endpoints: Vec<Endpoint> = Vec::new();
+
+    for captures in STRING_LITERAL.captures_iter(body) {
+        let Some(literal) = captures.get(1).or_else(|| captures.get(2)).or_else(|| captures.get(3)) else {
+            continue;
+        };
+        let Ok(text) = std::str::from_utf8(literal.as_bytes()) else {
+            continue;
+        };
+        // Template literals only contribute the part before the first placeholder
+        let text = text.split("${").next().unwrap_or_default();
+        let Some(kind) = endpoint_kind(text) else {
+            continue;
+        };
+        if endpoints.iter().any(|endpoint| endpoint.value == text) {
+            continue;
+        }
+        endpoints.push(Endpoint {
+            kind,
+            value: text.to_string(),
+            range: literal.start()..literal.start() + text.len(),
+        });
+    }
+
+    for captures in GRAPHQL_OPERATION.captures_iter(body) {
+        let value = format!(
+            "{} {}",
+            String::from_utf8_lossy(&captures[1]),
+            String::from_utf8_lossy(&captures[2])
+        );
+        if endpoints.iter().any(|endpoint| endpoint.value == value) {
+            continue;
+        }
+        endpoints.push(Endpoint {
+            kind: EndpointKind::Graphql,
+            value,
+            range: captures.get(0).unwrap().start()..captures.get(2).unwrap().end(),
+        });
+    }
+
+    endpoints.sort_by_key(|endpoint| endpoint.range.start);
+    endpoints
+}
+
+fn endpoint_kind(text: &str) -> Option<EndpointKind> {
+    let lower = text.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return (text.len() > 8).then_some(EndpointKind::Url);
+    }
+    if let Some(rest) = text.strip_prefix("//") {
+        let host = rest.split('/').next().unwrap_or_default();
+        return (host.contains('.') && host.bytes().all(|b| b.is_ascii_alphanumeric() || b"-.:".contains(&b)))
+            .then_some(EndpointKind::Url);
+    }
+    // Skip regular expressions, selectors and the like
+    if text.contains(['^', '|', '(', ')', '*', '{', '}', '[', ']', ',']) {
+        return None;
+    }
+    if let Some(rest) = text.strip_prefix('/') {
+        let starts_route = rest.bytes().next().is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_');
+        return (starts_route && rest.len() > 1).then_some(EndpointKind::Path);
+    }
+    if MIME_PREFIXES.iter().any(|prefix| lower.starts_with(prefix)) {
+        return None;
+    }
+    RELATIVE_ROUTE.is_match(text).then_some(EndpointKind::Path)
+}
@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::flow::model::{Flow, FlowId, FlowRequest};
+use crate::project::store::Project;
+use crate::sitemap::links::{extract_links, request_url};
+use crate::sitemap::scripts::{extract_endpoints, is_script};
+
+// Example values kept per parameter
+const MAX_SAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamLocation {
+    Query,
+    // Form fields and top-level JSON members
+    Body,
+}
+
+impl ParamLocation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Body => "body",
+        }
+    }
+}
+
+// Shape of a parameter value, used to spot IDs, flags and other interesting inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    Empty,
+    Boolean,
+    Integer,
+    Decimal,
+    Uuid,
+    Email,
+    Url,
+    Hex,
+    Object,
+    Array,
+    String,
+}
+
+impl ValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Decimal => "decimal",
+            Self::Uuid => "uuid",
+            Self::Email => "email",
+            Self::Url => "url",
+            Self::Hex => "hex",
+            Self::Object => "object",
+            Self::Array => "array",
+            Self::String => "string",
+        }
+    }
+
+    pub fn classify(value: &str) -> Self {
+        let digits = value.strip_prefix('-').unwrap_or(value);
+
+        if value.is_empty() {
+            Self::Empty
+        } else if matches!(value.to_ascii_lowercase().as_str(), "true" | "false") {
+            Self::Boolean
+        } else if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            Self::Integer
+        } else if digits.split_once('.').is_some_and(|(whole, fraction)| {
+            !whole.is_empty()
+                && !fraction.is_empty()
+                && whole.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+        }) {
+            Self::Decimal
+        } else if is_uuid(value) {
+            Self::Uuid
+        } else if is_email(value) {
+            Self::Email
+        } else if value.starts_with("http://") || value.starts_with("https://") {
+            Self::Url
+        } else if value.len() >= 8
+            && value.len().is_multiple_of(2)
+            && value.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            Self::Hex
+        } else {
+            Self::String
+        }
+    }
+
+    fn of_json(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Empty,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(number) if number.is_i64() || number.is_u64() => Self::Integer,
+            Value::Number(_) => Self::Decimal,
+            Value::String(text) => Self::classify(text),
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Parameter {
+    pub name: String,
+    pub location: ParamLocation,
+    pub types: BTreeSet<ValueType>,
+    // First few distinct values seen
+    pub samples: Vec<String>,
+}
+
+// A path segment, or the root of an origin. Intermediate nodes that were
+// neither requested nor linked to exist only to hold their children.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Node {
+    // A flow for this path went through the proxy
+    pub requested: bool,
+    // Some response linked to this path
+    pub referenced: bool,
+    pub methods: BTreeSet<String>,
+    pub statuses: BTreeSet<u16>,
+    pub flow_ids: Vec<FlowId>,
+    pub parameters: Vec<Parameter>,
+    pub children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    pub fn parameter(&self, location: ParamLocation, name: &str) -> Option<&Parameter> {
+        self.parameters
+            .iter()
+            .find(|parameter| parameter.location == location && parameter.name == name)
+    }
+
+    fn record_parameter(&mut self, location: ParamLocation, name: &str, value_type: ValueType, sample: String) {
+        let index = match self
+            .parameters
+            .iter()
+            .position(|parameter| parameter.location == location && parameter.name == name)
+        {
+            Some(index) => index,
+            None => {
+                self.parameters.push(Parameter {
+                    name: name.to_string(),
+                    location,
+                    types: BTreeSet::new(),
+                    samples: Vec::new(),
+                });
+                self.parameters.len() - 1
+            }
+        };
+
+        let parameter = &mut self.parameters[index];
+        parameter.types.insert(value_type);
+        if parameter.samples.len() < MAX_SAMPLES && !parameter.samples.contains(&sample) {
+            parameter.samples.push(sample);
+        }
+    }
+
+    fn record_query(&mut self, url: &Url) {
+        for (name, value) in url.query_pairs() {
+            self.record_parameter(ParamLocation::Query, &name, ValueType::classify(&value), value.into_owned());
+        }
+    }
+
+    fn record_body(&mut self, request: &FlowRequest) {
+        let body = request.decoded_body();
+        let body = body.decoded_or_raw();
+        if body.is_empty() {
+            return;
+        }
+        let content_type = request.header("content-type").unwrap_or("").to_ascii_lowercase();
+
+        if content_type.contains("x-www-form-urlencoded") {
+            for (name, value) in url::form_urlencoded::parse(body) {
+                self.record_parameter(ParamLocation::Body, &name, ValueType::classify(&value), value.into_owned());
+            }
+        } else if content_type.contains("json")
+            && let Ok(Value::Object(members)) = serde_json::from_slice::<Value>(body)
+        {
+            for (name, value) in &members {
+                let sample = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                self.record_parameter(ParamLocation::Body, name, ValueType::of_json(value), sample);
+            }
+        }
+    }
+}
+
+// Hierarchy of everything seen through the proxy: origin, then one level per
+// path segment. Query parameters are attached to the node for their path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SiteMap {
+    origins: BTreeMap<String, Node>,
+}
+
+impl SiteMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Build a site map from every flow stored in a project
+    pub fn from_project(project: &Project) -> Result<Self> {
+        let mut site_map = Self::new();
+        for summary in project.list_flows()? {
+            let flow = project
+                .load_flow(summary.id)?
+                .with_context(|| format!("Flow {} disappeared while reading the project", summary.id))?;
+            site_map.add_flow(&flow);
+        }
+        Ok(site_map)
+    }
+
+    // Record the request of a flow and every URL its response links to
+    pub fn add_flow(&mut self, flow: &Flow) {
+        let Some(url) = request_url(&flow.request) else {
+            return;
+        };
+
+        let node = self.node_mut(&url);
+        node.requested = true;
+        node.methods.insert(flow.request.method.to_ascii_uppercase());
+        if flow.id != 0 && !node.flow_ids.contains(&flow.id) {
+            node.flow_ids.push(flow.id);
+        }
+        node.record_query(&url);
+        node.record_body(&flow.request);
+
+        if let Some(response) = &flow.response {
+            node.statuses.insert(response.status);
+            for link in extract_links(&url, response) {
+                self.add_reference(&link.url);
+            }
+            if is_script(&url, response) {
+                for endpoint in extract_endpoints(response.decoded_body().text().as_bytes()) {
+                    if let Some(endpoint) = endpoint.url(&url) {
+                        self.add_reference(&endpoint);
+                    }
+                }
+            }
+        }
+    }
+
+    // Record a URL that was linked to without necessarily being requested
+    pub fn add_reference(&mut self, url: &Url) {
+        let node = self.node_mut(url);
+        node.referenced = true;
+        node.record_query(url);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.origins.is_empty()
+    }
+
+    // Origins such as `https://example.com` or `http://localhost:8080`
+    pub fn origins(&self) -> impl Iterator<Item = (&str, &Node)> {
+        self.origins.iter().map(|(origin, node)| (origin.as_str(), node))
+    }
+
+    // Look up the node for a URL; the query string is ignored
+    pub fn node(&self, url: &str) -> Option<&Node> {
+        let url = Url::parse(url).ok()?;
+        let mut node = self.origins.get(&origin_key(&url))?;
+        for segment in path_segments(&url) {
+            node = node.children.get(segment)?;
+        }
+        Some(node)
+    }
+
+    // Every node in depth-first order with the URL it stands for
+    pub fn entries(&self) -> Vec<(String, &Node)> {
+        let mut entries = Vec::new();
+        for (origin, node) in &self.origins {
+            entries.push((format!("{}/", origin), node));
+            collect(origin, node, &mut entries);
+        }
+        entries
+    }
+
+    // URLs that responses linked to but no flow has requested yet
+    pub fn unrequested(&self) -> Vec<String> {
+        self.entries()
+            .into_iter()
+            .filter(|(_, node)| node.referenced && !node.requested)
+            .map(|(url, _)| url)
+            .collect()
+    }
+
+    // Keep only the given origin, e.g. to export the map of a single target
+    pub fn retain_origin(&mut self, origin: &str) {
+        let origin = origin.trim_end_matches('/');
+        self.origins.retain(|key, _| key == origin || key.split_once("://").is_some_and(|(_, host)| host == origin));
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize site map")
+    }
+
+    // Indented tree with methods, statuses and parameters for each node
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for (origin, node) in &self.origins {
+            out.push_str(origin);
+            out.push_str(&annotation(node));
+            out.push('\n');
+            render_children(node, "", &mut out);
+        }
+        out
+    }
+
+    fn node_mut(&mut self, url: &Url) -> &mut Node {
+        let mut node = self.origins.entry(origin_key(url)).or_default();
+        for segment in path_segments(url) {
+            node = node.children.entry(segment.to_string()).or_default();
+        }
+        node
+    }
+}
+
+fn origin_key(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+// Non-empty path segments; `/a/b/` and `/a/b` share a node
+fn path_segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn collect<'a>(parent: &str, node: &'a Node, entries: &mut Vec<(String, &'a Node)>) {
+    for (segment, child) in &node.children {
+        let url = format!("{}/{}", parent, segment);
+        entries.push((url.clone(), child));
+        collect(&url, child, entries);
+    }
+}
+
+fn render_children(node: &Node, prefix: &str, out: &mut String) {
+    let count = node.children.len();
+    for (index, (segment, child)) in node.children.iter().enumerate() {
+        let last = index + 1 == count;
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        out.push_str(segment);
+        out.push_str(&annotation(child));
+        out.push('\n');
+        let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render_children(child, &child_prefix, out);
+    }
+}
+
+fn annotation(node: &Node) -> String {
+    let mut parts = Vec::new();
+
+    if node.requested {
+        parts.push(node.methods.iter().cloned().collect::<Vec<_>>().join(","));
+        if !node.statuses.is_empty() {
+            parts.push(node.statuses.iter().map(|status| status.to_string()).collect::<Vec<_>>().join(","));
+        }
+    } else if node.referenced {
+        parts.push("(not requested)".to_string());
+    }
+
+    for location in [ParamLocation::Query, ParamLocation::Body] {
+        let parameters: Vec<String> = node
+            .parameters
+            .iter()
+            .filter(|parameter| parameter.location == location)
+            .map(|parameter| {
+                let types: Vec<&str> = parameter.types.iter().map(|value_type| value_type.as_str()).collect();
+                format!("{}:{}", parameter.name, types.join("|"))
+            })
+            .collect();
+        if !parameters.is_empty() {
+            parts.push(format!("{} {}", location.as_str(), parameters.join(" ")));
+        }
+    }
+
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!("  [{}]", parts.join("; "))
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn is_email(value: &str) -> bool {
+    value.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !local.contains(char::is_whitespace)
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !domain.contains(['@', ' '])
+    })
+}
@@ -0,0 +1,467 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
+use anyhow::{bail, Result};
+use futures::stream::{self, StreamExt};
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::Serialize;
+use url::Url;
+
+use crate::flow::model::{Flow, FlowId, FlowRequest};
+use crate::project::scope::Scope;
+use crate::project::store::Project;
+use crate::proxy::forward::Forwarder;
+use crate::scanner::active::RateLimiter;
+use crate::sitemap::links::{extract_links, is_html, LinkKind};
+use crate::sitemap::tree::SiteMap;
+use crate::spider::forms::{parse_forms, FormDefaults};
+
+// Tag on flows fetched by the spider
+pub const SPIDER_TAG: &str = "spider";
+
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; ferrum-spider)";
+
+// Files the spider does not fetch since they cannot contain links
+const STATIC_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "svg", "ico", "bmp", "css", "woff", "woff2", "ttf", "otf", "eot", "mp3",
+    "mp4", "webm", "avi", "mov", "pdf", "zip", "gz", "tar", "exe", "dmg",
+];
+
+static SITEMAP_LOC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<loc>\s*(.*?)\s*</loc>").unwrap());
+
+// One request the spider made
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Visit {
+    pub method: String,
+    pub url: String,
+    pub depth: usize,
+    pub status: Option<u16>,
+    // Zero unless a project is attached
+    pub flow_id: FlowId,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CrawlReport {
+    pub visits: Vec<Visit>,
+    pub forms_submitted: usize,
+    // Discovered URLs that were not fetched because of the scope
+    pub out_of_scope: usize,
+    // Discovered URLs left over when the depth or page limit was reached
+    pub not_fetched: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Pending {
+    request: FlowRequest,
+    depth: usize,
+    // Submits a form, counted in the report once it has been sent
+    form: bool,
+}
+
+impl Pending {
+    fn new(request: FlowRequest, depth: usize) -> Self {
+        Self { request, depth, form: false }
+    }
+}
+
+// Crawls from seed URLs through a forwarder, following links, scripts,
+// redirects, robots.txt and sitemap.xml, and optionally submitting forms
+pub struct Spider {
+    forwarder: Forwarder,
+    scope: Scope,
+    max_depth: usize,
+    max_requests: usize,
+    concurrency: usize,
+    limiter: Option<Arc<RateLimiter>>,
+    headers: Vec<(String, String)>,
+    form_defaults: Option<FormDefaults>,
+    respect_robots: bool,
+    project: Option<Arc<Project>>,
+    site_map: Option<Arc<Mutex<SiteMap>>>,
+}
+
+impl Spider {
+    pub fn new(forwarder: Forwarder) -> Self {
+        Self {
+            forwarder,
+            scope: Scope::new(),
+            max_depth: 3,
+            max_requests: 500,
+            concurrency: 4,
+            limiter: None,
+            headers: Vec::new(),
+            form_defaults: None,
+            respect_robots: false,
+            project: None,
+            site_map: None,
+        }
+    }
+
+    // Where the spider may go; without include rules it stays on the seed hosts
+    pub fn with_scope(mut self, scope: Scope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    // Link hops from the seeds; seeds are depth 0
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn with_max_requests(mut self, requests: usize) -> Self {
+        self.max_requests = requests;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_rate_limit(mut self, per_second: f64) -> Self {
+        self.limiter = (per_second > 0.0).then(|| Arc::new(RateLimiter::new(per_second)));
+        self
+    }
+
+    // Extra header on every request, e.g. a session cookie
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Submit discovered forms, filling fields from the defaults
+    pub fn with_form_submission(mut self, defaults: FormDefaults) -> Self {
+        self.form_defaults = Some(defaults);
+        self
+    }
+
+    // Skip paths that robots.txt disallows for every user agent
+    pub fn with_respect_robots(mut self, respect: bool) -> Self {
+        self.respect_robots = respect;
+        self
+    }
+
+    // Record every fetched page into the project, tagged `spider`
+    pub fn with_project(mut self, project: Arc<Project>) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    // Add every fetched page (and the links it contains) to a shared site map
+    pub fn with_site_map(mut self, site_map: Arc<Mutex<SiteMap>>) -> Self {
+        self.site_map = Some(site_map);
+        self
+    }
+
+    // Crawl breadth first, one depth level at a time
+    pub async fn crawl(&self, seeds: &[Url]) -> Result<CrawlReport> {
+        if seeds.is_empty() {
+            bail!("No seed URLs to crawl");
+        }
+        let mut scope = self.scope.clone();
+        if scope.include.is_empty() {
+            let hosts: Vec<String> = seeds.iter().filter_map(|seed| seed.host_str().map(str::to_string)).collect();
+            scope.include = Scope::from_hosts(&hosts).include;
+        }
+
+        let mut report = CrawlReport::default();
+        let mut seen = HashSet::new();
+        let mut origins = HashSet::new();
+        let mut robots: HashMap<String, Robots> = HashMap::new();
+        let mut level: Vec<Pending> = Vec::new();
+
+        for seed in seeds {
+            self.discover(Pending::new(self.get(seed), 0), &scope, &mut seen, &mut origins, &mut level, &mut report);
+        }
+
+        for depth in 0..=self.max_depth {
+            if level.is_empty() {
+                break;
+            }
+
+            // robots.txt files are fetched first so their rules apply to the rest of the level
+            let (robots_files, rest): (Vec<Pending>, Vec<Pending>) =
+                level.drain(..).partition(|pending| pending.request.uri.ends_with("/robots.txt"));
+            let mut next = Vec::new();
+            for mut batch in [robots_files, rest] {
+                if self.respect_robots {
+                    batch.retain(|pending| robots_allow(&robots, &pending.request.uri));
+                }
+                let budget = self.max_requests.saturating_sub(report.visits.len());
+                if batch.len() > budget {
+                    report.not_fetched += batch.len() - budget;
+                    batch.truncate(budget);
+                }
+                if batch.is_empty() {
+                    continue;
+                }
+
+                debug!("Spider depth {}: fetching {} URL(s)", depth, batch.len());
+                let fetched: Vec<(Pending, Result<Flow>)> = stream::iter(batch)
+                    .map(|pending| async move {
+                        let result = self.fetch(pending.request.clone()).await;
+                        (pending, result)
+                    })
+                    .buffered(self.concurrency)
+                    .collect()
+                    .await;
+
+                for (pending, result) in fetched {
+                    let mut visit = Visit {
+                        method: pending.request.method.clone(),
+                        url: pending.request.uri.clone(),
+                        depth: pending.depth,
+                        status: None,
+                        flow_id: 0,
+                        error: None,
+                    };
+                    let flow = match result {
+                        Ok(flow) => flow,
+                        Err(e) => {
+                            warn!("Failed to fetch {}: {:#}", pending.request.uri, e);
+                            visit.error = Some(format!("{:#}", e));
+                            report.visits.push(visit);
+                            continue;
+                        }
+                    };
+                    visit.status = flow.response.as_ref().map(|response| response.status);
+                    visit.flow_id = flow.id;
+                    report.visits.push(visit);
+                    if pending.form {
+                        report.forms_submitted += 1;
+                    }
+
+                    for found in self.follow(&flow, depth + 1, &mut robots) {
+                        self.discover(found, &scope, &mut seen, &mut origins, &mut next, &mut report);
+                    }
+                }
+            }
+
+            if depth == self.max_depth {
+                report.not_fetched += next.len();
+            } else {
+                level = next;
+            }
+        }
+
+        info!(
+            "Spider made {} request(s), submitted {} form(s)",
+            report.visits.len(),
+            report.forms_submitted
+        );
+        Ok(report)
+    }
+
+    fn get(&self, url: &Url) -> FlowRequest {
+        let mut url = url.clone();
+        url.set_fragment(None);
+        FlowRequest::new("GET", url.as_str())
+    }
+
+    // Queue a request unless it was seen before or is out of scope. The first
+    // time an origin comes up, its robots.txt and sitemap.xml are queued too.
+    fn discover(
+        &self,
+        pending: Pending,
+        scope: &Scope,
+        seen: &mut HashSet<String>,
+        origins: &mut HashSet<String>,
+        queue: &mut Vec<Pending>,
+        report: &mut CrawlReport,
+    ) {
+        let Ok(url) = Url::parse(&pending.request.uri) else {
+            return;
+        };
+        if url.host().is_none() {
+            return;
+        }
+        if !scope.contains_url(&url) {
+            if seen.insert(format!("out {}", url)) {
+                report.out_of_scope += 1;
+            }
+            return;
+        }
+        let extension = url.path().rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+        if extension.is_some_and(|extension| STATIC_EXTENSIONS.contains(&extension.as_str())) {
+            return;
+        }
+
+        let origin = url.origin().ascii_serialization();
+        if origins.insert(origin.clone()) {
+            for path in ["/robots.txt", "/sitemap.xml"] {
+                if let Ok(url) = Url::parse(&format!("{}{}", origin, path)) {
+                    let request = self.get(&url);
+                    if seen.insert(request_key(&request)) {
+                        queue.push(Pending::new(request, pending.depth));
+                    }
+                }
+            }
+        }
+
+        if seen.insert(request_key(&pending.request)) {
+            queue.push(pending);
+        }
+    }
+
+    async fn fetch(&self, mut request: FlowRequest) -> Result<Flow> {
+        if request.header("user-agent").is_none() {
+            request.set_header("User-Agent", USER_AGENT);
+        }
+        for (name, value) in &self.headers {
+            request.set_header(name, value);
+        }
+        if let Some(limiter) = &self.limiter {
+            limiter.wait().await;
+        }
+
+        let mut flow = self.forwarder.forward(request).await?;
+        flow.tags.push(SPIDER_TAG.to_string());
+        if let Some(project) = &self.project {
+            let project = Arc::clone(project);
+            let stored = flow.clone();
+            flow.id = tokio::task::spawn_blocking(move || project.save_flow(&stored)).await??;
+        }
+        if let Some(site_map) = &self.site_map {
+            site_map.lock().unwrap().add_flow(&flow);
+        }
+        debug!("Spider fetched {} {}", flow.request.uri, flow.response.as_ref().map_or(0, |response| response.status));
+        Ok(flow)
+    }
+
+    // Requests for everything a fetched response points at, queued at `depth`
+    fn follow(&self, flow: &Flow, depth: usize, robots: &mut HashMap<String, Robots>) -> Vec<Pending> {
+        let (Some(response), Ok(url)) = (&flow.response, Url::parse(&flow.request.uri)) else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+
+        if url.path() == "/robots.txt" && response.status == 200 {
+            let parsed = Robots::parse(&url, &String::from_utf8_lossy(&response.body));
+            found.extend(parsed.urls.iter().map(|url| Pending::new(self.get(url), depth)));
+            robots.insert(url.origin().ascii_serialization(), parsed);
+            return found;
+        }
+
+        let body = String::from_utf8_lossy(&response.body);
+        if response.status == 200 && (body.contains("<urlset") || body.contains("<sitemapindex")) {
+            found.extend(parse_sitemap(&url, &body).iter().map(|loc| Pending::new(self.get(loc), depth)));
+            return found;
+        }
+
+        // Form actions are only followed by submitting the form
+        for link in extract_links(&url, response) {
+            if link.kind != LinkKind::Form {
+                found.push(Pending::new(self.get(&link.url), depth));
+            }
+        }
+
+        if let Some(defaults) = &self.form_defaults
+            && is_html(response)
+        {
+            for form in parse_forms(&url, &body) {
+                found.push(Pending {
+                    request: form.to_request(defaults),
+                    depth,
+                    form: true,
+                });
+            }
+        }
+
+        found
+    }
+}
+
+// The parts of a robots.txt the spider uses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    // URLs named by Allow, Disallow and Sitemap lines, for discovery
+    pub urls: Vec<Url>,
+    // Allow (true) and Disallow (false) rules for `User-agent: *`
+    pub rules: Vec<(bool, String)>,
+}
+
+impl Robots {
+    pub fn parse(base: &Url, robots: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut applies = false;
+
+        for line in robots.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            let field = field.trim().to_ascii_lowercase();
+            match field.as_str() {
+                "user-agent" => applies = value == "*",
+                "sitemap" => parsed.urls.extend(base.join(value).ok()),
+                "allow" | "disallow" if !value.is_empty() => {
+                    // Wildcard rules only name a path up to the first wildcard
+                    let path = value.split(['*', '$']).next().unwrap_or_default();
+                    if path.len() > 1 {
+                        parsed.urls.extend(base.join(path).ok());
+                    }
+                    if applies {
+                        parsed.rules.push((field == "allow", value.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        parsed
+    }
+
+    // The longest matching rule wins, and Allow wins a tie
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, rule)| robots_match(rule, path))
+            .max_by_key(|(allow, rule)| (rule.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+// Matches a path against a rule with `*` wildcards and an optional `$` anchor
+fn robots_match(rule: &str, path: &str) -> bool {
+    let (rule, anchored) = rule.strip_suffix('$').map_or((rule, false), |rule| (rule, true));
+    let mut parts = rule.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        if anchored && index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+// `<loc>` entries of a sitemap or sitemap index
+pub fn parse_sitemap(base: &Url, xml: &str) -> Vec<Url> {
+    SITEMAP_LOC
+        .captures_iter(xml)
+        .filter_map(|captures| base.join(&html_escape::decode_html_entities(&captures[1])).ok())
+        .collect()
+}
+
+// Requests are only made once per method, URL and body
+fn request_key(request: &FlowRequest) -> String {
+    format!("{} {} {}", request.method, request.uri, String::from_utf8_lossy(&request.body))
+}
+
+fn robots_allow(robots: &HashMap<String, Robots>, uri: &str) -> bool {
+    let Ok(url) = Url::parse(uri) else {
+        return true;
+    };
+    robots
+        .get(&url.origin().ascii_serialization())
+        .is_none_or(|robots| url.path() == "/robots.txt" || robots.allows(url.path()))
+}
@@ -0,0 +1,225 @@
+use std::sync::LazyLock;
+use regex::Regex;
+use url::Url;
+
+use crate::flow::model::FlowRequest;
+use crate::sitemap::links::resolve;
+
+static FORM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<form\b([^>]*)>(.*?)(?:</form\s*>|$)").unwrap());
+
+// Fields, with the text up to the closing tag for textarea and select
+static FIELD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?is)<(input|button)\b([^>]*)>|<(textarea|select)\b([^>]*)>(.*?)</(?:textarea|select)\s*>").unwrap()
+});
+
+static OPTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<option\b([^>]*)>([^<]*)").unwrap());
+
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)([a-zA-Z_:][-a-zA-Z0-9_:.]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#).unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+    pub name: String,
+    // Lowercased input type; `textarea` and `select` for those elements
+    pub kind: String,
+    // Value from the markup, if any
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+    pub action: Url,
+    // Uppercased; GET unless the form says otherwise
+    pub method: String,
+    pub enctype: String,
+    pub fields: Vec<FormField>,
+}
+
+// Values filled into form fields when the spider submits a form. Fields are
+// matched by name (case-insensitive substring) first, then by input type.
+#[derive(Debug, Clone)]
+pub struct FormDefaults {
+    by_name: Vec<(String, String)>,
+    fallback: String,
+}
+
+impl Default for FormDefaults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormDefaults {
+    pub fn new() -> Self {
+        Self {
+            by_name: Vec::new(),
+            fallback: "ferrum".to_string(),
+        }
+    }
+
+    // Use `value` for every field whose name contains `name`
+    pub fn with_value(mut self, name: &str, value: &str) -> Self {
+        self.by_name.push((name.to_ascii_lowercase(), value.to_string()));
+        self
+    }
+
+    // Value for a text-like field nothing more specific applies to
+    pub fn with_fallback(mut self, value: &str) -> Self {
+        self.fallback = value.to_string();
+        self
+    }
+
+    // The value submitted for a field, or None to leave it out
+    pub fn value_for(&self, field: &FormField) -> Option<String> {
+        let name = field.name.to_ascii_lowercase();
+        if let Some((_, value)) = self.by_name.iter().find(|(pattern, _)| name.contains(pattern.as_str())) {
+            return Some(value.clone());
+        }
+
+        let preset = |value: &str| Some(field.value.clone().filter(|value| !value.is_empty()).unwrap_or(value.to_string()));
+        match field.kind.as_str() {
+            "hidden" | "select" | "radio" | "submit" | "button" => field.value.clone().or(Some(String::new())),
+            "checkbox" => preset("on"),
+            "file" | "image" | "reset" => None,
+            "email" => preset("ferrum@example.com"),
+            "password" => preset("Ferrum-Spider-1"),
+            "number" | "range" => preset("1"),
+            "tel" => preset("5555555555"),
+            "url" => preset("https://example.com/"),
+            "date" => preset("2024-01-01"),
+            _ => preset(&self.fallback),
+        }
+    }
+}
+
+impl Form {
+    // The request that submitting this form with the given defaults would send
+    pub fn to_request(&self, defaults: &FormDefaults) -> FlowRequest {
+        let mut seen_radio = Vec::new();
+        let mut submitted = false;
+        let mut pairs = url::form_urlencoded::Serializer::new(String::new());
+
+        for field in &self.fields {
+            // Only the first radio button of a group and the first submit button are sent
+            match field.kind.as_str() {
+                "radio" if seen_radio.contains(&field.name) => continue,
+                "radio" => seen_radio.push(field.name.clone()),
+                "submit" | "button" if submitted => continue,
+                "submit" | "button" => submitted = true,
+                _ => {}
+            }
+            if let Some(value) = defaults.value_for(field) {
+                pairs.append_pair(&field.name, &value);
+            }
+        }
+        let encoded = pairs.finish();
+
+        if self.method == "GET" {
+            let mut action = self.action.clone();
+            action.set_query((!encoded.is_empty()).then_some(encoded.as_str()));
+            return FlowRequest::new("GET", action.as_str());
+        }
+
+        let mut request = FlowRequest::new(&self.method, self.action.as_str());
+        request.set_header("Content-Type", "application/x-www-form-urlencoded");
+        request.set_header("Content-Length", &encoded.len().to_string());
+        request.body = encoded.into_bytes();
+        request
+    }
+}
+
+// Every form in an HTML document whose action resolves to an http(s) URL
+pub fn parse_forms(base: &Url, html: &str) -> Vec<Form> {
+    FORM.captures_iter(html)
+        .filter_map(|form| {
+            let attributes = attributes(&form[1]);
+            let action = match attribute(&attributes, "action") {
+                Some(action) if !action.trim().is_empty() => resolve(base, action)?,
+                _ => {
+                    let mut url = base.clone();
+                    url.set_fragment(None);
+                    url
+                }
+            };
+            let method = attribute(&attributes, "method").unwrap_or("get").to_ascii_uppercase();
+
+            Some(Form {
+                action,
+                method: if method == "POST" { method } else { "GET".to_string() },
+                enctype: attribute(&attributes, "enctype")
+                    .unwrap_or("application/x-www-form-urlencoded")
+                    .to_ascii_lowercase(),
+                fields: fields(&form[2]),
+            })
+        })
+        .collect()
+}
+
+fn fields(body: &str) -> Vec<FormField> {
+    FIELD
+        .captures_iter(body)
+        .filter_map(|captures| {
+            let (element, attribute_text) = match (captures.get(1), captures.get(2)) {
+                (Some(element), Some(text)) => (element.as_str().to_ascii_lowercase(), text.as_str()),
+                _ => (captures[3].to_ascii_lowercase(), captures.get(4).map_or("", |text| text.as_str())),
+            };
+            let attributes = attributes(attribute_text);
+            let name = attribute(&attributes, "name")?.to_string();
+            if attributes.iter().any(|(key, _)| key == "disabled") {
+                return None;
+            }
+
+            let (kind, value) = match element.as_str() {
+                "input" => (
+                    attribute(&attributes, "type").unwrap_or("text").to_ascii_lowercase(),
+                    attribute(&attributes, "value").map(str::to_string),
+                ),
+                "button" => (
+                    attribute(&attributes, "type").unwrap_or("submit").to_ascii_lowercase(),
+                    attribute(&attributes, "value").map(str::to_string),
+                ),
+                "textarea" => (element, Some(decode(&captures[5]))),
+                _ => (element, select_value(&captures[5])),
+            };
+            Some(FormField { name, kind, value })
+        })
+        .collect()
+}
+
+// The selected option, or the first one
+fn select_value(options: &str) -> Option<String> {
+    let mut first = None;
+    for option in OPTION.captures_iter(options) {
+        let attributes = attributes(&option[1]);
+        let value = attribute(&attributes, "value").map_or_else(|| decode(option[2].trim()), str::to_string);
+        if attributes.iter().any(|(key, _)| key == "selected") {
+            return Some(value);
+        }
+        first.get_or_insert(value);
+    }
+    first
+}
+
+// Lowercased names and entity-decoded values; bare attributes have an empty value
+fn attributes(text: &str) -> Vec<(String, String)> {
+    ATTRIBUTE
+        .captures_iter(text)
+        .map(|captures| {
+            let value = captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .or_else(|| captures.get(4))
+                .map_or(String::new(), |value| decode(value.as_str()));
+            (captures[1].to_ascii_lowercase(), value)
+        })
+        .collect()
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+}
+
+fn decode(text: &str) -> String {
+    html_escape::decode_html_entities(text).into_owned()
+}
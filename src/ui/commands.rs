@@ -0,0 +1,1207 @@
+use anyhow::{anyhow, bail, Context, Result};
+use encoding_rs::UTF_8;
+use log::info;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::comparer::compare::{compare_flows, BodyDiff, CompareOptions, MessageDiff};
+use crate::comparer::diff::{ChangeKind, Granularity};
+use crate::curl::export::{to_curl, CurlOptions};
+use crate::decoder::smart::smart_decode;
+use crate::decoder::transform::{decode_chain, encode_chain, Transform};
+use crate::curl::import::{parse_curl, CurlCommand};
+use crate::filter::eval::Filter;
+use crate::flow::body::{Body, EncodingPolicy};
+use crate::flow::model::{Flow, FlowId, FlowRequest};
+use crate::flow::raw::{format_request, format_response, parse_request};
+use crate::har::export::write_har;
+use crate::har::import::import_har_file;
+use crate::intercept::external::ExternalInterceptor;
+use crate::intercept::plugin::{PluginConfig, PluginInterceptor};
+use crate::intercept::request::RequestInterceptor;
+use crate::intercept::response::ResponseInterceptor;
+use crate::intercept::script::ScriptInterceptor;
+use crate::intruder::attack::{describe_payloads, Attack};
+use crate::intruder::payload::PayloadSet;
+use crate::intruder::template::Template;
+use crate::jwt::attack::{crack, forge, variants, ClaimEdit, Signing};
+use crate::jwt::locate::{find_tokens, replace_token};
+use crate::jwt::token::Jwt;
+use crate::project::scope::Scope;
+use crate::project::store::{BodyPart, Project};
+use crate::proxy::forward::Forwarder;
+use crate::proxy::replay::replay_project_flows;
+use crate::proxy::upstream::{UpstreamClient, UpstreamConfig};
+use crate::repeater::session::Repeater;
+use crate::rules::map_remote::MapRemote;
+use crate::rules::replace::ReplaceRules;
+use crate::scanner::active::ActiveScanner;
+use crate::scanner::callback::CallbackListener;
+use crate::scanner::finding::{Finding, Severity};
+use crate::sitemap::links::request_url;
+use crate::scanner::passive::PassiveScanner;
+use crate::sequencer::analysis::analyze;
+use crate::sequencer::capture::collect_tokens;
+use crate::sitemap::tree::SiteMap;
+use crate::spider::crawl::Spider;
+use crate::spider::forms::FormDefaults;
+use crate::ui::cli::{
+    AuditArgs, CurlImportArgs, InterceptArgs, IntruderArgs, JwtAttackArgs, RepeatArgs, ScopeArgs, SequencerArgs, SpiderArgs,
+    UpstreamArgs,
+};
+
+// Number of body bytes shown when printing a single flow
+const BODY_PREVIEW_BYTES: usize = 4096;
+
+// `ferrum open`: list the flows of a project, or show one of them in full.
+// Compressed bodies are shown decoded unless `raw` asks for the bytes as sent.
+pub fn open_project(path: &Path, flow_id: Option<FlowId>, filter: Option<&Filter>, raw: bool) -> Result<()> {
+    let project = Project::open_read_only(path)?;
+
+    match flow_id {
+        Some(id) => print_flow(&project, id, raw),
+        None => print_flow_list(&project, filter),
+    }
+}
+
+fn print_flow_list(project: &Project, filter: Option<&Filter>) -> Result<()> {
+    let mut flows = project.list_flows()?;
+    if let Some(filter) = filter {
+        // Summaries leave out headers and bodies, so filtering needs each full flow
+        let mut kept = Vec::with_capacity(flows.len());
+        for summary in flows {
+            if project.load_flow(summary.id)?.is_some_and(|flow| filter.matches(&flow)) {
+                kept.push(summary);
+            }
+        }
+        flows = kept;
+    }
+
+    println!("{:>6}  {:<7} {:>6} {:>10} {:>8}  URI", "ID", "METHOD", "STATUS", "SIZE", "TIME");
+    for flow in &flows {
+        let status = flow
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let duration = flow
+            .duration_ms
+            .map(|ms| format!("{}ms", ms))
+            .unwrap_or_else(|| "-".to_string());
+        let tags = if flow.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", flow.tags.join(", "))
+        };
+
+        let replay = flow
+            .replay_of
+            .map(|id| format!("  (replay of {})", id))
+            .unwrap_or_default();
+
+        println!(
+            "{:>6}  {:<7} {:>6} {:>10} {:>8}  {}{}{}",
+            flow.id, flow.method, status, flow.response_body_size, duration, flow.uri, tags, replay
+        );
+    }
+    println!("{} flow(s)", flows.len());
+
+    Ok(())
+}
+
+// Bodies are left in the project and only read as far as they are shown
+fn print_flow(project: &Project, id: FlowId, raw: bool) -> Result<()> {
+    let Some(flow) = project.load_flow_metadata(id)? else {
+        bail!("Flow {} does not exist", id);
+    };
+
+    println!("{} {} {}", flow.request.method, flow.request.uri, flow.request.version);
+    for (name, value) in &flow.request.headers {
+        println!("{}: {}", name, value);
+    }
+    print_body(project, id, BodyPart::Request, &flow.request.headers, raw)?;
+
+    if let Some(response) = &flow.response {
+        println!();
+        println!("{} {}", response.version, response.status);
+        for (name, value) in &response.headers {
+            println!("{}: {}", name, value);
+        }
+        print_body(project, id, BodyPart::Response, &response.headers, raw)?;
+    }
+
+    if let Some(original_uri) = &flow.original_uri {
+        println!();
+        println!("Mapped from: {}", original_uri);
+    }
+    if let Some(tls) = &flow.tls {
+        println!();
+        println!("TLS: {:?}", tls);
+    }
+    if !flow.websocket_messages.is_empty() {
+        println!();
+        println!("{} WebSocket message(s)", flow.websocket_messages.len());
+    }
+    if !flow.tags.is_empty() {
+        println!("Tags: {}", flow.tags.join(", "));
+    }
+    if let Some(notes) = &flow.notes {
+        println!("Notes: {}", notes);
+    }
+
+    Ok(())
+}
+
+// A body decoded for reading when it is compressed or not UTF-8, otherwise as stored.
+// Only bodies that need decoding are read in full.
+fn print_body(project: &Project, id: FlowId, part: BodyPart, headers: &[(String, String)], raw: bool) -> Result<()> {
+    let size = project.body_size(id, part)?;
+    let unread = Body::new(headers, &[]);
+    let charset = unread.charset();
+    if raw || size == 0 || (!unread.is_encoded() && charset == UTF_8) {
+        return print_body_preview(project, id, part);
+    }
+
+    let raw_body = project.read_body(id, part, 0, size as usize)?;
+    let body = Body::new(headers, &raw_body);
+    if let Err(e) = body.decoded() {
+        println!();
+        println!("(Could not decode the body: {:#})", e);
+        return print_body_preview(project, id, part);
+    }
+
+    let text = body.text();
+    let shown = &text[..text.floor_char_boundary(BODY_PREVIEW_BYTES)];
+    println!();
+    println!("{}", shown);
+    if text.len() > shown.len() {
+        println!("... ({} of {} decoded bytes shown)", shown.len(), text.len());
+    }
+    let mut decoded_from = Vec::new();
+    if body.is_encoded() {
+        decoded_from.extend(body.content_encoding());
+    }
+    if charset != UTF_8 {
+        decoded_from.push(charset.name());
+    }
+    println!("({}, {} bytes as sent; --raw shows them)", decoded_from.join(", "), raw_body.len());
+
+    Ok(())
+}
+
+fn print_body_preview(project: &Project, id: FlowId, part: BodyPart) -> Result<()> {
+    let size = project.body_size(id, part)?;
+    if size == 0 {
+        return Ok(());
+    }
+
+    let preview = project.read_body(id, part, 0, BODY_PREVIEW_BYTES)?;
+    println!();
+    println!("{}", String::from_utf8_lossy(&preview));
+    if size > preview.len() as u64 {
+        println!("... ({} of {} bytes shown)", preview.len(), size);
+    }
+
+    Ok(())
+}
+
+// `ferrum export har`: write flows from a project as a HAR archive
+pub fn export_har(project_path: &Path, output: Option<&PathBuf>, ids: &[FlowId], filter: Option<&Filter>) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+    let mut flows = load_flows(&project, ids)?;
+    if let Some(filter) = filter {
+        flows.retain(|flow| filter.matches(flow));
+    }
+
+    match output {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            let mut writer = BufWriter::new(file);
+            write_har(&flows, &mut writer)?;
+            writer.flush()?;
+            eprintln!("Exported {} flow(s) to {}", flows.len(), path.display());
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut writer = stdout.lock();
+            write_har(&flows, &mut writer)?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Load the requested flows, or every flow in the project if none were given
+fn load_flows(project: &Project, ids: &[FlowId]) -> Result<Vec<Flow>> {
+    let ids: Vec<FlowId> = if ids.is_empty() {
+        project.list_flows()?.into_iter().map(|flow| flow.id).collect()
+    } else {
+        ids.to_vec()
+    };
+
+    ids.into_iter()
+        .map(|id| {
+            project
+                .load_flow(id)?
+                .with_context(|| format!("Flow {} does not exist", id))
+        })
+        .collect()
+}
+
+// `ferrum import har`: load HAR entries into a project as ordinary flows
+pub fn import_har(file: &Path, project_path: &Path) -> Result<()> {
+    let project = Project::open(project_path)?;
+    let ids = import_har_file(file, &project)?;
+
+    match (ids.first(), ids.last()) {
+        (Some(first), Some(last)) => {
+            println!("Imported {} flow(s) as IDs {}-{}", ids.len(), first, last)
+        }
+        _ => println!("No flows imported"),
+    }
+
+    Ok(())
+}
+
+// Build the forwarder for a command from its upstream and interceptor options, so
+// every tool sends traffic through the same chain as the proxy
+pub fn forwarder(upstream: &UpstreamArgs, intercept: &InterceptArgs) -> Result<Forwarder> {
+    let client = UpstreamClient::with_config(UpstreamConfig {
+        proxy: upstream.upstream_proxy.clone(),
+        accept_invalid_certs: upstream.insecure,
+    })?;
+    let mut forwarder = Forwarder::new().with_upstream(client);
+    if let Some(filter) = &intercept.intercept_filter {
+        forwarder = forwarder
+            .with_request_interceptor(RequestInterceptor::new().with_filter(filter.clone()))
+            .with_response_interceptor(ResponseInterceptor::new().with_filter(filter.clone()));
+    }
+
+    let encoding = if intercept.strip_encoding {
+        EncodingPolicy::Strip
+    } else {
+        EncodingPolicy::Reencode
+    };
+    if let Some(path) = &intercept.replace_rules {
+        let rules = ReplaceRules::load(path)?.with_encoding_policy(encoding);
+        info!("Loaded {} match-and-replace rule(s) from {}", rules.rules().len(), path.display());
+        forwarder = forwarder.with_replace_rules(rules);
+    }
+    if !intercept.scripts.is_empty() {
+        let mut scripts = ScriptInterceptor::new()
+            .with_max_operations(intercept.script_max_operations)
+            .with_encoding_policy(encoding);
+        for path in &intercept.scripts {
+            scripts.load(path)?;
+        }
+        forwarder = forwarder.with_scripts(scripts);
+    }
+    let mut plugin_configs = match &intercept.plugin_file {
+        Some(path) => PluginConfig::load(path)?,
+        None => Vec::new(),
+    };
+    plugin_configs.extend(intercept.plugins.iter().cloned());
+    if !plugin_configs.is_empty() {
+        let mut plugins = PluginInterceptor::new()
+            .with_upstream(forwarder.upstream().clone())
+            .with_encoding_policy(encoding);
+        for config in &plugin_configs {
+            plugins.load(config)?;
+        }
+        forwarder = forwarder.with_plugins(plugins);
+    }
+    if !intercept.external.is_empty() {
+        let mut external = ExternalInterceptor::new().with_encoding_policy(encoding);
+        for config in &intercept.external {
+            external.spawn(
+                config
+                    .clone()
+                    .with_timeout(Duration::from_millis(intercept.external_timeout_ms))
+                    .with_fail_mode(intercept.external_fail)
+                    .with_max_restarts(intercept.external_max_restarts),
+            )?;
+        }
+        forwarder = forwarder.with_external(external);
+    }
+    let mut map_remote = match &intercept.map_remote_rules {
+        Some(path) => MapRemote::load(path)?,
+        None => MapRemote::new(),
+    };
+    for rule in &intercept.map_remote {
+        map_remote.add(rule.clone());
+    }
+    if !map_remote.is_empty() {
+        info!("Redirecting requests with {} Map Remote rule(s)", map_remote.rules().len());
+        forwarder = forwarder.with_map_remote(map_remote);
+    }
+
+    Ok(forwarder)
+}
+
+// The scope given by --scope-file, --scope, --exclude and --scope-filter. A scope given on the
+// command line is saved to a writable project; without one, the project's saved
+// scope applies.
+pub fn scope(args: &ScopeArgs, project: Option<&Project>) -> Result<Scope> {
+    if args.scope_file.is_none() && args.include.is_empty() && args.exclude.is_empty() && args.scope_filter.is_none() {
+        return Ok(match project {
+            Some(project) => project.load_scope()?.unwrap_or_default(),
+            None => Scope::new(),
+        });
+    }
+
+    let mut scope = match &args.scope_file {
+        Some(path) => Scope::load(path)?,
+        None => Scope::new(),
+    };
+    scope.include.extend(args.include.iter().cloned());
+    scope.exclude.extend(args.exclude.iter().cloned());
+    if let Some(filter) = &args.scope_filter {
+        scope.filter = Some(filter.clone());
+    }
+    if let Some(project) = project
+        && !project.is_read_only()
+    {
+        project.save_scope(&scope)?;
+    }
+    Ok(scope)
+}
+
+// `ferrum replay`: resend flows through the interceptor chain
+pub async fn replay(
+    project_path: &Path,
+    ids: &[FlowId],
+    target: Option<&str>,
+    upstream: &UpstreamArgs,
+    intercept: &InterceptArgs,
+) -> Result<()> {
+    let project = Project::open(project_path)?;
+
+    let target = target
+        .map(|target| target.parse::<hyper::Uri>())
+        .transpose()
+        .context("Invalid replay target")?;
+
+    // By default replay every flow that is not itself a replay
+    let ids: Vec<FlowId> = if ids.is_empty() {
+        project
+            .list_flows()?
+            .into_iter()
+            .filter(|flow| flow.replay_of.is_none())
+            .map(|flow| flow.id)
+            .collect()
+    } else {
+        ids.to_vec()
+    };
+
+    let forwarder = forwarder(upstream, intercept)?;
+    let replayed = replay_project_flows(&project, &forwarder, &ids, target.as_ref()).await;
+
+    for result in replayed {
+        match (result.replay, result.error) {
+            (Some(replay), _) => println!(
+                "{:>6} -> {:<6} {} -> {}",
+                result.original,
+                replay,
+                status_label(&project, result.original)?,
+                status_label(&project, replay)?
+            ),
+            (None, error) => println!(
+                "{:>6} -> failed: {}",
+                result.original,
+                error.unwrap_or_default()
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+fn status_label(project: &Project, id: FlowId) -> Result<String> {
+    Ok(project
+        .load_flow(id)?
+        .and_then(|flow| flow.response)
+        .map(|response| response.status.to_string())
+        .unwrap_or_else(|| "-".to_string()))
+}
+
+// `ferrum export curl`: print a stored request as a curl command
+pub fn export_curl(project_path: &Path, id: FlowId, proxy: Option<String>, insecure: bool) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+    let flow = project
+        .load_flow(id)?
+        .with_context(|| format!("Flow {} does not exist", id))?;
+
+    println!("{}", to_curl(&flow.request, &CurlOptions { proxy, insecure }));
+    Ok(())
+}
+
+// `ferrum import curl`: show the parsed request, or send it through the interceptor chain
+pub async fn import_curl(args: &CurlImportArgs) -> Result<()> {
+    let parsed = parse_curl(&args.command)?;
+
+    if !args.send {
+        io::stdout().write_all(&format_request(&parsed.request))?;
+        println!();
+        return Ok(());
+    }
+
+    let forwarder = forwarder(&curl_upstream(&args.upstream, &parsed), &args.intercept)?;
+    let flow = forwarder.forward(parsed.request).await?;
+    if let Some(response) = &flow.response {
+        io::stdout().write_all(&format_response(response))?;
+        println!();
+    }
+
+    if let Some(path) = &args.project {
+        let id = Project::open(path)?.save_flow(&flow)?;
+        eprintln!("Recorded as flow {}", id);
+    }
+
+    Ok(())
+}
+
+// The upstream options, with a curl command's `-k` and `--proxy` standing in for flags not given
+fn curl_upstream(upstream: &UpstreamArgs, parsed: &CurlCommand) -> UpstreamArgs {
+    UpstreamArgs {
+        upstream_proxy: upstream.upstream_proxy.clone().or_else(|| parsed.proxy.clone()),
+        insecure: upstream.insecure || parsed.insecure,
+    }
+}
+
+// `ferrum repeat`: load a request, apply edits, then send it one or more times
+pub async fn repeat(args: &RepeatArgs) -> Result<()> {
+    let project = args
+        .project
+        .as_ref()
+        .map(|path| Project::open(path).map(Arc::new))
+        .transpose()?;
+
+    let curl = args.curl.as_deref().map(parse_curl).transpose()?;
+    let upstream = match &curl {
+        Some(parsed) => curl_upstream(&args.upstream, parsed),
+        None => args.upstream.clone(),
+    };
+    let mut repeater = Repeater::new(forwarder(&upstream, &args.intercept)?);
+    if let Some(project) = &project {
+        repeater = repeater.with_project(project.clone());
+    }
+
+    let tab = if let Some(path) = &args.file {
+        let raw = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        repeater.open_from_raw(&raw, args.https)?
+    } else if let Some(parsed) = curl {
+        let name = format!("{} {}", parsed.request.method, parsed.request.uri);
+        repeater.open_tab(name, parsed.request)
+    } else {
+        let (Some(id), Some(project)) = (args.flow, &project) else {
+            bail!("Specify --file, --curl or --flow with --project");
+        };
+        let flow = project
+            .load_flow(id)?
+            .with_context(|| format!("Flow {} does not exist", id))?;
+        repeater.open_from_flow(&flow)
+    };
+
+    let request = &mut repeater.tab_mut(tab).expect("tab was just opened").request;
+    if let Some(method) = &args.method {
+        request.method = method.to_ascii_uppercase();
+    }
+    if let Some(url) = &args.url {
+        url.parse::<hyper::Uri>().with_context(|| format!("Invalid URL {}", url))?;
+        request.uri = url.clone();
+    }
+    for name in &args.remove_header {
+        request.remove_header(name);
+    }
+    for header in &args.headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header {}, expected \"Name: value\"", header))?;
+        request.set_header(name.trim(), value.trim());
+    }
+    if let Some(body) = &args.body {
+        request.body = body.clone().into_bytes();
+    } else if let Some(path) = &args.body_file {
+        request.body = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    }
+
+    for number in 1..=args.count.max(1) {
+        let attempt = repeater.send(tab).await?;
+        match (&attempt.flow, &attempt.error) {
+            (Some(flow), _) => {
+                let response = flow.response.as_ref();
+                let recorded = if flow.id > 0 {
+                    format!("  (flow {})", flow.id)
+                } else {
+                    String::new()
+                };
+                eprintln!(
+                    "#{:<4} {:>6} {:>10} {:>8}{}",
+                    number,
+                    response.map(|r| r.status.to_string()).unwrap_or_else(|| "-".to_string()),
+                    response.map(|r| r.body.len()).unwrap_or(0),
+                    format!("{}ms", flow.duration_ms.unwrap_or(0)),
+                    recorded
+                );
+            }
+            (None, error) => eprintln!("#{:<4} error: {}", number, error.as_deref().unwrap_or("unknown")),
+        }
+    }
+
+    let last = repeater.tab(tab).and_then(|tab| tab.last_attempt());
+    let response = last.and_then(|attempt| attempt.flow.as_ref()?.response.as_ref());
+    if let (Some(response), false) = (response, args.quiet) {
+        io::stdout().write_all(&format_response(response))?;
+        println!();
+    }
+
+    Ok(())
+}
+
+// `ferrum intruder`: send a template once per payload assignment and tabulate the results
+pub async fn intruder(args: &IntruderArgs) -> Result<()> {
+    let project = args
+        .project
+        .as_ref()
+        .map(|path| Project::open(path).map(Arc::new))
+        .transpose()?;
+
+    let template = if let Some(path) = &args.file {
+        let raw = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let template = Template::parse(&raw, args.https)?;
+        if args.auto {
+            Template::auto(&template.render(&[])?)?
+        } else {
+            template
+        }
+    } else {
+        let (Some(id), Some(project)) = (args.flow, &project) else {
+            bail!("Specify --file or --flow with --project");
+        };
+        let flow = project
+            .load_flow(id)?
+            .with_context(|| format!("Flow {} does not exist", id))?;
+        Template::auto(&flow.request)?
+    };
+
+    let sets = args
+        .payloads
+        .iter()
+        .map(|spec| PayloadSet::parse_spec(spec, project.as_deref()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let scope = scope(&args.scope, project.as_deref())?;
+    let forwarder = forwarder(&args.upstream, &args.intercept)?.with_scope(scope.clone());
+    let mut attack = Attack::new(template, args.mode, sets, forwarder)?
+        .with_concurrency(args.concurrency)
+        .with_grep(&args.grep)?
+        .with_payload_encoding(args.encode.clone())
+        .with_scope(scope);
+    if let Some(delay) = args.delay_ms {
+        attack = attack.with_delay(std::time::Duration::from_millis(delay));
+    }
+    if let Some(project) = &project {
+        attack = attack.with_project(project.clone());
+    }
+
+    let total = attack.request_count();
+    let mut done = 0;
+    let results = attack
+        .run_with(|_| {
+            done += 1;
+            eprint!("\r{}/{} request(s) sent", done, total);
+        })
+        .await?;
+    eprintln!();
+
+    println!("{:>6} {:>6} {:>10} {:>8}  {:<5} PAYLOADS", "#", "STATUS", "LENGTH", "TIME", "GREP");
+    for result in &results {
+        let status = result
+            .status
+            .map(|status| status.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let length = result
+            .length
+            .map(|length| length.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let duration = result
+            .duration_ms
+            .map(|ms| format!("{}ms", ms))
+            .unwrap_or_else(|| "-".to_string());
+        let grep: String = result
+            .grep_matches
+            .iter()
+            .map(|&matched| if matched { '*' } else { '.' })
+            .collect();
+        let error = result
+            .error
+            .as_ref()
+            .map(|error| format!("  error: {}", error))
+            .unwrap_or_default();
+
+        println!(
+            "{:>6} {:>6} {:>10} {:>8}  {:<5} {}{}",
+            result.index + 1,
+            status,
+            length,
+            duration,
+            grep,
+            describe_payloads(&result.payloads),
+            error
+        );
+    }
+
+    Ok(())
+}
+
+// `ferrum sequencer`: collect tokens (or read them from a file) and report on their randomness
+pub async fn sequencer(args: &SequencerArgs) -> Result<()> {
+    let tokens = if let Some(path) = &args.tokens {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        let source = args.token.as_ref().context("--token is required when collecting tokens")?;
+        let request = if let Some(path) = &args.file {
+            let raw = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            parse_request(&raw, args.https)?
+        } else if let Some(command) = &args.curl {
+            parse_curl(command)?.request
+        } else {
+            let (Some(id), Some(path)) = (args.flow, &args.project) else {
+                bail!("Specify --file, --curl, --tokens or --flow with --project");
+            };
+            stored_request(path, id)?
+        };
+
+        let forwarder = forwarder(&args.upstream, &args.intercept)?;
+        let sample = collect_tokens(&forwarder, &request, source, args.count, args.concurrency).await?;
+        if sample.misses > 0 {
+            eprintln!("{} response(s) did not contain a token", sample.misses);
+        }
+        sample.tokens
+    };
+
+    if let Some(path) = &args.save {
+        std::fs::write(path, tokens.join("\n") + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+
+    let report = analyze(&tokens)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!("Tokens analysed:     {} ({} distinct)", report.sample_size, report.distinct);
+    println!("Length:              {}-{} characters", report.min_length, report.max_length);
+    println!("Character set:       {} characters, {} bit(s) each", report.charset.chars().count(), report.bits_per_char);
+    println!("Character entropy:   {:.2} bits per character", report.char_entropy);
+    println!("Character-level:     {:.1} bits", report.char_level_bits);
+    println!("Bit-level:           {:.1} bits", report.bit_level_bits);
+    println!("Compression ratio:   {:.2}", report.compression_ratio);
+    match &report.fips {
+        Some(fips) => println!(
+            "FIPS 140-2:          {} block(s); monobit {}, poker {}, runs {}, long run {} passed",
+            fips.blocks, fips.monobit_passed, fips.poker_passed, fips.runs_passed, fips.long_run_passed
+        ),
+        None => println!("FIPS 140-2:          not enough data for a 20,000-bit block"),
+    }
+    println!();
+    println!(
+        "Effective entropy is about {:.0} bits: {}",
+        report.effective_bits,
+        report.rating.as_str()
+    );
+    for finding in &report.findings {
+        println!("  - {}", finding);
+    }
+
+    Ok(())
+}
+
+fn stored_request(project_path: &Path, id: FlowId) -> Result<FlowRequest> {
+    Ok(Project::open_read_only(project_path)?
+        .load_flow(id)?
+        .with_context(|| format!("Flow {} does not exist", id))?
+        .request)
+}
+
+// `ferrum compare`: diff two flows, or a flow and its latest replay
+pub fn compare(
+    project_path: &Path,
+    old_id: FlowId,
+    new_id: Option<FlowId>,
+    options: &CompareOptions,
+    json: bool,
+) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+
+    let new_id = match new_id {
+        Some(id) => id,
+        None => project
+            .list_flows()?
+            .into_iter()
+            .filter(|flow| flow.replay_of == Some(old_id))
+            .map(|flow| flow.id)
+            .max()
+            .with_context(|| format!("Flow {} has no replays; give a second flow ID", old_id))?,
+    };
+
+    let load = |id| {
+        project
+            .load_flow(id)?
+            .with_context(|| format!("Flow {} does not exist", id))
+    };
+    let comparison = compare_flows(&load(old_id)?, &load(new_id)?, options);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison)?);
+        return Ok(());
+    }
+
+    println!("--- flow {}", old_id);
+    println!("+++ flow {}", new_id);
+    print_message_diff("Request", &comparison.request, options.granularity);
+    match &comparison.response {
+        Some(response) => print_message_diff("Response", response, options.granularity),
+        None => println!("\n== Response: missing on one side"),
+    }
+
+    Ok(())
+}
+
+fn print_message_diff(label: &str, diff: &MessageDiff, granularity: Granularity) {
+    println!();
+    if diff.is_identical() {
+        println!("== {}: identical", label);
+        return;
+    }
+    println!("== {}", label);
+
+    if let Some((old, new)) = &diff.first_line {
+        println!("-{}", old);
+        println!("+{}", new);
+    }
+    for header in &diff.headers {
+        for value in &header.old {
+            println!("-{}: {}", header.name, value);
+        }
+        for value in &header.new {
+            println!("+{}: {}", header.name, value);
+        }
+    }
+
+    match &diff.body {
+        BodyDiff::Identical => {}
+        BodyDiff::Json(changes) => {
+            println!();
+            for change in changes {
+                let show = |value: &Option<serde_json::Value>| {
+                    value.as_ref().map(|value| value.to_string()).unwrap_or_default()
+                };
+                match (&change.old, &change.new) {
+                    (Some(_), Some(_)) => println!("~{}: {} -> {}", change.path, show(&change.old), show(&change.new)),
+                    (Some(_), None) => println!("-{}: {}", change.path, show(&change.old)),
+                    _ => println!("+{}: {}", change.path, show(&change.new)),
+                }
+            }
+        }
+        BodyDiff::Text(chunks) if granularity == Granularity::Line => {
+            println!();
+            for chunk in chunks {
+                let prefix = match chunk.kind {
+                    ChangeKind::Equal => ' ',
+                    ChangeKind::Delete => '-',
+                    ChangeKind::Insert => '+',
+                };
+                for line in chunk.text.lines() {
+                    println!("{}{}", prefix, line);
+                }
+            }
+        }
+        BodyDiff::Text(chunks) => {
+            // Inline word diff in the style of `git diff --word-diff`
+            println!();
+            let mut text = String::new();
+            for chunk in chunks {
+                match chunk.kind {
+                    ChangeKind::Equal => text.push_str(&chunk.text),
+                    ChangeKind::Delete => text.push_str(&format!("[-{}-]", chunk.text)),
+                    ChangeKind::Insert => text.push_str(&format!("{{+{}+}}", chunk.text)),
+                }
+            }
+            println!("{}", text);
+        }
+        BodyDiff::Bytes(chunks) => {
+            println!();
+            for chunk in chunks.iter().filter(|chunk| chunk.kind != ChangeKind::Equal) {
+                let (sign, offset) = match chunk.kind {
+                    ChangeKind::Delete => ('-', chunk.old_offset),
+                    _ => ('+', chunk.new_offset),
+                };
+                let hex: Vec<String> = chunk.bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                println!("{}{:08x}: {}", sign, offset, hex.join(" "));
+            }
+        }
+    }
+}
+
+// `ferrum decode`: apply a chain of decodings, or peel layers off by guessing
+pub fn decode(input: Option<&str>, chain: &[Transform]) -> Result<()> {
+    let input = read_input(input)?;
+
+    if !chain.is_empty() {
+        io::stdout().write_all(&decode_chain(&input, chain)?)?;
+        println!();
+        return Ok(());
+    }
+
+    let steps = smart_decode(&input);
+    if steps.is_empty() {
+        bail!("Could not recognise an encoding; use --with to name one");
+    }
+    for step in &steps {
+        eprintln!("{}:", step.transform.name());
+        match std::str::from_utf8(&step.output) {
+            Ok(text) => eprintln!("  {}", text),
+            Err(_) => eprintln!("  ({} bytes of binary data)", step.output.len()),
+        }
+    }
+    eprintln!(
+        "Decoded with {}",
+        steps.iter().map(|step| step.transform.name()).collect::<Vec<_>>().join(",")
+    );
+
+    let last = steps.last().map(|step| step.output.as_slice()).unwrap_or_default();
+    io::stdout().write_all(last)?;
+    println!();
+    Ok(())
+}
+
+// `ferrum encode`: apply a chain of encodings in order
+pub fn encode(input: Option<&str>, chain: &[Transform]) -> Result<()> {
+    let input = read_input(input)?;
+    io::stdout().write_all(&encode_chain(&input, chain)?)?;
+    println!();
+    Ok(())
+}
+
+// The argument if given, otherwise stdin without its trailing newline
+fn read_input(input: Option<&str>) -> Result<Vec<u8>> {
+    match input {
+        Some(input) if input != "-" => Ok(input.as_bytes().to_vec()),
+        _ => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data).context("Failed to read stdin")?;
+            if data.ends_with(b"\n") {
+                data.pop();
+                if data.ends_with(b"\r") {
+                    data.pop();
+                }
+            }
+            Ok(data)
+        }
+    }
+}
+
+// `ferrum scan`: run the passive checks over every flow in a project
+pub fn scan(project_path: &Path, scope_args: &ScopeArgs, min_severity: Severity, json: bool) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+    let mut scanner = PassiveScanner::new().with_scope(scope(scope_args, Some(&project))?);
+    scanner.record_project(&project)?;
+
+    print_findings(scanner.findings(), min_severity, json)
+}
+
+// `ferrum audit`: send active probes for the selected flows and report what they find
+pub async fn audit(args: &AuditArgs) -> Result<()> {
+    let project = Arc::new(Project::open(&args.project)?);
+    let mut flows = Vec::new();
+    for id in &args.flow {
+        flows.push(project.load_flow(*id)?.with_context(|| format!("Flow {} does not exist", id))?);
+    }
+
+    // Without include rules, probes stay on the hosts being scanned
+    let mut scope = scope(&args.scope, Some(&project))?;
+    if scope.include.is_empty() {
+        let mut hosts: Vec<String> = flows
+            .iter()
+            .filter_map(|flow| request_url(&flow.request)?.host_str().map(str::to_string))
+            .collect();
+        hosts.sort();
+        hosts.dedup();
+        scope.include = Scope::from_hosts(&hosts).include;
+    }
+
+    let mut scanner = ActiveScanner::new(forwarder(&args.upstream, &args.intercept)?.with_scope(scope.clone()))
+        .with_scope(scope)
+        .with_rate_limit(args.rate)
+        .with_concurrency(args.concurrency)
+        .with_time_delay(std::time::Duration::from_secs(args.time_delay))
+        .with_project(Arc::clone(&project));
+    if !args.checks.is_empty() {
+        scanner = scanner.with_only_checks(&args.checks)?;
+    }
+    if let Some(addr) = args.callback {
+        scanner = scanner.with_callback(CallbackListener::start(addr).await?);
+    }
+
+    eprintln!("Running {} against {} flow(s)", scanner.check_names().join(", "), flows.len());
+    let findings = scanner.scan(&flows).await?;
+    print_findings(&findings, args.min_severity, args.json)
+}
+
+fn print_findings(findings: &[Finding], min_severity: Severity, json: bool) -> Result<()> {
+    let mut findings: Vec<&Finding> = findings
+        .iter()
+        .filter(|finding| finding.severity >= min_severity)
+        .collect();
+    findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.flow_id.cmp(&b.flow_id)));
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+        return Ok(());
+    }
+
+    for finding in &findings {
+        println!(
+            "{:<6}  {:<9}  #{:<5}  {}  {}",
+            finding.severity.as_str(),
+            finding.confidence.as_str(),
+            finding.flow_id,
+            finding.title,
+            finding.url
+        );
+        if !finding.detail.is_empty() {
+            println!("        {}", finding.detail);
+        }
+    }
+    eprintln!("{} finding(s)", findings.len());
+
+    Ok(())
+}
+
+// `ferrum sitemap`: print the site map of a project as a tree, URL list or JSON
+pub fn sitemap(project_path: &Path, host: Option<&str>, unrequested: bool, json: bool) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+    let mut site_map = SiteMap::from_project(&project)?;
+    if let Some(host) = host {
+        site_map.retain_origin(host);
+    }
+
+    if site_map.is_empty() {
+        eprintln!("No flows to map");
+        return Ok(());
+    }
+
+    if unrequested {
+        for url in site_map.unrequested() {
+            println!("{}", url);
+        }
+    } else if json {
+        println!("{}", site_map.to_json()?);
+    } else {
+        print!("{}", site_map.to_text());
+    }
+
+    Ok(())
+}
+
+// `ferrum spider`: crawl from seed URLs, optionally recording into a project
+pub async fn spider(args: &SpiderArgs) -> Result<()> {
+    let seeds = args
+        .seeds
+        .iter()
+        .map(|seed| url::Url::parse(seed).with_context(|| format!("Invalid seed URL {}", seed)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let project = args
+        .project
+        .as_ref()
+        .map(|path| Project::open(path).map(Arc::new))
+        .transpose()?;
+    let scope = scope(&args.scope, project.as_deref())?;
+    let mut spider = Spider::new(forwarder(&args.upstream, &args.intercept)?.with_scope(scope.clone()))
+        .with_scope(scope)
+        .with_max_depth(args.depth)
+        .with_max_requests(args.max_requests)
+        .with_concurrency(args.concurrency)
+        .with_rate_limit(args.rate)
+        .with_respect_robots(args.respect_robots);
+    for header in &args.headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Invalid header {}, expected \"Name: value\"", header))?;
+        spider = spider.with_header(name.trim(), value.trim());
+    }
+    if args.submit_forms {
+        let mut defaults = FormDefaults::new();
+        for pair in &args.form_value {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Invalid form value {}, expected \"name=value\"", pair))?;
+            defaults = defaults.with_value(name, value);
+        }
+        spider = spider.with_form_submission(defaults);
+    }
+    if let Some(project) = project {
+        spider = spider.with_project(project);
+    }
+
+    let report = spider.crawl(&seeds).await?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    for visit in &report.visits {
+        let status = visit.status.map_or("---".to_string(), |status| status.to_string());
+        println!("{}  {:>2}  {:<6} {}", status, visit.depth, visit.method, visit.url);
+        if let Some(error) = &visit.error {
+            println!("         {}", error);
+        }
+    }
+    eprintln!(
+        "{} request(s), {} form(s) submitted, {} out-of-scope URL(s) skipped, {} URL(s) left unfetched",
+        report.visits.len(),
+        report.forms_submitted,
+        report.out_of_scope,
+        report.not_fetched
+    );
+
+    Ok(())
+}
+
+// `ferrum jwt decode`
+pub fn jwt_decode(token: &str) -> Result<()> {
+    let jwt = Jwt::parse(token)?;
+    println!("{}", jwt.describe());
+    println!("Signature: {} bytes", jwt.signature.len());
+    Ok(())
+}
+
+// `ferrum jwt find`: list the tokens in a project's flows
+pub fn jwt_find(project_path: &Path, ids: &[FlowId]) -> Result<()> {
+    let project = Project::open_read_only(project_path)?;
+    let ids = if ids.is_empty() {
+        project.list_flows()?.iter().map(|summary| summary.id).collect()
+    } else {
+        ids.to_vec()
+    };
+
+    for id in ids {
+        let flow = project.load_flow(id)?.with_context(|| format!("Flow {} does not exist", id))?;
+        for found in find_tokens(&flow) {
+            let subject = found.jwt.claims.get("sub").map(|sub| sub.to_string()).unwrap_or_default();
+            println!(
+                "#{:<5} {:<28} {:<6} {:<20} {}",
+                id,
+                found.source,
+                found.jwt.alg().unwrap_or("-"),
+                subject,
+                found.token
+            );
+        }
+    }
+    Ok(())
+}
+
+// `ferrum jwt crack`
+pub fn jwt_crack(token: &str, wordlist: Option<&Path>) -> Result<()> {
+    let jwt = Jwt::parse(token)?;
+    let words = read_wordlist(wordlist)?;
+    match crack(&jwt, words.iter().map(String::as_str)) {
+        Some(secret) => println!("{}", secret),
+        None => bail!("Secret not found"),
+    }
+    Ok(())
+}
+
+// `ferrum jwt forge`
+pub fn jwt_forge(token: &str, claims: &[ClaimEdit], signing: &Signing) -> Result<()> {
+    let jwt = Jwt::parse(token)?;
+    println!("{}", forge(&jwt, claims, signing)?);
+    Ok(())
+}
+
+// `ferrum jwt attack`: send a flow's request once per forged token through the repeater
+pub async fn jwt_attack(args: &JwtAttackArgs) -> Result<()> {
+    let project = Arc::new(Project::open(&args.project)?);
+    let flow = project
+        .load_flow(args.flow)?
+        .with_context(|| format!("Flow {} does not exist", args.flow))?;
+    let found = find_tokens(&flow)
+        .into_iter()
+        .find(|found| !found.source.starts_with("response"))
+        .with_context(|| format!("The request of flow {} carries no JWT", args.flow))?;
+    eprintln!("Attacking the JWT in the {} ({})", found.source, found.jwt.alg().unwrap_or("no alg"));
+
+    // Re-sign automatically when the secret is known or can be guessed
+    let secret = match &args.secret {
+        Some(secret) => Some(secret.clone()),
+        None => {
+            let words = read_wordlist(args.wordlist.as_deref())?;
+            let cracked = crack(&found.jwt, words.iter().map(String::as_str));
+            if let Some(secret) = &cracked {
+                eprintln!("Cracked the HMAC secret: {:?}", secret);
+            }
+            cracked
+        }
+    };
+    let public_key = match &args.public_key {
+        Some(path) => Some(std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?),
+        None => None,
+    };
+    let variants = variants(
+        &found.jwt,
+        &args.claims,
+        secret.as_deref().map(str::as_bytes),
+        public_key.as_deref(),
+    )?;
+
+    let mut repeater = Repeater::new(forwarder(&args.upstream, &args.intercept)?).with_project(Arc::clone(&project));
+    let mut attacks = vec![("baseline".to_string(), flow.request.clone())];
+    attacks.extend(
+        variants
+            .iter()
+            .map(|variant| (variant.name.clone(), replace_token(&flow.request, &found.token, &variant.token))),
+    );
+
+    println!("{:>6} {:>10}  {:<7}  VARIANT", "STATUS", "LENGTH", "FLOW");
+    for (name, request) in attacks {
+        let tab = repeater.open_tab(name.clone(), request);
+        let attempt = repeater.send(tab).await?;
+        match &attempt.flow {
+            Some(flow) => {
+                let response = flow.response.as_ref();
+                println!(
+                    "{:>6} {:>10}  #{:<6}  {}",
+                    response.map_or("-".to_string(), |response| response.status.to_string()),
+                    response.map_or(0, |response| response.body.len()),
+                    flow.id,
+                    name
+                );
+            }
+            None => println!("{:>6} {:>10}  {:<7}  {} ({})", "-", "-", "-", name, attempt.error.as_deref().unwrap_or("unknown")),
+        }
+    }
+
+    Ok(())
+}
+
+fn read_wordlist(path: Option<&Path>) -> Result<Vec<String>> {
+    let Some(path) = path else {
+        return Ok(Vec::new());
+    };
+    let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(text.lines().map(|line| line.trim_end_matches('\r').to_string()).collect())
+}
@@ -6,6 +6,12 @@ pub struct Tui {
     // In a real implementation, this would use a library like tui-rs or crossterm
 }
 
+impl Default for Tui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Tui {
     pub fn new() -> Self {
         Self {}
@@ -1,2 +1,7 @@
 pub mod error;
 pub mod logger;
+
+// Serde default for flags that are on unless a rules file turns them off
+pub fn default_true() -> bool {
+    true
+}
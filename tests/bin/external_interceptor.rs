@@ -0,0 +1,85 @@
+// A stand-in external interceptor for the tests, so they need no other language
+// runtime. The first argument picks how it behaves:
+//
+//     hooks   rewrites messages the way the tests expect, each on its own thread
+//     silent  reads messages and never answers
+//     once    answers the first message, then exits
+//     exit    exits straight away
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+fn main() {
+    let mode = std::env::args().nth(1).unwrap_or_default();
+    let stdout = Arc::new(Mutex::new(std::io::stdout()));
+    let say = |stdout: &Mutex<std::io::Stdout>, line: &str| {
+        let mut stdout = stdout.lock().unwrap();
+        let _ = writeln!(stdout, "{}", line);
+        let _ = stdout.flush();
+    };
+
+    match mode.as_str() {
+        "exit" => {}
+        "silent" => for _ in std::io::stdin().lock().lines() {},
+        "once" => {
+            let mut line = String::new();
+            if std::io::stdin().read_line(&mut line).is_ok()
+                && let Ok(message) = serde_json::from_str::<Value>(&line)
+            {
+                say(&stdout, &json!({"id": message["id"], "tags": ["once"]}).to_string());
+            }
+        }
+        "hooks" => {
+            say(&stdout, "starting up");
+            for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                let stdout = Arc::clone(&stdout);
+                std::thread::spawn(move || say(&stdout, &handle(message).to_string()));
+            }
+        }
+        _ => {
+            eprintln!("Unknown mode {:?}", mode);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn body(value: &Value) -> Vec<u8> {
+    STANDARD.decode(value.as_str().unwrap_or_default()).unwrap_or_default()
+}
+
+fn handle(message: Value) -> Value {
+    let mut reply = json!({"id": message["id"]});
+    let mut request = message["request"].clone();
+    let uri = request["uri"].as_str().unwrap_or_default().to_string();
+
+    if message["hook"] == "request" {
+        if uri.contains("slow") {
+            std::thread::sleep(Duration::from_millis(300));
+        }
+        if uri.ends_with("/blocked") {
+            reply["response"] = json!({"status": 403, "body": STANDARD.encode(b"nope")});
+        } else {
+            if let Some(headers) = request["headers"].as_array_mut() {
+                headers.push(json!(["X-External", "helper"]));
+            }
+            request["body"] = json!(STANDARD.encode(body(&request["body"]).to_ascii_uppercase()));
+            reply["request"] = request;
+            reply["tags"] = json!([uri.rsplit('/').next().unwrap_or_default()]);
+        }
+    } else {
+        let mut response = message["response"].clone();
+        if response["status"] == 500 {
+            reply["error"] = json!("cannot handle server errors");
+        } else {
+            response["body"] = json!(STANDARD.encode(b"rewritten"));
+            reply["response"] = response;
+        }
+    }
+    reply
+}
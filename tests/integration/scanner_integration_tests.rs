@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::net::TcpListener;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest};
+use ferrum::project::scope::Scope;
+use ferrum::project::store::Project;
+use ferrum::proxy::forward::Forwarder;
+use ferrum::scanner::active::{ActiveScanner, SCANNER_TAG};
+use ferrum::scanner::callback::CallbackListener;
+
+// One deliberately vulnerable endpoint per built-in check
+async fn vulnerable(req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let path = req.uri().path().to_string();
+    let value = req
+        .uri()
+        .query()
+        .and_then(|query| url::form_urlencoded::parse(query.as_bytes()).next())
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default();
+    let page = |status: u16, body: String| Response::builder().status(status).body(Full::new(Bytes::from(body))).unwrap();
+
+    let response = match path.as_str() {
+        // Reflects the query into HTML as-is
+        "/search" => {
+            let mut response = page(200, format!("<html><body>Results for {}</body></html>", value));
+            response.headers_mut().insert("content-type", "text/html".parse().unwrap());
+            response
+        }
+        // The same, compressed as a browser would get it
+        "/search-gz" => {
+            let html = format!("<html><body>Results for {}</body></html>", value);
+            let mut response = Response::new(Full::new(Bytes::from(Transform::Gzip.encode(html.as_bytes()).unwrap())));
+            response.headers_mut().insert("content-type", "text/html".parse().unwrap());
+            response.headers_mut().insert("content-encoding", "gzip".parse().unwrap());
+            response
+        }
+        // Concatenates the ID into SQL and shows database errors
+        "/product" if value.contains(['\'', '"', '\\']) => page(500, "You have an error in your SQL syntax near line 1".into()),
+        "/product" => page(200, "Product page".into()),
+        // Evaluates an appended condition
+        "/article" => {
+            let id = value.strip_suffix(" AND 1=1").unwrap_or(&value);
+            if id.bytes().all(|b| b.is_ascii_digit()) && !id.is_empty() {
+                page(200, format!("Article {} {}", id, "lorem ipsum ".repeat(20)))
+            } else {
+                page(404, "Not found".into())
+            }
+        }
+        // Sleeps when asked to by the database
+        "/report" => {
+            if let Some(seconds) = value.split("SLEEP(").nth(1).and_then(|rest| rest.split(')').next()) {
+                tokio::time::sleep(Duration::from_secs(seconds.parse().unwrap_or(0))).await;
+            }
+            page(200, "Report".into())
+        }
+        // Reads files relative to a public directory
+        "/download" if value.contains("../") || value == "/etc/passwd" => {
+            page(200, "root:x:0:0:root:/root:/bin/bash\n".into())
+        }
+        "/download" => page(404, "No such file".into()),
+        // Redirects wherever it is told to
+        "/go" => match value.parse::<hyper::header::HeaderValue>() {
+            Ok(location) => {
+                let mut response = page(302, String::new());
+                response.headers_mut().insert("location", location);
+                response
+            }
+            Err(_) => page(400, "Bad redirect".into()),
+        },
+        // Fetches a URL server-side
+        "/fetch" => {
+            if value.starts_with("http://127.0.0.1")
+                && let Ok(uri) = value.parse::<hyper::Uri>()
+            {
+                let client = Client::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(HttpConnector::new());
+                let _ = client.get(uri).await;
+            }
+            page(200, "Fetched".into())
+        }
+        // Writes the value into a header without stripping newlines
+        "/lang" => {
+            let mut lines = value.split('\n').map(|line| line.trim_end_matches('\r'));
+            let mut response = page(200, "Language set".into());
+            if let Ok(language) = lines.next().unwrap_or_default().parse() {
+                response.headers_mut().insert("content-language", language);
+            }
+            for line in lines {
+                if let Some((name, header)) = line.split_once(": ")
+                    && let (Ok(name), Ok(header)) = (name.parse::<hyper::header::HeaderName>(), header.parse())
+                {
+                    response.headers_mut().insert(name, header);
+                }
+            }
+            response
+        }
+        _ => page(404, "Not found".into()),
+    };
+
+    let _ = req.into_body().collect().await;
+    Ok(response)
+}
+
+async fn start_vulnerable_app() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service_fn(vulnerable))
+                    .await;
+            });
+        }
+    });
+    Ok(addr)
+}
+
+#[tokio::test]
+async fn test_active_scanner_finds_every_vulnerability() -> Result<()> {
+    let app = start_vulnerable_app().await?;
+    let project = Arc::new(Project::in_memory()?);
+
+    let flows: Vec<Flow> = [
+        "/search?q=shoes",
+        "/search-gz?q=shoes",
+        "/product?id=1",
+        "/article?id=1",
+        "/report?id=1",
+        "/download?file=report.txt",
+        "/go?next=/home",
+        "/fetch?url=http://example.com/",
+        "/lang?l=en",
+    ]
+    .iter()
+    .map(|path| Flow::new(FlowRequest::new("GET", &format!("http://{}{}", app, path))))
+    .collect();
+
+    let scanner = ActiveScanner::new(Forwarder::new())
+        .with_scope(Scope::from_hosts(&["127.0.0.1".to_string()]))
+        .with_concurrency(8)
+        .with_rate_limit(500.0)
+        .with_time_delay(Duration::from_secs(1))
+        .with_callback_wait(Duration::from_millis(300))
+        .with_callback(CallbackListener::start("127.0.0.1:0".parse()?).await?)
+        .with_project(Arc::clone(&project));
+
+    let findings = scanner.scan(&flows).await?;
+
+    let found: BTreeSet<(String, String)> = findings
+        .iter()
+        .map(|finding| {
+            let path = finding.url.split(['?', '#']).next().unwrap().rsplit('/').next().unwrap().to_string();
+            (finding.check.clone(), path)
+        })
+        .collect();
+    let expected: BTreeSet<(String, String)> = [
+        ("reflected-xss", "search"),
+        ("reflected-xss", "search-gz"),
+        ("sql-injection", "product"),
+        ("sql-injection", "article"),
+        ("sql-injection", "report"),
+        ("path-traversal", "download"),
+        ("open-redirect", "go"),
+        ("ssrf", "fetch"),
+        ("header-injection", "lang"),
+    ]
+    .iter()
+    .map(|(check, path)| (check.to_string(), path.to_string()))
+    .collect();
+    assert_eq!(found, expected);
+
+    let detail = |path: &str| {
+        findings
+            .iter()
+            .find(|finding| finding.check == "sql-injection" && finding.url.contains(path))
+            .map(|finding| finding.detail.clone())
+            .unwrap()
+    };
+    assert!(detail("/product").contains("error-based"));
+    assert!(detail("/article").contains("boolean-based"));
+    assert!(detail("/report").contains("time-based"));
+
+    // Every probe is recorded, and findings point at the probe that proved them
+    let recorded = project.list_flows()?;
+    assert!(recorded.len() > flows.len());
+    assert!(recorded.iter().all(|flow| flow.tags == vec![SCANNER_TAG.to_string()]));
+    for finding in &findings {
+        let probe = project.load_flow(finding.flow_id)?.unwrap();
+        for evidence in &finding.evidence {
+            assert!(evidence.bytes(&probe).is_some(), "Evidence of {} does not fit its flow", finding.check);
+        }
+    }
+
+    Ok(())
+}
@@ -6,9 +6,34 @@ mod unit {
     mod request_interceptor_tests;
     mod response_interceptor_tests;
     mod proxy_server_tests;
+    mod project_tests;
+    mod har_export_tests;
+    mod har_import_tests;
+    mod curl_tests;
+    mod repeater_tests;
+    mod intruder_tests;
+    mod sequencer_tests;
+    mod comparer_tests;
+    mod decoder_tests;
+    mod sitemap_tests;
+    mod passive_scanner_tests;
+    mod active_scanner_tests;
+    mod spider_tests;
+    mod jwt_tests;
+    mod replace_rules_tests;
+    mod map_local_tests;
+    mod map_remote_tests;
+    mod block_rules_tests;
+    mod filter_tests;
+    mod scope_tests;
+    mod script_tests;
+    mod plugin_tests;
+    mod external_tests;
+    mod body_tests;
 }
 
 // Integration tests
 mod integration {
     mod proxy_integration_tests;
+    mod scanner_integration_tests;
 }
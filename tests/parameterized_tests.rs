@@ -2,9 +2,7 @@ use hyper::{Request, Method, Uri, Response, StatusCode};
 use http_body_util::{Full, BodyExt, Empty};
 use bytes::Bytes;
 use anyhow::Result;
-use std::net::SocketAddr;
 use rstest::*;
-use tokio::time::Duration;
 
 use ferrum::proxy::server::ProxyServer;
 use ferrum::intercept::request::RequestInterceptor;
@@ -62,6 +60,7 @@ fn get_request() -> RequestFixture {
 #[fixture]
 fn post_request() -> RequestFixture {
     RequestFixture::new(Method::POST, "http://example.com/test")
+        .with_header("Content-Type", "application/x-www-form-urlencoded")
         .with_body("test=data")
 }
 
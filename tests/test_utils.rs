@@ -37,6 +37,8 @@ pub fn get_test_port() -> u16 {
 }
 
 /// Get a dynamic test address
+// Only the parameterized tests bind through this; the main test crate binds port 0 directly
+#[allow(dead_code)]
 pub fn get_test_addr() -> SocketAddr {
     format!("127.0.0.1:{}", get_test_port()).parse().unwrap()
 }
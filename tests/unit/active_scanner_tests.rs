@@ -0,0 +1,201 @@
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use futures::future::BoxFuture;
+use httpmock::MockServer;
+use rstest::rstest;
+use ferrum::flow::model::{Flow, FlowRequest};
+use ferrum::project::scope::Scope;
+use ferrum::proxy::forward::Forwarder;
+use ferrum::scanner::active::{unique_token, ActiveCheck, ActiveScanner, RateLimiter, ScanContext};
+use ferrum::scanner::callback::CallbackListener;
+use ferrum::scanner::finding::{Confidence, Finding, Location, Severity};
+use ferrum::scanner::insertion::{insertion_points, InsertionPoint, PointKind};
+use crate::test_utils::init_test_logging;
+
+fn request() -> FlowRequest {
+    let mut request = FlowRequest::new("POST", "http://example.com/api/items?id=7&q=red+shoes#top");
+    request.set_header("Cookie", "session=abc; theme=dark");
+    request.set_header("Content-Type", "application/json");
+    request.body = br#"{"name":"Bob","tags":["a",2],"meta":{"n/x":true}}"#.to_vec();
+    request
+}
+
+#[tokio::test]
+async fn test_insertion_points_cover_query_body_and_cookies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let points = insertion_points(&request());
+    let described: Vec<(PointKind, &str, &str)> = points
+        .iter()
+        .map(|point| (point.kind, point.name.as_str(), point.value.as_str()))
+        .collect();
+
+    assert_eq!(
+        described,
+        vec![
+            (PointKind::Query, "id", "7"),
+            (PointKind::Query, "q", "red shoes"),
+            (PointKind::Json, "/meta/n~1x", "true"),
+            (PointKind::Json, "/name", "Bob"),
+            (PointKind::Json, "/tags/0", "a"),
+            (PointKind::Json, "/tags/1", "2"),
+            (PointKind::Cookie, "session", "abc"),
+            (PointKind::Cookie, "theme", "dark"),
+        ]
+    );
+
+    let mut form = FlowRequest::new("POST", "http://example.com/login");
+    form.set_header("Content-Type", "application/x-www-form-urlencoded");
+    form.body = b"user=alice&pass=x%26y".to_vec();
+    let points = insertion_points(&form);
+    assert_eq!(points[1].kind, PointKind::Form);
+    assert_eq!(points[1].value, "x&y");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_injection_encodes_for_context() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let original = request();
+    let points = insertion_points(&original);
+    let point = |name: &str| -> &InsertionPoint { points.iter().find(|point| point.name == name).unwrap() };
+
+    let injected = point("q").inject(&original, "a&b=' <x>");
+    assert_eq!(injected.uri, "http://example.com/api/items?id=7&q=a%26b%3D%27+%3Cx%3E#top");
+    let evidence = point("q").locate(&injected).unwrap();
+    assert_eq!(evidence.location, Location::Url);
+    assert_eq!(&injected.uri[evidence.start..evidence.end], "a%26b%3D%27+%3Cx%3E");
+
+    let injected = point("/name").inject(&original, "\"quoted\"\r\n");
+    let json: serde_json::Value = serde_json::from_slice(&injected.body)?;
+    assert_eq!(json["name"], "\"quoted\"\r\n");
+    assert_eq!(json["tags"][1], 2);
+    let evidence = point("/name").locate(&injected).unwrap();
+    assert_eq!(&injected.body[evidence.start..evidence.end], br#""\"quoted\"\r\n""#);
+
+    let injected = point("theme").inject(&original, "light; admin=1");
+    assert_eq!(injected.header("cookie"), Some("session=abc; theme=light%3B%20admin=1"));
+    let evidence = point("theme").locate(&injected).unwrap();
+    assert!(matches!(evidence.location, Location::RequestHeader(_)));
+
+    Ok(())
+}
+
+#[rstest]
+#[case(&[], "anything.test", true)]
+#[case(&["example.com"], "EXAMPLE.com", true)]
+#[case(&["example.com"], "api.example.com", false)]
+#[case(&["*.example.com"], "api.example.com", true)]
+#[case(&["*.example.com"], "example.com", true)]
+#[case(&["*.example.com"], "badexample.com", false)]
+#[tokio::test]
+async fn test_host_scope(#[case] scope: &[&str], #[case] host: &str, #[case] expected: bool) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let hosts: Vec<String> = scope.iter().map(|host| host.to_string()).collect();
+    let url = url::Url::parse(&format!("http://{}/", host))?;
+    assert_eq!(Scope::from_hosts(&hosts).contains_url(&url), expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rate_limiter_spaces_requests() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let limiter = RateLimiter::new(20.0);
+    let started = Instant::now();
+    for _ in 0..5 {
+        limiter.wait().await;
+    }
+    // The first request goes immediately, the next four wait 50ms each
+    assert!(started.elapsed() >= Duration::from_millis(190));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_callback_listener_records_hits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let listener = CallbackListener::start("127.0.0.1:0".parse()?).await?;
+    let token = unique_token();
+    assert_ne!(token, unique_token());
+
+    assert!(listener.wait_for(&token, Duration::from_millis(100)).await.is_none());
+
+    let flow = Forwarder::new()
+        .forward(FlowRequest::new("GET", &format!("{}/extra", listener.url(&token))))
+        .await?;
+    assert_eq!(flow.response.unwrap().status, 200);
+
+    let hit = listener.wait_for(&token, Duration::from_secs(1)).await.unwrap();
+    assert_eq!(hit.method, "GET");
+    assert_eq!(hit.path, format!("/{}/extra", token));
+    assert_eq!(listener.hits().len(), 1);
+
+    Ok(())
+}
+
+// Flags parameters whose value shows up as a response header
+struct DebugParameter;
+
+impl ActiveCheck for DebugParameter {
+    fn name(&self) -> &'static str {
+        "debug"
+    }
+
+    fn scan<'a>(&'a self, context: &'a ScanContext, point: &'a InsertionPoint) -> BoxFuture<'a, Result<Vec<Finding>>> {
+        Box::pin(async move {
+            let probe = context.probe(point, "debug").await?;
+            let enabled = probe.response.as_ref().is_some_and(|response| response.header("x-debug").is_some());
+            Ok(enabled
+                .then(|| Finding::new("debug-mode", "Debug mode", Severity::Medium, Confidence::Certain, &probe))
+                .into_iter()
+                .collect())
+        })
+    }
+}
+
+#[tokio::test]
+async fn test_custom_check_respects_scope() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let debug = server.mock(|when, then| {
+        when.method("GET").path("/page").query_param("mode", "debug");
+        then.status(200).header("X-Debug", "on").body("debug");
+    });
+    let normal = server.mock(|when, then| {
+        when.method("GET").path("/page").query_param("mode", "normal");
+        then.status(200).body("normal");
+    });
+
+    let scanner = ActiveScanner::empty(Forwarder::new())
+        .with_check(std::sync::Arc::new(DebugParameter))
+        .with_scope(Scope::from_hosts(&["127.0.0.1".to_string()]));
+    assert_eq!(scanner.check_names(), vec!["debug"]);
+    assert!(ActiveScanner::new(Forwarder::new()).with_only_checks(&["nope".to_string()]).is_err());
+
+    let in_scope = Flow::new(FlowRequest::new("GET", &server.url("/page?mode=normal")));
+    let out_of_scope = Flow::new(FlowRequest::new("GET", &format!("http://localhost:{}/page?mode=normal", server.port())));
+
+    let findings = scanner.scan(&[in_scope, out_of_scope]).await?;
+
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].check, "debug-mode");
+    assert!(findings[0].url.contains("mode=debug"));
+    // One baseline and one probe, both for the in-scope flow
+    normal.assert_hits(1);
+    debug.assert_hits(1);
+
+    Ok(())
+}
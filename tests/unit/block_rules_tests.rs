@@ -0,0 +1,134 @@
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::flow::model::FlowRequest;
+use ferrum::rules::block::{BlockAction, BlockRule, BlockRules};
+use ferrum::rules::matcher::RuleMatch;
+use crate::test_utils::init_test_logging;
+
+#[rstest]
+#[case("example.com", RuleMatch::default().with_host("example.com"))]
+#[case("https://*.example.com/api/*", RuleMatch::default().with_host("*.example.com").with_path("/api/*"))]
+#[case("post example.com/login", RuleMatch::default().with_host("example.com").with_path("/login").with_method("POST"))]
+#[tokio::test]
+async fn test_rule_match_pattern(#[case] pattern: &str, #[case] expected: RuleMatch) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(pattern.parse::<RuleMatch>()?, expected);
+    assert!("/api/*".parse::<RuleMatch>().is_err());
+    assert!("G3T example.com".parse::<RuleMatch>().is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("reset", BlockAction::Reset)]
+#[case("204", BlockAction::NoContent)]
+#[case("404", BlockAction::Respond { status: 404, body: String::new(), headers: Default::default() })]
+#[case("503:Down: try later", BlockAction::Respond {
+    status: 503,
+    body: "Down: try later".to_string(),
+    headers: Default::default(),
+})]
+#[tokio::test]
+async fn test_action_parsing(#[case] text: &str, #[case] expected: BlockAction) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(text.parse::<BlockAction>()?, expected);
+    assert!("drop".parse::<BlockAction>().is_err());
+    assert!("42".parse::<BlockAction>().is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("cdn.example.com", "cdn.example.com", BlockAction::Reset)]
+#[case("*.google-analytics.com=204", "*.google-analytics.com", BlockAction::NoContent)]
+#[case("example.com/items;v=2/*", "example.com/items;v=2/*", BlockAction::Reset)]
+#[case("example.com/items;v=2/*=reset", "example.com/items;v=2/*", BlockAction::Reset)]
+#[case("POST example.com/a=b/*=400:use a=b", "POST example.com/a=b/*", BlockAction::Respond {
+    status: 400,
+    body: "use a=b".to_string(),
+    headers: Default::default(),
+})]
+#[tokio::test]
+async fn test_rule_parsing(#[case] text: &str, #[case] pattern: &str, #[case] action: BlockAction) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(text.parse::<BlockRule>()?, BlockRule::new(pattern.parse()?, action));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resolve_and_responses() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut rules = BlockRules::new();
+    rules.add("*.google-analytics.com=204".parse()?);
+    rules.add("POST api.example.com/payments/*=503:Service Unavailable".parse()?);
+    rules.add("api.example.com/payments/*".parse()?);
+
+    let action = |method: &str, uri: &str| rules.resolve(&FlowRequest::new(method, uri));
+
+    let analytics = action("GET", "https://www.google-analytics.com/collect?v=1").unwrap();
+    let response = analytics.response().unwrap();
+    assert_eq!(response.status, 204);
+    assert!(response.body.is_empty());
+
+    let outage = action("POST", "https://api.example.com/payments/charge").unwrap();
+    let response = outage.response().unwrap();
+    assert_eq!(response.status, 503);
+    assert_eq!(response.body, b"Service Unavailable");
+    assert_eq!(response.header("content-type"), Some("text/plain; charset=utf-8"));
+    assert_eq!(response.header("content-length"), Some("19"));
+
+    // Later rules catch what earlier ones leave alone
+    assert_eq!(action("GET", "https://api.example.com/payments/7"), Some(BlockAction::Reset));
+    assert_eq!(BlockAction::Reset.response(), None);
+    assert_eq!(action("GET", "https://api.example.com/users"), None);
+
+    rules.set_enabled(2, false)?;
+    assert_eq!(rules.resolve(&FlowRequest::new("GET", "https://api.example.com/payments/7")), None);
+    assert!(rules.set_enabled(3, false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rules_load_from_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("block.json");
+    std::fs::write(
+        &path,
+        r#"[
+            {"name": "no tracking", "match": {"host": "*.doubleclick.net"}, "action": "reset"},
+            {"match": {"host": "api.example.com", "methods": ["PUT"]}, "action": "no_content"},
+            {"match": {"path": "/health"}, "action": {"respond": {"status": 500, "body": "{\"ok\":false}",
+             "headers": {"Content-Type": "application/json"}}}}
+        ]"#,
+    )?;
+
+    let rules = BlockRules::load(&path)?;
+    assert_eq!(rules.rules().len(), 3);
+    assert_eq!(rules.rules()[1].action, BlockAction::NoContent);
+
+    let response = rules.rules()[2].action.response().unwrap();
+    assert_eq!(response.status, 500);
+    assert_eq!(response.header("content-type"), Some("application/json"));
+    assert_eq!(response.body, br#"{"ok":false}"#);
+
+    let rule = BlockRule::new(RuleMatch::default(), BlockAction::NoContent).with_name("all");
+    assert_eq!(serde_json::to_value(&rule)?["action"], "no_content");
+
+    std::fs::write(&path, r#"[{"action": "explode"}]"#)?;
+    assert!(BlockRules::load(&path).is_err());
+
+    Ok(())
+}
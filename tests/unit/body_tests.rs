@@ -0,0 +1,223 @@
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::decoder::transform::{Transform, MAX_DECODED_BYTES};
+use ferrum::filter::eval::Filter;
+use ferrum::flow::body::{content_encodings, EncodingPolicy};
+use ferrum::flow::model::{FlowRequest, FlowResponse};
+use ferrum::intercept::script::ScriptInterceptor;
+use ferrum::intercept::wire::WireResponse;
+use crate::test_utils::init_test_logging;
+
+const PAGE: &str = r#"{"user":"alice","role":"guest","items":[1,2,3,4,5,6,7,8,9,10]}"#;
+
+fn encoded_response(content_encoding: &str, transforms: &[Transform], content: &[u8]) -> Result<FlowResponse> {
+    let mut response = FlowResponse::new(200);
+    response.set_header("Content-Type", "application/json");
+    response.set_header("Content-Encoding", content_encoding);
+    let mut body = content.to_vec();
+    for transform in transforms {
+        body = transform.encode(&body)?;
+    }
+    response.set_header("Content-Length", &body.len().to_string());
+    response.body = body;
+    Ok(response)
+}
+
+#[rstest]
+#[case("gzip", vec![Transform::Gzip])]
+#[case("x-gzip", vec![Transform::Gzip])]
+#[case("deflate", vec![Transform::Deflate])]
+#[case("br", vec![Transform::Brotli])]
+#[case("zstd", vec![Transform::Zstd])]
+#[case("deflate, gzip", vec![Transform::Deflate, Transform::Gzip])]
+#[tokio::test]
+async fn test_decoding(#[case] content_encoding: &str, #[case] transforms: Vec<Transform>) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let response = encoded_response(content_encoding, &transforms, PAGE.as_bytes())?;
+    assert_eq!(content_encodings(&response.headers), Some(transforms));
+
+    let body = response.decoded_body();
+    assert!(body.is_encoded());
+    assert_eq!(body.content_encoding(), Some(content_encoding));
+    assert_eq!(body.decoded()?, PAGE.as_bytes());
+    assert_eq!(body.text(), PAGE);
+    assert_eq!(body.raw(), response.body.as_slice());
+    assert_ne!(body.raw(), PAGE.as_bytes());
+
+    // Plain and identity bodies are their own content
+    let mut plain = FlowResponse::new(200);
+    plain.body = PAGE.as_bytes().to_vec();
+    assert!(!plain.decoded_body().is_encoded());
+    plain.set_header("Content-Encoding", "identity");
+    assert!(!plain.decoded_body().is_encoded());
+    assert_eq!(plain.decoded_body().decoded()?, PAGE.as_bytes());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_charsets() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut response = FlowResponse::new(200);
+    response.set_header("Content-Type", "text/html; charset=\"windows-1252\"");
+    response.body = b"caf\xe9".to_vec();
+    assert_eq!(response.decoded_body().charset().name(), "windows-1252");
+    assert_eq!(response.decoded_body().text(), "café");
+
+    // Text written back is encoded in the declared charset
+    assert!(response.set_body_text("crème brûlée", EncodingPolicy::Reencode)?);
+    assert_eq!(response.body, b"cr\xe8me br\xfbl\xe9e");
+
+    response.set_header("Content-Type", "text/plain; charset=Shift_JIS");
+    response.body = b"\x93\xfa\x96\x7b".to_vec();
+    assert_eq!(response.decoded_body().text(), "日本");
+
+    // A byte order mark wins, and unknown or missing charsets fall back to UTF-8
+    response.body = b"\xef\xbb\xbfna\xc3\xafve".to_vec();
+    assert_eq!(response.decoded_body().text(), "naïve");
+    response.set_header("Content-Type", "text/plain; charset=klingon");
+    response.body = "naïve".as_bytes().to_vec();
+    assert_eq!(response.decoded_body().charset().name(), "UTF-8");
+    assert_eq!(response.decoded_body().text(), "naïve");
+    response.remove_header("Content-Type");
+    response.body = b"bad \xff byte".to_vec();
+    assert_eq!(response.decoded_body().text(), "bad \u{fffd} byte");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_modification() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let original = encoded_response("br", &[Transform::Brotli], PAGE.as_bytes())?;
+
+    // Setting the content it already has leaves the bytes as sent
+    let mut response = original.clone();
+    assert!(!response.set_decoded_body(PAGE.as_bytes(), EncodingPolicy::Reencode)?);
+    assert!(!response.set_body_text(PAGE, EncodingPolicy::Strip)?);
+    assert_eq!(response, original);
+
+    // Changed content is compressed again and Content-Length follows
+    let admin = PAGE.replace("guest", "admin");
+    assert!(response.set_body_text(&admin, EncodingPolicy::Reencode)?);
+    assert_eq!(response.header("Content-Encoding"), Some("br"));
+    assert_eq!(Transform::Brotli.decode(&response.body)?, admin.as_bytes());
+    assert_eq!(response.header("Content-Length"), Some(response.body.len().to_string().as_str()));
+
+    // or sent uncompressed without Content-Encoding
+    let mut response = original.clone();
+    assert!(response.set_decoded_body(admin.as_bytes(), EncodingPolicy::Strip)?);
+    assert_eq!(response.header("Content-Encoding"), None);
+    assert_eq!(response.body, admin.as_bytes());
+    assert_eq!(response.header("Content-Length"), Some(admin.len().to_string().as_str()));
+
+    // Requests work the same way
+    let mut request = FlowRequest::new("POST", "http://example.com/upload");
+    request.set_header("Content-Encoding", "gzip");
+    request.body = Transform::Gzip.encode(b"a=1")?;
+    assert_eq!(request.decoded_body().text(), "a=1");
+    assert!(request.set_body_text("a=2", EncodingPolicy::Reencode)?);
+    assert_eq!(Transform::Gzip.decode(&request.body)?, b"a=2");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_undecodable_bodies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Encodings ferrum cannot undo keep their raw bytes
+    let mut response = FlowResponse::new(200);
+    response.set_header("Content-Encoding", "compress");
+    response.body = b"\x1f\x9d\x90raw".to_vec();
+    let body = response.decoded_body();
+    assert!(body.is_encoded());
+    assert!(body.decoded().unwrap_err().to_string().contains("Unsupported Content-Encoding compress"));
+    assert_eq!(body.decoded_or_raw(), response.body.as_slice());
+    assert!(response.set_body_text("new", EncodingPolicy::Reencode).is_err());
+    assert!(response.set_body_text("new", EncodingPolicy::Strip)?);
+    assert_eq!(response.body, b"new");
+    assert_eq!(response.header("Content-Encoding"), None);
+
+    // As do bodies that claim an encoding they do not have
+    let mut response = FlowResponse::new(200);
+    response.set_header("Content-Encoding", "gzip");
+    response.body = b"not gzip".to_vec();
+    assert!(response.decoded_body().decoded().unwrap_err().to_string().contains("gzip decoding failed"));
+    assert_eq!(response.decoded_body().text(), "not gzip");
+
+    // and bombs that would decompress past the limit
+    response.set_header("Content-Encoding", "zstd");
+    response.body = Transform::Zstd.encode(&vec![0; MAX_DECODED_BYTES + 1])?;
+    assert!(response.body.len() < 64 * 1024);
+    assert!(response.decoded_body().decoded().unwrap_err().to_string().contains("larger than"));
+    assert_eq!(response.decoded_body().decoded_or_raw(), response.body.as_slice());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_interceptors_see_decoded_bodies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let request = FlowRequest::new("GET", "http://example.com/me");
+    let original = encoded_response("gzip", &[Transform::Gzip], PAGE.as_bytes())?;
+
+    // Filters match the decoded content but size the bytes as sent
+    let filter = Filter::parse(r#"res.body contains "alice""#)?;
+    assert!(filter.matches_response(&request, &original));
+    let size = format!("res.size == {}", original.body.len());
+    assert!(Filter::parse(&size)?.matches_response(&request, &original));
+
+    // Plugins and external processes get the decoded content
+    let wire = WireResponse::from(&original);
+    assert_eq!(wire.body, PAGE.as_bytes());
+    let mut response = original.clone();
+    wire.apply(&mut response, EncodingPolicy::Reencode)?;
+    assert_eq!(response, original);
+    let mut wire = WireResponse::from(&original);
+    wire.body = b"{}".to_vec();
+    wire.apply(&mut response, EncodingPolicy::Strip)?;
+    assert_eq!(response.header("Content-Encoding"), None);
+    assert_eq!(response.body, b"{}");
+
+    // Scripts read and write text, with the raw bytes still at hand
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("promote.rhai");
+    std::fs::write(
+        &path,
+        r#"
+        fn on_response(req, res) {
+            if res.raw_body.len() == res.body.len() {
+                throw "body was not decoded";
+            }
+            let body = res.body;
+            body.replace("guest", "admin");
+            res.body = body;
+        }
+        "#,
+    )?;
+    let mut scripts = ScriptInterceptor::new();
+    scripts.load(&path)?;
+    let mut response = original.clone();
+    scripts.on_response(&request, &mut response);
+    assert_eq!(response.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(Transform::Gzip.decode(&response.body)?, PAGE.replace("guest", "admin").as_bytes());
+
+    let mut scripts = ScriptInterceptor::new().with_encoding_policy(EncodingPolicy::Strip);
+    scripts.load(&path)?;
+    let mut response = original.clone();
+    scripts.on_response(&request, &mut response);
+    assert_eq!(response.header("Content-Encoding"), None);
+    assert_eq!(response.body, PAGE.replace("guest", "admin").as_bytes());
+
+    Ok(())
+}
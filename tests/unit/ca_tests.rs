@@ -1,4 +1,3 @@
-use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 use anyhow::Result;
 use ferrum::certificates::ca::CertificateAuthority;
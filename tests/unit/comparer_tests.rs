@@ -0,0 +1,179 @@
+use anyhow::Result;
+use serde_json::json;
+use ferrum::comparer::compare::{change_counts, compare_flows, diff_body, diff_headers, BodyDiff, CompareOptions};
+use ferrum::comparer::diff::{diff_bytes, diff_text, ChangeKind, Granularity};
+use ferrum::comparer::json::{diff_json, JsonChangeKind};
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use crate::test_utils::init_test_logging;
+
+fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+fn flow(status: u16, body: &str) -> Flow {
+    let mut flow = Flow::new(FlowRequest::new("GET", "http://example.com/profile"));
+    let mut response = FlowResponse::new(status);
+    response.headers = headers(&[("Date", "Mon"), ("Content-Type", "text/html")]);
+    response.body = body.as_bytes().to_vec();
+    flow.response = Some(response);
+    flow
+}
+
+#[tokio::test]
+async fn test_text_diff_granularities() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let lines = diff_text("a\nb\nc\n", "a\nB\nc\n", Granularity::Line);
+    let kinds: Vec<ChangeKind> = lines.iter().map(|chunk| chunk.kind).collect();
+    assert_eq!(kinds, vec![ChangeKind::Equal, ChangeKind::Delete, ChangeKind::Insert, ChangeKind::Equal]);
+    assert_eq!(lines[1].text, "b\n");
+    assert_eq!(lines[2].text, "B\n");
+
+    let words = diff_text("hello admin user", "hello guest user", Granularity::Word);
+    let changed: Vec<(ChangeKind, &str)> = words
+        .iter()
+        .filter(|chunk| chunk.kind != ChangeKind::Equal)
+        .map(|chunk| (chunk.kind, chunk.text.as_str()))
+        .collect();
+    assert_eq!(changed, vec![(ChangeKind::Delete, "admin"), (ChangeKind::Insert, "guest")]);
+
+    let bytes = diff_bytes(b"\x00\x01\x02\x03", b"\x00\xff\x02\x03\x04");
+    let changed: Vec<(ChangeKind, usize, usize, Vec<u8>)> = bytes
+        .iter()
+        .filter(|chunk| chunk.kind != ChangeKind::Equal)
+        .map(|chunk| (chunk.kind, chunk.old_offset, chunk.new_offset, chunk.bytes.clone()))
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            (ChangeKind::Delete, 1, 1, vec![0x01]),
+            (ChangeKind::Insert, 1, 1, vec![0xff]),
+            (ChangeKind::Insert, 4, 4, vec![0x04]),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_json_diff_paths() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let old = json!({"user": {"id": 1, "roles": ["user"]}, "token": "a", "content-type": "x"});
+    let new = json!({"user": {"id": 2, "roles": ["user", "admin"]}, "content-type": "x", "debug": true});
+
+    let changes = diff_json(&old, &new);
+    let summary: Vec<(&str, JsonChangeKind)> = changes
+        .iter()
+        .map(|change| (change.path.as_str(), change.kind))
+        .collect();
+    // Keys of the old document come first, in sorted order, then added keys
+    assert_eq!(
+        summary,
+        vec![
+            ("$.token", JsonChangeKind::Removed),
+            ("$.user.id", JsonChangeKind::Changed),
+            ("$.user.roles[1]", JsonChangeKind::Added),
+            ("$.debug", JsonChangeKind::Added),
+        ]
+    );
+    assert_eq!(changes[1].old, Some(json!(1)));
+    assert_eq!(changes[1].new, Some(json!(2)));
+
+    let quoted = diff_json(&json!({"a-b": 1}), &json!({"a-b": 2}));
+    assert_eq!(quoted[0].path, r#"$["a-b"]"#);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_body_diff_selection() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(diff_body(b"same", b"same", Granularity::Line), BodyDiff::Identical);
+    assert_eq!(
+        diff_body(br#"{"a": 1}"#, br#"{ "a":1 }"#, Granularity::Line),
+        BodyDiff::Identical,
+        "Formatting-only JSON changes are not differences"
+    );
+    assert!(matches!(diff_body(br#"{"a":1}"#, br#"{"a":2}"#, Granularity::Word), BodyDiff::Json(_)));
+    assert!(matches!(diff_body(b"one", b"two", Granularity::Word), BodyDiff::Text(_)));
+    assert!(matches!(diff_body(b"one", b"two", Granularity::Byte), BodyDiff::Bytes(_)));
+    assert!(matches!(diff_body(b"\xff\xfe", b"\xff", Granularity::Line), BodyDiff::Bytes(_)));
+
+    let diff = diff_body(b"abc", b"abXYc", Granularity::Byte);
+    assert_eq!(change_counts(&diff), (0, 2));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compare_flows() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let old = flow(200, "<p>Hello user</p>\n");
+    let mut new = flow(403, "<p>Forbidden</p>\n");
+    new.response.as_mut().unwrap().headers = headers(&[("Date", "Tue"), ("Content-Type", "text/html"), ("X-Cache", "miss")]);
+
+    let options = CompareOptions {
+        granularity: Granularity::Line,
+        ignore_headers: vec!["date".to_string()],
+    };
+    let comparison = compare_flows(&old, &new, &options);
+
+    assert!(comparison.request.is_identical());
+    let response = comparison.response.expect("both flows have responses");
+    assert_eq!(
+        response.first_line,
+        Some(("HTTP/1.1 200".to_string(), "HTTP/1.1 403".to_string()))
+    );
+    assert_eq!(response.headers.len(), 1, "Date is ignored");
+    assert_eq!(response.headers[0].name, "X-Cache");
+    assert!(response.headers[0].old.is_empty());
+    assert!(matches!(response.body, BodyDiff::Text(_)));
+
+    let multi = diff_headers(
+        &headers(&[("Set-Cookie", "a=1"), ("Set-Cookie", "b=2")]),
+        &headers(&[("set-cookie", "a=1")]),
+        &[],
+    );
+    assert_eq!(multi.len(), 1);
+    assert_eq!(multi[0].old, vec!["a=1", "b=2"]);
+    assert_eq!(multi[0].new, vec!["a=1"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compare_compressed_bodies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let compressed = |body: &str, encoding: &str, transform: Transform| -> Result<Flow> {
+        let mut flow = flow(200, "");
+        let response = flow.response.as_mut().unwrap();
+        response.headers.push(("Content-Encoding".to_string(), encoding.to_string()));
+        response.body = transform.encode(body.as_bytes())?;
+        Ok(flow)
+    };
+
+    // The same page compressed differently has an identical body, and changes show as text
+    let old = compressed("<p>Hello user</p>\n", "gzip", Transform::Gzip)?;
+    let same = compressed("<p>Hello user</p>\n", "br", Transform::Brotli)?;
+    let options = CompareOptions::default();
+    assert_eq!(compare_flows(&old, &same, &options).response.unwrap().body, BodyDiff::Identical);
+
+    let new = compressed("<p>Hello admin</p>\n", "gzip", Transform::Gzip)?;
+    let response = compare_flows(&old, &new, &options).response.unwrap();
+    let BodyDiff::Text(chunks) = response.body else {
+        panic!("decoded bodies should be diffed as text");
+    };
+    assert!(chunks.iter().any(|chunk| chunk.kind == ChangeKind::Insert && chunk.text.contains("admin")));
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::curl::export::{to_curl, CurlOptions};
+use ferrum::curl::import::{parse_curl, split_shell_words};
+use ferrum::flow::model::FlowRequest;
+use crate::test_utils::init_test_logging;
+
+fn json_request() -> FlowRequest {
+    let mut request = FlowRequest::new("POST", "https://api.example.com/v1/users");
+    request.headers = vec![
+        ("Host".to_string(), "api.example.com".to_string()),
+        ("Content-Type".to_string(), "application/json".to_string()),
+        ("Content-Length".to_string(), "17".to_string()),
+        ("X-Note".to_string(), "it's here".to_string()),
+    ];
+    request.body = br#"{"name":"o'neil"}"#.to_vec();
+    request
+}
+
+#[tokio::test]
+async fn test_curl_export_formats_request() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let command = to_curl(&json_request(), &CurlOptions::default());
+
+    assert_eq!(
+        command,
+        r#"curl https://api.example.com/v1/users -H 'Content-Type: application/json' -H 'X-Note: it'\''s here' --data-raw '{"name":"o'\''neil"}'"#
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_curl_export_proxy_and_insecure() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let options = CurlOptions {
+        proxy: Some("http://127.0.0.1:8080".to_string()),
+        insecure: false,
+    };
+    let command = to_curl(&FlowRequest::new("DELETE", "https://example.com/item/1"), &options);
+
+    assert_eq!(
+        command,
+        "curl -X DELETE https://example.com/item/1 --proxy http://127.0.0.1:8080 -k",
+        "HTTPS through a proxy should add -k"
+    );
+
+    let plain = to_curl(&FlowRequest::new("HEAD", "http://example.com/"), &options);
+    assert_eq!(plain, "curl -I http://example.com/ --proxy http://127.0.0.1:8080");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_curl_import_browser_command() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Shaped like Chrome's "Copy as cURL (bash)"
+    let command = r#"curl 'https://example.com/api/search?q=test' \
+  -H 'accept: application/json' \
+  -H 'cookie: session=abc123; theme=dark' \
+  -H $'x-quoted: it\'s' \
+  --data-raw '{"page":2}' \
+  --compressed"#;
+
+    let parsed = parse_curl(command)?;
+    let request = &parsed.request;
+
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.uri, "https://example.com/api/search?q=test");
+    assert_eq!(request.header("cookie"), Some("session=abc123; theme=dark"));
+    assert_eq!(request.header("x-quoted"), Some("it's"));
+    assert_eq!(request.body, br#"{"page":2}"#);
+    assert!(request.header("accept-encoding").is_some(), "--compressed should add Accept-Encoding");
+    assert!(!parsed.insecure);
+
+    Ok(())
+}
+
+#[rstest]
+#[case("curl -XPUT example.com/a", "PUT", "http://example.com/a")]
+#[case("curl -sSLk https://example.com/", "GET", "https://example.com/")]
+#[case("curl -I http://example.com/", "HEAD", "http://example.com/")]
+#[case("curl -G -d q=1 -d page=2 http://example.com/s", "GET", "http://example.com/s?q=1&page=2")]
+#[case("curl --request=patch --url http://example.com/p", "PATCH", "http://example.com/p")]
+#[tokio::test]
+async fn test_curl_import_methods_and_urls(
+    #[case] command: &str,
+    #[case] method: &str,
+    #[case] uri: &str,
+) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let parsed = parse_curl(command)?;
+    assert_eq!(parsed.request.method, method);
+    assert_eq!(parsed.request.uri, uri);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_curl_import_auth_proxy_and_form_data() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let parsed = parse_curl(
+        "curl -k -x http://127.0.0.1:8080 -u admin:secret -d user=a -d pass=b https://example.com/login",
+    )?;
+
+    assert!(parsed.insecure);
+    assert_eq!(parsed.proxy.as_deref(), Some("http://127.0.0.1:8080"));
+    assert_eq!(parsed.request.header("authorization"), Some("Basic YWRtaW46c2VjcmV0"));
+    assert_eq!(parsed.request.body, b"user=a&pass=b");
+    assert_eq!(
+        parsed.request.header("content-type"),
+        Some("application/x-www-form-urlencoded")
+    );
+
+    assert!(parse_curl("wget http://example.com").is_err());
+    assert!(parse_curl("curl -H 'unterminated").is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_curl_round_trip_binary_body() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("PUT", "http://example.com/blob");
+    request.headers.push(("Content-Type".to_string(), "application/octet-stream".to_string()));
+    request.body = vec![0x00, 0xff, b'\'', b'\\', b'a', 0x7f];
+
+    let command = to_curl(&request, &CurlOptions::default());
+    let parsed = parse_curl(&command)?;
+
+    assert_eq!(parsed.request.method, "PUT");
+    assert_eq!(parsed.request.body, request.body);
+    assert_eq!(parsed.request.headers, request.headers);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_curl_export_body_starting_with_at() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // curl would read a local file for `--data-binary @/etc/passwd`
+    let mut request = FlowRequest::new("POST", "http://example.com/notes");
+    request.body = b"@/etc/passwd".to_vec();
+
+    let command = to_curl(&request, &CurlOptions::default());
+    assert_eq!(command, "curl http://example.com/notes --data-raw @/etc/passwd");
+    assert_eq!(parse_curl(&command)?.request.body, request.body);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_split_shell_words() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let words = split_shell_words(r#"a 'b c' "d \"e\"" f\ g $'h\ni'"#)?;
+    assert_eq!(words, vec!["a", "b c", "d \"e\"", "f g", "h\ni"]);
+
+    Ok(())
+}
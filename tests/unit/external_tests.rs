@@ -0,0 +1,151 @@
+use std::time::Duration;
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::flow::model::{FlowRequest, FlowResponse};
+use ferrum::intercept::external::{ExternalConfig, ExternalInterceptor, FailMode, EXTERNAL_TAG};
+use crate::test_utils::init_test_logging;
+
+// The helper process built from tests/bin/external_interceptor.rs, in one of its modes
+fn helper(mode: &str) -> ExternalConfig {
+    ExternalConfig::new(env!("CARGO_BIN_EXE_ferrum-test-interceptor")).with_arg(mode)
+}
+
+#[rstest]
+#[case("python3 hooks.py --verbose", "python3", vec!["hooks.py", "--verbose"])]
+#[case("  ./filter  ", "./filter", vec![])]
+#[case(r#"'/opt/my tools/filter' --match "a b" c\ d ''"#, "/opt/my tools/filter", vec!["--match", "a b", "c d", ""])]
+#[tokio::test]
+async fn test_config_parsing(#[case] text: &str, #[case] program: &str, #[case] args: Vec<&str>) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let config: ExternalConfig = text.parse()?;
+    assert_eq!(config.program, program);
+    assert_eq!(config.args, args);
+    assert_eq!(config.fail, FailMode::Open);
+    assert_eq!(config.to_string().parse::<ExternalConfig>()?, config);
+    assert!("   ".parse::<ExternalConfig>().is_err());
+    assert!("filter 'unterminated".parse::<ExternalConfig>().is_err());
+
+    assert_eq!("CLOSED".parse::<FailMode>()?, FailMode::Closed);
+    assert_eq!("open".parse::<FailMode>()?.as_str(), "open");
+    assert!("ajar".parse::<FailMode>().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_and_response_hooks() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut external = ExternalInterceptor::new();
+    external.spawn(helper("hooks"))?;
+
+    let mut request = FlowRequest::new("POST", "http://api.example.com/users");
+    request.body = b"hello".to_vec();
+    let outcome = external.on_request(&mut request).await;
+    assert_eq!(outcome.response, None);
+    assert_eq!(outcome.tags, vec!["users"]);
+    assert_eq!(request.header("x-external"), Some("helper"));
+    assert_eq!(request.body, b"HELLO");
+
+    let mut response = FlowResponse::new(200);
+    let tags = external.on_response(&request, &mut response).await;
+    assert!(tags.is_empty());
+    assert_eq!(response.body, b"rewritten");
+
+    let outcome = external.on_request(&mut FlowRequest::new("GET", "http://example.com/blocked")).await;
+    let response = outcome.response.unwrap();
+    assert_eq!((response.status, response.body), (403, b"nope".to_vec()));
+
+    // Replies are matched by id, so a slow message does not get a fast one's answer
+    let mut slow = FlowRequest::new("GET", "http://example.com/slow");
+    let mut fast = FlowRequest::new("GET", "http://example.com/fast");
+    let (slow_outcome, fast_outcome) = tokio::join!(external.on_request(&mut slow), external.on_request(&mut fast));
+    assert_eq!(slow_outcome.tags, vec!["slow"]);
+    assert_eq!(fast_outcome.tags, vec!["fast"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failure_modes() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let silent = helper("silent").with_timeout(Duration::from_millis(200));
+    let hooks = helper("hooks");
+
+    // Failing open lets traffic through untouched
+    let mut external = ExternalInterceptor::new();
+    external.spawn(silent.clone())?;
+    let mut request = FlowRequest::new("GET", "http://example.com/");
+    let original = request.clone();
+    let outcome = external.on_request(&mut request).await;
+    assert_eq!(outcome.response, None);
+    assert_eq!(request, original);
+
+    // Failing closed answers with 502, for timeouts and reported errors alike
+    let mut external = ExternalInterceptor::new();
+    external.spawn(silent.with_fail_mode(FailMode::Closed))?;
+    let outcome = external.on_request(&mut request).await;
+    let response = outcome.response.unwrap();
+    assert_eq!(response.status, 502);
+    assert!(String::from_utf8_lossy(&response.body).contains("did not answer within 200 ms"));
+
+    let mut external = ExternalInterceptor::new();
+    external.spawn(hooks.with_fail_mode(FailMode::Closed))?;
+    let mut response = FlowResponse::new(500);
+    let tags = external.on_response(&request, &mut response).await;
+    assert_eq!(tags, vec![EXTERNAL_TAG]);
+    assert_eq!(response.status, 502);
+    assert!(String::from_utf8_lossy(&response.body).contains("cannot handle server errors"));
+
+    // A process that exits is started again for the next message
+    let mut external = ExternalInterceptor::new();
+    external.spawn(helper("once"))?;
+    for _ in 0..2 {
+        let outcome = external.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+        assert_eq!(outcome.tags, vec!["once"]);
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    assert!(ExternalInterceptor::new().spawn(ExternalConfig::new("/nonexistent/interceptor")).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restart_backoff_and_limit() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    async fn failure(external: &ExternalInterceptor) -> String {
+        let outcome = external.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+        outcome.response.map_or_else(String::new, |response| String::from_utf8_lossy(&response.body).into_owned())
+    }
+
+    // A process that never answers is restarted with a growing wait, then given up on
+    let mut external = ExternalInterceptor::new();
+    external.spawn(helper("exit").with_fail_mode(FailMode::Closed).with_max_restarts(2))?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let first = failure(&external).await;
+    assert!(!first.contains("restarting it in") && !first.contains("gave up"), "{}", first);
+    assert!(failure(&external).await.contains("restarting it in"));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let second = failure(&external).await;
+    assert!(!second.contains("restarting it in") && !second.contains("gave up"), "{}", second);
+    assert!(failure(&external).await.contains("gave up after 2 restart(s)"));
+
+    // Answering resets the count, so a process that exits after every message keeps going
+    let mut external = ExternalInterceptor::new();
+    external.spawn(helper("once").with_max_restarts(1))?;
+    for _ in 0..3 {
+        let outcome = external.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+        assert_eq!(outcome.tags, vec!["once"]);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::filter::eval::Filter;
+use ferrum::filter::parse::{Expr, Field, Op, Side};
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse, TlsInfo};
+use crate::test_utils::init_test_logging;
+
+fn sample_flow() -> Flow {
+    let mut request = FlowRequest::new("POST", "https://api.example.com/v2/orders?page=3");
+    request.set_header("Content-Type", "application/json");
+    request.set_header("X-Api-Key", "k-123");
+    request.body = br#"{"item":"book","qty":2}"#.to_vec();
+
+    let mut response = FlowResponse::new(502);
+    response.set_header("Content-Type", "text/html; charset=utf-8");
+    response.set_header("Set-Cookie", "a=1");
+    response.set_header("Set-Cookie", "session=xyz; HttpOnly");
+    response.body = b"<h1>Upstream error</h1>".repeat(100);
+
+    let mut flow = Flow::new(request);
+    flow.id = 42;
+    flow.response = Some(response);
+    flow.duration_ms = Some(1500);
+    flow.tags = vec!["repeater".to_string(), "idor".to_string()];
+    flow.tls = Some(TlsInfo {
+        server_name: Some("api.example.com".to_string()),
+        protocol_version: Some("TLSv1.3".to_string()),
+        ..Default::default()
+    });
+    flow
+}
+
+#[rstest]
+#[case(r#"host ~ "api\." && method == POST && status >= 500 && res.body contains "error""#, true)]
+#[case(r#"host ~ "^www\.""#, false)]
+#[case("method == post", true)]
+#[case("method != GET", true)]
+#[case("HOST == API.example.com", true)]
+#[case(r#"path == /v2/orders && query contains "page=3""#, true)]
+#[case("port == 443 && scheme == https", true)]
+#[case("status == 502 and not status == 200", true)]
+#[case(r#"status ~ "^5""#, true)]
+#[case("status < 500 || duration > 1s", true)]
+#[case("duration >= 2s", false)]
+#[case("duration < 1600ms", true)]
+#[case("res.size > 2kb && req.size < 1kb", true)]
+#[case("size > 1mb", false)]
+#[case("req.header.X-Api-Key == k-123", true)]
+#[case("res.header.set-cookie ~ HttpOnly", true)]
+#[case("res.header.set-cookie !~ Secure", true)]
+#[case("header.x-api-key", true)]
+#[case("res.header.x-api-key", false)]
+#[case("req.headers contains \"X-Api-Key: k-\"", true)]
+#[case("req.content_type contains JSON", true)]
+#[case("res.content_type contains json", false)]
+#[case("content_type contains json", true)]
+#[case(r#"req.body ~ "\"qty\":\s*\d+""#, true)]
+#[case("body contains book && body contains Upstream", true)]
+#[case("tag == idor", true)]
+#[case("tag != idor", false)]
+#[case("tag ~ ^rep", true)]
+#[case("tls && tls.version == tlsv1.3 && tls.sni == api.example.com", true)]
+#[case("!tls", false)]
+#[case("tls.cipher", false)]
+#[case("tls.cipher != x", false)]
+#[case("res && id == 42", true)]
+#[case("notes", false)]
+#[case("!(method == GET || method == PUT) && (status >= 500)", true)]
+#[tokio::test]
+async fn test_evaluate(#[case] source: &str, #[case] expected: bool) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let filter: Filter = source.parse()?;
+    assert_eq!(filter.matches(&sample_flow()), expected, "{}", source);
+    assert_eq!(filter.to_string(), source);
+
+    Ok(())
+}
+
+#[rstest]
+#[case("", "Empty filter", 0)]
+#[case("hostname == x", "Unknown field `hostname`", 0)]
+#[case("host ==", "Expected a value but found the end of the filter", 7)]
+#[case("host < 5", "`<` compares numbers, but `host` is text", 5)]
+#[case("status >= abc", "Expected a number but found `abc` for `status`", 10)]
+#[case("size > 2parsecs", "Unexpected unit `parsecs` for `size`", 7)]
+#[case("duration > 1kb", "Unexpected unit `kb` for `duration`", 11)]
+#[case("tls == true", "`tls` is true or false and cannot be compared; use `tls` or `!tls`", 4)]
+#[case("host ~ \"(\"", "Invalid regex: unclosed group", 7)]
+#[case("host == \"abc", "Unterminated string", 8)]
+#[case("(host == a", "Expected `)` but found the end of the filter", 10)]
+#[case("host == a status == 200", "Expected `&&` or `||` before `status`", 10)]
+#[case("host == a & method == GET", "Expected `&&`", 10)]
+#[case("&& host", "Expected a field but found `&&`", 0)]
+#[case("method == GET)", "Unexpected `)`", 13)]
+#[tokio::test]
+async fn test_parse_errors(#[case] source: &str, #[case] message: &str, #[case] position: usize) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let error = Filter::parse(source).unwrap_err();
+    assert_eq!(error.message, message);
+    assert_eq!(error.position, position);
+
+    // The caret points at the offending character
+    let rendered = error.to_string();
+    let caret = rendered.lines().last().unwrap();
+    assert_eq!(caret.len() - 3, position);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ast_and_partial_exchanges() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Only headers, bodies, content types and sizes take a `req.`/`res.` prefix
+    assert_eq!(Filter::parse("res.status >= 400").unwrap_err().message, "Unknown field `res.status`");
+
+    let filter = Filter::parse("not req.header.Authorization && method == GET")?;
+    let Expr::And(left, right) = filter.expr() else {
+        panic!("expected an AND at the top");
+    };
+    assert!(matches!(
+        left.as_ref(),
+        Expr::Not(inner) if matches!(inner.as_ref(), Expr::Present(Field::Header(Side::Request, Some(name))) if name == "Authorization")
+    ));
+    assert!(matches!(right.as_ref(), Expr::Compare { field: Field::Method, op: Op::Eq, .. }));
+
+    // Requests not yet answered: response fields have no values
+    let request = FlowRequest::new("GET", "https://shop.example.com/cart");
+    assert!(filter.matches_request(&request));
+    assert!(Filter::parse("tls")?.matches_request(&request));
+    assert!(!Filter::parse("status >= 0")?.matches_request(&request));
+    assert!(!Filter::parse("status != 200")?.matches_request(&request));
+    assert!(!Filter::parse("res")?.matches_request(&request));
+
+    let response = FlowResponse::new(404);
+    assert!(Filter::parse("res && status == 404 && !tag")?.matches_response(&request, &response));
+
+    // Origin-form requests resolve their host through the Host header
+    let mut relative = FlowRequest::new("GET", "/login");
+    relative.set_header("Host", "intranet.local:8080");
+    assert!(Filter::parse("host == intranet.local && port == 8080 && url ~ ^http://")?.matches_request(&relative));
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse, Timings};
+use ferrum::har::export::{flows_to_har, write_har};
+use crate::test_utils::init_test_logging;
+
+fn form_flow() -> Flow {
+    let mut request = FlowRequest::new("POST", "https://example.com/login?next=%2Fhome&lang=en");
+    request.headers = vec![
+        ("Host".to_string(), "example.com".to_string()),
+        ("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()),
+        ("Cookie".to_string(), "theme=dark; tracking=1".to_string()),
+    ];
+    request.body = b"user=admin&pass=s3cr%21t".to_vec();
+
+    let mut response = FlowResponse::new(302);
+    response.headers = vec![
+        ("Location".to_string(), "/home".to_string()),
+        ("Set-Cookie".to_string(), "session=abc; Path=/; HttpOnly; Secure".to_string()),
+        ("Content-Type".to_string(), "text/html".to_string()),
+    ];
+    response.body = b"<a href=\"/home\">Found</a>".to_vec();
+
+    let mut flow = Flow::new(request);
+    flow.response = Some(response);
+    flow.timings = Some(Timings {
+        send_ms: 1.0,
+        wait_ms: 20.0,
+        receive_ms: 4.0,
+    });
+    flow
+}
+
+#[tokio::test]
+async fn test_har_export_request_details() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let har = flows_to_har(&[form_flow()]);
+    assert_eq!(har.log.version, "1.2");
+    assert_eq!(har.log.creator.name, "ferrum");
+
+    let entry = &har.log.entries[0];
+    assert_eq!(entry.time, 25.0, "Time should be the sum of the timings");
+    assert_eq!(entry.timings.wait, 20.0);
+
+    let request = &entry.request;
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.cookies.len(), 2);
+    assert_eq!(request.cookies[0].name, "theme");
+    assert_eq!(request.query_string.len(), 2);
+    assert_eq!(request.query_string[0].value, "/home", "Query values should be decoded");
+
+    let post_data = request.post_data.as_ref().expect("POST body should be exported");
+    assert_eq!(post_data.mime_type, "application/x-www-form-urlencoded");
+    assert_eq!(post_data.params.len(), 2);
+    assert_eq!(post_data.params[1].value.as_deref(), Some("s3cr!t"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_export_response_details() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let har = flows_to_har(&[form_flow()]);
+    let response = &har.log.entries[0].response;
+
+    assert_eq!(response.status, 302);
+    assert_eq!(response.status_text, "Found");
+    assert_eq!(response.redirect_url, "/home");
+    assert_eq!(response.cookies.len(), 1);
+    assert_eq!(response.cookies[0].http_only, Some(true));
+    assert_eq!(response.cookies[0].secure, Some(true));
+    assert_eq!(response.content.mime_type, "text/html");
+    assert_eq!(response.content.encoding, None, "Text bodies should not be encoded");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_export_encodes_binary_content() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut flow = Flow::new(FlowRequest::new("GET", "http://example.com/logo.png"));
+    let mut response = FlowResponse::new(200);
+    response.headers.push(("Content-Type".to_string(), "image/png".to_string()));
+    response.body = vec![0x89, b'P', b'N', b'G', 0xff, 0x00];
+    flow.response = Some(response);
+
+    let har = flows_to_har(&[flow]);
+    let content = &har.log.entries[0].response.content;
+
+    assert_eq!(content.encoding.as_deref(), Some("base64"));
+    assert_eq!(content.text.as_deref(), Some("iVBOR/8A"));
+    assert_eq!(content.size, 6);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_export_writes_valid_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // A flow without a response is still exported
+    let unanswered = Flow::new(FlowRequest::new("GET", "http://example.com/timeout"));
+
+    let mut output = Vec::new();
+    write_har(&[form_flow(), unanswered], &mut output)?;
+
+    let json: serde_json::Value = serde_json::from_slice(&output)?;
+    let entries = json["log"]["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+
+    // Browsers expect these fields to be present
+    assert!(entries[0]["cache"].is_object());
+    assert!(entries[0]["startedDateTime"].as_str().unwrap().ends_with('Z'));
+    assert_eq!(entries[0]["response"]["redirectURL"], "/home");
+    assert_eq!(entries[1]["response"]["status"], 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_export_decodes_compressed_content() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let page = "<html>caf\u{e9}</html>";
+    let mut flow = form_flow();
+    let response = flow.response.as_mut().unwrap();
+    response.headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+    response.body = Transform::Gzip.encode(page.as_bytes())?;
+    let sent = response.body.len() as i64;
+
+    let har = flows_to_har(&[flow.clone()]);
+    let content = &har.log.entries[0].response.content;
+    assert_eq!(content.text.as_deref(), Some(page));
+    assert_eq!(content.encoding, None);
+    assert_eq!(content.size, page.len() as i64);
+    assert_eq!(content.compression, Some(page.len() as i64 - sent));
+    assert_eq!(har.log.entries[0].response.body_size, sent);
+
+    // Binary content is base64 of the decompressed bytes
+    let response = flow.response.as_mut().unwrap();
+    response.headers[2].1 = "image/png".to_string();
+    response.body = Transform::Gzip.encode(&[0x89, b'P', b'N', b'G', 0xff, 0x00])?;
+    let har = flows_to_har(&[flow]);
+    let content = &har.log.entries[0].response.content;
+    assert_eq!(content.encoding.as_deref(), Some("base64"));
+    assert_eq!(content.text.as_deref(), Some("iVBOR/8A"));
+
+    Ok(())
+}
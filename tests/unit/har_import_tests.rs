@@ -0,0 +1,185 @@
+use anyhow::Result;
+use tempfile::TempDir;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use ferrum::har::export::flows_to_har;
+use ferrum::har::import::{entry_to_flow, har_to_flows, import_har_file, read_har};
+use ferrum::project::store::Project;
+use crate::test_utils::init_test_logging;
+
+// Trimmed-down entry in the shape Chrome devtools produces
+const CHROME_HAR: &str = r#"{
+  "log": {
+    "version": "1.2",
+    "creator": { "name": "WebInspector", "version": "537.36" },
+    "pages": [],
+    "entries": [
+      {
+        "startedDateTime": "2024-03-01T10:00:00.123Z",
+        "time": 35.5,
+        "request": {
+          "method": "POST",
+          "url": "https://api.example.com/v1/items?page=2",
+          "httpVersion": "http/2.0",
+          "headers": [
+            { "name": ":authority", "value": "api.example.com" },
+            { "name": "content-type", "value": "application/json" }
+          ],
+          "queryString": [{ "name": "page", "value": "2" }],
+          "cookies": [],
+          "headersSize": -1,
+          "bodySize": 13,
+          "postData": { "mimeType": "application/json", "text": "{\"name\":\"x\"}" }
+        },
+        "response": {
+          "status": 201,
+          "statusText": "",
+          "httpVersion": "http/2.0",
+          "headers": [{ "name": "content-type", "value": "image/gif" }],
+          "cookies": [],
+          "content": { "size": 3, "mimeType": "image/gif", "text": "R0lG", "encoding": "base64" },
+          "redirectURL": "",
+          "headersSize": -1,
+          "bodySize": -1,
+          "_transferSize": 120
+        },
+        "cache": {},
+        "timings": { "blocked": 1.2, "dns": -1, "ssl": -1, "connect": -1, "send": 0.5, "wait": 30, "receive": 5 },
+        "serverIPAddress": "93.184.216.34",
+        "_initiator": { "type": "script" }
+      },
+      {
+        "startedDateTime": "2024-03-01T10:00:01.000Z",
+        "time": -1,
+        "request": {
+          "method": "GET",
+          "url": "https://api.example.com/aborted",
+          "httpVersion": "",
+          "headers": [],
+          "queryString": [],
+          "cookies": [],
+          "headersSize": -1,
+          "bodySize": 0
+        },
+        "response": {
+          "status": 0,
+          "statusText": "",
+          "httpVersion": "",
+          "headers": [],
+          "cookies": [],
+          "content": { "size": 0, "mimeType": "x-unknown" },
+          "redirectURL": "",
+          "headersSize": -1,
+          "bodySize": -1
+        },
+        "cache": {},
+        "timings": { "send": 0, "wait": 0, "receive": 0 }
+      }
+    ]
+  }
+}"#;
+
+#[tokio::test]
+async fn test_har_import_converts_browser_entries() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let har = read_har(CHROME_HAR.as_bytes())?;
+    let flows = har_to_flows(&har)?;
+    assert_eq!(flows.len(), 2);
+
+    let flow = &flows[0];
+    assert_eq!(flow.request.method, "POST");
+    assert_eq!(flow.request.uri, "https://api.example.com/v1/items?page=2");
+    assert_eq!(flow.request.body, br#"{"name":"x"}"#);
+    assert_eq!(flow.request.headers.len(), 1, "Pseudo-headers should be dropped");
+    assert_eq!(flow.started_at.to_rfc3339(), "2024-03-01T10:00:00.123+00:00");
+    assert_eq!(flow.timings.unwrap().wait_ms, 30.0);
+
+    let response = flow.response.as_ref().unwrap();
+    assert_eq!(response.status, 201);
+    assert_eq!(response.body, b"GIF", "Base64 content should be decoded");
+
+    // Aborted requests come through without a response
+    assert!(flows[1].response.is_none());
+
+    // as do statuses that are not valid HTTP ones
+    for status in [-1, 42, 65736] {
+        let mut entry = har.log.entries[0].clone();
+        entry.response.status = status;
+        assert!(entry_to_flow(&entry)?.response.is_none(), "status {}", status);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_export_import_round_trip() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("PUT", "http://example.com/upload");
+    request.headers.push(("Content-Type".to_string(), "application/json".to_string()));
+    request.body = r#"{"name":"café"}"#.as_bytes().to_vec();
+
+    let mut response = FlowResponse::new(204);
+    response.headers.push(("X-Request-Id".to_string(), "42".to_string()));
+
+    let mut flow = Flow::new(request);
+    flow.response = Some(response);
+    flow.notes = Some("json upload".to_string());
+
+    let imported = har_to_flows(&flows_to_har(std::slice::from_ref(&flow)))?;
+    assert_eq!(imported[0].request, flow.request);
+    assert_eq!(imported[0].response.as_ref().unwrap().headers, flow.response.unwrap().headers);
+    assert_eq!(imported[0].notes, flow.notes);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_import_compressed_content() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let page = b"<p>caf\xe9</p>";
+    let mut response = FlowResponse::new(200);
+    response.headers.push(("Content-Type".to_string(), "text/html; charset=windows-1252".to_string()));
+    response.headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+    response.body = Transform::Gzip.encode(page)?;
+    response.headers.push(("Content-Length".to_string(), response.body.len().to_string()));
+
+    let mut flow = Flow::new(FlowRequest::new("GET", "http://example.com/menu"));
+    flow.response = Some(response);
+
+    // The exported content is decompressed, so the imported body is too
+    let imported = har_to_flows(&flows_to_har(&[flow]))?;
+    let response = imported[0].response.as_ref().unwrap();
+    assert_eq!(response.body, page);
+    assert_eq!(response.header("Content-Encoding"), None);
+    assert_eq!(response.header("Content-Length"), Some(page.len().to_string().as_str()));
+    assert_eq!(response.decoded_body().text(), "<p>caf\u{e9}</p>");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_har_import_file_into_project() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let temp_dir = TempDir::new()?;
+    let har_path = temp_dir.path().join("browser.har");
+    std::fs::write(&har_path, CHROME_HAR)?;
+
+    let project = Project::in_memory()?;
+    let ids = import_har_file(&har_path, &project)?;
+
+    assert_eq!(ids.len(), 2);
+    assert_eq!(project.flow_count()?, 2);
+
+    let stored = project.load_flow(ids[0])?.unwrap();
+    assert_eq!(stored.response.unwrap().status, 201);
+
+    Ok(())
+}
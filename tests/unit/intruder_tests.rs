@@ -0,0 +1,162 @@
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use ferrum::intruder::attack::{Attack, AttackMode};
+use ferrum::intruder::payload::PayloadSet;
+use ferrum::intruder::template::Template;
+use ferrum::proxy::forward::Forwarder;
+use crate::test_utils::init_test_logging;
+
+const TEMPLATE: &[u8] = "POST /login?lang=§en§ HTTP/1.1\r\nHost: example.com\r\nContent-Length: 20\r\n\r\nuser=§admin§&pass=§x§".as_bytes();
+
+fn attack(mode: AttackMode, sets: Vec<PayloadSet>) -> Result<Attack> {
+    Attack::new(Template::parse(TEMPLATE, false)?, mode, sets, Forwarder::new())
+}
+
+fn words(payloads: &[&str]) -> PayloadSet {
+    PayloadSet::list(payloads.iter().map(|payload| payload.as_bytes()))
+}
+
+fn rendered(attack: &Attack, index: usize) -> Vec<Option<String>> {
+    attack
+        .payloads_for(index)
+        .into_iter()
+        .map(|payload| payload.map(|payload| String::from_utf8(payload).unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_template_markers_and_render() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let template = Template::parse(TEMPLATE, false)?;
+    assert_eq!(template.positions(), 3);
+    assert_eq!(template.defaults(), vec![&b"en"[..], b"admin", b"x"]);
+    assert_eq!(template.to_marked(), TEMPLATE);
+
+    let request = template.render(&[None, Some(b"root"), Some(b"hunter22")])?;
+    assert_eq!(request.uri, "http://example.com/login?lang=en");
+    assert_eq!(request.body, b"user=root&pass=hunter22");
+    assert_eq!(request.header("content-length"), Some("23"), "Content-Length follows the payload");
+
+    assert!(Template::parse("GET /§a HTTP/1.1\r\nHost: x\r\n\r\n".as_bytes(), false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_template_auto_marks_parameters() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("POST", "https://example.com/api?id=7&debug");
+    request.headers.push(("Host".to_string(), "example.com".to_string()));
+    request.headers.push(("Cookie".to_string(), "session=abc; theme=dark".to_string()));
+    request.headers.push(("Content-Type".to_string(), "application/json".to_string()));
+    request.body = br#"{"name": "bob", "age": 30, "tags": ["a"], "ok": true}"#.to_vec();
+
+    let template = Template::auto(&request)?;
+    let defaults: Vec<String> = template
+        .defaults()
+        .into_iter()
+        .map(|value| String::from_utf8_lossy(value).into_owned())
+        .collect();
+    assert_eq!(defaults, vec!["7", "abc", "dark", "bob", "30", "a", "true"]);
+
+    // Rendering the defaults gives back the original request
+    assert_eq!(template.render(&[])?, request);
+
+    let mut form = FlowRequest::new("POST", "http://example.com/login");
+    form.headers.push(("Content-Type".to_string(), "application/x-www-form-urlencoded".to_string()));
+    form.body = b"user=a&pass=b".to_vec();
+    let template = Template::auto(&form)?;
+    assert_eq!(template.positions(), 2);
+    assert_eq!(template.render(&[Some(b"x"), Some(b"y")])?.body, b"user=x&pass=y");
+
+    Ok(())
+}
+
+#[rstest]
+#[case("list:a,b,c", &["a", "b", "c"])]
+#[case("range:8..11", &["8", "9", "10", "11"])]
+#[case("range:001..010:3", &["001", "004", "007", "010"])]
+#[case("range:3..-3:-3", &["3", "0", "-3"])]
+#[case("chars:ab:1..5:2", &["ab", "ababab", "ababababab"])]
+#[tokio::test]
+async fn test_payload_specs(#[case] spec: &str, #[case] expected: &[&str]) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let set = PayloadSet::parse_spec(spec, None)?;
+    let payloads: Vec<String> = set.iter().map(|p| String::from_utf8(p).unwrap()).collect();
+    assert_eq!(payloads, expected);
+    assert_eq!(set.len(), expected.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_payloads_from_files_and_responses() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("words.txt");
+    std::fs::write(&path, "admin\r\n\nroot\nguest\n")?;
+    assert_eq!(PayloadSet::from_file(&path)?, words(&["admin", "root", "guest"]));
+
+    let mut flows = Vec::new();
+    for body in [r#"{"csrf":"t1","next":"t2"}"#, r#"{"csrf":"t1"}"#, r#"{"csrf":"t3"}"#] {
+        let mut flow = Flow::new(FlowRequest::new("GET", "http://example.com/"));
+        let mut response = FlowResponse::new(200);
+        response.body = body.as_bytes().to_vec();
+        flow.response = Some(response);
+        flows.push(flow);
+    }
+    let set = PayloadSet::from_responses(&flows, r#""csrf":"(\w+)""#)?;
+    assert_eq!(set, words(&["t1", "t3"]));
+
+    assert!(PayloadSet::parse_spec("extract:x", None).is_err(), "extract needs a project");
+    assert!(PayloadSet::parse_spec("range:5..1", None).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_attack_modes_assign_payloads() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let sniper = attack(AttackMode::Sniper, vec![words(&["1", "2"])])?;
+    assert_eq!(sniper.request_count(), 6);
+    assert_eq!(rendered(&sniper, 0), vec![Some("1".into()), None, None]);
+    assert_eq!(rendered(&sniper, 3), vec![None, Some("2".into()), None]);
+    assert_eq!(rendered(&sniper, 4), vec![None, None, Some("1".into())]);
+
+    let ram = attack(AttackMode::BatteringRam, vec![words(&["1", "2"])])?;
+    assert_eq!(ram.request_count(), 2);
+    assert_eq!(rendered(&ram, 1), vec![Some("2".into()); 3]);
+
+    let pitchfork = attack(
+        AttackMode::Pitchfork,
+        vec![words(&["en", "de"]), words(&["a", "b", "c"]), words(&["x", "y"])],
+    )?;
+    assert_eq!(pitchfork.request_count(), 2);
+    assert_eq!(rendered(&pitchfork, 1), vec![Some("de".into()), Some("b".into()), Some("y".into())]);
+
+    let cluster = attack(
+        AttackMode::ClusterBomb,
+        vec![words(&["en"]), words(&["a", "b"]), words(&["x", "y", "z"])],
+    )?;
+    assert_eq!(cluster.request_count(), 6);
+    assert_eq!(rendered(&cluster, 1), vec![Some("en".into()), Some("b".into()), Some("x".into())]);
+    assert_eq!(rendered(&cluster, 5), vec![Some("en".into()), Some("b".into()), Some("z".into())]);
+
+    assert!(attack(AttackMode::Pitchfork, vec![words(&["a"])]).is_err(), "Pitchfork needs a set per position");
+    assert!(attack(AttackMode::Sniper, vec![words(&["a"]), words(&["b"])]).is_err());
+    assert!("cluster-bomb".parse::<AttackMode>().is_ok());
+    assert!("shotgun".parse::<AttackMode>().is_err());
+
+    Ok(())
+}
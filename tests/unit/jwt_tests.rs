@@ -0,0 +1,223 @@
+use anyhow::Result;
+use rstest::rstest;
+use serde_json::json;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use ferrum::jwt::attack::{crack, forge, variants, ClaimEdit, Signing};
+use ferrum::jwt::locate::{find_tokens, replace_token};
+use ferrum::jwt::token::Jwt;
+use ferrum::scanner::finding::Location;
+use ferrum::scanner::passive::{JwtWeaknesses, PassiveCheck};
+use crate::test_utils::init_test_logging;
+
+// The jwt.io example token, signed with `your-256-bit-secret`
+const TOKEN: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+                     eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+                     SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+
+const PUBLIC_KEY: &[u8] = b"-----BEGIN PUBLIC KEY-----\nMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE\n-----END PUBLIC KEY-----\n";
+
+#[tokio::test]
+async fn test_decode_and_verify() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let jwt = Jwt::parse(TOKEN)?;
+    assert_eq!(jwt.alg(), Some("HS256"));
+    assert_eq!(jwt.claims["name"], "John Doe");
+    assert_eq!(jwt.signature.len(), 32);
+    assert!(jwt.describe().contains("\"sub\": \"1234567890\""));
+
+    assert!(jwt.verify_hmac(b"your-256-bit-secret"));
+    assert!(!jwt.verify_hmac(b"wrong"));
+    // Untouched tokens re-encode byte for byte
+    assert_eq!(jwt.to_string(), TOKEN);
+    assert_eq!(jwt.sign_hmac(b"your-256-bit-secret")?, TOKEN);
+
+    assert!(Jwt::parse("not.a.jwt").is_err());
+    assert!(Jwt::parse("eyJhbGciOiJub25lIn0.eyJzdWIiOiJ4In0").is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("role=admin", ClaimEdit::Set("role".to_string(), json!("admin")))]
+#[case("admin=true", ClaimEdit::Set("admin".to_string(), json!(true)))]
+#[case("exp=9999999999", ClaimEdit::Set("exp".to_string(), json!(9999999999u64)))]
+#[case("groups=[\"a\",\"b\"]", ClaimEdit::Set("groups".to_string(), json!(["a", "b"])))]
+#[case("-exp", ClaimEdit::Remove("exp".to_string()))]
+#[tokio::test]
+async fn test_claim_edit_parsing(#[case] text: &str, #[case] expected: ClaimEdit) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(text.parse::<ClaimEdit>()?, expected);
+    assert!("role".parse::<ClaimEdit>().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forge_and_crack() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let jwt = Jwt::parse(TOKEN)?;
+    assert_eq!(crack(&jwt, ["nope"]).as_deref(), Some("your-256-bit-secret"));
+    let other = Jwt::parse(&forge(&jwt, &[], &Signing::Secret(b"hunter2".to_vec()))?)?;
+    assert_eq!(crack(&other, []), None);
+    assert_eq!(crack(&other, ["letmein", "hunter2"]).as_deref(), Some("hunter2"));
+
+    let edits = vec!["name=Admin".parse()?, "-iat".parse()?];
+    let resigned = Jwt::parse(&forge(&jwt, &edits, &Signing::Secret(b"your-256-bit-secret".to_vec()))?)?;
+    assert_eq!(resigned.claims["name"], "Admin");
+    assert!(!resigned.claims.contains_key("iat"));
+    assert!(resigned.verify_hmac(b"your-256-bit-secret"));
+
+    let none = forge(&jwt, &edits, &Signing::None("none".to_string()))?;
+    assert!(none.ends_with('.'));
+    assert_eq!(Jwt::parse(&none)?.alg(), Some("none"));
+
+    let kept = Jwt::parse(&forge(&jwt, &edits, &Signing::Original)?)?;
+    assert_eq!(kept.signature, jwt.signature);
+    assert!(!kept.verify_hmac(b"your-256-bit-secret"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_attack_variants() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let rs256 = forge(&Jwt::parse(TOKEN)?, &[], &Signing::None("RS256".to_string()))?;
+    let jwt = Jwt::parse(&format!("{}c2ln", rs256))?;
+    let edits = vec!["sub=admin".parse()?];
+
+    let variants = variants(&jwt, &edits, Some(b"secret"), Some(PUBLIC_KEY))?;
+    let names: Vec<&str> = variants.iter().map(|variant| variant.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec![
+            "original signature",
+            "signature stripped",
+            "alg none",
+            "alg None",
+            "alg NONE",
+            "alg nOnE",
+            "signed with secret \"secret\"",
+            "HS256 keyed with the public key",
+            "HS256 keyed with the public key (final newline toggled)",
+        ]
+    );
+    for variant in &variants {
+        assert_eq!(Jwt::parse(&variant.token)?.claims["sub"], "admin");
+    }
+
+    // Algorithm confusion: HS256 keyed with the exact bytes of the PEM file
+    let confused = Jwt::parse(&variants[7].token)?;
+    assert_eq!(confused.alg(), Some("HS256"));
+    assert!(confused.verify_hmac(PUBLIC_KEY));
+    assert!(Jwt::parse(&variants[8].token)?.verify_hmac(PUBLIC_KEY.trim_ascii_end()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_and_replace_tokens() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("POST", &format!("https://api.example.com/me?access_token={}", TOKEN));
+    request.set_header("Authorization", &format!("Bearer {}", TOKEN));
+    request.set_header("Cookie", &format!("theme=dark; session={}", TOKEN));
+    request.set_header("Content-Type", "application/json");
+    request.body = format!(r#"{{"refresh":"{}"}}"#, TOKEN).into_bytes();
+    request.set_header("Content-Length", &request.body.len().to_string());
+    let mut flow = Flow::new(request);
+    let mut response = FlowResponse::new(200);
+    response.body = format!(r#"{{"token":"{}"}}"#, TOKEN).into_bytes();
+    flow.response = Some(response);
+
+    let found = find_tokens(&flow);
+    let sources: Vec<&str> = found.iter().map(|found| found.source.as_str()).collect();
+    assert_eq!(
+        sources,
+        vec!["URL", "request header Authorization", "request header Cookie", "request body", "response body"]
+    );
+    assert_eq!(found[1].evidence.location, Location::RequestHeader(0));
+    for found in &found {
+        assert_eq!(found.evidence.bytes(&flow).as_deref(), Some(TOKEN.as_bytes()));
+    }
+
+    let replaced = replace_token(&flow.request, TOKEN, "eyJ.new.");
+    assert_eq!(replaced.uri, "https://api.example.com/me?access_token=eyJ.new.");
+    assert_eq!(replaced.header("authorization"), Some("Bearer eyJ.new."));
+    assert_eq!(replaced.header("cookie"), Some("theme=dark; session=eyJ.new."));
+    assert_eq!(replaced.body, br#"{"refresh":"eyJ.new."}"#);
+    assert_eq!(replaced.header("content-length"), Some("22"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_and_replace_tokens_in_encoded_bodies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // A gzip body in windows-1252 with a byte that is not valid UTF-8 ahead of the token
+    let mut content = b"name=Jos\xe9&token=".to_vec();
+    content.extend_from_slice(TOKEN.as_bytes());
+    let mut request = FlowRequest::new("POST", "https://api.example.com/login");
+    request.set_header("Content-Type", "application/x-www-form-urlencoded; charset=windows-1252");
+    request.set_header("Content-Encoding", "gzip");
+    request.body = Transform::Gzip.encode(&content)?;
+    request.set_header("Content-Length", &request.body.len().to_string());
+    let flow = Flow::new(request);
+
+    let found = find_tokens(&flow);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].evidence.location, Location::RequestBody);
+    assert_eq!(found[0].evidence.bytes(&flow).as_deref(), Some(TOKEN.as_bytes()));
+
+    let replaced = replace_token(&flow.request, TOKEN, "eyJ.new.");
+    assert_eq!(replaced.header("content-encoding"), Some("gzip"));
+    assert_eq!(Transform::Gzip.decode(&replaced.body)?, b"name=Jos\xe9&token=eyJ.new.");
+    assert_eq!(replaced.header("content-length"), Some(replaced.body.len().to_string().as_str()));
+
+    // A body that cannot be decoded is rewritten byte for byte
+    let mut request = FlowRequest::new("POST", "https://api.example.com/login");
+    request.set_header("Content-Encoding", "compress");
+    request.body = [b"\xff\xfe".as_slice(), TOKEN.as_bytes()].concat();
+    let replaced = replace_token(&request, TOKEN, "eyJ.new.");
+    assert_eq!(replaced.body, b"\xff\xfeeyJ.new.");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_passive_jwt_check() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let with_token = |token: &str| {
+        let mut request = FlowRequest::new("GET", "https://api.example.com/me");
+        request.set_header("Authorization", &format!("Bearer {}", token));
+        Flow::new(request)
+    };
+    let jwt = Jwt::parse(TOKEN)?;
+
+    let findings = JwtWeaknesses.scan(&with_token(TOKEN));
+    assert_eq!(findings.len(), 1);
+    assert_eq!(findings[0].check, "jwt-weak-secret");
+    assert!(findings[0].detail.contains("your-256-bit-secret"));
+
+    let unsigned = forge(&jwt, &[], &Signing::None("none".to_string()))?;
+    let findings = JwtWeaknesses.scan(&with_token(&unsigned));
+    assert_eq!(findings[0].check, "jwt-alg-none");
+
+    let strong = forge(&jwt, &[], &Signing::Secret(b"f3b1c0de-5a7e-4d1e-9c2b-strong".to_vec()))?;
+    assert!(JwtWeaknesses.scan(&with_token(&strong)).is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use anyhow::Result;
+use rstest::rstest;
+use ferrum::flow::model::FlowRequest;
+use ferrum::rules::map_local::{content_type, MapLocal, MapLocalRule};
+use ferrum::rules::matcher::RuleMatch;
+use crate::test_utils::init_test_logging;
+
+#[rstest]
+#[case("app.js", "application/javascript; charset=utf-8")]
+#[case("INDEX.HTML", "text/html; charset=utf-8")]
+#[case("data.json", "application/json")]
+#[case("logo.svg", "image/svg+xml")]
+#[case("blob.bin", "application/octet-stream")]
+#[case("README", "application/octet-stream")]
+#[tokio::test]
+async fn test_content_type_guess(#[case] file: &str, #[case] expected: &str) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(content_type(Path::new(file)), expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rule_shorthand() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let rule: MapLocalRule = "https://*.example.com/static/*=./build".parse()?;
+    assert_eq!(rule.matcher.host.as_deref(), Some("*.example.com"));
+    assert_eq!(rule.matcher.path.as_deref(), Some("/static/*"));
+    assert_eq!(rule.local, Path::new("./build"));
+
+    let rule: MapLocalRule = "api.example.com=stub.json".parse()?;
+    assert_eq!(rule.matcher, RuleMatch::default().with_host("api.example.com"));
+
+    assert!("https://example.com/app.js".parse::<MapLocalRule>().is_err());
+    assert!("/app.js=app.js".parse::<MapLocalRule>().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_map_file_with_custom_status_and_headers() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("maintenance.html");
+    std::fs::write(&file, "<h1>Back soon</h1>")?;
+
+    let mut map_local = MapLocal::new();
+    map_local.add(
+        MapLocalRule::new(RuleMatch::default().with_host("example.com").with_method("GET"), &file)
+            .with_status(503)
+            .with_header("Retry-After", "120"),
+    );
+
+    assert!(map_local.resolve(&FlowRequest::new("POST", "http://example.com/")).is_none());
+    assert!(map_local.resolve(&FlowRequest::new("GET", "http://other.test/")).is_none());
+
+    let response = map_local.resolve(&FlowRequest::new("GET", "http://example.com/any/page")).unwrap().read().await;
+    assert_eq!(response.status, 503);
+    assert_eq!(response.body, b"<h1>Back soon</h1>");
+    assert_eq!(response.header("content-type"), Some("text/html; charset=utf-8"));
+    assert_eq!(response.header("retry-after"), Some("120"));
+    assert_eq!(response.header("content-length"), Some("18"));
+
+    // Disabled rules fall through to upstream
+    map_local.set_enabled(0, false)?;
+    assert!(map_local.resolve(&FlowRequest::new("GET", "http://example.com/")).is_none());
+    assert!(map_local.set_enabled(3, true).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_map_directory() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir_all(dir.path().join("js"))?;
+    std::fs::write(dir.path().join("index.html"), "home")?;
+    std::fs::write(dir.path().join("js").join("app v2.js"), "console.log(1)")?;
+    std::fs::write(dir.path().join("js").join("index.html"), "scripts")?;
+
+    let map_local = MapLocal::from_rules(vec![MapLocalRule::new(
+        RuleMatch::default().with_host("example.com").with_path("/static/*"),
+        dir.path(),
+    )]);
+    let read = |uri: &str| {
+        let local = map_local.resolve(&FlowRequest::new("GET", uri)).unwrap();
+        async move { local.read().await }
+    };
+
+    let script = read("http://example.com/static/js/app%20v2.js?v=3").await;
+    assert_eq!(script.status, 200);
+    assert_eq!(script.body, b"console.log(1)");
+    assert_eq!(script.header("content-type"), Some("application/javascript; charset=utf-8"));
+
+    assert_eq!(read("http://example.com/static/").await.body, b"home");
+    assert_eq!(read("http://example.com/static/js").await.body, b"scripts");
+
+    // Missing files and escapes from the directory are answered with a 404, never forwarded
+    assert_eq!(read("http://example.com/static/missing.css").await.status, 404);
+    assert_eq!(read("http://example.com/static/js/..%2F..%2Fsecret").await.status, 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rules_load_from_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("map-local.json");
+    std::fs::write(
+        &path,
+        r#"[
+            {"name": "stub api", "match": {"host": "api.example.com", "path": "/v1/*"}, "local": "stub.json",
+             "status": 201, "headers": {"X-Stub": "1"}},
+            {"local": "./build", "enabled": false}
+        ]"#,
+    )?;
+
+    let map_local = MapLocal::load(&path)?;
+    let rules = map_local.rules();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].status, Some(201));
+    assert_eq!(rules[0].headers["X-Stub"], "1");
+    assert!(!rules[1].enabled);
+    assert_eq!(rules[1].matcher, RuleMatch::default());
+
+    std::fs::write(&path, r#"[{"match": {}}]"#)?;
+    assert!(MapLocal::load(&path).is_err());
+
+    Ok(())
+}
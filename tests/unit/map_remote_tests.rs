@@ -0,0 +1,174 @@
+use anyhow::Result;
+use httpmock::MockServer;
+use rstest::rstest;
+use ferrum::flow::model::{Flow, FlowRequest};
+use ferrum::project::store::Project;
+use ferrum::proxy::forward::Forwarder;
+use ferrum::rules::map_remote::{HostHeader, Location, MapRemote, MapRemoteRule};
+use crate::test_utils::init_test_logging;
+
+#[rstest]
+#[case("https://api.example.com/v1", Some("https"), "api.example.com", None, "/v1")]
+#[case("HTTP://LocalHost:3000", Some("http"), "localhost", Some(3000), "")]
+#[case("*.example.com/", None, "*.example.com", None, "/")]
+#[case("http://[::1]:8080/api", Some("http"), "[::1]", Some(8080), "/api")]
+#[case("[::1]", None, "[::1]", None, "")]
+#[tokio::test]
+async fn test_location_parsing(
+    #[case] text: &str,
+    #[case] scheme: Option<&str>,
+    #[case] host: &str,
+    #[case] port: Option<u16>,
+    #[case] path: &str,
+) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let location: Location = text.parse()?;
+    assert_eq!(location.scheme.as_deref(), scheme);
+    assert_eq!(location.host, host);
+    assert_eq!(location.port, port);
+    assert_eq!(location.path, path);
+    assert_eq!(location.to_string().parse::<Location>()?, location);
+
+    assert!("ftp://example.com".parse::<Location>().is_err());
+    assert!("example.com:http".parse::<Location>().is_err());
+    assert!("https:///path".parse::<Location>().is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("https://api.example.com/v1/users?page=2", Some("http://localhost:3000/api/users?page=2"))]
+#[case("https://api.example.com/v1", Some("http://localhost:3000/api"))]
+#[case("https://api.example.com/v10/users", None)]
+#[case("http://api.example.com/v1/users", None)]
+#[case("https://api.example.com:8443/v1/users", None)]
+#[case("https://www.example.com/v1/users", None)]
+#[tokio::test]
+async fn test_rewrite_prefix(#[case] uri: &str, #[case] expected: Option<&str>) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let map_remote = MapRemote::from_rules(vec!["https://api.example.com/v1=http://localhost:3000/api/".parse()?]);
+    let mut request = FlowRequest::new("GET", uri);
+
+    let original = map_remote.apply(&mut request);
+    match expected {
+        Some(expected) => {
+            assert_eq!(original.as_deref(), Some(uri));
+            assert_eq!(request.uri, expected);
+            assert_eq!(request.header("host"), Some("localhost:3000"));
+        }
+        None => {
+            assert_eq!(original, None);
+            assert_eq!(request.uri, uri);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_host_header_and_rule_order() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut map_remote = MapRemote::new();
+    map_remote.add(
+        MapRemoteRule::new("*.example.com".parse()?, "https://staging.test".parse()?)
+            .with_host_header(HostHeader::Preserve),
+    );
+    map_remote.add(MapRemoteRule::new("cdn.example.com".parse()?, "http://unused.test".parse()?));
+    map_remote.add(
+        MapRemoteRule::new("example.org".parse()?, "127.0.0.1:9000".parse()?)
+            .with_host_header(HostHeader::Set("tenant.example.org".to_string())),
+    );
+
+    // The first matching rule wins; the scheme and port of the original are kept when `to` leaves them out
+    let mut request = FlowRequest::new("GET", "http://cdn.example.com:8080/a.js");
+    request.set_header("Host", "cdn.example.com:8080");
+    map_remote.apply(&mut request);
+    assert_eq!(request.uri, "https://staging.test/a.js");
+    assert_eq!(request.header("host"), Some("cdn.example.com:8080"));
+
+    let mut request = FlowRequest::new("POST", "http://example.org/login");
+    map_remote.apply(&mut request);
+    assert_eq!(request.uri, "http://127.0.0.1:9000/login");
+    assert_eq!(request.header("host"), Some("tenant.example.org"));
+
+    map_remote.set_enabled(0, false)?;
+    let mut request = FlowRequest::new("GET", "https://cdn.example.com/a.js");
+    map_remote.apply(&mut request);
+    assert_eq!(request.uri, "http://unused.test/a.js");
+    assert!(map_remote.set_enabled(9, false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rules_load_from_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("map-remote.json");
+    std::fs::write(
+        &path,
+        r#"[
+            {"name": "dev api", "from": "https://api.example.com/v1", "to": "http://localhost:3000", "host_header": "preserve"},
+            {"from": "example.org", "to": "example.net", "host_header": {"set": "example.org"}, "enabled": false}
+        ]"#,
+    )?;
+
+    let map_remote = MapRemote::load(&path)?;
+    let rules = map_remote.rules();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].to.port, Some(3000));
+    assert_eq!(rules[0].host_header, HostHeader::Preserve);
+    assert_eq!(rules[1].host_header, HostHeader::Set("example.org".to_string()));
+    assert!(!rules[1].enabled);
+    assert_eq!(serde_json::to_value(&rules[0].from)?, "https://api.example.com/v1");
+
+    std::fs::write(&path, r#"[{"from": "gopher://example.com", "to": "example.net"}]"#)?;
+    assert!(MapRemote::load(&path).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forwarder_records_original_and_effective_target() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET").path("/api/users").header("host", "api.example.com");
+        then.status(200).body("local dev server");
+    });
+
+    let rule: MapRemoteRule = format!("https://api.example.com/v1={}/api", server.base_url()).parse()?;
+    let forwarder = Forwarder::new().with_map_remote(MapRemote::from_rules(vec![
+        rule.with_host_header(HostHeader::Preserve),
+    ]));
+
+    let flow = forwarder.forward(FlowRequest::new("GET", "https://api.example.com/v1/users")).await?;
+    mock.assert();
+    assert_eq!(flow.original_uri.as_deref(), Some("https://api.example.com/v1/users"));
+    assert_eq!(flow.request.uri, server.url("/api/users"));
+    assert_eq!(flow.tls, None);
+    assert_eq!(flow.response.as_ref().unwrap().body, b"local dev server");
+
+    // Both targets survive a round trip through a project
+    let project = Project::in_memory()?;
+    let id = project.save_flow(&flow)?;
+    let loaded = project.load_flow(id)?.unwrap();
+    assert_eq!(loaded.original_uri, flow.original_uri);
+    assert_eq!(loaded.request.uri, flow.request.uri);
+
+    let unmapped = Flow::new(FlowRequest::new("GET", "http://example.com/"));
+    let id = project.save_flow(&unmapped)?;
+    assert_eq!(project.load_flow(id)?.unwrap().original_uri, None);
+
+    Ok(())
+}
@@ -0,0 +1,346 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use httpmock::prelude::*;
+use serde_json::json;
+use ferrum::flow::model::{FlowRequest, FlowResponse};
+use ferrum::intercept::plugin::{PluginConfig, PluginInterceptor};
+use crate::test_utils::init_test_logging;
+
+// Memory, a bump allocator and the log import that every test plugin shares
+const PRELUDE: &str = r#"
+  (import "ferrum" "log" (func $log (param i32 i32 i32)))
+  (import "ferrum" "http_request" (func $http_request (param i32 i32) (result i64)))
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 4096))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $heap))
+    (global.set $heap (i32.add (local.get $ptr) (local.get $len)))
+    (block $done
+      (loop $grow
+        (br_if $done (i32.le_u (global.get $heap) (i32.mul (memory.size) (i32.const 65536))))
+        (br_if $done (i32.eq (memory.grow (i32.const 1)) (i32.const -1)))
+        (br $grow)))
+    (local.get $ptr))
+"#;
+
+// Text placed in a data segment, with where it starts and its length packed for returning
+fn data(offset: usize, text: &str) -> (String, i64) {
+    let escaped = text.replace('\\', "\\\\").replace('"', "\\\"");
+    let segment = format!(r#"(data (i32.const {}) "{}")"#, offset, escaped);
+    (segment, ((offset as i64) << 32) | text.len() as i64)
+}
+
+fn write_plugin(dir: &Path, name: &str, body: &str) -> Result<PathBuf> {
+    let wasm = wat::parse_str(format!("(module {} {})", PRELUDE, body))?;
+    let path = dir.join(name);
+    std::fs::write(&path, wasm)?;
+    Ok(path)
+}
+
+#[tokio::test]
+async fn test_request_and_response_hooks() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let request_output = json!({
+        "request": {
+            "method": "PUT",
+            "uri": "http://api.example.com/users/1",
+            "headers": [["X-Plugin", "rewrite"]],
+            "body": "eyJyb2xlIjoiYWRtaW4ifQ=="
+        },
+        "tags": ["rewritten"]
+    });
+    let response_output = json!({"response": {"status": 201, "body": "AAEC"}, "tags": ["binary"]});
+    let (log_data, log_text) = data(0, "rewriting request");
+    let (request_data, request_packed) = data(64, &request_output.to_string());
+    let (response_data, response_packed) = data(1024, &response_output.to_string());
+    let rewrite = write_plugin(
+        dir.path(),
+        "rewrite.wasm",
+        &format!(
+            r#"{} {} {}
+            (func (export "on_request") (param i32 i32) (result i64)
+              (call $log (i32.const 2) (i32.const 0) (i32.const {}))
+              (i64.const {}))
+            (func (export "on_response") (param i32 i32) (result i64)
+              (i64.const {}))"#,
+            log_data,
+            request_data,
+            response_data,
+            log_text & 0xffff_ffff,
+            request_packed,
+            response_packed
+        ),
+    )?;
+    let (answer_data, answer_packed) = data(0, r#"{"response":{"status":403,"body":"ZGVuaWVk"},"tags":["denied"]}"#);
+    let answer = write_plugin(
+        dir.path(),
+        "answer.wasm",
+        &format!(
+            r#"{}
+            (func (export "on_request") (param i32 i32) (result i64) (i64.const {}))"#,
+            answer_data, answer_packed
+        ),
+    )?;
+    let passthrough = write_plugin(
+        dir.path(),
+        "passthrough.wasm",
+        r#"(func (export "on_response") (param i32 i32) (result i64) (i64.const 0))"#,
+    )?;
+
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&rewrite))?;
+    plugins.load(&PluginConfig::new(&passthrough))?;
+    assert_eq!(plugins.names(), vec!["rewrite.wasm", "passthrough.wasm"]);
+
+    let mut request = FlowRequest::new("POST", "http://api.example.com/users");
+    request.body = b"{}".to_vec();
+    let outcome = plugins.on_request(&mut request).await;
+    assert_eq!(outcome.response, None);
+    assert_eq!(outcome.tags, vec!["rewritten"]);
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.uri, "http://api.example.com/users/1");
+    assert_eq!(request.header("x-plugin"), Some("rewrite"));
+    assert_eq!(request.body, br#"{"role":"admin"}"#);
+
+    let mut response = FlowResponse::new(200);
+    let tags = plugins.on_response(&request, &mut response).await;
+    assert_eq!(tags, vec!["binary"]);
+    assert_eq!(response.status, 201);
+    assert_eq!(response.body, vec![0, 1, 2]);
+
+    // A plugin that answers stops the ones after it
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&answer))?;
+    plugins.load(&PluginConfig::new(&rewrite))?;
+    let mut request = FlowRequest::new("GET", "http://example.com/admin");
+    let outcome = plugins.on_request(&mut request).await;
+    assert_eq!(outcome.tags, vec!["denied"]);
+    assert_eq!(outcome.response.map(|response| (response.status, response.body)), Some((403, b"denied".to_vec())));
+    assert_eq!(request.method, "GET");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_configuration() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // The plugin keeps its configuration and returns it from on_request;
+    // configurations shorter than 5 bytes, like `null`, are rejected
+    let dir = tempfile::tempdir()?;
+    let path = write_plugin(
+        dir.path(),
+        "configured.wasm",
+        r#"
+        (global $config (mut i32) (i32.const 0))
+        (global $config_len (mut i32) (i32.const 0))
+        (func (export "configure") (param $ptr i32) (param $len i32) (result i32)
+          (global.set $config (local.get $ptr))
+          (global.set $config_len (local.get $len))
+          (i32.lt_u (local.get $len) (i32.const 5)))
+        (func (export "on_request") (param i32 i32) (result i64)
+          (i64.or
+            (i64.shl (i64.extend_i32_u (global.get $config)) (i64.const 32))
+            (i64.extend_i32_u (global.get $config_len))))"#,
+    )?;
+
+    let error = PluginInterceptor::new().load(&PluginConfig::new(&path)).unwrap_err();
+    assert!(error.to_string().contains("rejected its configuration"), "{:#}", error);
+
+    std::fs::write(
+        dir.path().join("plugins.json"),
+        r#"[{"path": "configured.wasm", "name": "tagger", "config": {"tags": ["from-config"]}, "max_fuel": 5000}]"#,
+    )?;
+    let configs = PluginConfig::load(&dir.path().join("plugins.json"))?;
+    assert_eq!(
+        configs,
+        vec![PluginConfig {
+            name: Some("tagger".to_string()),
+            ..PluginConfig::new(&path)
+                .with_config(json!({"tags": ["from-config"]}))
+                .with_max_fuel(5000)
+        }]
+    );
+
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&configs[0])?;
+    assert_eq!(plugins.names(), vec!["tagger"]);
+    let outcome = plugins.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+    assert_eq!(outcome.tags, vec!["from-config"]);
+
+    assert!("".parse::<PluginConfig>().is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resource_limits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let spin = write_plugin(
+        dir.path(),
+        "spin.wasm",
+        r#"(func (export "on_request") (param i32 i32) (result i64) (loop $forever (br $forever)) (i64.const 0))"#,
+    )?;
+    let (tag_data, tag_packed) = data(0, r#"{"tags":["reached"]}"#);
+    let tagger = write_plugin(
+        dir.path(),
+        "tagger.wasm",
+        &format!(r#"{} (func (export "on_request") (param i32 i32) (result i64) (i64.const {}))"#, tag_data, tag_packed),
+    )?;
+    let (garbage_data, garbage_packed) = data(0, "not json");
+    let garbage = write_plugin(
+        dir.path(),
+        "garbage.wasm",
+        &format!(r#"{} (func (export "on_request") (param i32 i32) (result i64) (i64.const {}))"#, garbage_data, garbage_packed),
+    )?;
+
+    // A plugin that runs out of fuel or returns garbage changes nothing, and the next one still runs
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&spin).with_max_fuel(10_000))?;
+    plugins.load(&PluginConfig::new(&garbage))?;
+    plugins.load(&PluginConfig::new(&tagger))?;
+    let mut request = FlowRequest::new("GET", "http://example.com/");
+    let original = request.clone();
+    let outcome = plugins.on_request(&mut request).await;
+    assert_eq!(request, original);
+    assert_eq!(outcome.tags, vec!["reached"]);
+
+    // Growing memory past the limit fails
+    let (grew_data, grew_packed) = data(0, r#"{"tags":["grew"]}"#);
+    let greedy = write_plugin(
+        dir.path(),
+        "greedy.wasm",
+        &format!(
+            r#"{}
+            (func (export "on_request") (param i32 i32) (result i64)
+              (if (i32.eq (memory.grow (i32.const 32)) (i32.const -1)) (then unreachable))
+              (i64.const {}))"#,
+            grew_data, grew_packed
+        ),
+    )?;
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&greedy).with_max_memory(1024 * 1024))?;
+    plugins.load(&PluginConfig::new(&tagger))?;
+    let outcome = plugins.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+    assert_eq!(outcome.tags, vec!["reached"]);
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&greedy))?;
+    let outcome = plugins.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+    assert_eq!(outcome.tags, vec!["grew"]);
+
+    // Modules needing more than the limit, other imports or no hooks are refused
+    let big = dir.path().join("big.wasm");
+    std::fs::write(
+        &big,
+        wat::parse_str(r#"(module (memory (export "memory") 32) (func (export "alloc") (param i32) (result i32) (i32.const 0)) (func (export "on_request") (param i32 i32) (result i64) (i64.const 0)))"#)?,
+    )?;
+    assert!(PluginInterceptor::new().load(&PluginConfig::new(&big).with_max_memory(1024 * 1024)).is_err());
+    PluginInterceptor::new().load(&PluginConfig::new(&big))?;
+
+    let wasi = dir.path().join("wasi.wasm");
+    std::fs::write(
+        &wasi,
+        wat::parse_str(r#"(module (import "wasi_snapshot_preview1" "fd_write" (func (param i32 i32 i32 i32) (result i32))) (memory (export "memory") 1) (func (export "alloc") (param i32) (result i32) (i32.const 0)) (func (export "on_request") (param i32 i32) (result i64) (i64.const 0)))"#)?,
+    )?;
+    assert!(PluginInterceptor::new().load(&PluginConfig::new(&wasi)).is_err());
+    let hookless = write_plugin(dir.path(), "hookless.wasm", "")?;
+    assert!(PluginInterceptor::new().load(&PluginConfig::new(&hookless)).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_http_sub_requests() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let token_mock = server.mock(|when, then| {
+        when.method(GET).path("/token");
+        then.status(200).body("secret-token");
+    });
+
+    // The plugin fetches a URL and answers the request with whatever came back
+    let dir = tempfile::tempdir()?;
+    let sub_request = json!({"method": "GET", "uri": server.url("/token")}).to_string();
+    let (request_data, _) = data(0, &sub_request);
+    let path = write_plugin(
+        dir.path(),
+        "fetch.wasm",
+        &format!(
+            r#"{}
+            (func (export "on_request") (param i32 i32) (result i64)
+              (call $http_request (i32.const 0) (i32.const {})))"#,
+            request_data,
+            sub_request.len()
+        ),
+    )?;
+
+    // Sub-requests are refused unless the host is allowed
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&path))?;
+    let outcome = plugins.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+    assert_eq!(outcome.response, None);
+    token_mock.assert_hits(0);
+
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&path).with_allow_host("127.0.0.1"))?;
+    let outcome = plugins.on_request(&mut FlowRequest::new("GET", "http://example.com/")).await;
+    let response = outcome.response.unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"secret-token");
+    token_mock.assert();
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restart_after_trap() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Every second call marks the instance as poisoned and traps; a call on a
+    // poisoned instance would report it
+    let dir = tempfile::tempdir()?;
+    let (clean_data, clean_packed) = data(0, r#"{"tags":["clean"]}"#);
+    let (poisoned_data, poisoned_packed) = data(64, r#"{"tags":["poisoned"]}"#);
+    let path = write_plugin(
+        dir.path(),
+        "flaky.wasm",
+        &format!(
+            r#"{} {}
+            (global $calls (mut i32) (i32.const 0))
+            (global $poisoned (mut i32) (i32.const 0))
+            (func (export "dealloc") (param i32 i32))
+            (func (export "on_request") (param i32 i32) (result i64)
+              (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+              (if (i32.eq (global.get $calls) (i32.const 2))
+                (then (global.set $poisoned (i32.const 1)) unreachable))
+              (if (result i64) (global.get $poisoned)
+                (then (i64.const {}))
+                (else (i64.const {}))))"#,
+            clean_data, poisoned_data, poisoned_packed, clean_packed
+        ),
+    )?;
+
+    let mut plugins = PluginInterceptor::new();
+    plugins.load(&PluginConfig::new(&path))?;
+    let mut tags = Vec::new();
+    for _ in 0..4 {
+        let mut request = FlowRequest::new("GET", "http://example.com/");
+        let original = request.clone();
+        tags.push(plugins.on_request(&mut request).await.tags);
+        assert_eq!(request, original);
+    }
+    assert_eq!(tags, vec![vec!["clean"], vec![], vec!["clean"], vec![]]);
+
+    Ok(())
+}
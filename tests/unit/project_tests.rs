@@ -0,0 +1,158 @@
+use anyhow::Result;
+use chrono::Utc;
+use tempfile::TempDir;
+use ferrum::flow::model::{
+    Direction, Flow, FlowRequest, FlowResponse, TlsInfo, WebSocketMessage, WebSocketOpcode,
+};
+use ferrum::project::store::{BodyPart, Project};
+use crate::test_utils::init_test_logging;
+
+fn sample_flow() -> Flow {
+    let mut request = FlowRequest::new("POST", "https://example.com/api/login");
+    request.headers.push(("Content-Type".to_string(), "application/json".to_string()));
+    request.body = br#"{"user":"admin"}"#.to_vec();
+
+    let mut response = FlowResponse::new(200);
+    response.headers.push(("Set-Cookie".to_string(), "session=abc".to_string()));
+    response.body = vec![b'x'; 100_000];
+
+    let mut flow = Flow::new(request);
+    flow.response = Some(response);
+    flow.duration_ms = Some(42);
+    flow.tls = Some(TlsInfo {
+        server_name: Some("example.com".to_string()),
+        protocol_version: Some("TLSv1.3".to_string()),
+        cipher_suite: Some("TLS13_AES_128_GCM_SHA256".to_string()),
+        alpn_protocol: Some("http/1.1".to_string()),
+    });
+    flow.websocket_messages.push(WebSocketMessage {
+        timestamp: Utc::now(),
+        direction: Direction::ServerToClient,
+        opcode: WebSocketOpcode::Text,
+        payload: b"hello".to_vec(),
+    });
+    flow.tags = vec!["auth".to_string(), "login".to_string()];
+    flow.notes = Some("Check for user enumeration".to_string());
+    flow
+}
+
+#[tokio::test]
+async fn test_project_round_trips_flows() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let project = Project::in_memory()?;
+    let flow = sample_flow();
+
+    let id = project.save_flow(&flow)?;
+    let loaded = project.load_flow(id)?.expect("Flow should exist");
+
+    // Everything but the assigned ID should survive the round trip
+    assert_eq!(loaded.id, id);
+    assert_eq!(loaded.request, flow.request);
+    assert_eq!(loaded.response, flow.response);
+    assert_eq!(loaded.tls, flow.tls);
+    assert_eq!(loaded.websocket_messages.len(), 1);
+    assert_eq!(loaded.websocket_messages[0].payload, b"hello");
+    assert_eq!(loaded.tags, flow.tags);
+    assert_eq!(loaded.notes, flow.notes);
+    assert_eq!(loaded.duration_ms, Some(42));
+
+    assert!(project.load_flow(id + 1)?.is_none(), "Unknown flow should not load");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_project_reopens_and_continues_session() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("engagement.ferrum");
+
+    let first_id = {
+        let project = Project::open(&path)?;
+        project.save_flow(&sample_flow())?
+    };
+
+    // Reopening the file keeps earlier flows and appends new ones
+    let project = Project::open(&path)?;
+    assert_eq!(project.flow_count()?, 1);
+
+    let second_id = project.save_flow(&sample_flow())?;
+    assert!(second_id > first_id, "New flows should get fresh IDs");
+
+    project.add_tag(first_id, "reviewed")?;
+    project.set_notes(first_id, Some("Done"))?;
+
+    let summaries = project.list_flows()?;
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(summaries[0].status, Some(200));
+    assert_eq!(summaries[0].response_body_size, 100_000);
+    assert!(summaries[0].tags.contains(&"reviewed".to_string()));
+    assert_eq!(summaries[1].tags, vec!["auth".to_string(), "login".to_string()]);
+
+    let loaded = project.load_flow(first_id)?.unwrap();
+    assert_eq!(loaded.notes.as_deref(), Some("Done"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_project_reads_bodies_lazily() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let project = Project::in_memory()?;
+    let mut flow = sample_flow();
+    flow.response.as_mut().unwrap().body = (0..=255u8).cycle().take(10_000).collect();
+    let id = project.save_flow(&flow)?;
+
+    assert_eq!(project.body_size(id, BodyPart::Response)?, 10_000);
+
+    // Only the requested window is returned
+    let chunk = project.read_body(id, BodyPart::Response, 256, 4)?;
+    assert_eq!(chunk, vec![0, 1, 2, 3]);
+
+    // Reads past the end are clamped
+    let tail = project.read_body(id, BodyPart::Response, 9_998, 100)?;
+    assert_eq!(tail.len(), 2);
+
+    let request = project.read_body(id, BodyPart::Request, 0, 1024)?;
+    assert_eq!(request, flow.request.body);
+
+    // Metadata loads leave the bodies out and keep everything else
+    let metadata = project.load_flow_metadata(id)?.unwrap();
+    assert!(metadata.request.body.is_empty());
+    assert!(metadata.response.as_ref().unwrap().body.is_empty());
+    assert_eq!(metadata.request.headers, flow.request.headers);
+    assert_eq!(metadata.response.unwrap().headers, flow.response.unwrap().headers);
+    assert_eq!(metadata.tls, flow.tls);
+    assert_eq!(metadata.tags, flow.tags);
+    assert!(project.load_flow_metadata(id + 1)?.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_read_only_project_rejects_writes() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let temp_dir = TempDir::new()?;
+    let path = temp_dir.path().join("old.ferrum");
+
+    let id = Project::open(&path)?.save_flow(&sample_flow())?;
+
+    let project = Project::open_read_only(&path)?;
+    assert!(project.is_read_only());
+    assert!(project.load_flow(id)?.is_some(), "Read-only projects can still be browsed");
+    assert!(project.save_flow(&sample_flow()).is_err(), "Saving should fail");
+    assert!(project.add_tag(id, "nope").is_err(), "Tagging should fail");
+
+    // Opening a missing file read-only is an error rather than creating it
+    assert!(Project::open_read_only(temp_dir.path().join("missing.ferrum")).is_err());
+
+    Ok(())
+}
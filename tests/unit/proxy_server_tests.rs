@@ -1,9 +1,8 @@
 use std::net::SocketAddr;
-use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio::time::{timeout, Duration};
 use anyhow::Result;
-use hyper::{body::Incoming, Request, Uri};
+use hyper::Request;
 use hyper_util::client::legacy::Client;
 use hyper_util::rt::TokioExecutor;
 use hyper_util::client::legacy::connect::HttpConnector;
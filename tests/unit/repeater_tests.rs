@@ -0,0 +1,93 @@
+use anyhow::Result;
+use ferrum::flow::model::{Flow, FlowRequest};
+use ferrum::flow::raw::{format_request, parse_request};
+use ferrum::proxy::forward::Forwarder;
+use ferrum::repeater::session::Repeater;
+use crate::test_utils::init_test_logging;
+
+#[tokio::test]
+async fn test_parse_raw_request_origin_form() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let raw = b"POST /api/login?next=%2F HTTP/1.1\r\nHost: example.com:8443\r\nContent-Type: application/json\r\nContent-Length: 999\r\n\r\n{\"user\":\"a\"}";
+
+    let request = parse_request(raw, true)?;
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.uri, "https://example.com:8443/api/login?next=%2F");
+    assert_eq!(request.version, "HTTP/1.1");
+    assert_eq!(request.header("content-type"), Some("application/json"));
+    assert_eq!(request.body, br#"{"user":"a"}"#, "Body should not be cut to a stale Content-Length");
+
+    let plain = parse_request(raw, false)?;
+    assert!(plain.uri.starts_with("http://example.com:8443/"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_parse_raw_request_as_edited_by_hand() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Bare LF line endings, leading blank line and a trailing newline after the head
+    let raw = b"\nGET http://example.com/a HTTP/1.1\nAccept: */*\n\n\n";
+    let request = parse_request(raw, false)?;
+    assert_eq!(request.uri, "http://example.com/a");
+    assert_eq!(request.headers, vec![("Accept".to_string(), "*/*".to_string())]);
+    assert!(request.body.is_empty());
+
+    assert!(parse_request(b"GET /a HTTP/1.1\r\n\r\n", false).is_err(), "Missing Host should fail");
+    assert!(parse_request(b"", false).is_err());
+    assert!(parse_request(b"GET / HTTP/1.1\r\nHost example.com\r\n\r\n", false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_raw_request_round_trip() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("PUT", "http://example.com/items/3");
+    request.headers.push(("Host".to_string(), "example.com".to_string()));
+    request.headers.push(("X-Token".to_string(), "abc".to_string()));
+    request.body = b"name=new".to_vec();
+
+    let parsed = parse_request(&format_request(&request), false)?;
+    assert_eq!(parsed, request);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_repeater_tabs() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut repeater = Repeater::new(Forwarder::new());
+
+    let mut flow = Flow::new(FlowRequest::new("GET", "http://example.com/history"));
+    flow.id = 42;
+    let from_flow = repeater.open_from_flow(&flow);
+    let from_curl = repeater.open_from_curl("curl -d a=1 http://example.com/form")?;
+    let from_raw = repeater.open_from_raw(b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n", true)?;
+
+    assert_eq!(repeater.tabs().len(), 3);
+    assert_eq!(repeater.tab(from_flow).unwrap().name, "#42 GET http://example.com/history");
+    assert_eq!(repeater.tab(from_curl).unwrap().request.method, "POST");
+    assert_eq!(repeater.tab(from_raw).unwrap().request.uri, "https://example.com/");
+
+    repeater.tab_mut(from_flow).unwrap().request.method = "DELETE".to_string();
+    assert_eq!(repeater.tab(from_flow).unwrap().request.method, "DELETE");
+    assert_eq!(flow.request.method, "GET", "Editing a tab should not touch the original flow");
+
+    assert!(repeater.close_tab(from_curl).is_some());
+    assert!(repeater.tab(from_curl).is_none());
+    assert!(repeater.send(from_curl).await.is_err(), "Sending a closed tab should fail");
+
+    let reopened = repeater.open_tab("new", FlowRequest::new("GET", "http://example.com/"));
+    assert!(reopened > from_raw, "Tab IDs should never be reused");
+
+    Ok(())
+}
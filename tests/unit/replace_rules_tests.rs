@@ -0,0 +1,254 @@
+use anyhow::Result;
+use httpmock::MockServer;
+use rstest::rstest;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use ferrum::proxy::forward::Forwarder;
+use ferrum::rules::matcher::{glob_match, RuleMatch};
+use ferrum::rules::replace::{Part, ReplaceRule, ReplaceRules, Stage};
+use crate::test_utils::init_test_logging;
+
+#[rstest]
+#[case("*", "anything", true)]
+#[case("/api/*", "/api/users/7", true)]
+#[case("/api/*", "/apiv2", false)]
+#[case("/api", "/api/users", false)]
+#[case("*.example.com", "cdn.example.com", true)]
+#[case("*.example.com", "example.com", false)]
+#[case("/users/*/edit", "/users/7/edit", true)]
+#[case("/users/*/edit", "/users/7/view", false)]
+#[tokio::test]
+async fn test_glob_match(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(glob_match(pattern, text), expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rule_match() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut request = FlowRequest::new("POST", "https://API.example.com/v1/items?page=2");
+    request.set_header("Content-Type", "application/json; charset=utf-8");
+
+    assert!(RuleMatch::default().matches_request(&request));
+    assert!(RuleMatch::default().with_host("*.example.com").with_path("/v1/*").matches_request(&request));
+    assert!(RuleMatch::default().with_host("api.example.com:443").matches_request(&request));
+    assert!(!RuleMatch::default().with_host("api.example.com:8443").matches_request(&request));
+    assert!(RuleMatch::default().with_method("post").with_content_type("JSON").matches_request(&request));
+    assert!(!RuleMatch::default().with_method("GET").matches_request(&request));
+    assert!(!RuleMatch::default().with_content_type("xml").matches_request(&request));
+
+    // Origin-form targets are matched through the Host header
+    let mut relative = FlowRequest::new("GET", "/v1/items");
+    relative.set_header("Host", "api.example.com");
+    assert!(RuleMatch::default().with_host("api.example.com").with_path("/v1/items").matches_request(&relative));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_rules() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let rules = ReplaceRules::from_rules(vec![
+        ReplaceRule::new(Stage::Request, Part::FirstLine, "/v1/", "/v2/"),
+        ReplaceRule::new(Stage::Request, Part::Header("User-Agent".to_string()), r"Chrome/(\d+)", "Chrome/$1 ferrum")
+            .with_regex(),
+        ReplaceRule::new(Stage::Request, Part::Header("X-Debug".to_string()), "", "1"),
+        ReplaceRule::new(Stage::Request, Part::Header("Cookie".to_string()), r"^.*$", "").with_regex(),
+        ReplaceRule::new(Stage::Request, Part::Body, r#""role":"(\w+)""#, r#""role":"admin","was":"$1""#).with_regex(),
+        ReplaceRule::new(Stage::Request, Part::Body, "$1", "literal").with_match(RuleMatch::default().with_host("other.test")),
+        ReplaceRule::new(Stage::Response, Part::Body, "user", "nobody"),
+    ])?;
+
+    let mut request = FlowRequest::new("POST", "http://example.com/v1/users");
+    request.set_header("User-Agent", "Mozilla/5.0 Chrome/120.0");
+    request.set_header("Cookie", "session=abc");
+    request.body = br#"{"name":"bob","role":"user"}"#.to_vec();
+    request.set_header("Content-Length", &request.body.len().to_string());
+
+    assert_eq!(rules.apply_request(&mut request), 5);
+    assert_eq!(request.uri, "http://example.com/v2/users");
+    assert_eq!(request.header("user-agent"), Some("Mozilla/5.0 Chrome/120 ferrum.0"));
+    assert_eq!(request.header("x-debug"), Some("1"));
+    assert_eq!(request.header("cookie"), None);
+    assert_eq!(request.body, br#"{"name":"bob","role":"admin","was":"user"}"#);
+    assert_eq!(request.header("content-length"), Some(request.body.len().to_string().as_str()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_response_rules_reencode_compressed_bodies() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut rules = ReplaceRules::new();
+    rules.add(
+        ReplaceRule::new(Stage::Response, Part::Body, "\"admin\":false", "\"admin\":true")
+            .with_match(RuleMatch::default().with_content_type("json")),
+    )?;
+    rules.add(ReplaceRule::new(Stage::Response, Part::FirstLine, " 403", " 200"))?;
+    assert!(rules.add(ReplaceRule::new(Stage::Response, Part::Body, "(", "").with_regex()).is_err());
+
+    let request = FlowRequest::new("GET", "http://example.com/me");
+    let mut response = FlowResponse::new(403);
+    response.headers = vec![
+        ("Content-Type".to_string(), "application/json".to_string()),
+        ("Content-Encoding".to_string(), "gzip".to_string()),
+    ];
+    response.body = Transform::Gzip.encode(br#"{"admin":false}"#)?;
+    response.set_header("Content-Length", &response.body.len().to_string());
+
+    assert_eq!(rules.apply_response(&request, &mut response), 2);
+    assert_eq!(response.status, 200);
+    assert_eq!(Transform::Gzip.decode(&response.body)?, br#"{"admin":true}"#);
+    assert_eq!(response.header("content-length"), Some(response.body.len().to_string().as_str()));
+
+    // Encodings ferrum cannot undo are left alone
+    response.headers[1].1 = "compress".to_string();
+    let before = response.body.clone();
+    rules.apply_response(&request, &mut response);
+    assert_eq!(response.body, before);
+
+    // Disabled rules are skipped
+    rules.set_enabled(1, false)?;
+    let mut forbidden = FlowResponse::new(403);
+    assert_eq!(rules.apply_response(&request, &mut forbidden), 0);
+    assert!(rules.set_enabled(5, false).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rules_load_from_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("rules.json");
+    std::fs::write(
+        &path,
+        r#"[
+            {"name": "api v2", "stage": "request", "part": "first_line", "pattern": "/v1/", "replacement": "/v2/",
+             "match": {"host": "*.example.com", "methods": ["GET"]}},
+            {"stage": "response", "part": {"header": "Server"}, "pattern": "", "replacement": "", "enabled": false}
+        ]"#,
+    )?;
+
+    let rules = ReplaceRules::load(&path)?;
+    let loaded = rules.rules();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].matcher.host.as_deref(), Some("*.example.com"));
+    assert_eq!(loaded[1].part, Part::Header("Server".to_string()));
+    assert!(!loaded[1].enabled);
+
+    let saved = dir.path().join("saved.json");
+    rules.save(&saved)?;
+    assert_eq!(ReplaceRules::load(&saved)?.rules(), loaded);
+
+    std::fs::write(&path, r#"[{"stage": "sideways", "part": "body"}]"#)?;
+    assert!(ReplaceRules::load(&path).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forwarder_applies_rules_edited_at_runtime() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let mock = server.mock(|when, then| {
+        when.method("GET").path("/v2/status").header("x-env", "staging");
+        then.status(200).body("status: down");
+    });
+
+    let forwarder = Forwarder::new();
+    let shared = forwarder.clone();
+    {
+        let mut rules = shared.replace_rules().write().await;
+        rules.add(ReplaceRule::new(Stage::Request, Part::FirstLine, "/v1/", "/v2/"))?;
+        rules.add(ReplaceRule::new(Stage::Request, Part::Header("X-Env".to_string()), "", "staging"))?;
+        rules.add(ReplaceRule::new(Stage::Response, Part::Body, "down", "up"))?;
+    }
+
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/v1/status"))).await?;
+    mock.assert();
+    assert!(flow.request.uri.ends_with("/v2/status"));
+    assert_eq!(flow.response.unwrap().body, b"status: up");
+
+    shared.replace_rules().write().await.clear();
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/v1/status"))).await?;
+    assert_eq!(flow.response.unwrap().status, 404);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forwarder_reloads_edited_rules_file() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method("GET").path("/status");
+        then.status(200).body("status: down");
+    });
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("rules.json");
+    std::fs::write(&path, r#"[{"stage": "response", "part": "body", "pattern": "down", "replacement": "up"}]"#)?;
+    let forwarder = Forwarder::new().with_replace_rules(ReplaceRules::load(&path)?);
+
+    let body = |flow: Flow| flow.response.map(|response| response.body).unwrap_or_default();
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/status"))).await?;
+    assert_eq!(body(flow), b"status: up");
+
+    std::fs::write(&path, r#"[{"stage": "response", "part": "body", "pattern": "down", "replacement": "degraded"}]"#)?;
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/status"))).await?;
+    assert_eq!(body(flow), b"status: degraded");
+    assert_eq!(forwarder.replace_rules().read().await.rules()[0].replacement, "degraded");
+
+    // A broken edit keeps the rules that last loaded
+    std::fs::write(&path, r#"[{"stage": "response", "part": "body", "pattern": "(", "regex": true}]"#)?;
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/status"))).await?;
+    assert_eq!(body(flow), b"status: degraded");
+
+    std::fs::write(&path, "[]")?;
+    let flow = forwarder.forward(FlowRequest::new("GET", &server.url("/status"))).await?;
+    assert_eq!(body(flow), b"status: down");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reload_keeps_rules_added_at_runtime() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("rules.json");
+    std::fs::write(&path, r#"[{"name": "file", "stage": "response", "part": "body", "pattern": "down", "replacement": "up"}]"#)?;
+    let mut rules = ReplaceRules::load(&path)?;
+    rules.add(ReplaceRule::new(Stage::Request, Part::Header("X-Debug".into()), "", "1").with_name("runtime"))?;
+
+    std::fs::write(&path, r#"[{"name": "edited", "stage": "response", "part": "body", "pattern": "down", "replacement": "degraded"}]"#)?;
+    rules.reload()?;
+    let names: Vec<String> = rules.rules().into_iter().map(|rule| rule.name).collect();
+    assert_eq!(names, vec!["edited", "runtime"]);
+
+    // Emptying the file drops only its own rules
+    std::fs::write(&path, "[]")?;
+    rules.reload()?;
+    let names: Vec<String> = rules.rules().into_iter().map(|rule| rule.name).collect();
+    assert_eq!(names, vec!["runtime"]);
+
+    Ok(())
+}
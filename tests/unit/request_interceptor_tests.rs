@@ -1,6 +1,7 @@
-use hyper::{Request, Uri, Method, body::Incoming};
+use hyper::{Request, Uri, Method};
 use anyhow::Result;
 use bytes::Bytes;
+use ferrum::flow::model::FlowRequest;
 use ferrum::intercept::request::RequestInterceptor;
 use crate::test_utils::init_test_logging;
 use http_body_util::Empty;
@@ -80,3 +81,21 @@ async fn test_request_interceptor_processes_when_enabled() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_request_interceptor_filter() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // Without a filter every request is intercepted
+    let interceptor = RequestInterceptor::new();
+    assert!(interceptor.filter().is_none());
+    assert!(interceptor.matches(&FlowRequest::new("GET", "http://example.com/")));
+
+    let interceptor = RequestInterceptor::new().with_filter(r#"host ~ "api\." && method == POST"#.parse()?);
+    assert!(interceptor.matches(&FlowRequest::new("POST", "http://api.example.com/login")));
+    assert!(!interceptor.matches(&FlowRequest::new("GET", "http://api.example.com/login")));
+    assert!(!interceptor.matches(&FlowRequest::new("POST", "http://www.example.com/login")));
+
+    Ok(())
+}
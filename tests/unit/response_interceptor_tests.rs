@@ -2,6 +2,7 @@ use hyper::{Response, StatusCode};
 use anyhow::Result;
 use bytes::Bytes;
 use http_body_util::{Full, BodyExt};
+use ferrum::flow::model::{FlowRequest, FlowResponse};
 use ferrum::intercept::response::ResponseInterceptor;
 use crate::test_utils::init_test_logging;
 
@@ -80,3 +81,18 @@ async fn test_response_interceptor_processes_when_enabled() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_response_interceptor_filter() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let request = FlowRequest::new("GET", "http://example.com/");
+    assert!(ResponseInterceptor::new().matches(&request, &FlowResponse::new(200)));
+
+    let interceptor = ResponseInterceptor::new().with_filter("status >= 500".parse()?);
+    assert!(interceptor.matches(&request, &FlowResponse::new(502)));
+    assert!(!interceptor.matches(&request, &FlowResponse::new(200)));
+
+    Ok(())
+}
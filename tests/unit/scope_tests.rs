@@ -0,0 +1,195 @@
+use anyhow::Result;
+use rstest::rstest;
+use url::Url;
+use ferrum::flow::model::FlowRequest;
+use ferrum::project::scope::{Scope, ScopeRule};
+use ferrum::project::store::Project;
+use ferrum::ui::cli::ScopeArgs;
+use ferrum::ui::commands;
+use crate::test_utils::init_test_logging;
+
+#[rstest]
+#[case("example.com", ScopeRule::host("example.com"))]
+#[case("https://*.Example.com:8443/api", ScopeRule::host("*.example.com").with_scheme("https").with_port(8443).with_path("/api"))]
+#[case("10.0.0.0/8", ScopeRule::ip("10.0.0.0/8".parse().unwrap()))]
+#[case("192.168.1.5", ScopeRule::ip("192.168.1.5/32".parse().unwrap()))]
+#[case("[::1]:3000", ScopeRule::ip("::1/128".parse().unwrap()).with_port(3000))]
+#[case("*:8080", ScopeRule::default().with_port(8080))]
+#[tokio::test]
+async fn test_rule_parsing(#[case] text: &str, #[case] expected: ScopeRule) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let rule: ScopeRule = text.parse()?;
+    assert_eq!(rule, expected);
+    assert_eq!(rule.to_string().parse::<ScopeRule>()?, rule);
+
+    assert!("".parse::<ScopeRule>().is_err());
+    assert!("example.com:http".parse::<ScopeRule>().is_err());
+    assert!("regex:(".parse::<ScopeRule>().is_err());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("https://shop.example.com/cart", true)]
+#[case("http://example.com/", true)]
+#[case("https://badexample.com/", false)]
+#[case("https://static.example.com/app.js", false)]
+#[case("https://example.com/logout", false)]
+#[case("https://example.com/logout/all", false)]
+#[case("https://example.com/logouts", true)]
+#[case("http://10.1.2.3:8080/admin", true)]
+#[case("http://10.1.2.3:9000/admin", false)]
+#[case("http://11.0.0.1:8080/", false)]
+#[case("https://api.partner.test/v2/orders", true)]
+#[case("https://api.partner.test/v1/orders", false)]
+#[tokio::test]
+async fn test_contains(#[case] url: &str, #[case] expected: bool) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let scope = Scope::new()
+        .with_include("*.example.com".parse()?)
+        .with_include("10.0.0.0/8".parse()?)
+        .with_include(r"regex:^https://api\.partner\.test/v2/".parse()?)
+        .with_exclude("static.example.com".parse()?)
+        .with_exclude("*/logout".parse()?)
+        .with_exclude("*:9000".parse()?);
+
+    assert_eq!(scope.contains_url(&Url::parse(url)?), expected, "{}", url);
+    assert_eq!(scope.contains(&FlowRequest::new("GET", url)), expected, "{}", url);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_defaults_and_flags() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    // An empty scope takes everything, even requests without a usable URL
+    let everything = Scope::new();
+    assert!(everything.is_unrestricted());
+    assert!(everything.contains(&FlowRequest::new("GET", "/no-host")));
+
+    let scope = Scope::from_hosts(&["example.com".to_string()]);
+    let outside = FlowRequest::new("GET", "https://tracker.test/pixel");
+    let mut relative = FlowRequest::new("GET", "/login");
+    relative.set_header("Host", "example.com");
+    assert!(scope.contains(&relative));
+    assert!(!scope.contains(&FlowRequest::new("GET", "/no-host")));
+
+    // Out-of-scope traffic is recorded and intercepted unless switched off
+    assert!(!scope.contains(&outside));
+    assert!(scope.records(&outside) && scope.intercepts(&outside));
+    let scope = scope.with_record_out_of_scope(false).with_intercept_out_of_scope(false);
+    assert!(!scope.records(&outside) && !scope.intercepts(&outside));
+    assert!(scope.records(&relative) && scope.intercepts(&relative));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_load_and_save() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("scope.json");
+    std::fs::write(
+        &path,
+        r#"{
+            "include": [
+                {"host": "*.example.com", "scheme": "https"},
+                {"ip": "172.16.0.0/12", "port": 8443, "path": "/api/"}
+            ],
+            "exclude": [{"regex": "\\.(png|css)$"}],
+            "record_out_of_scope": false
+        }"#,
+    )?;
+
+    let scope = Scope::load(&path)?;
+    assert!(!scope.record_out_of_scope);
+    assert!(scope.intercept_out_of_scope);
+    assert!(scope.contains_url(&Url::parse("https://www.example.com/")?));
+    assert!(!scope.contains_url(&Url::parse("http://www.example.com/")?));
+    assert!(!scope.contains_url(&Url::parse("https://www.example.com/logo.png")?));
+    assert!(scope.contains_url(&Url::parse("https://172.20.0.1:8443/api/users")?));
+    assert!(!scope.contains_url(&Url::parse("https://172.20.0.1:8443/apiv2")?));
+
+    scope.save(&path)?;
+    assert_eq!(Scope::load(&path)?, scope);
+
+    std::fs::write(&path, r#"{"include": [{"ip": "not-an-ip"}]}"#)?;
+    assert!(Scope::load(&path).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_saved_in_project() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("engagement.ferrum");
+    let project = Project::open(&path)?;
+    assert_eq!(project.load_scope()?, None);
+
+    let args = ScopeArgs {
+        include: vec!["*.example.com".parse()?],
+        exclude: vec!["admin.example.com".parse()?],
+        scope_filter: Some("method != OPTIONS".parse()?),
+        scope_file: None,
+    };
+    let scope = commands::scope(&args, Some(&project))?;
+    assert_eq!(project.load_scope()?, Some(scope.clone()));
+    drop(project);
+
+    // Later runs without scope options pick up the saved scope
+    let no_args = ScopeArgs::default();
+    let project = Project::open_read_only(&path)?;
+    let saved = commands::scope(&no_args, Some(&project))?;
+    assert_eq!(saved, scope);
+    assert!(saved.contains_url(&Url::parse("https://api.example.com/")?));
+    assert!(!saved.contains_url(&Url::parse("https://admin.example.com/")?));
+    assert!(!saved.contains(&FlowRequest::new("OPTIONS", "https://api.example.com/")));
+
+    // Scope options on a read-only project apply without replacing what was saved
+    let other = ScopeArgs { include: vec!["other.test".parse()?], ..Default::default() };
+    assert!(commands::scope(&other, Some(&project))?.contains_url(&Url::parse("http://other.test/")?));
+    assert_eq!(project.load_scope()?, Some(scope));
+
+    assert!(commands::scope(&no_args, None)?.is_unrestricted());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_filter() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let scope = Scope::new()
+        .with_include("*.example.com".parse()?)
+        .with_filter(r#"method != OPTIONS && path !~ "\.(png|css)$""#.parse()?);
+    assert!(!scope.is_unrestricted());
+
+    assert!(scope.contains(&FlowRequest::new("POST", "https://api.example.com/login")));
+    assert!(!scope.contains(&FlowRequest::new("OPTIONS", "https://api.example.com/login")));
+    assert!(!scope.contains(&FlowRequest::new("GET", "https://www.example.com/logo.png")));
+    assert!(!scope.contains(&FlowRequest::new("POST", "https://other.test/login")));
+
+    // URLs on their own are filtered as GET requests
+    assert!(scope.contains_url(&Url::parse("https://api.example.com/login")?));
+    assert!(!scope.contains_url(&Url::parse("https://www.example.com/site.css")?));
+
+    // The filter is saved as its expression
+    let json = serde_json::to_string(&scope)?;
+    assert!(json.contains(r#""filter":"method != OPTIONS"#), "{}", json);
+    assert_eq!(serde_json::from_str::<Scope>(&json)?, scope);
+    assert!(serde_json::from_str::<Scope>(r#"{"filter": "method =="}"#).is_err());
+
+    Ok(())
+}
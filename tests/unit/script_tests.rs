@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::Utc;
+use ferrum::flow::model::{Direction, FlowRequest, FlowResponse, WebSocketMessage, WebSocketOpcode};
+use ferrum::intercept::script::ScriptInterceptor;
+use crate::test_utils::init_test_logging;
+
+fn write_script(dir: &Path, name: &str, source: &str) -> Result<PathBuf> {
+    let path = dir.join(name);
+    std::fs::write(&path, source)?;
+    Ok(path)
+}
+
+#[tokio::test]
+async fn test_request_and_response_hooks() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = write_script(
+        dir.path(),
+        "tweak.rhai",
+        r#"
+        fn on_request(req) {
+            if req.host == "api.example.com" {
+                req.method = "put";
+                req.set_header("Authorization", "Bearer test");
+                req.remove_header("Cookie");
+                let body = req.body;
+                body.replace("guest", "admin");
+                req.body = body;
+                req.tag("elevated");
+            }
+            if req.path == "/maintenance" {
+                return response(503, "down for maintenance");
+            }
+        }
+
+        fn on_response(req, res) {
+            if res.header("X-Frame-Options") == () {
+                res.tag("no-xfo");
+            }
+            res.status = 200;
+            res.body = `${req.method} ${res.body}`;
+            res.add_header("X-Script", "1");
+        }
+        "#,
+    )?;
+
+    let mut scripts = ScriptInterceptor::new();
+    scripts.load(&path)?;
+    assert_eq!(scripts.paths(), vec![path.as_path()]);
+
+    let mut request = FlowRequest::new("POST", "https://api.example.com/users");
+    request.set_header("Cookie", "session=1");
+    request.body = br#"{"role":"guest"}"#.to_vec();
+    let outcome = scripts.on_request(&mut request);
+    assert_eq!(outcome.response, None);
+    assert_eq!(outcome.tags, vec!["elevated"]);
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.header("authorization"), Some("Bearer test"));
+    assert_eq!(request.header("cookie"), None);
+    assert_eq!(request.body, br#"{"role":"admin"}"#);
+
+    let mut response = FlowResponse::new(403);
+    response.body = b"forbidden".to_vec();
+    let tags = scripts.on_response(&request, &mut response);
+    assert_eq!(tags, vec!["no-xfo"]);
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, b"PUT forbidden");
+    assert_eq!(response.header("x-script"), Some("1"));
+
+    // Returning a response answers the request
+    let mut request = FlowRequest::new("GET", "https://www.example.com/maintenance");
+    let outcome = scripts.on_request(&mut request);
+    let response = outcome.response.unwrap();
+    assert_eq!(response.status, 503);
+    assert_eq!(response.body, b"down for maintenance");
+    assert_eq!(response.header("content-type"), Some("text/plain; charset=utf-8"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hot_reload() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = write_script(dir.path(), "header.rhai", r#"fn on_request(req) { req.set_header("X-Version", "1"); }"#)?;
+    let mut scripts = ScriptInterceptor::new();
+    scripts.load(&path)?;
+
+    let version = |scripts: &ScriptInterceptor| {
+        let mut request = FlowRequest::new("GET", "http://example.com/");
+        scripts.on_request(&mut request);
+        request.header("x-version").map(str::to_string)
+    };
+    assert_eq!(version(&scripts).as_deref(), Some("1"));
+
+    std::fs::write(&path, r#"fn on_request(req) { req.set_header("X-Version", "two"); }"#)?;
+    assert_eq!(version(&scripts).as_deref(), Some("two"));
+
+    // A broken edit keeps the last version that compiled
+    std::fs::write(&path, r#"fn on_request(req) { req.set_header("X-Version", "3" }"#)?;
+    assert_eq!(version(&scripts).as_deref(), Some("two"));
+
+    // Loading a broken script fails up front
+    let broken = write_script(dir.path(), "broken.rhai", "fn on_request(req) {")?;
+    assert!(scripts.load(&broken).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sandbox_limits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let spin = write_script(
+        dir.path(),
+        "spin.rhai",
+        r#"fn on_request(req) { req.set_header("X-Started", "yes"); loop { } }"#,
+    )?;
+    let import = write_script(
+        dir.path(),
+        "import.rhai",
+        r#"fn on_request(req) { import "secrets" as s; req.tag("imported"); }"#,
+    )?;
+    let tagger = write_script(dir.path(), "tagger.rhai", r#"fn on_request(req) { req.tag("reached"); }"#)?;
+
+    let mut scripts = ScriptInterceptor::new().with_max_operations(10_000);
+    scripts.load(&spin)?;
+    scripts.load(&import)?;
+    scripts.load(&tagger)?;
+
+    // Failing scripts leave the request alone and the next script still runs
+    let mut request = FlowRequest::new("GET", "http://example.com/");
+    let original = request.clone();
+    let outcome = scripts.on_request(&mut request);
+    assert_eq!(request, original);
+    assert_eq!(outcome.tags, vec!["reached"]);
+
+    let eval = write_script(dir.path(), "eval.rhai", r#"fn on_request(req) { eval("1 + 1"); }"#)?;
+    assert!(ScriptInterceptor::new().load(&eval).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_websocket_messages() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let dir = tempfile::tempdir()?;
+    let path = write_script(
+        dir.path(),
+        "ws.rhai",
+        r#"
+        fn on_websocket_message(req, msg) {
+            if msg.opcode == "ping" {
+                msg.discard();
+            } else if msg.direction == "client" && req.host == "chat.example.com" {
+                msg.text = msg.text.to_upper();
+            }
+        }
+        "#,
+    )?;
+    let mut scripts = ScriptInterceptor::new();
+    scripts.load(&path)?;
+
+    let request = FlowRequest::new("GET", "wss://chat.example.com/socket");
+    let mut message = WebSocketMessage {
+        timestamp: Utc::now(),
+        direction: Direction::ClientToServer,
+        opcode: WebSocketOpcode::Text,
+        payload: b"hello".to_vec(),
+    };
+    assert!(scripts.on_websocket_message(&request, &mut message));
+    assert_eq!(message.payload, b"HELLO");
+
+    message.opcode = WebSocketOpcode::Ping;
+    assert!(!scripts.on_websocket_message(&request, &mut message));
+
+    Ok(())
+}
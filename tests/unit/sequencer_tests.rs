@@ -0,0 +1,112 @@
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ferrum::flow::model::FlowResponse;
+use ferrum::sequencer::analysis::{analyze, fips_tests, Rating, FIPS_BLOCK_BITS};
+use ferrum::sequencer::capture::TokenSource;
+use crate::test_utils::init_test_logging;
+
+fn random_hex_tokens(count: usize) -> Vec<String> {
+    let mut rng = StdRng::seed_from_u64(7);
+    (0..count)
+        .map(|_| (0..32).map(|_| format!("{:x}", rng.gen_range(0..16u8))).collect())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_random_tokens_rate_well() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let report = analyze(&random_hex_tokens(400))?;
+
+    assert_eq!(report.sample_size, 400);
+    assert_eq!(report.distinct, 400);
+    assert_eq!(report.charset, "0123456789abcdef");
+    assert_eq!(report.bits_per_char, 4);
+    assert!(report.char_entropy > 3.95, "char entropy {}", report.char_entropy);
+    assert!(report.effective_bits > 112.0, "effective bits {}", report.effective_bits);
+
+    let fips = report.fips.as_ref().expect("400 x 128 bits covers two FIPS blocks");
+    assert_eq!(fips.blocks, 2);
+    assert!(fips.all_passed(), "{:?}", fips);
+
+    assert_eq!(report.rating, Rating::Excellent);
+    assert!(report.findings.is_empty(), "{:?}", report.findings);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sequential_tokens_rate_poorly() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut tokens: Vec<String> = (1000..1500).map(|n| format!("sess-{:08}", n)).collect();
+    tokens.push(tokens[0].clone());
+
+    let report = analyze(&tokens)?;
+
+    assert_eq!(report.distinct, 500);
+    assert_eq!(report.constant_positions, 10, "Prefix, padding and thousands digit never change");
+    assert!(report.effective_bits < 32.0);
+    assert_eq!(report.rating, Rating::VeryPoor);
+    assert!(report.findings.iter().any(|finding| finding.contains("duplicate")));
+    assert!(report.findings.iter().any(|finding| finding.contains("compress")));
+
+    assert!(analyze(&[]).is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fips_tests_reject_biased_bits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let zeros = vec![false; FIPS_BLOCK_BITS];
+    let results = fips_tests(&zeros);
+    assert_eq!(results.blocks, 1);
+    assert_eq!(results.monobit_passed, 0);
+    assert_eq!(results.poker_passed, 0);
+    assert_eq!(results.runs_passed, 0);
+    assert_eq!(results.long_run_passed, 0);
+
+    let alternating: Vec<bool> = (0..FIPS_BLOCK_BITS).map(|i| i % 2 == 0).collect();
+    let results = fips_tests(&alternating);
+    assert_eq!(results.monobit_passed, 1, "Balanced ones and zeros pass monobit");
+    assert_eq!(results.runs_passed, 0, "Only runs of length one");
+
+    assert_eq!(fips_tests(&zeros[..100]).blocks, 0, "Partial blocks are not tested");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_token_sources() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut response = FlowResponse::new(200);
+    response.headers = vec![
+        ("Set-Cookie".to_string(), "theme=dark; Path=/".to_string()),
+        ("Set-Cookie".to_string(), "SESSIONID=abc123; HttpOnly".to_string()),
+        ("X-Request-Token".to_string(), "tok-9".to_string()),
+    ];
+    response.body = br#"<input name="csrf" value="f00d">"#.to_vec();
+
+    let cookie: TokenSource = "cookie:SESSIONID".parse()?;
+    let header: TokenSource = "header:x-request-token".parse()?;
+    let body: TokenSource = r#"regex:value="(\w+)""#.parse()?;
+    let missing: TokenSource = "cookie:other".parse()?;
+
+    assert_eq!(cookie.extract(&response).as_deref(), Some("abc123"));
+    assert_eq!(header.extract(&response).as_deref(), Some("tok-9"));
+    assert_eq!(body.extract(&response).as_deref(), Some("f00d"));
+    assert_eq!(missing.extract(&response), None);
+
+    assert!("query:x".parse::<TokenSource>().is_err());
+    assert!("regex:(".parse::<TokenSource>().is_err());
+
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use anyhow::Result;
+use rstest::rstest;
+use url::Url;
+use ferrum::decoder::transform::Transform;
+use ferrum::flow::model::{Flow, FlowRequest, FlowResponse};
+use ferrum::project::store::Project;
+use ferrum::sitemap::links::{extract_links, LinkKind};
+use ferrum::sitemap::scripts::{extract_endpoints, EndpointKind};
+use ferrum::sitemap::tree::{ParamLocation, SiteMap, ValueType};
+use crate::test_utils::init_test_logging;
+
+fn html_flow(method: &str, uri: &str, status: u16, html: &str) -> Flow {
+    let mut flow = Flow::new(FlowRequest::new(method, uri));
+    let mut response = FlowResponse::new(status);
+    response.headers = vec![("Content-Type".to_string(), "text/html; charset=utf-8".to_string())];
+    response.body = html.as_bytes().to_vec();
+    flow.response = Some(response);
+    flow
+}
+
+#[rstest]
+#[case("", ValueType::Empty)]
+#[case("TRUE", ValueType::Boolean)]
+#[case("-42", ValueType::Integer)]
+#[case("3.14", ValueType::Decimal)]
+#[case("6fa459ea-ee8a-3ca4-894e-db77e160355e", ValueType::Uuid)]
+#[case("alice@example.com", ValueType::Email)]
+#[case("https://example.com/next", ValueType::Url)]
+#[case("5d41402abc4b2a76", ValueType::Hex)]
+#[case("hello world", ValueType::String)]
+#[tokio::test]
+async fn test_value_classification(#[case] value: &str, #[case] expected: ValueType) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    assert_eq!(ValueType::classify(value), expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_extract_links_from_html_and_headers() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let base = Url::parse("https://example.com/shop/index.html")?;
+    let mut response = FlowResponse::new(302);
+    response.headers = vec![
+        ("Location".to_string(), "/login?next=%2Fshop".to_string()),
+        ("Content-Type".to_string(), "text/html".to_string()),
+    ];
+    response.body = br#"
+        <a href="cart?item=1&amp;qty=2#top">Cart</a>
+        <img data-href="/ignored" src='img/logo.png'>
+        <form action=/search method=get></form>
+        <a href="javascript:void(0)">x</a>
+        <a href="mailto:shop@example.com">mail</a>
+        <base href="https://cdn.example.net/assets/">
+        <script src="app.js"></script>
+        <meta http-equiv="refresh" content="5; url=/timeout">
+    "#
+    .to_vec();
+
+    let links: Vec<(String, LinkKind)> = extract_links(&base, &response)
+        .into_iter()
+        .map(|link| (link.url.to_string(), link.kind))
+        .collect();
+
+    assert_eq!(
+        links,
+        vec![
+            ("https://example.com/login?next=%2Fshop".to_string(), LinkKind::Redirect),
+            ("https://example.com/shop/cart?item=1&qty=2".to_string(), LinkKind::Anchor),
+            ("https://example.com/shop/img/logo.png".to_string(), LinkKind::Resource),
+            ("https://example.com/search".to_string(), LinkKind::Form),
+            ("https://cdn.example.net/assets/app.js".to_string(), LinkKind::Resource),
+            ("https://cdn.example.net/timeout".to_string(), LinkKind::Redirect),
+        ]
+    );
+
+    // Compressed pages give the same links
+    response.body = Transform::Gzip.encode(&response.body)?;
+    response.headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+    assert_eq!(extract_links(&base, &response).len(), links.len());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_site_map_marks_requested_and_referenced_nodes() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut site_map = SiteMap::new();
+    site_map.add_flow(&html_flow(
+        "GET",
+        "https://example.com/api/users?id=7&active=true",
+        200,
+        r#"<a href="/api/users?id=abc">next</a><a href="/admin/">admin</a>"#,
+    ));
+
+    let mut login = html_flow("POST", "https://example.com/login", 302, "");
+    login.request.set_header("Content-Type", "application/x-www-form-urlencoded");
+    login.request.body = b"user=alice%40example.com&remember=1".to_vec();
+    site_map.add_flow(&login);
+
+    let mut api = Flow::new(FlowRequest::new("PUT", "https://example.com/api/users/5"));
+    api.request.set_header("Content-Type", "application/json");
+    api.request.body = br#"{"name":"Bob","age":31,"tags":["a"]}"#.to_vec();
+    site_map.add_flow(&api);
+
+    let origins: Vec<&str> = site_map.origins().map(|(origin, _)| origin).collect();
+    assert_eq!(origins, vec!["https://example.com"]);
+
+    let users = site_map.node("https://example.com/api/users").unwrap();
+    assert!(users.requested);
+    assert!(users.referenced);
+    assert!(users.methods.contains("GET"));
+    assert!(users.statuses.contains(&200));
+    let id = users.parameter(ParamLocation::Query, "id").unwrap();
+    assert_eq!(id.types.iter().copied().collect::<Vec<_>>(), vec![ValueType::Integer, ValueType::String]);
+    assert_eq!(id.samples, vec!["7", "abc"]);
+    assert!(users.parameter(ParamLocation::Query, "active").unwrap().types.contains(&ValueType::Boolean));
+
+    let admin = site_map.node("https://example.com/admin").unwrap();
+    assert!(!admin.requested);
+    assert!(admin.referenced);
+
+    let login = site_map.node("https://example.com/login").unwrap();
+    assert!(login.parameter(ParamLocation::Body, "user").unwrap().types.contains(&ValueType::Email));
+
+    let user = site_map.node("https://example.com/api/users/5").unwrap();
+    let age = user.parameter(ParamLocation::Body, "age").unwrap();
+    assert!(age.types.contains(&ValueType::Integer));
+    assert!(user.parameter(ParamLocation::Body, "tags").unwrap().types.contains(&ValueType::Array));
+
+    // The intermediate /api node was never requested itself
+    let api_node = site_map.node("https://example.com/api/").unwrap();
+    assert!(!api_node.requested && !api_node.referenced);
+
+    assert_eq!(site_map.unrequested(), vec!["https://example.com/admin"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_site_map_renders_text_and_json() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut site_map = SiteMap::new();
+    site_map.add_flow(&html_flow(
+        "GET",
+        "http://localhost:8080/docs/intro?page=2",
+        200,
+        r#"<a href="/about">about</a>"#,
+    ));
+
+    let text = site_map.to_text();
+    assert_eq!(
+        text,
+        "http://localhost:8080\n\
+         ├── about  [(not requested)]\n\
+         └── docs\n    \
+             └── intro  [GET; 200; query page:integer]\n"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&site_map.to_json()?)?;
+    let intro = &json["origins"]["http://localhost:8080"]["children"]["docs"]["children"]["intro"];
+    assert_eq!(intro["requested"], true);
+    assert_eq!(intro["parameters"][0]["name"], "page");
+    assert_eq!(intro["parameters"][0]["types"][0], "integer");
+
+    site_map.retain_origin("example.com");
+    assert!(site_map.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_site_map_from_project() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let project = Project::in_memory()?;
+    let first = project.save_flow(&html_flow("GET", "http://example.com/", 200, r#"<a href="/a">a</a>"#))?;
+    let second = project.save_flow(&html_flow("GET", "http://example.com/a", 200, "done"))?;
+
+    let site_map = SiteMap::from_project(&project)?;
+    assert_eq!(site_map.node("http://example.com/").unwrap().flow_ids, vec![first]);
+
+    let a = site_map.node("http://example.com/a").unwrap();
+    assert!(a.requested && a.referenced);
+    assert_eq!(a.flow_ids, vec![second]);
+    assert!(site_map.unrequested().is_empty());
+
+    Ok(())
+}
+
+const BUNDLE: &str = r#"
+const API = "https://api.example.com/v2/";
+fetch("/api/users?active=1").then(r => r.json());
+axios.post('/api/orders', body, {headers: {"Content-Type": "application/json"}});
+const avatar = `/static/avatars/${user.id}.png`;
+const legacy = "admin/export.php";
+const route = "/api/users";
+const pattern = /^\/[a-z]+$/;
+const selector = "/(foo|bar)/";
+const date = "dd/mm/yyyy";
+const cdn = "//cdn.example.net/lib.js";
+const GET_USER = gql`query GetUser($id: ID!) { user(id: $id) { name } }`;
+"#;
+
+#[tokio::test]
+async fn test_extract_endpoints_from_script() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let endpoints = extract_endpoints(BUNDLE.as_bytes());
+    let found: Vec<(EndpointKind, &str)> = endpoints.iter().map(|endpoint| (endpoint.kind, endpoint.value.as_str())).collect();
+    assert_eq!(
+        found,
+        vec![
+            (EndpointKind::Url, "https://api.example.com/v2/"),
+            (EndpointKind::Path, "/api/users?active=1"),
+            (EndpointKind::Path, "/api/orders"),
+            (EndpointKind::Path, "/static/avatars/"),
+            (EndpointKind::Path, "admin/export.php"),
+            (EndpointKind::Path, "/api/users"),
+            (EndpointKind::Url, "//cdn.example.net/lib.js"),
+            (EndpointKind::Graphql, "query GetUser"),
+        ]
+    );
+    for endpoint in &endpoints {
+        assert!(BUNDLE[endpoint.range.clone()].starts_with(endpoint.value.split(' ').next().unwrap()));
+    }
+
+    // Relative routes are resolved against the origin, not the script's directory
+    let base = Url::parse("https://app.example.com/assets/js/main.js")?;
+    assert_eq!(endpoints[4].url(&base).unwrap().as_str(), "https://app.example.com/admin/export.php");
+    assert_eq!(endpoints[6].url(&base).unwrap().as_str(), "https://cdn.example.net/lib.js");
+    assert!(endpoints[7].url(&base).is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_site_map_adds_script_endpoints_as_unrequested() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let mut flow = Flow::new(FlowRequest::new("GET", "https://app.example.com/assets/main.js"));
+    let mut response = FlowResponse::new(200);
+    response.headers = vec![("Content-Type".to_string(), "application/javascript".to_string())];
+    response.body = BUNDLE.as_bytes().to_vec();
+    flow.response = Some(response);
+
+    let mut site_map = SiteMap::new();
+    site_map.add_flow(&flow);
+
+    let unrequested = site_map.unrequested();
+    assert!(unrequested.contains(&"https://app.example.com/api/orders".to_string()));
+    assert!(unrequested.contains(&"https://app.example.com/admin/export.php".to_string()));
+    assert!(unrequested.contains(&"https://api.example.com/v2".to_string()));
+    let users = site_map.node("https://app.example.com/api/users").unwrap();
+    assert!(users.referenced && !users.requested);
+    assert_eq!(users.parameters[0].name, "active");
+
+    // Compressed bundles give the same endpoints
+    let response = flow.response.as_mut().unwrap();
+    response.body = Transform::Gzip.encode(&response.body)?;
+    response.headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+    let mut compressed = SiteMap::new();
+    compressed.add_flow(&flow);
+    assert_eq!(compressed.unrequested(), unrequested);
+
+    Ok(())
+}
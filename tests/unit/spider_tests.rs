@@ -0,0 +1,310 @@
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use httpmock::{Method, MockServer};
+use rstest::rstest;
+use url::Url;
+use ferrum::project::store::Project;
+use ferrum::proxy::forward::Forwarder;
+use ferrum::sitemap::tree::SiteMap;
+use ferrum::spider::crawl::{parse_sitemap, Robots, Spider, SPIDER_TAG};
+use ferrum::spider::forms::{parse_forms, FormDefaults, FormField};
+use crate::test_utils::init_test_logging;
+
+const LOGIN_PAGE: &str = r#"<html><body>
+<form action="/login" method="post">
+  <input type="hidden" name="csrf" value="t0k&amp;en">
+  <input name="username">
+  <input type="password" name="password">
+  <input type="email" name="contact" value="">
+  <input type="checkbox" name="remember">
+  <input type="radio" name="plan" value="free" checked><input type="radio" name="plan" value="pro">
+  <input type="file" name="avatar">
+  <input type="text" name="nickname" disabled>
+  <select name="country"><option value="us">US</option><option value="nz" selected>NZ</option></select>
+  <textarea name="bio">Hello &lt;world&gt;</textarea>
+  <button type="submit" name="go" value="1">Sign in</button>
+  <input type="submit" name="alt" value="Other">
+</form>
+<form><input name="q"></form>
+<form action="javascript:void(0)"><input name="x"></form>
+</body></html>"#;
+
+#[tokio::test]
+async fn test_parse_forms_and_fields() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let base = Url::parse("http://example.com/account/start#top")?;
+    let forms = parse_forms(&base, LOGIN_PAGE);
+
+    assert_eq!(forms.len(), 2);
+    assert_eq!(forms[0].action.as_str(), "http://example.com/login");
+    assert_eq!(forms[0].method, "POST");
+    assert_eq!(forms[0].enctype, "application/x-www-form-urlencoded");
+    assert_eq!(
+        forms[0].fields[0],
+        FormField {
+            name: "csrf".to_string(),
+            kind: "hidden".to_string(),
+            value: Some("t0k&en".to_string()),
+        }
+    );
+    let names: Vec<&str> = forms[0].fields.iter().map(|field| field.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["csrf", "username", "password", "contact", "remember", "plan", "plan", "avatar", "country", "bio", "go", "alt"]
+    );
+
+    // A form without an action submits to the page itself
+    assert_eq!(forms[1].action.as_str(), "http://example.com/account/start");
+    assert_eq!(forms[1].method, "GET");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_form_submission_uses_defaults() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let base = Url::parse("http://example.com/")?;
+    let forms = parse_forms(&base, LOGIN_PAGE);
+    let defaults = FormDefaults::new().with_value("user", "alice").with_fallback("test");
+
+    let request = forms[0].to_request(&defaults);
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.uri, "http://example.com/login");
+    assert_eq!(request.header("content-type"), Some("application/x-www-form-urlencoded"));
+    assert_eq!(request.header("content-length"), Some(request.body.len().to_string().as_str()));
+    assert_eq!(
+        String::from_utf8(request.body)?,
+        "csrf=t0k%26en&username=alice&password=Ferrum-Spider-1&contact=ferrum%40example.com&remember=on\
+         &plan=free&country=nz&bio=Hello+%3Cworld%3E&go=1"
+    );
+
+    let request = forms[1].to_request(&defaults);
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.uri, "http://example.com/?q=test");
+    assert!(request.body.is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+#[case("text", None, Some("ferrum"))]
+#[case("text", Some("kept"), Some("kept"))]
+#[case("number", None, Some("1"))]
+#[case("hidden", None, Some(""))]
+#[case("file", Some("x"), None)]
+#[case("reset", None, None)]
+#[tokio::test]
+async fn test_form_defaults_by_type(
+    #[case] kind: &str,
+    #[case] value: Option<&str>,
+    #[case] expected: Option<&str>,
+) -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let field = FormField {
+        name: "field".to_string(),
+        kind: kind.to_string(),
+        value: value.map(str::to_string),
+    };
+    assert_eq!(FormDefaults::new().value_for(&field).as_deref(), expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_parse_robots_and_sitemap() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let base = Url::parse("http://example.com/robots.txt")?;
+    let robots = "User-agent: googlebot\n\
+                  Disallow: /nobots\n\
+                  \n\
+                  User-agent: *\n\
+                  Disallow: /admin/ # staff only\n\
+                  Disallow: /*.php$\n\
+                  Allow: /admin/help\n\
+                  Disallow:\n\
+                  Sitemap: https://example.com/sitemap-pages.xml\n";
+    let robots = Robots::parse(&base, robots);
+
+    let urls: Vec<&str> = robots.urls.iter().map(Url::as_str).collect();
+    assert_eq!(
+        urls,
+        vec![
+            "http://example.com/nobots",
+            "http://example.com/admin/",
+            "http://example.com/admin/help",
+            "https://example.com/sitemap-pages.xml",
+        ]
+    );
+    assert_eq!(robots.rules.len(), 3);
+    assert!(robots.allows("/nobots"));
+    assert!(!robots.allows("/admin/users"));
+    assert!(robots.allows("/admin/help/faq"));
+    assert!(!robots.allows("/shop/cart.php"));
+    assert!(robots.allows("/shop/cart.php.bak"));
+
+    let xml = r#"<?xml version="1.0"?><urlset>
+        <url><loc>http://example.com/a?x=1&amp;y=2</loc></url>
+        <url><LOC> /b </LOC></url>
+    </urlset>"#;
+    let locs: Vec<String> = parse_sitemap(&base, xml).iter().map(Url::to_string).collect();
+    assert_eq!(locs, vec!["http://example.com/a?x=1&y=2", "http://example.com/b"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spider_crawls_site_within_limits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let html = |then: httpmock::Then, body: String| {
+        then.status(200).header("Content-Type", "text/html").body(body);
+    };
+    server.mock(|when, then| {
+        when.method(Method::GET).path("/robots.txt");
+        then.status(200).body("User-agent: *\nDisallow: /private\nSitemap: /sitemap.xml\n");
+    });
+    server.mock(|when, then| {
+        when.method(Method::GET).path("/sitemap.xml");
+        then.status(200).body("<urlset><url><loc>/from-sitemap</loc></url></urlset>");
+    });
+    server.mock(|when, then| {
+        when.method(Method::GET).path("/");
+        html(
+            then,
+            r#"<a href="/about">About</a><a href="http://elsewhere.test/">Out</a>
+               <img src="/logo.png"><script src="/app.js"></script>
+               <form method="post" action="/contact"><input name="email" type="email"></form>
+               <a href="/level1">Deeper</a>"#
+                .to_string(),
+        );
+    });
+    let about = server.mock(|when, then| {
+        when.method(Method::GET).path("/about");
+        html(then, r#"<a href="/">Home</a><a href="/about#team">Team</a>"#.to_string());
+    });
+    let script = server.mock(|when, then| {
+        when.method(Method::GET).path("/app.js");
+        then.status(200).header("Content-Type", "application/javascript").body("init()");
+    });
+    let sitemap_page = server.mock(|when, then| {
+        when.method(Method::GET).path("/from-sitemap");
+        then.status(200).body("listed");
+    });
+    let contact = server.mock(|when, then| {
+        when.method(Method::POST).path("/contact").body("email=ferrum%40example.com");
+        then.status(200).body("thanks");
+    });
+    server.mock(|when, then| {
+        when.method(Method::GET).path("/level1");
+        html(then, r#"<a href="/level2">Deeper</a>"#.to_string());
+    });
+    let level2 = server.mock(|when, then| {
+        when.method(Method::GET).path("/level2");
+        html(then, r#"<a href="/level3">Deeper</a>"#.to_string());
+    });
+    let level3 = server.mock(|when, then| {
+        when.method(Method::GET).path("/level3");
+        then.status(200).body("too deep");
+    });
+    let private = server.mock(|when, then| {
+        when.method(Method::GET).path("/private");
+        then.status(200).body("secret");
+    });
+
+    let project = Arc::new(Project::in_memory()?);
+    let site_map = Arc::new(Mutex::new(SiteMap::new()));
+    let spider = Spider::new(Forwarder::new())
+        .with_max_depth(2)
+        .with_concurrency(3)
+        .with_form_submission(FormDefaults::new())
+        .with_project(Arc::clone(&project))
+        .with_site_map(Arc::clone(&site_map));
+
+    let report = spider.crawl(&[Url::parse(&server.url("/"))?]).await?;
+
+    about.assert_hits(1);
+    script.assert_hits(1);
+    sitemap_page.assert_hits(1);
+    contact.assert_hits(1);
+    level2.assert_hits(1);
+    // One hop past the depth limit, and disallowed by robots.txt when asked to
+    level3.assert_hits(0);
+    private.assert_hits(1);
+    assert_eq!(report.forms_submitted, 1);
+    assert_eq!(report.out_of_scope, 1);
+    assert_eq!(report.not_fetched, 1);
+    assert!(report.visits.iter().all(|visit| visit.status == Some(200) && visit.flow_id > 0));
+
+    let recorded = project.list_flows()?;
+    assert_eq!(recorded.len(), report.visits.len());
+    assert!(recorded.iter().all(|flow| flow.tags == vec![SPIDER_TAG.to_string()]));
+    {
+        let site_map = site_map.lock().unwrap();
+        assert!(site_map.node(&server.url("/level2")).is_some_and(|node| node.requested));
+        assert!(site_map.node(&server.url("/level3")).is_some_and(|node| !node.requested));
+    }
+
+    // With robots.txt respected and a tight budget
+    let report = Spider::new(Forwarder::new())
+        .with_respect_robots(true)
+        .with_max_requests(2)
+        .crawl(&[Url::parse(&server.url("/private"))?])
+        .await?;
+    private.assert_hits(1);
+    sitemap_page.assert_hits(1);
+    assert_eq!(report.visits.len(), 2);
+    assert_eq!(report.not_fetched, 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spider_counts_forms_it_submits() -> Result<()> {
+    // Initialize test logging
+    init_test_logging();
+
+    let server = MockServer::start();
+    let form = r#"<form method="post" action="/subscribe"><input name="email" type="email"></form>"#;
+    for path in ["/", "/news"] {
+        server.mock(|when, then| {
+            when.method(Method::GET).path(path);
+            then.status(200).header("Content-Type", "text/html").body(format!(
+                r#"{}<form action="http://elsewhere.test/search"><input name="q"></form><a href="/news">News</a>"#,
+                form
+            ));
+        });
+    }
+    let subscribe = server.mock(|when, then| {
+        when.method(Method::POST).path("/subscribe");
+        then.status(200).body("subscribed");
+    });
+
+    // The same form on two pages is sent once, and an out-of-scope form not at all
+    let report = Spider::new(Forwarder::new())
+        .with_form_submission(FormDefaults::new())
+        .crawl(&[Url::parse(&server.url("/"))?])
+        .await?;
+    subscribe.assert_hits(1);
+    assert_eq!(report.forms_submitted, 1);
+
+    // Forms left over at the request limit were never submitted
+    let report = Spider::new(Forwarder::new())
+        .with_form_submission(FormDefaults::new())
+        .with_max_requests(1)
+        .crawl(&[Url::parse(&server.url("/"))?])
+        .await?;
+    subscribe.assert_hits(1);
+    assert_eq!(report.forms_submitted, 0);
+
+    Ok(())
+}